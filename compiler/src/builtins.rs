@@ -8,6 +8,7 @@ use crate::compiler_types::*;
 use crate::context::*;
 use crate::globals::Globals;
 use crate::leveldata::*;
+use crate::path_scopes::{FsAccess, PathScopes};
 use errors::{create_error, RuntimeError};
 use ahash::AHashMap;
 use parser::ast::ObjectMode;
@@ -25,6 +26,7 @@ use crate::value_storage::*;
 use std::io::stdout;
 use std::io::Write;
 
+use std::cmp::Ordering;
 use std::collections::hash_map::DefaultHasher;
 
 // BUILT IN STD
@@ -76,6 +78,202 @@ fn div_zero_check(b: f64, op: &str, builtin: &str, info: &CompilerInfo) -> Resul
     }
 }
 
+// @ints can be passed wherever a @number is expected
+fn coerce_argument(val: Value, arg_type: &str) -> Value {
+    match val {
+        Value::Int(n) if arg_type == "Number" => Value::Number(n as f64),
+        a => a,
+    }
+}
+
+// checks that a builtin that was only allowed for some directories can use the path
+#[allow(clippy::result_large_err)]
+fn check_path(
+    globals: &Globals,
+    func: Builtin,
+    path: &str,
+    builtin: &str,
+    info: &CompilerInfo,
+) -> Result<(), RuntimeError> {
+    globals
+        .permissions
+        .check_path(func, Path::new(path))
+        .map_err(|message| RuntimeError::BuiltinError {
+            builtin: builtin.to_string(),
+            message,
+            info: info.clone(),
+        })
+}
+
+#[allow(clippy::result_large_err)]
+fn number_arg(
+    val: &Value,
+    index: usize,
+    builtin: &str,
+    info: &CompilerInfo,
+    globals: &mut Globals,
+) -> Result<f64, RuntimeError> {
+    match val {
+        Value::Number(n) => Ok(*n),
+        Value::Int(n) => Ok(*n as f64),
+        a => Err(RuntimeError::BuiltinError {
+            builtin: builtin.to_string(),
+            message: format!(
+                "Expected Number for argument {}, found {}",
+                index,
+                a.clone().to_str(globals)
+            ),
+            info: info.clone(),
+        }),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum NumOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    IntDiv,
+    Mod,
+    Pow,
+}
+
+// two @ints give an exact @int (or an overflow error), anything else
+// is done with @numbers
+#[allow(clippy::result_large_err)]
+fn arith(
+    a: &Value,
+    b: &Value,
+    op: NumOp,
+    builtin: &str,
+    info: &CompilerInfo,
+    globals: &mut Globals,
+) -> Result<Value, RuntimeError> {
+    let x = number_arg(a, 1, builtin, info, globals)?;
+    let y = number_arg(b, 2, builtin, info, globals)?;
+    match op {
+        NumOp::Div | NumOp::IntDiv => div_zero_check(y, "divide", builtin, info)?,
+        NumOp::Mod => div_zero_check(y, "modulo", builtin, info)?,
+        _ => (),
+    }
+
+    if let (Value::Int(a), Value::Int(b)) = (a, b) {
+        let (a, b) = (*a, *b);
+        let out = match op {
+            NumOp::Add => a.checked_add(b),
+            NumOp::Sub => a.checked_sub(b),
+            NumOp::Mul => a.checked_mul(b),
+            NumOp::Div => return Ok(Value::Number(x / y)),
+            // rounds towards negative infinity, like the float version
+            NumOp::IntDiv => a.checked_div(b).map(|q| {
+                if a % b != 0 && (a < 0) != (b < 0) {
+                    q - 1
+                } else {
+                    q
+                }
+            }),
+            NumOp::Mod => a.checked_rem_euclid(b),
+            NumOp::Pow if b < 0 => return Ok(Value::Number(x.powf(y))),
+            NumOp::Pow => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+        };
+        return match out {
+            Some(n) => Ok(Value::Int(n)),
+            None => Err(RuntimeError::BuiltinError {
+                builtin: builtin.to_string(),
+                message: "Integer overflow: the result doesn't fit in an @int".to_string(),
+                info: info.clone(),
+            }),
+        };
+    }
+
+    Ok(Value::Number(match op {
+        NumOp::Add => x + y,
+        NumOp::Sub => x - y,
+        NumOp::Mul => x * y,
+        NumOp::Div => x / y,
+        NumOp::IntDiv => (x / y).floor(),
+        NumOp::Mod => x.rem_euclid(y),
+        NumOp::Pow => x.powf(y),
+    }))
+}
+
+#[allow(clippy::result_large_err)]
+fn compare_numbers(
+    a: &Value,
+    b: &Value,
+    builtin: &str,
+    info: &CompilerInfo,
+    globals: &mut Globals,
+) -> Result<Option<Ordering>, RuntimeError> {
+    if let (Value::Int(a), Value::Int(b)) = (a, b) {
+        return Ok(Some(a.cmp(b)));
+    }
+    let x = number_arg(a, 1, builtin, info, globals)?;
+    let y = number_arg(b, 2, builtin, info, globals)?;
+    Ok(x.partial_cmp(&y))
+}
+
+// the integer a value stands for, for the bitwise operators
+#[allow(clippy::result_large_err)]
+fn bit_int(
+    val: &Value,
+    index: usize,
+    builtin: &str,
+    info: &CompilerInfo,
+    globals: &mut Globals,
+) -> Result<i64, RuntimeError> {
+    if let Value::Int(n) = val {
+        return Ok(*n);
+    }
+    let n = number_arg(val, index, builtin, info, globals)?;
+    if n.fract() != 0.0 || n.abs() >= i64::MAX as f64 {
+        return Err(RuntimeError::BuiltinError {
+            builtin: builtin.to_string(),
+            message: format!("Bitwise operators only work on integers, found {}", n),
+            info: info.clone(),
+        });
+    }
+    Ok(n as i64)
+}
+
+// the result is an @int if both sides are
+#[allow(clippy::result_large_err)]
+fn bitwise(
+    a: &Value,
+    b: &Value,
+    op: impl Fn(i64, i64) -> i64,
+    builtin: &str,
+    info: &CompilerInfo,
+    globals: &mut Globals,
+) -> Result<Value, RuntimeError> {
+    let out = op(
+        bit_int(a, 1, builtin, info, globals)?,
+        bit_int(b, 2, builtin, info, globals)?,
+    );
+    Ok(match (a, b) {
+        (Value::Int(_), Value::Int(_)) => Value::Int(out),
+        _ => Value::Number(out as f64),
+    })
+}
+
+#[allow(clippy::result_large_err)]
+fn shift_amount(
+    val: &Value,
+    builtin: &str,
+    info: &CompilerInfo,
+    globals: &mut Globals,
+) -> Result<u32, RuntimeError> {
+    match bit_int(val, 2, builtin, info, globals)? {
+        a @ 0..=63 => Ok(a as u32),
+        a => Err(RuntimeError::BuiltinError {
+            builtin: builtin.to_string(),
+            message: format!("Cannot shift by {} bits (expected 0 to 63)", a),
+            info: info.clone(),
+        }),
+    }
+}
+
 //use text_io;
 use errors::compiler_info::{CodeArea, CompilerInfo};
 
@@ -100,6 +298,7 @@ pub fn context_trigger(context: &Context, uid_counter: &mut usize) -> GdObj {
         func_id: context.func_id,
         mode: ObjectMode::Trigger,
         unique_id: *uid_counter,
+        merged_ids: Vec::new(),
     }
 }
 
@@ -307,6 +506,10 @@ impl Value {
                     Some(value) => Some(*value),
                     None => get_impl(self.to_num(globals), member),
                 },
+                // like in arguments, an @int can be used as a @number
+                Value::Int(_) => {
+                    get_impl(type_id!(int), member).or_else(|| get_impl(type_id!(number), member))
+                }
                 Value::TriggerFunc(f) => {
                     if member.as_ref() == "start_group" {
                         Some(store_const_value(
@@ -339,7 +542,10 @@ macro_rules! typed_argument_check {
 
     (($globals:ident, $arg_index:ident, $arguments:ident, $info:ident, $context:ident, $builtin:ident) ($($arg_name:ident),*): $arg_type:ident) => {
 
-        let  ( $($arg_name),*) = match clone_and_get_value($arguments[$arg_index], $globals, $context.start_group, true) {
+        let  ( $($arg_name),*) = match coerce_argument(
+            clone_and_get_value($arguments[$arg_index], $globals, $context.start_group, true),
+            stringify!($arg_type),
+        ) {
             Value::$arg_type($($arg_name),*) => ($($arg_name),*),
 
             a => {
@@ -478,8 +684,14 @@ macro_rules! builtins {
             )*
         ];
 
+        pub const BUILTIN_DESCRIPTIONS: &[(&str, &str)] = &[
+            $(
+                (stringify!($name), $desc),
+            )*
+        ];
+
         #[derive(Debug, Clone)]
-        pub struct BuiltinPermissions (AHashMap<Builtin, bool>);
+        pub struct BuiltinPermissions (AHashMap<Builtin, bool>, PathScopes);
 
         impl BuiltinPermissions {
             pub fn new() -> Self {
@@ -487,7 +699,7 @@ macro_rules! builtins {
                 $(
                     map.insert(Builtin::$variant, $safe);
                 )*
-                Self(map)
+                Self(map, PathScopes::default())
             }
             pub fn is_allowed(&self, b: Builtin) -> bool {
                 self.0[&b]
@@ -495,6 +707,18 @@ macro_rules! builtins {
             pub fn set(&mut self, b: Builtin, setting: bool) {
                 self.0.insert(b, setting);
             }
+            /// Allows the builtins that read or change the file system, but only for
+            /// paths inside `dir` (and the other directories allowed this way)
+            pub fn allow_path(&mut self, access: FsAccess, dir: &Path) {
+                for b in access.builtins() {
+                    self.set(*b, true);
+                }
+                self.1.allow(access, dir);
+            }
+            /// Returns an error message if the builtin is only allowed for other paths
+            pub fn check_path(&self, b: Builtin, path: &Path) -> Result<(), String> {
+                self.1.check(b, path)
+            }
             pub fn is_safe(&self, b: Builtin) -> bool {
                 match b {
                     $(
@@ -549,7 +773,10 @@ macro_rules! builtins {
                 if !$globals.permissions.is_safe(func) {
                     return Err(RuntimeError::BuiltinError {
                         builtin: String::from(func),
-                        message: format!("This built-in function requires an explicit `--allow {}` flag when running the script", String::from(func)),
+                        message: match FsAccess::of(func) {
+                            Some(access) => format!("This built-in function requires an explicit `--allow {}` or `{}=<dir>` flag when running the script", String::from(func), access.flag()),
+                            None => format!("This built-in function requires an explicit `--allow {}` flag when running the script", String::from(func)),
+                        },
                         $info,
                     })
                 } else {
@@ -560,6 +787,18 @@ macro_rules! builtins {
                     })
                 }
             }
+            if let Err(message) = $globals.package_permissions.check(func, &$info) {
+                return Err(RuntimeError::BuiltinError {
+                    builtin: String::from(func),
+                    message,
+                    $info,
+                });
+            }
+            if !$globals.permissions.is_safe(func)
+                || matches!(func, Builtin::Time | Builtin::Random | Builtin::GetInput)
+            {
+                $globals.impure_calls += 1;
+            }
             for full_context in contexts.iter() {
                 let $full_context: *mut FullContext = full_context;
                 let $context = full_context.inner();
@@ -761,28 +1000,40 @@ builtins! {
         Value::Null
     }
 
+    [Breakpoint] #[safe = true, desc = "Pauses the build here when it's run with a debugger (`spwn dap`), and does nothing otherwise", example = "$.breakpoint()"]
+    fn breakpoint(#["none"]) {
+        arg_length!(info, 0, arguments, "Expected no arguments".to_string(), builtin);
+        let ctx = unsafe { FullContext::from_ptr(full_context) };
+        crate::debugger::on_breakpoint_call(ctx, globals, &info);
+        Value::Null
+    }
+
     [Time] #[safe = true, desc = "Gets the current system time in seconds", example = "now = $.time()"]
     fn time(#["none"]) {
         #[cfg(not(target_arch = "wasm32"))]
         {
             arg_length!(info, 0, arguments, "Expected no arguments".to_string(), builtin);
             use std::time::SystemTime;
-            let now = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-                Ok(time) => time,
-                Err(e) => {
-                    return Err(RuntimeError::BuiltinError {
-                        builtin,
-                        message: format!("System time error: {}", e),
-                        info,
-                    })
+            if let Some(time) = globals.reproducible.fixed_time() {
+                Value::Number(time)
+            } else {
+                let now = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+                    Ok(time) => time,
+                    Err(e) => {
+                        return Err(RuntimeError::BuiltinError {
+                            builtin,
+                            message: format!("System time error: {}", e),
+                            info,
+                        })
+                    }
                 }
+                .as_secs_f64();
+                Value::Number(now)
             }
-            .as_secs_f64();
-            Value::Number(now)
         }
 
         #[cfg(target_arch = "wasm32")]
-        Value::Number(0.0)
+        Value::Number(globals.reproducible.fixed_time().unwrap_or(0.0))
     }
 
     [SpwnVersion] #[safe = true, desc = "Gets the current version of spwn", example = "$.spwn_version()"]
@@ -794,11 +1045,23 @@ builtins! {
 
     [GetInput] #[safe = true, desc = "Gets some input from the user", example = "// inp = $.get_input('What is your name?')"]
     fn get_input((prompt): Str) {
-        print!("{}", prompt);
-        stdout()
-            .flush()
-            .expect("Unexpected error occurred when trying to get user input");
-        Value::Str(text_io::read!("{}\n"))
+        match globals.reproducible.next_input() {
+            Some(Ok(input)) => Value::Str(input),
+            Some(Err(message)) => {
+                return Err(RuntimeError::BuiltinError {
+                    builtin,
+                    message,
+                    info,
+                })
+            }
+            None => {
+                print!("{}", prompt);
+                stdout()
+                    .flush()
+                    .expect("Unexpected error occurred when trying to get user input");
+                Value::Str(text_io::read!("{}\n"))
+            }
+        }
     }
 
 
@@ -932,11 +1195,11 @@ builtins! {
         }
     }
 
-    [Hash] #[safe = true, desc = "Calculates the numerical hash of a value", example = "$.hash(\"hello\")"] fn hash((n)) { Value::Number( {
+    [Hash] #[safe = true, desc = "Calculates the numerical hash of a value", example = "$.hash(\"hello\")"] fn hash((n)) { Value::Int( {
         let mut s = DefaultHasher::new();
         n.hash(&mut s, globals);
-        s.finish() / 1000
-    } as f64 ) }
+        s.finish()
+    } as i64 ) }
 
     [Sin] #[safe = true, desc = "Calculates the sin of an angle in radians", example = "$.sin(3.1415)"] fn sin((n): Number) { Value::Number(n.sin()) }
     [Cos] #[safe = true, desc = "Calculates the cos of an angle in radians", example = "$.cos(3.1415)"] fn cos((n): Number) { Value::Number(n.cos()) }
@@ -1037,8 +1300,9 @@ $.add(obj {
                     func_id: context.func_id,
                     mode: ObjectMode::Object,
                     unique_id: globals.uid_counter,
-
+                    merged_ids: Vec::new(),
                 };
+                globals.record_origin(obj.unique_id, &info);
                 (*globals).objects.push(obj)
             }
             ObjectMode::Trigger => {
@@ -1049,6 +1313,7 @@ $.add(obj {
                     ..context_trigger(context, &mut globals.uid_counter)
                 }
                 .context_parameters(context);
+                globals.record_origin(obj.unique_id, &info);
                 (*globals).trigger_order += 1.0;
                 (*globals).func_ids[context.func_id]
                     .obj_list
@@ -1103,6 +1368,7 @@ $.edit_obj(object, X, 600)
         let (okey, oval) = {
             let (key, pattern) = match key {
                 Value::Number(n) => (n as u16, None),
+                Value::Int(n) => (n as u16, None),
 
                 Value::Dict(d) => {
                     // this is specifically for object_key dicts
@@ -1143,6 +1409,7 @@ $.edit_obj(object, X, 600)
                         match &globals.stored_values[*id.unwrap()] {
                             // check if the ID is actually an int. it should be
                             Value::Number(n) => *n as u16,
+                            Value::Int(n) => *n as u16,
                             _ => {
                                 return Err(RuntimeError::TypeError {
                                     expected: "number".to_string(),
@@ -1198,6 +1465,7 @@ $.edit_obj(object, X, 600)
             let out_val = match &value {
                 // its just converting value to objparam basic level stuff
                 Value::Number(n) => ObjParam::Number(*n),
+                Value::Int(n) => ObjParam::Number(*n as f64),
                 Value::Str(s) => ObjParam::Text(s.clone()),
                 Value::TriggerFunc(g) => ObjParam::Group(g.start_group),
 
@@ -1333,8 +1601,6 @@ $.random(1..11) // returns a random integer between 1 and 10
     fn random(#["see example"]) {
         #[cfg(not(target_arch = "wasm32"))]
         {
-            use rand::seq::SliceRandom;
-            use rand::Rng;
             if arguments.len() > 2 {
                 return Err(RuntimeError::BuiltinError {
                     builtin,
@@ -1344,7 +1610,7 @@ $.random(1..11) // returns a random integer between 1 and 10
             }
 
             if arguments.is_empty() {
-                Value::Number(rand::thread_rng().gen())
+                Value::Number(globals.reproducible.random_number())
             } else {
                 let val = match convert_type(&globals.stored_values[arguments[0]].clone(), type_id!(array), &info, globals, context) {
                     Ok(Value::Array(v)) => v,
@@ -1358,7 +1624,7 @@ $.random(1..11) // returns a random integer between 1 and 10
                 };
 
                 if arguments.len() == 1 {
-                    let rand_elem = val.choose(&mut rand::thread_rng());
+                    let rand_elem = globals.reproducible.random_index(val.len()).map(|i| &val[i]);
 
                     if rand_elem.is_some() {
                         clone_and_get_value(
@@ -1375,6 +1641,9 @@ $.random(1..11) // returns a random integer between 1 and 10
                         Value::Number(n) => {
                             convert_to_int(*n, &info)?
                         },
+                        Value::Int(n) => {
+                            convert_to_int(*n as f64, &info)?
+                        },
                         _ => {
                             return Err(RuntimeError::BuiltinError {
                                 builtin,
@@ -1387,7 +1656,7 @@ $.random(1..11) // returns a random integer between 1 and 10
                     let mut out_arr = Vec::<StoredValue>::new();
 
                     for _ in 0..times {
-                        let rand_elem = val.choose(&mut rand::thread_rng());
+                        let rand_elem = globals.reproducible.random_index(val.len()).map(|i| &val[i]);
 
                         if rand_elem.is_some() {
                             out_arr.push(clone_value(
@@ -1500,6 +1769,7 @@ $.random(1..11) // returns a random integer between 1 and 10
                     }
                     _ => "text",
                 };
+                check_path(globals, Builtin::ReadFile, &p, &builtin, &info)?;
                 let path = std::path::PathBuf::from(p);
 
                 if !path.exists() {
@@ -1710,7 +1980,7 @@ $.random(1..11) // returns a random integer between 1 and 10
 
     [WriteFile] #[safe = false, desc = "Writes a string to a file in the local file system (any previous content will be overwritten, and a new file will be created if it does not already exist)", example = "$.write_file(\"file.txt\", \"Hello\")"]
     fn writefile((path): Str, (data): Str) {
-
+        check_path(globals, Builtin::WriteFile, &path, &builtin, &info)?;
 
         match fs::write(path, data) {
             Ok(_) => (),
@@ -1726,6 +1996,7 @@ $.random(1..11) // returns a random integer between 1 and 10
     }
 
     [DeleteFile] #[safe = false, desc = "Deletes a file in the local file system", example = "$.deletefile(\"file.txt\")"] fn deletefile((path): Str) {
+        check_path(globals, Builtin::DeleteFile, &path, &builtin, &info)?;
         match fs::remove_file(path) {
             Ok(_) => (),
             Err(e) => {
@@ -1740,10 +2011,12 @@ $.random(1..11) // returns a random integer between 1 and 10
     }
 
     [FileExists] #[safe = false, desc = "Checks if a member exists in the local file system", example = "$.fileexists(\"file.txt\")"] fn fileexists((path): Str) {
+        check_path(globals, Builtin::FileExists, &path, &builtin, &info)?;
         Value::Bool(fs::metadata(path).is_ok())
     }
 
     [FileKind] #[safe = false, desc = "Returns the kind of a member of the local file system", example = "$.filekind(\"file.txt\")"] fn filekind((path): Str) {
+        check_path(globals, Builtin::FileKind, &path, &builtin, &info)?;
         match fs::metadata(path) {
             Ok(meta) => {
                 let kind = match meta.file_type() {
@@ -1764,6 +2037,7 @@ $.random(1..11) // returns a random integer between 1 and 10
     }
 
     [MetaData] #[safe = false, desc = "Returns the metadata of a file or directory in the local file system", example = "$.metadata(\"file.txt\")"] fn metadata((path): Str) {
+        check_path(globals, Builtin::MetaData, &path, &builtin, &info)?;
         match fs::metadata(path) {
             Ok(meta) => {
                 let mut dict: AHashMap<LocalIntern<String>, StoredValue> = AHashMap::default();
@@ -1786,6 +2060,7 @@ $.random(1..11) // returns a random integer between 1 and 10
     }
 
     [ReadDir] #[safe = false, desc = "Reads the contents of a directory in the local file system", example = "$.readdir(\"/\")"] fn readdir((path): Str) {
+        check_path(globals, Builtin::ReadDir, &path, &builtin, &info)?;
         let mut arr: Vec<StoredValue> = vec![];
         for entry in fs::read_dir(path).unwrap() {
             let entry = match entry {
@@ -1806,6 +2081,7 @@ $.random(1..11) // returns a random integer between 1 and 10
     }
 
     [MkDir] #[safe = false, desc = "Creates a directory in the local file system", example = "$.mkdir(\"/\")"] fn mkdir((path): Str) {
+        check_path(globals, Builtin::MkDir, &path, &builtin, &info)?;
         match fs::create_dir(path) {
             Ok(_) => (),
             Err(e) => {
@@ -1820,6 +2096,7 @@ $.random(1..11) // returns a random integer between 1 and 10
     }
 
     [RmDir] #[safe = false, desc = "Removes an empty directory in the local file system", example = "$.rmdir(\"folder\")"] fn rmdir((path): Str) {
+        check_path(globals, Builtin::RmDir, &path, &builtin, &info)?;
         match fs::remove_dir(path) {
             Ok(_) => (),
             Err(e) => {
@@ -1834,6 +2111,7 @@ $.random(1..11) // returns a random integer between 1 and 10
     }
 
     [RmDirAll] #[safe = false, desc = "Removes a directory in the local file system", example = "$.rmdirall(\"folder\")"] fn rmdir_all((path): Str) {
+        check_path(globals, Builtin::RmDirAll, &path, &builtin, &info)?;
         match fs::remove_dir_all(path) {
             Ok(_) => (),
             Err(e) => {
@@ -1920,6 +2198,7 @@ $.assert(name_age == {
             Value::Array(arr) => {
                 let out = (arr).remove(match index {
                     Value::Number(n) => n as usize,
+                    Value::Int(n) => n as usize,
                     _ => {
                         return Err(RuntimeError::BuiltinError {
                             builtin,
@@ -1933,6 +2212,7 @@ $.assert(name_age == {
 
             Value::Str(s) => Value::Str(s.remove(match index {
                 Value::Number(n) => n as usize,
+                Value::Int(n) => n as usize,
                 _ => {
                     return Err(RuntimeError::BuiltinError {
                         builtin,
@@ -2139,6 +2419,9 @@ $.assert($.call(m, [1, 2]) == 3)
             Value::Number(start) => {
                 Value::Range(convert_to_int(start, &info)?, end, 1)
             }
+            Value::Int(start) => {
+                Value::Range(convert_to_int(start as f64, &info)?, end, 1)
+            }
             Value::Range(start, step, old_step) => {
                 if old_step != 1 {
 
@@ -2185,17 +2468,29 @@ $.assert($.call(m, [1, 2]) == 3)
 
     // unary operators
     [IncrOp] #[safe = true, desc = "Default implementation of the `n++` operator", example = "let n = 0\n$._increment_(n)\n$.assert(n == 1)"]
-    fn _increment_(mut (a): Number) { a += 1.0; Value::Number(a - 1.0)}
+    fn _increment_(mut (a)) { let old = a.clone(); a = arith(&a, &Value::Int(1), NumOp::Add, &builtin, &info, globals)?; old }
     [DecrOp] #[safe = true, desc = "Default implementation of the `n--` operator", example = "let n = 1\n$._decrement_(n)\n$.assert(n == 0)"]
-    fn _decrement_(mut (a): Number) { a -= 1.0; Value::Number(a + 1.0)}
+    fn _decrement_(mut (a)) { let old = a.clone(); a = arith(&a, &Value::Int(1), NumOp::Sub, &builtin, &info, globals)?; old }
 
     [PreIncrOp] #[safe = true, desc = "Default implementation of the `++n` operator", example = "let n = 0\n$.assert($._pre_increment_(n) == 1)"]
-    fn _pre_increment_(mut (a): Number) { a += 1.0; Value::Number(a)}
+    fn _pre_increment_(mut (a)) { a = arith(&a, &Value::Int(1), NumOp::Add, &builtin, &info, globals)?; a.clone() }
     [PreDecrOp] #[safe = true, desc = "Default implementation of the `--n` operator", example = "let n = 1\n$.assert($._pre_decrement_(n) == 0)"]
-    fn _pre_decrement_(mut (a): Number) { a -= 1.0; Value::Number(a)}
+    fn _pre_decrement_(mut (a)) { a = arith(&a, &Value::Int(1), NumOp::Sub, &builtin, &info, globals)?; a.clone() }
 
     [NegOp] #[safe = true, desc = "Default implementation of the `-n` operator", example = "$.assert($._negate_(1) == -1)"]
-    fn _negate_((a): Number) { Value::Number(-a)}
+    fn _negate_((a)) {
+        match a {
+            Value::Int(n) => arith(&Value::Int(0), &Value::Int(n), NumOp::Sub, &builtin, &info, globals)?,
+            a => Value::Number(-number_arg(&a, 1, &builtin, &info, globals)?),
+        }
+    }
+    [BitNotOp] #[safe = true, desc = "Default implementation of the `~n` operator", example = "$.assert($._bit_not_(0) == -1)"]
+    fn _bit_not_((a)) {
+        match a {
+            Value::Int(n) => Value::Int(!n),
+            a => Value::Number(!bit_int(&a, 1, &builtin, &info, globals)? as f64),
+        }
+    }
     [NotOp] #[safe = true, desc = "Default implementation of the `!b` operator", example = "$.assert($._not_(false))"]
     fn _not_((a)) {
         match a {
@@ -2242,14 +2537,14 @@ $.assert($.call(m, [1, 2]) == 3)
 
 
     [MoreThanOp] #[safe = true, desc = "Default implementation of the `>` operator", example = "$._more_than_(100, 50)"]
-    fn _more_than_((a): Number, (b): Number) { Value::Bool(a > b) }
+    fn _more_than_((a), (b)) { Value::Bool(matches!(compare_numbers(&a, &b, &builtin, &info, globals)?, Some(Ordering::Greater))) }
     [LessThanOp] #[safe = true, desc = "Default implementation of the `<` operator", example = "$._less_than_(50, 100)"]
-    fn _less_than_((a): Number, (b): Number) { Value::Bool(a < b) }
+    fn _less_than_((a), (b)) { Value::Bool(matches!(compare_numbers(&a, &b, &builtin, &info, globals)?, Some(Ordering::Less))) }
 
     [MoreOrEqOp] #[safe = true, desc = "Default implementation of the `>=` operator", example = "$._more_or_equal_(100, 100)"]
-    fn _more_or_equal_((a): Number, (b): Number) { Value::Bool(a >= b) }
+    fn _more_or_equal_((a), (b)) { Value::Bool(matches!(compare_numbers(&a, &b, &builtin, &info, globals)?, Some(Ordering::Greater | Ordering::Equal))) }
     [LessOrEqOp] #[safe = true, desc = "Default implementation of the `<=` operator", example = "$._less_or_equal_(100, 100)"]
-    fn _less_or_equal_((a): Number, (b): Number) { Value::Bool(a <= b) }
+    fn _less_or_equal_((a), (b)) { Value::Bool(matches!(compare_numbers(&a, &b, &builtin, &info, globals)?, Some(Ordering::Less | Ordering::Equal))) }
 
     [EqOp] #[safe = true, desc = "Default implementation of the `==` operator", example = "$._equal_(\"hello\", \"hello\")"]
     [[RAW]] fn _equal_((a), (b)) {
@@ -2276,21 +2571,16 @@ $.assert($.call(m, [1, 2]) == 3)
     }
 
     [DividedByOp] #[safe = true, desc = "Default implementation of the `/` operator", example = "$._divided_by_(64, 8)"]
-    fn _divided_by_((a): Number, (b): Number) {
-        div_zero_check(b, "divide", &builtin, &info)?;
-        Value::Number(a / b)
-    }
+    fn _divided_by_((a), (b)) { arith(&a, &b, NumOp::Div, &builtin, &info, globals)? }
     [IntdividedByOp] #[safe = true, desc = "Default implementation of the `/%` operator", example = "$._intdivided_by_(64, 8)"]
-    fn _intdivided_by_((a): Number, (b): Number) {
-        div_zero_check(b, "divide", &builtin, &info)?;
-        Value::Number((a / b).floor())
-    }
+    fn _intdivided_by_((a), (b)) { arith(&a, &b, NumOp::IntDiv, &builtin, &info, globals)? }
     [TimesOp] #[safe = true, desc = "Default implementation of the `*` operator", example = "$._times_(8, 8)"]
-    fn _times_((a), (b): Number) {
+    fn _times_((a), (b)) {
+        let n = number_arg(&b, 2, &builtin, &info, globals)?;
         match a {
-            Value::Number(a) => Value::Number(a * b),
+            a @ (Value::Number(_) | Value::Int(_)) => arith(&a, &b, NumOp::Mul, &builtin, &info, globals)?,
             Value::Str(a) => {
-                let number = convert_to_int(b, &info)?;
+                let number = convert_to_int(n, &info)?;
                 if number >= 0 {
                     Value::Str(a.repeat(number as usize))
                 } else {
@@ -2299,7 +2589,7 @@ $.assert($.call(m, [1, 2]) == 3)
                         message: format!(
                             "Expected {}, found {}",
                             "a positive number",
-                            b,
+                            n,
                         ),
                         info,
                     })
@@ -2307,7 +2597,7 @@ $.assert($.call(m, [1, 2]) == 3)
             },
             Value::Array(ar) => {
                 let mut new_out = Vec::<StoredValue>::new();
-                for _ in 0..convert_to_int(b, &info)? {
+                for _ in 0..convert_to_int(n, &info)? {
                     for value in &ar {
                         new_out.push(clone_value(
                             *value,
@@ -2340,16 +2630,15 @@ $.assert($.call(m, [1, 2]) == 3)
         }
     }
     [ModOp] #[safe = true, desc = "Default implementation of the `%` operator", example = "$._mod_(70, 8)"]
-    fn _mod_((a): Number, (b): Number) {
-        div_zero_check(b, "modulo", &builtin, &info)?;
-        Value::Number(a.rem_euclid(b))
-    }
+    fn _mod_((a), (b)) { arith(&a, &b, NumOp::Mod, &builtin, &info, globals)? }
     [PowOp] #[safe = true, desc = "Default implementation of the `^` operator", example = "$._pow_(8, 2)"]
-    fn _pow_((a): Number, (b): Number) { Value::Number(a.powf(b)) }
+    fn _pow_((a), (b)) { arith(&a, &b, NumOp::Pow, &builtin, &info, globals)? }
     [PlusOp] #[safe = true, desc = "Default implementation of the `+` operator", example = "$._plus_(32, 32)"]
     fn _plus_((a), (b)) {
         match (a, b) {
-            (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+            (a @ (Value::Number(_) | Value::Int(_)), b @ (Value::Number(_) | Value::Int(_))) => {
+                arith(&a, &b, NumOp::Add, &builtin, &info, globals)?
+            }
             (Value::Str(a), Value::Str(b)) => Value::Str(a + &b),
             (Value::Array(a), Value::Array(b)) => Value::Array({
                 let mut new_arr = Vec::new();
@@ -2380,7 +2669,23 @@ $.assert($.call(m, [1, 2]) == 3)
         }
     }
     [MinusOp] #[safe = true, desc = "Default implementation of the `-` operator", example = "$._minus_(128, 64)"]
-    fn _minus_((a): Number, (b): Number) { Value::Number(a - b) }
+    fn _minus_((a), (b)) { arith(&a, &b, NumOp::Sub, &builtin, &info, globals)? }
+    [BitAndOp] #[safe = true, desc = "Default implementation of the `.&` operator", example = "$.assert($._bit_and_(0b1100, 0b1010) == 0b1000)"]
+    fn _bit_and_((a), (b)) { bitwise(&a, &b, |a, b| a & b, &builtin, &info, globals)? }
+    [BitOrOp] #[safe = true, desc = "Default implementation of the `.|` operator", example = "$.assert($._bit_or_(0b1100, 0b1010) == 0b1110)"]
+    fn _bit_or_((a), (b)) { bitwise(&a, &b, |a, b| a | b, &builtin, &info, globals)? }
+    [BitXorOp] #[safe = true, desc = "Default implementation of the `.^` operator", example = "$.assert($._bit_xor_(0b1100, 0b1010) == 0b0110)"]
+    fn _bit_xor_((a), (b)) { bitwise(&a, &b, |a, b| a ^ b, &builtin, &info, globals)? }
+    [ShiftLeftOp] #[safe = true, desc = "Default implementation of the `<<` operator", example = "$.assert($._shift_left_(1, 4) == 16)"]
+    fn _shift_left_((a), (b)) {
+        shift_amount(&b, &builtin, &info, globals)?;
+        bitwise(&a, &b, |a, b| a << b, &builtin, &info, globals)?
+    }
+    [ShiftRightOp] #[safe = true, desc = "Default implementation of the `>>` operator", example = "$.assert($._shift_right_(16, 4) == 1)"]
+    fn _shift_right_((a), (b)) {
+        shift_amount(&b, &builtin, &info, globals)?;
+        bitwise(&a, &b, |a, b| a >> b, &builtin, &info, globals)?
+    }
     [AssignOp] #[safe = true, desc = "Default implementation of the `=` operator", example = "let val = 0\n$._assign_(val, 64)\n$.assert(val == 64)"]
     fn _assign_(mut (a), (b)) {
         a = b;
@@ -2534,11 +2839,13 @@ $.assert($.call(m, [1, 2]) == 3)
     fn _as_((a), (t): TypeIndicator) { convert_type(&a,t,&info,globals,context)? }
 
     [SubtractOp] #[safe = true, desc = "Default implementation of the `-=` operator", example = "let val = 25\n$._subtract_(val, 10)\n$.assert(val == 15)"]
-    fn _subtract_(mut (a): Number, (b): Number) { a -= b; Value::Null }
+    fn _subtract_(mut (a), (b)) { a = arith(&a, &b, NumOp::Sub, &builtin, &info, globals)?; Value::Null }
     [AddOp] #[safe = true, desc = "Default implementation of the `+=` operator", example = "let val = 25\n$._add_(val, 10)\n$.assert(val == 35)"]
     fn _add_(mut (a), (b)) {
         match (&mut a, b) {
-            (Value::Number(a), Value::Number(b)) => *a += b,
+            (a @ (Value::Number(_) | Value::Int(_)), b @ (Value::Number(_) | Value::Int(_))) => {
+                *a = arith(a, &b, NumOp::Add, &builtin, &info, globals)?
+            }
             (Value::Str(a), Value::Str(b)) => *a += &b,
             (Value::Array(a), Value::Array(b)) => {
                 for el in b.iter() {
@@ -2562,11 +2869,12 @@ $.assert($.call(m, [1, 2]) == 3)
         Value::Null
     }
     [MultiplyOp] #[safe = true, desc = "Default implementation of the `*=` operator", example = "let val = 5\n$._multiply_(val, 10)\n$.assert(val == 50)"]
-    fn _multiply_(mut (a), (b): Number)         {
+    fn _multiply_(mut (a), (b)) {
+        let n = number_arg(&b, 2, &builtin, &info, globals)?;
         match &mut a {
-            Value::Number(a) => *a *= b,
+            a @ (Value::Number(_) | Value::Int(_)) => *a = arith(a, &b, NumOp::Mul, &builtin, &info, globals)?,
             Value::Str(a) => {
-                let number = convert_to_int(b, &info)?;
+                let number = convert_to_int(n, &info)?;
                 if number >= 0 {
                     *a = a.repeat(number as usize)
                 } else {
@@ -2575,7 +2883,7 @@ $.assert($.call(m, [1, 2]) == 3)
                         message: format!(
                             "Expected {}, found {}",
                             "a positive number",
-                            b,
+                            n,
                         ),
                         info,
                     })
@@ -2601,25 +2909,13 @@ $.assert($.call(m, [1, 2]) == 3)
         Value::Null
     }
     [DivideOp] #[safe = true, desc = "Default implementation of the `/=` operator", example = "let val = 9\n$._divide_(val, 3)\n$.assert(val == 3)"]
-    fn _divide_(mut (a): Number, (b): Number) {
-        div_zero_check(b, "divide", &builtin, &info)?;
-        a /= b;
-        Value::Null
-    }
+    fn _divide_(mut (a), (b)) { a = arith(&a, &b, NumOp::Div, &builtin, &info, globals)?; Value::Null }
     [IntdivideOp] #[safe = true, desc = "Default implementation of the `/%=` operator", example = "let val = 10\n$._intdivide_(val, 3)\n$.assert(val == 3)"]
-    fn _intdivide_(mut (a): Number, (b): Number) {
-        div_zero_check(b, "divide", &builtin, &info)?;
-        a /= b; a = a.floor();
-        Value::Null
-    }
+    fn _intdivide_(mut (a), (b)) { a = arith(&a, &b, NumOp::IntDiv, &builtin, &info, globals)?; Value::Null }
     [ExponateOp] #[safe = true, desc = "Default implementation of the `^=` operator", example = "let val = 3\n$._exponate_(val, 3)\n$.assert(val == 27)"]
-    fn _exponate_(mut (a): Number, (b): Number) { a = a.powf(b); Value::Null }
+    fn _exponate_(mut (a), (b)) { a = arith(&a, &b, NumOp::Pow, &builtin, &info, globals)?; Value::Null }
     [ModulateOp] #[safe = true, desc = "Default implementation of the `%=` operator", example = "let val = 10\n$._modulate_(val, 3)\n$.assert(val == 1)"]
-    fn _modulate_(mut (a): Number, (b): Number) {
-        div_zero_check(b, "modulo", &builtin, &info)?;
-        a = a.rem_euclid(b);
-        Value::Null
-    }
+    fn _modulate_(mut (a), (b)) { a = arith(&a, &b, NumOp::Mod, &builtin, &info, globals)?; Value::Null }
 
     [EitherOp] #[safe = true, desc = "Default implementation of the `|` operator", example = "$._either_(@number, @counter)"]
     fn _either_((a), (b)) {
@@ -6,15 +6,23 @@ use internment::LocalIntern;
 use shared::BreakType;
 use shared::ImportType;
 use shared::SpwnSource;
+use shared::StoredValue;
 
 use crate::builtins::*;
 use crate::context::*;
+use crate::context_limits::{check_contexts, ContextLimits, DEFAULT_CONTEXT_WARNING};
+use crate::debugger::{self, Debugger};
 use errors::compiler_info::CodeArea;
 use errors::compiler_info::CompilerInfo;
 use parser::ast;
 
 use crate::globals::Globals;
 use crate::leveldata::*;
+use crate::lints::{self, Level};
+use crate::module_cache;
+use crate::profiler::{profile, Frame, Profiler};
+use crate::reproducible::{self, Reproducible};
+use crate::sync_groups;
 use crate::value::*;
 use crate::value_storage::*;
 use crate::STD_PATH;
@@ -23,55 +31,75 @@ use ahash::AHashMap;
 use std::io::Write;
 use std::mem;
 
-use errors::RuntimeError;
+use errors::{Lint, RuntimeError, Warning};
 
 use parser::parser::ParseNotes;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use crate::compiler_types::*;
 
 use ariadne::Color as TColor;
 use ariadne::Fmt;
 
+/// Settings for a build that aren't part of the script
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    /// Where `#[cache_output]` modules are saved between builds, see `module_cache`
+    pub module_cache: Option<PathBuf>,
+    /// Records the time and values used by every macro call and import, see `profiler`
+    pub profile: bool,
+    /// Warns when a scope is compiled in more contexts than this, see `context_limits`
+    pub context_warning: Option<usize>,
+    /// Fails when a scope is compiled in more contexts than this
+    pub max_contexts: Option<usize>,
+    /// Shows the `context_explosion` warning as soon as it's found, since the
+    /// build might never finish. It's still added to `Globals::warnings`
+    pub show_warning: Option<fn(&Warning)>,
+    /// Stops at breakpoints and steps through the statements, see `debugger`
+    pub debugger: Option<Debugger>,
+    /// Seeds `$.random`, instead of the `#[seed]` of the script, see `reproducible`
+    pub seed: Option<u64>,
+    /// What `$.time` returns instead of the system time
+    pub fixed_time: Option<f64>,
+    /// The answers `$.get_input` gives instead of asking the user
+    pub inputs: Option<Vec<String>>,
+    /// Stops the build with an error once it is set, so an outdated build
+    /// can be abandoned from another thread
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions {
+            module_cache: None,
+            profile: false,
+            context_warning: Some(DEFAULT_CONTEXT_WARNING),
+            max_contexts: None,
+            show_warning: None,
+            debugger: None,
+            seed: None,
+            fixed_time: None,
+            inputs: None,
+            cancel: None,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn compile_spwn(
     statements: Vec<ast::Statement>,
     source: SpwnSource,
     included_paths: Vec<PathBuf>,
+    options: CompileOptions,
     notes: ParseNotes,
     permissions: BuiltinPermissions,
     initial_level: String,
     std_out: &mut impl Write,
 ) -> Result<Globals, RuntimeError> {
-    //variables that get changed throughout the compiling
-
-    let mut globals = Globals::new(source.clone(), permissions, initial_level, std_out);
-    globals.includes = included_paths;
-
     let print_with_color = |a: &str, color| println!("{}", a.fg(color));
-
-    // if statements.is_empty() {
-    //     return Err(RuntimeError::CustomError(create_error(
-    //         CompilerInfo::from_area(crate::compiler_info::CodeArea {
-    //             file: LocalIntern::new(path),
-    //             pos: (0, 0),
-    //         }),
-    //         "this script is empty",
-    //         &[],
-    //         None,
-    //     )));
-    // }
-    let mut start_context = FullContext::new(&globals);
-    //store at pos 0
-    // store_value(Value::Builtins, 1, &mut globals, &start_context);
-    // store_value(Value::Null, 1, &mut globals, &start_context);
-
-    let start_info = CompilerInfo {
-        ..CompilerInfo::from_area(errors::compiler_info::CodeArea {
-            file: LocalIntern::new(source.clone()),
-            pos: (0, 0),
-        })
-    };
     use std::time::Instant;
 
     //println!("Importing standard library...");
@@ -80,57 +108,16 @@ pub fn compile_spwn(
     #[cfg(not(target_arch = "wasm32"))]
     let start_time = Instant::now();
 
-    if !notes.tag.tags.iter().any(|x| x.0 == "no_std") {
-        import_module(
-            &ImportType::Lib(STD_PATH.to_string()),
-            &mut start_context,
-            &mut globals,
-            start_info.clone(),
-            false,
-        )?;
-
-        if let FullContext::Split(_, _) = start_context {
-            return Err(RuntimeError::CustomError(create_error(
-                start_info,
-                "The standard library can not split the context",
-                &[],
-                None,
-            )));
-        }
-
-        if let Value::Dict(d) = &globals.stored_values[start_context.inner().return_value] {
-            for (a, b, c) in d.iter().map(|(k, v)| (*k, *v, -1)) {
-                start_context.inner().new_redefinable_variable(a, b, c)
-            }
-        } else {
-            return Err(RuntimeError::CustomError(create_error(
-                start_info,
-                "The standard library must return a dictionary",
-                &[],
-                None,
-            )));
-        }
-    }
-
-    compile_scope(&statements, &mut start_context, &mut globals, start_info)?;
-    if !statements.is_empty() {
-        for fc in start_context.with_breaks() {
-            let c = fc.inner();
-            let end_pos = statements.last().unwrap().pos.1;
-            if let Some((r, i)) = c.broken {
-                return Err(RuntimeError::BreakNeverUsedError {
-                    breaktype: r,
-                    info: CompilerInfo::from_area(i),
-                    broke: i,
-                    dropped: CodeArea {
-                        pos: (end_pos, end_pos),
-                        file: LocalIntern::new(source),
-                    },
-                    reason: "the program ended".to_string(),
-                });
-            }
-        }
-    }
+    let globals = compile_script(
+        statements,
+        source,
+        included_paths,
+        options,
+        notes,
+        permissions,
+        initial_level,
+        std_out,
+    )?;
 
     print_with_color("———————————————————————————\n", TColor::White);
 
@@ -197,18 +184,202 @@ pub fn compile_spwn(
     Ok(globals)
 }
 
+// compiles the script without printing the build messages
+#[allow(clippy::result_large_err, clippy::too_many_arguments)]
+pub fn compile_script(
+    statements: Vec<ast::Statement>,
+    source: SpwnSource,
+    included_paths: Vec<PathBuf>,
+    options: CompileOptions,
+    notes: ParseNotes,
+    permissions: BuiltinPermissions,
+    initial_level: String,
+    std_out: &mut impl Write,
+) -> Result<Globals<'_>, RuntimeError> {
+    //variables that get changed throughout the compiling
+
+    let mut globals = Globals::new(source.clone(), permissions, initial_level, std_out);
+    globals.includes = included_paths;
+    globals.module_cache = options.module_cache;
+    if options.profile {
+        globals.profiler = Some(Profiler::new(globals.stored_values.total_stored));
+    }
+    globals.debugger = options.debugger;
+    globals.cancel = options.cancel;
+    globals.show_warning = options.show_warning;
+
+    // if statements.is_empty() {
+    //     return Err(RuntimeError::CustomError(create_error(
+    //         CompilerInfo::from_area(crate::compiler_info::CodeArea {
+    //             file: LocalIntern::new(path),
+    //             pos: (0, 0),
+    //         }),
+    //         "this script is empty",
+    //         &[],
+    //         None,
+    //     )));
+    // }
+    let mut start_context = FullContext::new(&globals);
+    start_context.set_root();
+    //store at pos 0
+    // store_value(Value::Builtins, 1, &mut globals, &start_context);
+    // store_value(Value::Null, 1, &mut globals, &start_context);
+
+    let start_info = CompilerInfo {
+        ..CompilerInfo::from_area(errors::compiler_info::CodeArea {
+            file: LocalIntern::new(source.clone()),
+            pos: (0, 0),
+        })
+    };
+    check_lints(&statements, &notes, &mut globals)?;
+
+    // `context_explosion` can only be allowed or denied for the whole script
+    let (context_warning, max_contexts) =
+        match lints::file_level(&notes.tag, source.clone(), Lint::ContextExplosion) {
+            Level::Allow => (None, options.max_contexts),
+            Level::Warn => (options.context_warning, options.max_contexts),
+            Level::Deny => {
+                let max = match (options.context_warning, options.max_contexts) {
+                    (Some(warn), Some(max)) => Some(warn.min(max)),
+                    (warn, max) => warn.or(max),
+                };
+                (None, max)
+            }
+        };
+    globals.context_limits = ContextLimits::new(context_warning, max_contexts);
+
+    let seed = match options.seed {
+        Some(seed) => Some(seed),
+        None => reproducible::file_seed(&notes.tag, source.clone())?,
+    };
+    globals.reproducible = Reproducible::new(seed, options.fixed_time, options.inputs);
+
+    if !notes.tag.tags.iter().any(|x| x.0 == "no_std") {
+        import_std(&mut start_context, &mut globals, start_info.clone())?;
+    }
+
+    compile_scope(&statements, &mut start_context, &mut globals, start_info)?;
+    if !statements.is_empty() {
+        for fc in start_context.with_breaks() {
+            let c = fc.inner();
+            let end_pos = statements.last().unwrap().pos.1;
+            if let Some((r, i)) = c.broken {
+                return Err(RuntimeError::BreakNeverUsedError {
+                    breaktype: r,
+                    info: CompilerInfo::from_area(i),
+                    broke: i,
+                    dropped: CodeArea {
+                        pos: (end_pos, end_pos),
+                        file: LocalIntern::new(source),
+                    },
+                    reason: "the program ended".to_string(),
+                });
+            }
+        }
+    }
+
+    let values = globals.stored_values.total_stored;
+    if let Some(profiler) = &mut globals.profiler {
+        profiler.finish(values);
+    }
+    Ok(globals)
+}
+
+// imports the standard library and adds its values as variables
+#[allow(clippy::result_large_err)]
+pub fn import_std(
+    start_context: &mut FullContext,
+    globals: &mut Globals,
+    start_info: CompilerInfo,
+) -> Result<(), RuntimeError> {
+    import_module(
+        &ImportType::Lib(STD_PATH.to_string()),
+        start_context,
+        globals,
+        start_info.clone(),
+        false,
+    )?;
+
+    if let FullContext::Split(_, _) = start_context {
+        return Err(RuntimeError::CustomError(create_error(
+            start_info,
+            "The standard library can not split the context",
+            &[],
+            None,
+        )));
+    }
+
+    if let Value::Dict(d) = &globals.stored_values[start_context.inner().return_value] {
+        for (a, b, c) in d.iter().map(|(k, v)| (*k, *v, -1)) {
+            start_context.inner().new_redefinable_variable(a, b, c)
+        }
+        Ok(())
+    } else {
+        Err(RuntimeError::CustomError(create_error(
+            start_info,
+            "The standard library must return a dictionary",
+            &[],
+            None,
+        )))
+    }
+}
+
+// runs the lints on the file that is being compiled, and keeps the warnings.
+// warnings that are denied with `#[deny(...)]` are errors
+#[allow(clippy::result_large_err)]
+pub fn check_lints(
+    statements: &[ast::Statement],
+    notes: &ParseNotes,
+    globals: &mut Globals,
+) -> Result<(), RuntimeError> {
+    let (warnings, mut denied) =
+        crate::lints::lint_file(statements, &notes.tag, globals.path.as_ref().clone());
+    if !denied.is_empty() {
+        return Err(denied.remove(0).into_error());
+    }
+    for warning in warnings {
+        // a module that is imported more than once is linted more than once
+        let duplicate = globals
+            .warnings
+            .iter()
+            .any(|w| w.lint == warning.lint && w.position == warning.position);
+        if !duplicate {
+            globals.warnings.push(warning);
+        }
+    }
+    Ok(())
+}
+
 use crate::compiler_types::EvalExpression;
 
 pub fn compile_scope(
     statements: &[ast::Statement],
     contexts: &mut FullContext,
     globals: &mut Globals,
-    mut info: CompilerInfo,
+    info: CompilerInfo,
 ) -> Result<(), RuntimeError> {
     if contexts.iter().next().is_none() {
         return Ok(());
     }
     contexts.enter_scope();
+    compile_statements(statements, contexts, globals, info)?;
+
+    // TODO: get rid of lifetimes
+
+    contexts.exit_scope();
+
+    Ok(())
+}
+
+/// Compiles the statements in the current scope instead of a new one, so the
+/// variables they define are kept afterwards (like in the REPL)
+#[allow(clippy::result_large_err)]
+pub fn compile_statements(
+    statements: &[ast::Statement],
+    contexts: &mut FullContext,
+    globals: &mut Globals,
+    mut info: CompilerInfo,
+) -> Result<(), RuntimeError> {
     contexts.reset_return_vals(globals);
 
     for statement in statements.iter() {
@@ -225,6 +396,17 @@ pub fn compile_scope(
         //     contexts.len()
         // );
         info.position.pos = statement.pos;
+        debugger::on_statement(contexts, globals, &info);
+        if let Some(cancel) = &globals.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(RuntimeError::CustomError(create_error(
+                    info,
+                    "The build was cancelled",
+                    &[],
+                    None,
+                )));
+            }
+        }
 
         // println!(
         //     "{}:0:{}",
@@ -282,7 +464,12 @@ pub fn compile_scope(
 
                     match globals.stored_values[val].clone() {
                         Value::Dict(ref d) => {
-                            let iter = d.iter().map(|(k, v)| {
+                            let file = info.position.file;
+                            let visible: Vec<_> = d
+                                .iter()
+                                .filter(|(_, v)| !globals.is_hidden(**v, file))
+                                .collect();
+                            let iter = visible.into_iter().map(|(k, v)| {
                                 (
                                     *k,
                                     clone_value(
@@ -356,6 +543,73 @@ pub fn compile_scope(
                 //Value::TypeIndicator(globals.type_id_count)
             }
 
+            Sync(sync) => match &sync.part {
+                None => {
+                    let id = sync_groups::new_sync_group(globals);
+                    for full_context in contexts.iter() {
+                        let fn_context = full_context.inner().start_group;
+                        let val = store_const_value(
+                            Value::SyncGroup(id),
+                            globals,
+                            fn_context,
+                            info.position,
+                        );
+                        let value = ast::ValueBody::Resolved(val)
+                            .to_variable(sync.group.pos)
+                            .to_expression();
+                        do_assignment(
+                            &sync.group.to_expression(),
+                            &Some(value),
+                            full_context,
+                            globals,
+                            &info,
+                            false,
+                            0,
+                            None,
+                        )?;
+                    }
+                }
+                Some((part, body)) => {
+                    sync.group.to_value(contexts, globals, info.clone(), true)?;
+                    for full_context in contexts.iter() {
+                        let (_, val) = full_context.inner_value();
+                        let id = match &globals.stored_values[val] {
+                            Value::SyncGroup(id) => *id,
+                            a => {
+                                return Err(RuntimeError::TypeError {
+                                    expected: "@sync_group".to_string(),
+                                    found: a.get_type_str(globals),
+                                    val_def: globals.get_area(val),
+                                    info,
+                                })
+                            }
+                        };
+                        part.eval(full_context, globals, info.clone(), true)?;
+                        for full_context in full_context.iter() {
+                            let (context, part_val) = full_context.inner_value();
+                            let key = {
+                                use std::hash::Hasher;
+                                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                                globals.stored_values[part_val].hash(&mut hasher, globals);
+                                hasher.finish()
+                            };
+                            let part_id = sync_groups::get_part(globals, id, key);
+                            sync_groups::start_part(context, globals, id, part_id, &info);
+
+                            let groups_start = globals.closed_groups + 1;
+                            let objects_start = globals.uid_counter + 1;
+                            compile_scope(body, full_context, globals, info.clone())?;
+
+                            let part = &mut globals.sync_groups[id].parts[part_id];
+                            part.groups_made
+                                .push(groups_start..globals.closed_groups + 1);
+                            part.objects_made
+                                .push(objects_start..globals.uid_counter + 1);
+                        }
+                    }
+                }
+            },
+
             If(if_stmt) => {
                 if_stmt
                     .condition
@@ -518,15 +772,17 @@ pub fn compile_scope(
                     params.insert(1, ObjParam::Number(1268.0));
                     (*globals).trigger_order += 1.0;
 
-                    (*globals).func_ids[context.func_id].obj_list.push((
-                        GdObj {
-                            params,
+                    let obj = GdObj {
+                        params,
 
-                            ..context_trigger(context, &mut globals.uid_counter)
-                        }
-                        .context_parameters(context),
-                        TriggerOrder(globals.trigger_order),
-                    ))
+                        ..context_trigger(context, &mut globals.uid_counter)
+                    }
+                    .context_parameters(context);
+                    globals.record_origin(obj.unique_id, &info);
+
+                    globals.func_ids[context.func_id]
+                        .obj_list
+                        .push((obj, TriggerOrder(globals.trigger_order)))
                 }
             }
 
@@ -693,7 +949,13 @@ pub fn compile_scope(
                         }
                         Value::Dict(d) => {
                             // its a dict!
-                            for (k, v) in d {
+                            // private entries from other files are skipped
+                            let file = info.position.file;
+                            let visible: Vec<_> = d
+                                .into_iter()
+                                .filter(|(_, v)| !globals.is_hidden(*v, file))
+                                .collect();
+                            for (k, v) in visible {
                                 // going through the dict items
                                 full_context.disable_breaks(BreakType::ContinueLoop);
 
@@ -874,6 +1136,17 @@ pub fn compile_scope(
                     globals.pop_preserved();
                 }
             }
+            // parse_spwn doesn't return statements with syntax errors,
+            // but they can come from parse_spwn_partial
+            Invalid(_) => {
+                return Err(RuntimeError::CustomError(create_error(
+                    info,
+                    "This statement has a syntax error",
+                    &[],
+                    None,
+                )))
+            }
+
             Break => {
                 //set all contexts to broken
                 for c in contexts.iter() {
@@ -933,29 +1206,123 @@ pub fn compile_scope(
                     };
                 }
                 if !statement.arrow {
+                    check_contexts(contexts, globals, &info)?;
                     break;
                 }
             }
 
-            Error(e) => {
-                let mut errors = Vec::new();
+            ImportNames(i) => {
+                import_module(&i.module, contexts, globals, info.clone(), false)?;
+                for full_context in contexts.iter() {
+                    let (context, module) = full_context.inner_value();
+                    let fn_context = context.start_group;
 
-                e.message.eval(contexts, globals, info.clone(), true)?;
-                for c in contexts.iter() {
-                    let err = globals.stored_values[c.inner().return_value]
-                        .clone()
-                        .to_str(globals);
-                    errors.push((info.position, err))
+                    let members = match &globals.stored_values[module] {
+                        Value::Dict(d) => d.clone(),
+                        a => {
+                            return Err(RuntimeError::TypeError {
+                                expected: "dictionary".to_string(),
+                                found: a.get_type_str(globals),
+                                val_def: globals.get_area(module),
+                                info,
+                            })
+                        }
+                    };
+                    for name in &i.names {
+                        let area = CodeArea {
+                            pos: name.pos,
+                            ..info.position
+                        };
+                        let val = match members.get(&name.name) {
+                            Some(v) if globals.is_hidden(*v, info.position.file) => {
+                                return Err(private_error(&name.name, *v, area, globals, &info));
+                            }
+                            Some(v) => *v,
+                            None => {
+                                return Err(RuntimeError::CustomError(create_error(
+                                    info.clone(),
+                                    "Imported name not found",
+                                    &[(area, &format!("The module has no `{}`", name.name))],
+                                    None,
+                                )))
+                            }
+                        };
+                        let stored = clone_value(
+                            val,
+                            globals,
+                            fn_context,
+                            !globals.is_mutable(val),
+                            globals.get_area(val),
+                        );
+                        context.new_redefinable_variable(
+                            name.alias.unwrap_or(name.name),
+                            stored,
+                            0,
+                        );
+                    }
                 }
+            }
+
+            Try(t) => {
+                for full_context in contexts.iter() {
+                    // if the try block fails, the context goes back to how it was
+                    // before it, and the triggers and objects it added are removed.
+                    // changes to values made before the error are kept
+                    let before = full_context.clone();
+                    let objects = globals.objects.len();
+                    let triggers: Vec<usize> =
+                        globals.func_ids.iter().map(|f| f.obj_list.len()).collect();
+                    let err = match compile_scope(&t.try_body, full_context, globals, info.clone())
+                    {
+                        Ok(()) => continue,
+                        Err(err) => err,
+                    };
+                    *full_context = before;
+                    globals.objects.truncate(objects);
+                    globals.func_ids.truncate(triggers.len());
+                    for (func_id, len) in globals.func_ids.iter_mut().zip(triggers) {
+                        func_id.obj_list.truncate(len);
+                    }
 
-                let mut new_errors = Vec::new();
-                for (area, msg) in errors.iter() {
-                    new_errors.push((*area, msg.as_str()))
+                    if let Some(symbol) = &t.catch_symbol {
+                        let error = error_value(
+                            &err,
+                            globals,
+                            full_context.inner().start_group,
+                            info.position,
+                        );
+                        do_assignment(
+                            &symbol.to_expression(),
+                            &Some(
+                                ast::ValueBody::Resolved(error)
+                                    .to_variable(symbol.pos)
+                                    .to_expression(),
+                            ),
+                            full_context,
+                            globals,
+                            &info,
+                            false,
+                            -1, // removed at the end of the catch block
+                            None,
+                        )?;
+                    }
+                    compile_scope(&t.catch_body, full_context, globals, info.clone())?;
                 }
+            }
 
-                let err = create_error(info, "Runtime Error", &new_errors, None);
+            Error(e) => {
+                let mut messages = Vec::new();
 
-                return Err(RuntimeError::CustomError(err));
+                e.message.eval(contexts, globals, info.clone(), true)?;
+                for c in contexts.iter() {
+                    let err = match globals.stored_values[c.inner().return_value].clone() {
+                        Value::Str(s) => s,
+                        val => val.to_str(globals),
+                    };
+                    messages.push(err)
+                }
+
+                return Err(RuntimeError::ThrownError { messages, info });
             }
         }
 
@@ -966,6 +1333,7 @@ pub fn compile_scope(
             //resetting the context if async
             let mut list = c;
 
+            let stored_count = list.len();
             for context in contexts.with_breaks() {
                 if let Some((r, i)) = context.inner().broken {
                     if let BreakType::Macro(_, true) = r {
@@ -981,11 +1349,16 @@ pub fn compile_scope(
                     }
                 }
             }
+            // the contexts that returned inside the arrow statement are added to the ones before it
+            globals
+                .context_limits
+                .record_split(info.position, list.len() - stored_count);
             *contexts = FullContext::stack(&mut list.into_iter()).unwrap();
         }
 
         //try to merge contexts
-        merge_all_contexts(contexts, globals, false);
+        merge_all_contexts(contexts, globals, false, &info);
+        check_contexts(contexts, globals, &info)?;
 
         if contexts.iter().next().is_none() {
             break;
@@ -1005,12 +1378,42 @@ pub fn compile_scope(
             globals.collect_garbage(contexts);
         }
     }
+    Ok(())
+}
 
-    // TODO: get rid of lifetimes
+// the dictionary a caught error is turned into
+fn error_value(
+    err: &RuntimeError,
+    globals: &mut Globals,
+    fn_context: Group,
+    area: CodeArea,
+) -> StoredValue {
+    let position = err.info().position;
+    let file = match position.file.as_ref() {
+        SpwnSource::File(path) | SpwnSource::BuiltIn(path) => path.display().to_string(),
+        SpwnSource::String(_) => "<source>".to_string(),
+    };
 
-    contexts.exit_scope();
+    let mut location = AHashMap::new();
+    for (key, val) in [
+        ("file", Value::Str(file)),
+        ("start", Value::Number(position.pos.0 as f64)),
+        ("end", Value::Number(position.pos.1 as f64)),
+    ] {
+        let stored = store_const_value(val, globals, fn_context, area);
+        location.insert(LocalIntern::new(key.to_string()), stored);
+    }
 
-    Ok(())
+    let mut dict = AHashMap::new();
+    for (key, val) in [
+        ("message", Value::Str(err.message())),
+        ("kind", Value::Str(err.kind().to_string())),
+        ("location", Value::Dict(location)),
+    ] {
+        let stored = store_const_value(val, globals, fn_context, area);
+        dict.insert(LocalIntern::new(key.to_string()), stored);
+    }
+    store_const_value(Value::Dict(dict), globals, fn_context, area)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -1585,6 +1988,7 @@ pub fn merge_all_contexts(
     contexts: &mut FullContext,
     globals: &mut Globals,
     check_return_vals: bool,
+    info: &CompilerInfo,
 ) {
     if let FullContext::Split(_, _) = contexts {
         let mut broken = Vec::new();
@@ -1599,7 +2003,7 @@ pub fn merge_all_contexts(
 
         if not_broken.len() > 1 {
             loop {
-                if !merge_contexts(&mut not_broken, globals, check_return_vals) {
+                if !merge_contexts(&mut not_broken, globals, check_return_vals, info) {
                     break;
                 }
             }
@@ -1700,16 +2104,39 @@ pub fn get_import_path(
     })
 }
 
+#[allow(clippy::result_large_err)]
 pub fn import_module(
     path: &ImportType,
     contexts: &mut FullContext,
     globals: &mut Globals,
     info: CompilerInfo,
     forced: bool,
+) -> Result<(), RuntimeError> {
+    profile(
+        globals,
+        || Frame::Import(path.clone()),
+        |globals| compile_module(path, contexts, globals, info, forced),
+    )
+}
+
+#[allow(clippy::result_large_err)]
+fn compile_module(
+    path: &ImportType,
+    contexts: &mut FullContext,
+    globals: &mut Globals,
+    info: CompilerInfo,
+    forced: bool,
 ) -> Result<(), RuntimeError> {
     if !forced {
         if let Some(ret) = globals.prev_imports.get(path).cloned() {
             merge_impl(&mut globals.implementations, &ret.1);
+            if let Some(files) = globals.module_files.get(path).cloned() {
+                globals.imported_files.extend(files);
+            }
+            // so modules importing this one aren't cached either
+            if globals.impure_modules.contains(path) {
+                globals.impure_calls += 1;
+            }
             for c in contexts.iter() {
                 c.inner().return_value = ret.0;
             }
@@ -1804,6 +2231,35 @@ pub fn import_module(
         }
     };
 
+    if let SpwnSource::File(file) = &module_path {
+        globals
+            .package_permissions
+            .load(file, &globals.permissions, &info)?;
+    }
+
+    let files_start = globals.imported_files.len();
+    globals
+        .imported_files
+        .push((module_path.clone(), module_cache::hash_text(&unparsed)));
+
+    if !forced {
+        if let Some(dir) = globals.module_cache.clone() {
+            if let Some((output, imp, files)) = module_cache::load(&dir, &module_path, globals) {
+                merge_impl(&mut globals.implementations, &imp);
+                for c in contexts.iter() {
+                    c.inner().return_value = output;
+                }
+                globals.imported_files.truncate(files_start);
+                globals.imported_files.extend(files.iter().cloned());
+                globals.module_files.insert(path.clone(), files);
+                globals.prev_imports.insert(path.clone(), (output, imp));
+                globals.built_in_path = stored_built_in_path;
+                return Ok(());
+            }
+        }
+    }
+    let module_start = module_cache::ModuleStart::new(globals);
+
     let (parsed, notes) =
         match parser::parser::parse_spwn(unparsed, module_path.clone(), BUILTIN_NAMES) {
             Ok(p) => p,
@@ -1811,6 +2267,7 @@ pub fn import_module(
         };
 
     let mut start_context = FullContext::new(globals);
+    start_context.set_root();
 
     globals.push_new_preserved();
     for c in contexts.with_breaks() {
@@ -1872,7 +2329,7 @@ pub fn import_module(
 
     let stored_path = globals.path;
 
-    (*globals).path = LocalIntern::new(module_path);
+    globals.path = LocalIntern::new(module_path.clone());
 
     let mut new_info = info.clone();
 
@@ -1883,6 +2340,16 @@ pub fn import_module(
         new_info.current_module = l.clone();
     }
 
+    // only the user's own modules are linted, not libraries and the files they import
+    if new_info.current_module.is_empty() {
+        if let Err(err) = check_lints(&parsed, &notes, globals) {
+            return Err(RuntimeError::PackageError {
+                err: Box::new(err),
+                info,
+            });
+        }
+    }
+
     match compile_scope(&parsed, &mut start_context, globals, new_info) {
         Ok(_) => (),
         Err(err) => {
@@ -1968,13 +2435,36 @@ pub fn import_module(
     }
 
     if save_value {
-        globals.prev_imports.insert(
-            path.clone(),
-            (
-                output_saved.unwrap_or(globals.NULL_STORAGE),
-                impl_saved.unwrap_or_default(),
-            ),
-        );
+        let output = output_saved.unwrap_or(globals.NULL_STORAGE);
+        let impl_saved = impl_saved.unwrap_or_default();
+
+        let mut files = Vec::new();
+        for file in &globals.imported_files[files_start..] {
+            if !files.contains(file) {
+                files.push(file.clone());
+            }
+        }
+        let impure = module_start.is_impure(globals);
+        if impure {
+            globals.impure_modules.insert(path.clone());
+        }
+        if let Some(dir) = &globals.module_cache {
+            if !forced && !impure && !module_start.has_side_effects(globals) {
+                module_cache::save(
+                    dir,
+                    &module_path,
+                    &files,
+                    output,
+                    &impl_saved,
+                    &module_start,
+                    globals,
+                );
+            }
+        }
+        globals.module_files.insert(path.clone(), files);
+        globals
+            .prev_imports
+            .insert(path.clone(), (output, impl_saved));
     }
 
     Ok(())
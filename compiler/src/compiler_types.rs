@@ -1,6 +1,7 @@
 use crate::builtins::*;
 
 use crate::context::*;
+use crate::context_limits::check_contexts;
 use crate::globals::Globals;
 use crate::leveldata::*;
 use crate::value::*;
@@ -16,6 +17,7 @@ use errors::compiler_info::CompilerInfo;
 use ahash::AHashMap;
 
 use crate::compiler::compile_scope;
+use crate::profiler::{profile, Frame};
 
 use internment::LocalIntern;
 use shared::StoredValue;
@@ -39,11 +41,28 @@ pub struct FunctionId {
     pub obj_list: Vec<(GdObj, TriggerOrder)>, //list of objects in this function id, + their order id
 }
 
+pub type SyncGroupId = usize;
 pub type SyncPartId = usize;
-#[derive(Clone)]
+
+// parts of a sync group never run at the same time, so groups that are only
+// used inside one part can be reused by the other parts (see `sync_groups.rs`)
+#[derive(Clone, Debug)]
 pub struct SyncGroup {
-    pub parts: Vec<SyncPartId>,
-    pub groups_used: Vec<ArbitraryId>, // groups that are already used by this sync group, and can be reused in later parts
+    pub parts: Vec<SyncPart>,
+    // toggled off whenever a part starts
+    pub toggle_group: Group,
+}
+
+#[derive(Clone, Debug)]
+pub struct SyncPart {
+    pub key: u64, // hash of the value the part was indexed with
+    // toggled on when this part starts
+    pub toggle_group: Group,
+    // every time the part was compiled, the arbitrary groups and object ids that were made inside it
+    pub groups_made: Vec<std::ops::Range<ArbitraryId>>,
+    pub objects_made: Vec<std::ops::Range<usize>>,
+    // the unique ids of the toggle triggers that start the part
+    pub start_triggers: Vec<usize>,
 }
 
 pub fn handle_operator(
@@ -172,6 +191,18 @@ pub fn convert_to_int(num: f64, info: &CompilerInfo) -> Result<i32, RuntimeError
             None,
         )));
     }
+    // `as` would saturate (or turn NaN into 0) without telling anyone
+    if !(i32::MIN as f64..=i32::MAX as f64).contains(&num) {
+        return Err(RuntimeError::CustomError(create_error(
+            info.clone(),
+            &format!(
+                "{} is out of the range of integers that can be used here",
+                num
+            ),
+            &[],
+            None,
+        )));
+    }
     Ok(num as i32)
 }
 
@@ -204,6 +235,11 @@ impl From<ast::Operator> for Builtin {
             IntDividedBy => IntdividedByOp,
             Star => TimesOp,
             Modulo => ModOp,
+            BitAnd => BitAndOp,
+            BitOr => BitOrOp,
+            BitXor => BitXorOp,
+            ShiftLeft => ShiftLeftOp,
+            ShiftRight => ShiftRightOp,
             Power => PowOp,
             Plus => PlusOp,
             Minus => MinusOp,
@@ -310,7 +346,24 @@ impl EvalExpression for ast::Expression {
     }
 }
 
+#[allow(clippy::result_large_err)]
 pub fn execute_macro(
+    macro_call: (MacroFuncData, Vec<ast::Argument>),
+    contexts: &mut FullContext,
+    globals: &mut Globals,
+    parent: StoredValue,
+    info: CompilerInfo,
+) -> Result<(), RuntimeError> {
+    let call = info.position;
+    profile(
+        globals,
+        || Frame::Macro(call),
+        |globals| run_macro(macro_call, contexts, globals, parent, info),
+    )
+}
+
+#[allow(clippy::result_large_err)]
+fn run_macro(
     (m, args): (MacroFuncData, Vec<ast::Argument>),
     contexts: &mut FullContext,
     globals: &mut Globals,
@@ -666,6 +719,9 @@ Should be used like this: value.macro(arguments)",
             }
         }
         //dbg!(out_contexts.len(), info.position);
+        globals
+            .context_limits
+            .record_split(info.position, out_contexts.len().saturating_sub(1));
         if !out_contexts.is_empty() {
             *full_context = FullContext::stack(&mut out_contexts.into_iter()).unwrap();
         }
@@ -676,6 +732,8 @@ Should be used like this: value.macro(arguments)",
             }
         }
     }
+    // a macro that splits can be called many times in one statement
+    check_contexts(contexts, globals, &info)?;
 
     globals.pop_preserved();
     Ok(())
@@ -771,16 +829,17 @@ pub fn eval_dict(
         for (expr_index, def) in dict.iter().enumerate() {
             match def {
                 ast::DictDef::Def(d) => {
-                    dict_out.insert(
-                        d.0,
-                        clone_value(
-                            results[expr_index],
-                            globals,
-                            context.start_group,
-                            !globals.is_mutable(results[expr_index]),
-                            info.position,
-                        ),
+                    let entry = clone_value(
+                        results[expr_index],
+                        globals,
+                        context.start_group,
+                        !globals.is_mutable(results[expr_index]),
+                        info.position,
                     );
+                    if marked_private(&d.1) {
+                        globals.stored_values.map[entry].private = true;
+                    }
+                    dict_out.insert(d.0, entry);
                 }
                 ast::DictDef::Extract(_) => {
                     let val = clone_and_get_value(
@@ -790,7 +849,11 @@ pub fn eval_dict(
                         !globals.is_mutable(results[expr_index]),
                     );
                     dict_out.extend(match val.clone() {
-                        Value::Dict(d) => d.clone(),
+                        // private entries from other files are left out
+                        Value::Dict(d) => d
+                            .into_iter()
+                            .filter(|(_, v)| !globals.is_hidden(*v, info.position.file))
+                            .collect::<Vec<_>>(),
                         a => {
                             return Err(RuntimeError::TypeError {
                                 expected: "dictionary".to_string(),
@@ -875,6 +938,9 @@ impl ToTriggerFunc for ast::CompoundStatement {
             );
 
             if !carried_breaks.is_empty() {
+                globals
+                    .context_limits
+                    .record_split(info.position, carried_breaks.len());
                 prev_context = FullContext::Split(
                     prev_context.clone().into(),
                     FullContext::stack(&mut carried_breaks.into_iter())
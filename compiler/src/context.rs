@@ -5,6 +5,7 @@ use crate::leveldata::*;
 use crate::value::{strict_value_equality, Value};
 use crate::value_storage::{clone_value, store_val_m};
 use errors::compiler_info::CodeArea;
+use errors::compiler_info::CompilerInfo;
 
 //use std::boxed::Box;
 use ahash::AHashMap;
@@ -40,10 +41,14 @@ pub enum FullContext {
 }
 
 impl FullContext {
+    // the root context pointer has to be set with `set_root` once the
+    // context is in its final place, since it moves when returned from here
     pub fn new(globals: &Globals) -> Self {
-        let mut new = FullContext::Single(Context::new(globals));
-        new.inner().root_context_ptr = &mut new;
-        new
+        FullContext::Single(Context::new(globals))
+    }
+
+    pub fn set_root(&mut self) {
+        self.inner().root_context_ptr = self;
     }
     pub fn inner(&mut self) -> &mut Context {
         match self {
@@ -66,6 +71,21 @@ impl FullContext {
         }
     }
 
+    /// How many contexts there are, including the broken ones
+    pub fn count(&self) -> usize {
+        let mut count = 0;
+        let mut node = self;
+        loop {
+            match node {
+                FullContext::Split(left, right) => {
+                    count += left.count();
+                    node = right;
+                }
+                FullContext::Single(_) => return count + 1,
+            }
+        }
+    }
+
     pub fn enter_scope(&mut self) {
         for context in self.with_breaks() {
             for stack in context.inner().variables.values_mut() {
@@ -386,6 +406,7 @@ pub fn merge_contexts(
     contexts: &mut Vec<Context>,
     globals: &mut Globals,
     check_return_vals: bool,
+    info: &CompilerInfo,
 ) -> bool {
     let mut mergable_ind = Vec::<usize>::new();
     let mut ref_c = 0;
@@ -454,15 +475,17 @@ pub fn merge_contexts(
         params.insert(1, ObjParam::Number(1268.0));
         (*globals).trigger_order += 1.0;
 
-        (*globals).func_ids[context.func_id].obj_list.push((
-            GdObj {
-                params,
+        let obj = GdObj {
+            params,
 
-                ..context_trigger(context, &mut globals.uid_counter)
-            }
-            .context_parameters(context),
-            TriggerOrder(globals.trigger_order),
-        ))
+            ..context_trigger(context, &mut globals.uid_counter)
+        }
+        .context_parameters(context);
+        globals.record_origin(obj.unique_id, info);
+
+        globals.func_ids[context.func_id]
+            .obj_list
+            .push((obj, TriggerOrder(globals.trigger_order)))
     };
     add_spawn_trigger(&contexts[ref_c]);
     for i in mergable_ind.iter() {
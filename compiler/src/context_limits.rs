@@ -0,0 +1,123 @@
+//! Keeps track of how many contexts are being compiled at once
+//!
+//! Macros that return in more than one context (like a `return` inside a
+//! trigger function) split the compilation, and everything after the split is
+//! compiled once per context. Splitting in a loop makes the number of contexts
+//! grow exponentially, so the compiler remembers where the splits happened and
+//! warns (or stops) when a scope has too many contexts.
+
+use ahash::AHashMap;
+use errors::compiler_info::{CodeArea, CompilerInfo};
+use errors::{Lint, RuntimeError, Warning};
+
+use crate::context::FullContext;
+use crate::globals::Globals;
+
+/// The default for `CompileOptions::context_warning`
+pub const DEFAULT_CONTEXT_WARNING: usize = 256;
+
+// how many split origins are shown in the warning or error
+const MAX_ORIGINS: usize = 5;
+
+#[derive(Debug, Default)]
+pub struct ContextLimits {
+    // warn the first time a scope has more contexts than this
+    warn_at: Option<usize>,
+    // fail when a scope has more contexts than this
+    max: Option<usize>,
+    warned: bool,
+    // how many contexts were added by the splits at each area
+    splits: AHashMap<CodeArea, usize>,
+    /// The most contexts a scope has had at once
+    pub peak: usize,
+}
+
+impl ContextLimits {
+    pub fn new(warn_at: Option<usize>, max: Option<usize>) -> Self {
+        ContextLimits {
+            warn_at,
+            max,
+            ..Default::default()
+        }
+    }
+
+    /// Remembers that the expression at `area` split one context into `added + 1` contexts
+    pub fn record_split(&mut self, area: CodeArea, added: usize) {
+        if added > 0 {
+            *self.splits.entry(area).or_default() += added;
+        }
+    }
+
+    /// The areas that split the most contexts, and how many contexts they added
+    pub fn origins(&self) -> Vec<(CodeArea, usize)> {
+        let mut origins: Vec<_> = self.splits.iter().map(|(a, n)| (*a, *n)).collect();
+        // the area is only compared so the order doesn't depend on the hash map
+        origins.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.pos.cmp(&b.0.pos)));
+        origins.truncate(MAX_ORIGINS);
+        origins
+    }
+}
+
+/// Checks the number of contexts in the scope that is being compiled, and warns or
+/// fails if there are too many. `info` should be the statement that was just compiled.
+#[allow(clippy::result_large_err)]
+pub fn check_contexts(
+    contexts: &FullContext,
+    globals: &mut Globals,
+    info: &CompilerInfo,
+) -> Result<(), RuntimeError> {
+    let limits = &mut globals.context_limits;
+    // without any splits there's only ever one context
+    if limits.splits.is_empty() {
+        return Ok(());
+    }
+    let count = contexts.count();
+    limits.peak = limits.peak.max(count);
+
+    if let Some(max) = limits.max {
+        if count > max {
+            return Err(RuntimeError::TooManyContexts {
+                limit: max,
+                origins: limits.origins(),
+                info: info.clone(),
+            });
+        }
+    }
+
+    match limits.warn_at {
+        Some(warn_at) if count > warn_at && !limits.warned => {
+            limits.warned = true;
+            let mut labels = vec![(
+                info.position,
+                format!("This is compiled in {} contexts", count),
+            )];
+            labels.extend(limits.origins().into_iter().map(|(area, added)| {
+                (
+                    area,
+                    format!(
+                        "Split into {} more context{} here",
+                        added,
+                        if added == 1 { "" } else { "s" }
+                    ),
+                )
+            }));
+            let warning = Warning {
+                lint: Lint::ContextExplosion,
+                message: format!(
+                    "More than {} contexts are being compiled at once, which makes the build slow. Use --max-contexts to stop the build when it gets stuck",
+                    warn_at
+                ),
+                position: info.position,
+                labels,
+            };
+            // the other warnings are shown after the build, which this one
+            // might never get to
+            if let Some(show) = globals.show_warning {
+                show(&warning);
+            }
+            globals.warnings.push(warning);
+        }
+        _ => (),
+    }
+    Ok(())
+}
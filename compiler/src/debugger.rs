@@ -0,0 +1,160 @@
+//! Pauses the compilation so it can be stepped through, for `spwn dap`
+//!
+//! The compiler asks the debugger before every statement and at every
+//! `$.breakpoint()` call if it should stop there. When it stops, the
+//! [`DebugClient`] gets the contexts and values the statement is compiled
+//! with, and tells the debugger how to carry on.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use errors::compiler_info::{CodeArea, CompilerInfo};
+
+use crate::context::FullContext;
+use crate::globals::Globals;
+
+/// How the compilation carries on after stopping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Runs until the next breakpoint
+    Continue,
+    /// Stops at the next statement, even inside a macro that is called
+    In,
+    /// Stops at the next statement that isn't inside a macro that is called
+    Over,
+    /// Stops at the next statement after the current macro returns
+    Out,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The first statement, if the debugger was made with `stop_on_entry`
+    Entry,
+    /// A breakpoint from the client
+    Breakpoint,
+    /// A `$.breakpoint()` call
+    BreakpointCall,
+    Step,
+}
+
+/// Everything that can be looked at while the compilation is stopped
+pub struct Paused<'p, 'a> {
+    pub reason: StopReason,
+    /// The statement or `$.breakpoint()` call it stopped at
+    pub area: CodeArea,
+    /// The macro calls it's inside of, outermost first
+    pub call_stack: &'p [CodeArea],
+    pub contexts: &'p mut FullContext,
+    pub globals: &'p mut Globals<'a>,
+}
+
+pub trait DebugClient {
+    /// Returns `true` if there's a breakpoint on the statement at `area`
+    fn has_breakpoint(&mut self, area: CodeArea) -> bool;
+
+    /// Returns `false` if stepping shouldn't stop at `area`, like in libraries
+    fn can_stop(&mut self, _area: CodeArea) -> bool {
+        true
+    }
+
+    /// Called when the compilation stops, and returns how it should carry on
+    fn paused(&mut self, paused: Paused) -> Step;
+}
+
+#[derive(Clone)]
+pub struct Debugger {
+    client: Rc<RefCell<dyn DebugClient>>,
+    step: Step,
+    // how many macro calls deep the last step was asked for
+    depth: usize,
+    stopped: bool,
+}
+
+impl fmt::Debug for Debugger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Debugger")
+            .field("step", &self.step)
+            .field("depth", &self.depth)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Debugger {
+    pub fn new(client: Rc<RefCell<dyn DebugClient>>, stop_on_entry: bool) -> Self {
+        Debugger {
+            client,
+            step: if stop_on_entry {
+                Step::In
+            } else {
+                Step::Continue
+            },
+            depth: 0,
+            stopped: false,
+        }
+    }
+
+    fn step_stops(&self, depth: usize) -> bool {
+        match self.step {
+            Step::Continue => false,
+            Step::In => true,
+            Step::Over => depth <= self.depth,
+            Step::Out => depth < self.depth,
+        }
+    }
+}
+
+fn pause(
+    reason: StopReason,
+    contexts: &mut FullContext,
+    globals: &mut Globals,
+    info: &CompilerInfo,
+) {
+    let client = match &globals.debugger {
+        Some(debugger) => debugger.client.clone(),
+        None => return,
+    };
+    let step = client.borrow_mut().paused(Paused {
+        reason,
+        area: info.position,
+        call_stack: &info.call_stack,
+        contexts,
+        globals,
+    });
+    if let Some(debugger) = &mut globals.debugger {
+        debugger.step = step;
+        debugger.depth = info.call_stack.len();
+        debugger.stopped = true;
+    }
+}
+
+/// Called before every statement, with `info` at the statement
+pub fn on_statement(contexts: &mut FullContext, globals: &mut Globals, info: &CompilerInfo) {
+    let debugger = match &globals.debugger {
+        Some(debugger) => debugger,
+        None => return,
+    };
+    // the statement is skipped if every context has returned or broken out
+    if contexts.iter().next().is_none() {
+        return;
+    }
+    let mut client = debugger.client.borrow_mut();
+    let reason = if client.has_breakpoint(info.position) {
+        StopReason::Breakpoint
+    } else if debugger.step_stops(info.call_stack.len()) && client.can_stop(info.position) {
+        if debugger.stopped {
+            StopReason::Step
+        } else {
+            StopReason::Entry
+        }
+    } else {
+        return;
+    };
+    drop(client);
+    pause(reason, contexts, globals, info);
+}
+
+/// Called by `$.breakpoint()`, which always stops
+pub fn on_breakpoint_call(contexts: &mut FullContext, globals: &mut Globals, info: &CompilerInfo) {
+    pause(StopReason::BreakpointCall, contexts, globals, info);
+}
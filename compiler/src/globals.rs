@@ -1,4 +1,5 @@
 use errors::RuntimeError;
+use errors::Warning;
 use internment::LocalIntern;
 use shared::BreakType;
 use shared::ImportType;
@@ -12,12 +13,18 @@ use crate::context::VariableData;
 use errors::compiler_info::CodeArea;
 
 use crate::context::FullContext;
-use crate::leveldata::GdObj;
+use crate::context_limits::ContextLimits;
+use crate::debugger::Debugger;
+use crate::leveldata::{GdObj, ObjectOrigin};
 
 use crate::compiler_types::*;
+use crate::module_cache::ModuleFiles;
+use crate::package_permissions::PackagePermissions;
+use crate::profiler::Profiler;
+use crate::reproducible::Reproducible;
 use crate::value::*;
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 
 //use std::boxed::Box;
 use crate::value_storage::*;
@@ -25,6 +32,8 @@ use errors::compiler_info::CompilerInfo;
 
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 #[allow(non_snake_case)]
 pub struct Globals<'a> {
@@ -50,16 +59,40 @@ pub struct Globals<'a> {
     pub initial_objects: Option<StoredValue>,
 
     pub prev_imports: AHashMap<ImportType, (StoredValue, Implementations)>,
+    // the directory `#[cache_output]` modules are saved to between builds
+    pub module_cache: Option<PathBuf>,
+    // every file read by an import, with a hash of its contents
+    pub imported_files: ModuleFiles,
+    // the files each module in `prev_imports` read
+    pub module_files: AHashMap<ImportType, ModuleFiles>,
+    // how many times a built-in function that depends on more than its arguments
+    // (like `$.readfile` or `$.random`) was called. modules that call one aren't cached
+    pub impure_calls: usize,
+    // the modules in `prev_imports` that called one
+    pub impure_modules: AHashSet<ImportType>,
 
     pub trigger_order: f64,
 
     pub uid_counter: usize,
+    pub object_origins: AHashMap<usize, ObjectOrigin>,
     pub implementations: Implementations,
 
     pub sync_groups: Vec<SyncGroup>,
     pub includes: Vec<PathBuf>,
 
     pub permissions: BuiltinPermissions,
+    // the narrowed permissions of the pckp libraries that were imported
+    pub package_permissions: PackagePermissions,
+    pub reproducible: Reproducible,
+    // warnings from the lints, shown after the build
+    pub warnings: Vec<Warning>,
+    pub profiler: Option<Profiler>,
+    pub context_limits: ContextLimits,
+    // see `CompileOptions::show_warning`
+    pub show_warning: Option<fn(&Warning)>,
+    pub debugger: Option<Debugger>,
+    // set from another thread to stop the build, see `CompileOptions::cancel`
+    pub cancel: Option<Arc<AtomicBool>>,
 
     pub TYPE_MEMBER_NAME: LocalIntern<String>,
     pub SELF_MEMBER_NAME: LocalIntern<String>,
@@ -124,6 +157,24 @@ impl<'a> Globals<'a> {
         }
     }
 
+    // private values can't be used outside of the file they were defined in
+    pub fn is_hidden(&self, p: StoredValue, file: LocalIntern<SpwnSource>) -> bool {
+        match self.stored_values.map.get(p) {
+            Some(val) => val.private && val.def_area.file != file,
+            None => unreachable!(),
+        }
+    }
+
+    pub fn record_origin(&mut self, unique_id: usize, info: &CompilerInfo) {
+        self.object_origins.insert(
+            unique_id,
+            ObjectOrigin {
+                area: info.position,
+                call_stack: info.call_stack.clone(),
+            },
+        );
+    }
+
     pub fn get_type_str(&self, p: StoredValue) -> String {
         let val = &self.stored_values[p];
         let typ = match val {
@@ -242,9 +293,15 @@ Arrays are used to store lists of values at compile time.
             type_ids: AHashMap::default(),
 
             prev_imports: AHashMap::default(),
+            module_cache: None,
+            imported_files: Vec::new(),
+            module_files: AHashMap::default(),
+            impure_calls: 0,
+            impure_modules: AHashSet::default(),
             type_id_count: 0,
             trigger_order: 0.0,
             uid_counter: 0,
+            object_origins: AHashMap::default(),
 
             //val_id: storage.map.len() as StoredValue,
             stored_values: storage,
@@ -256,13 +313,18 @@ Arrays are used to store lists of values at compile time.
             objects: Vec::new(),
             initial_string,
             implementations: AHashMap::default(),
-            sync_groups: vec![SyncGroup {
-                parts: vec![0],
-                groups_used: Vec::new(),
-            }],
+            sync_groups: Vec::new(),
             includes: Vec::new(),
 
             permissions,
+            package_permissions: PackagePermissions::default(),
+            reproducible: Reproducible::default(),
+            warnings: Vec::new(),
+            profiler: None,
+            context_limits: ContextLimits::default(),
+            show_warning: None,
+            debugger: None,
+            cancel: None,
             TYPE_MEMBER_NAME: LocalIntern::new(String::from("type")),
             SELF_MEMBER_NAME: LocalIntern::new(String::from("self")),
             BUILTIN_STORAGE: builtin_storage,
@@ -305,6 +367,8 @@ Arrays are used to store lists of values at compile time.
         add_type("pattern", 18);
         add_type("object_key", 19);
         add_type("epsilon", 20);
+        add_type("sync_group", 21);
+        add_type("int", 22);
 
         globals.type_id_count = globals.type_ids.len() as u16;
 
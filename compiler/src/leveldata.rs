@@ -4,6 +4,7 @@ use crate::builtins::*;
 use crate::compiler_types::FunctionId;
 use crate::context::Context;
 use ahash::{AHashMap, AHashSet};
+use errors::compiler_info::CodeArea;
 use parser::ast::ObjectMode;
 use std::hash::Hash;
 
@@ -118,6 +119,19 @@ pub struct GdObj {
     pub params: AHashMap<u16, ObjParam>,
     pub mode: ObjectMode,
     pub unique_id: usize,
+    // the unique ids of the triggers the optimizer merged into this one
+    pub merged_ids: Vec<usize>,
+}
+
+// where an object was created in the source code, stored in
+// `Globals::object_origins` by the object's unique id
+// (the optimizer keeps the unique id for the triggers it makes, and
+// the unique ids of the triggers it merges in `GdObj::merged_ids`)
+#[derive(Clone, Debug)]
+pub struct ObjectOrigin {
+    pub area: CodeArea,
+    // the macro calls that led to the object, outermost first
+    pub call_stack: Vec<CodeArea>,
 }
 
 impl GdObj {
@@ -126,6 +140,21 @@ impl GdObj {
 
         (*self).clone()
     }
+
+    /// The unique ids of every object this one was made from, which can be
+    /// looked up in `Globals::object_origins`
+    pub fn origin_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        std::iter::once(self.unique_id).chain(self.merged_ids.iter().copied())
+    }
+
+    /// Remembers that the objects with these unique ids were merged into this one
+    pub fn merge_origins(&mut self, ids: impl IntoIterator<Item = usize>) {
+        for id in ids {
+            if id != self.unique_id && !self.merged_ids.contains(&id) {
+                self.merged_ids.push(id);
+            }
+        }
+    }
 }
 
 pub fn get_used_ids(ls: &str) -> [AHashSet<u16>; 4] {
@@ -240,10 +269,17 @@ pub fn append_objects(
     mut objects: Vec<GdObj>,
     old_ls: &str,
 ) -> Result<(String, [usize; 4]), String> {
+    let used_ids = assign_ids(&mut objects, old_ls)?;
+    Ok((serialize_objects(objects), used_ids))
+}
+
+/// Replaces the arbitrary ids of the objects with ids that aren't used in the
+/// level yet, and returns how many groups, colors, block ids and item ids are used
+pub fn assign_ids(objects: &mut [GdObj], old_ls: &str) -> Result<[usize; 4], String> {
     let mut closed_ids = get_used_ids(old_ls);
 
     //collect all specific ids mentioned into closed_[id] lists
-    for obj in &objects {
+    for obj in objects.iter() {
         for prop in obj.params.values() {
             let class_index;
             let id;
@@ -293,7 +329,7 @@ pub fn append_objects(
 
     const ID_MAX: u16 = 999;
 
-    for obj in &mut objects {
+    for obj in objects.iter_mut() {
         for prop in obj.params.values_mut() {
             let class_index;
             let ids: Vec<&mut Id>;
@@ -363,8 +399,16 @@ pub fn append_objects(
         }
     }
 
-    //println!("group_map: {:?}", id_maps[0]);
+    Ok([
+        closed_ids[0].len(),
+        closed_ids[1].len(),
+        closed_ids[2].len(),
+        closed_ids[3].len(),
+    ])
+}
 
+/// The level string of the objects, which should have specific ids
+pub fn serialize_objects(objects: Vec<GdObj>) -> String {
     fn serialize_obj(mut trigger: GdObj) -> String {
         let mut obj_string = String::new();
         match trigger.mode {
@@ -440,15 +484,7 @@ pub fn append_objects(
     for obj in objects {
         full_obj_string += &serialize_obj(obj)
     }
-    Ok((
-        full_obj_string,
-        [
-            closed_ids[0].len(),
-            closed_ids[1].len(),
-            closed_ids[2].len(),
-            closed_ids[3].len(),
-        ],
-    ))
+    full_obj_string
 }
 
 pub fn apply_fn_ids(func_ids: &[FunctionId]) -> Vec<GdObj> {
@@ -6,9 +6,18 @@ pub mod builtins;
 pub mod compiler;
 pub mod compiler_types;
 pub mod context;
+pub mod context_limits;
+pub mod debugger;
 pub mod globals;
 pub mod leveldata;
+pub mod lints;
+pub mod module_cache;
+pub mod package_permissions;
 pub mod parse_levelstring;
+pub mod path_scopes;
+pub mod profiler;
+pub mod reproducible;
+pub mod sync_groups;
 pub mod value;
 pub mod value_storage;
 
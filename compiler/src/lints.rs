@@ -0,0 +1,554 @@
+//! Warnings about code that compiles, but is probably a mistake
+//!
+//! The lints only look at the syntax tree of a file, so they run once per file
+//! before it's compiled, and don't know anything about the values.
+
+use errors::compiler_info::CodeArea;
+use errors::{Lint, Warning};
+use internment::LocalIntern;
+use parser::ast;
+use shared::{FileRange, SpwnSource};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BindingKind {
+    Variable,
+    Trigger,
+    Import,
+    // macro arguments, loop variables and such, which are never reported
+    Argument,
+}
+
+struct Binding {
+    name: LocalIntern<String>,
+    pos: FileRange,
+    kind: BindingKind,
+    used: bool,
+    // the level of the unused lint where the binding was defined
+    level: Level,
+}
+
+#[derive(Default)]
+struct Scope {
+    bindings: Vec<Binding>,
+    // names used before they were defined, like a macro calling itself,
+    // which are looked up again at the end of the scope
+    unresolved: Vec<LocalIntern<String>>,
+}
+
+struct Linter {
+    file: LocalIntern<SpwnSource>,
+    scopes: Vec<Scope>,
+    // the `#[allow]`, `#[warn]` and `#[deny]` attributes around the current
+    // statement, `None` is all lints (`#[allow(warnings)]`)
+    levels: Vec<(Option<Lint>, Level)>,
+    warnings: Vec<Warning>,
+    denied: Vec<Warning>,
+}
+
+/// Lints a file, and returns the warnings, and the warnings that are denied with `#[deny(...)]`
+pub fn lint_file(
+    statements: &[ast::Statement],
+    file_tag: &ast::Attribute,
+    file: SpwnSource,
+) -> (Vec<Warning>, Vec<Warning>) {
+    let mut linter = Linter {
+        file: LocalIntern::new(file),
+        scopes: vec![Scope::default()],
+        levels: Vec::new(),
+        warnings: Vec::new(),
+        denied: Vec::new(),
+    };
+    linter.push_levels(file_tag);
+    linter.statements(statements);
+    linter.pop_scope();
+    // unused variables are found at the end of their scope
+    linter.warnings.sort_by_key(|w| w.position.pos);
+    (linter.warnings, linter.denied)
+}
+
+/// The level of a lint in a file's tag, for the lints that are found while compiling
+/// (like `context_explosion`) instead of by the linter
+pub fn file_level(file_tag: &ast::Attribute, file: SpwnSource, lint: Lint) -> Level {
+    let mut linter = Linter {
+        file: LocalIntern::new(file),
+        scopes: Vec::new(),
+        levels: Vec::new(),
+        warnings: Vec::new(),
+        denied: Vec::new(),
+    };
+    linter.push_levels(file_tag);
+    linter.level(lint)
+}
+
+fn simple_symbol(var: &ast::Variable) -> Option<LocalIntern<String>> {
+    match &var.value.body {
+        ast::ValueBody::Symbol(name) if var.path.is_empty() && var.operator.is_none() => {
+            Some(*name)
+        }
+        _ => None,
+    }
+}
+
+fn is_trigger_function(expr: &ast::Expression) -> bool {
+    matches!(
+        expr.values.as_slice(),
+        [ast::Variable {
+            value: ast::ValueLiteral {
+                body: ast::ValueBody::CmpStmt(_)
+            },
+            operator: None,
+            path,
+            ..
+        }] if path.is_empty()
+    )
+}
+
+impl Linter {
+    fn area(&self, pos: FileRange) -> CodeArea {
+        CodeArea {
+            file: self.file,
+            pos,
+        }
+    }
+
+    // returns how many levels were added, so they can be removed after the statement
+    fn push_levels(&mut self, attr: &ast::Attribute) -> usize {
+        let before = self.levels.len();
+        for (key, args) in &attr.tags {
+            let level = match key.as_str() {
+                "allow" => Level::Allow,
+                "warn" => Level::Warn,
+                "deny" => Level::Deny,
+                _ => continue,
+            };
+            for arg in args {
+                let name = match arg.value.values.as_slice() {
+                    [var] => simple_symbol(var),
+                    _ => None,
+                };
+                match name.as_ref().map(|n| n.as_str()) {
+                    Some("warnings") => self.levels.push((None, level)),
+                    Some(name) => {
+                        if let Some(lint) = Lint::from_name(name) {
+                            self.levels.push((Some(lint), level))
+                        }
+                    }
+                    None => (),
+                }
+            }
+        }
+        self.levels.len() - before
+    }
+
+    fn level(&self, lint: Lint) -> Level {
+        self.levels
+            .iter()
+            .rev()
+            .find(|(l, _)| l.is_none() || *l == Some(lint))
+            .map(|(_, level)| *level)
+            .unwrap_or(Level::Warn)
+    }
+
+    fn warn(
+        &mut self,
+        level: Level,
+        lint: Lint,
+        message: String,
+        labels: Vec<(FileRange, String)>,
+    ) {
+        let warning = Warning {
+            lint,
+            message,
+            position: self.area(labels[0].0),
+            labels: labels
+                .into_iter()
+                .map(|(pos, label)| (self.area(pos), label))
+                .collect(),
+        };
+        match level {
+            Level::Allow => (),
+            Level::Warn => self.warnings.push(warning),
+            Level::Deny => self.denied.push(warning),
+        }
+    }
+
+    fn lookup(&mut self, name: LocalIntern<String>) -> Option<&mut Binding> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|s| s.bindings.iter_mut().rev().find(|b| b.name == name))
+    }
+
+    fn use_name(&mut self, name: LocalIntern<String>) {
+        match self.lookup(name) {
+            Some(binding) => binding.used = true,
+            None => self.scopes.last_mut().unwrap().unresolved.push(name),
+        }
+    }
+
+    fn define(&mut self, name: LocalIntern<String>, pos: FileRange, kind: BindingKind) {
+        let level = self.level(match kind {
+            BindingKind::Trigger => Lint::UnusedTriggers,
+            BindingKind::Import => Lint::UnusedImports,
+            _ => Lint::UnusedVariables,
+        });
+        self.scopes.last_mut().unwrap().bindings.push(Binding {
+            name,
+            pos,
+            kind,
+            used: false,
+            level,
+        });
+    }
+
+    fn pop_scope(&mut self) {
+        let mut scope = self.scopes.pop().unwrap();
+        for name in scope.unresolved {
+            match scope.bindings.iter_mut().rev().find(|b| b.name == name) {
+                Some(binding) => binding.used = true,
+                None => {
+                    if let Some(parent) = self.scopes.last_mut() {
+                        parent.unresolved.push(name)
+                    }
+                }
+            }
+        }
+        for binding in scope.bindings {
+            if binding.used || binding.name.starts_with('_') {
+                continue;
+            }
+            let (lint, message, label) = match binding.kind {
+                BindingKind::Variable => (
+                    Lint::UnusedVariables,
+                    format!("Unused variable `{}`", binding.name),
+                    "This variable is never used",
+                ),
+                BindingKind::Trigger => (
+                    Lint::UnusedTriggers,
+                    format!("Trigger function `{}` is never called", binding.name),
+                    "This trigger function is never used",
+                ),
+                BindingKind::Import => (
+                    Lint::UnusedImports,
+                    format!("Unused import `{}`", binding.name),
+                    "This import is never used",
+                ),
+                BindingKind::Argument => continue,
+            };
+            self.warn(
+                binding.level,
+                lint,
+                message,
+                vec![(binding.pos, label.to_string())],
+            );
+        }
+    }
+
+    fn scoped_statements(&mut self, statements: &[ast::Statement]) {
+        self.scopes.push(Scope::default());
+        self.statements(statements);
+        self.pop_scope();
+    }
+
+    fn statements(&mut self, statements: &[ast::Statement]) {
+        let mut ended_by: Option<FileRange> = None;
+        for (i, statement) in statements.iter().enumerate() {
+            if let Some(end) = ended_by.take() {
+                let unreachable = (statement.pos.0, statements.last().unwrap().pos.1);
+                let level = self.level(Lint::UnreachableCode);
+                self.warn(
+                    level,
+                    Lint::UnreachableCode,
+                    "Unreachable code".to_string(),
+                    vec![
+                        (unreachable, "This code is never run".to_string()),
+                        (end, "Because nothing after this runs".to_string()),
+                    ],
+                );
+                // the rest of the statements are still checked for uses
+                for statement in &statements[i..] {
+                    self.statement(statement);
+                }
+                return;
+            }
+            self.statement(statement);
+
+            use ast::StatementBody::*;
+            if !statement.arrow && matches!(statement.body, Return(_) | Break | Continue | Error(_))
+            {
+                ended_by = Some(statement.pos);
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &ast::Statement) {
+        use ast::StatementBody::*;
+
+        let attr = match &statement.body {
+            Definition(def) => Some(&def.symbol.tag),
+            Call(call) => Some(&call.function.tag),
+            Expr(expr) => expr.values.first().map(|v| &v.tag),
+            TypeDef { attr, .. } => Some(attr),
+            _ => None,
+        };
+        let pushed = attr.map(|a| self.push_levels(a)).unwrap_or(0);
+
+        match &statement.body {
+            Definition(def) => self.definition(def),
+            Call(call) => self.variable(&call.function),
+            Expr(expr) => {
+                if is_trigger_function(expr) {
+                    let level = self.level(Lint::UnusedTriggers);
+                    self.warn(
+                        level,
+                        Lint::UnusedTriggers,
+                        "Trigger function is never called".to_string(),
+                        vec![(
+                            expr.get_pos(),
+                            "This trigger function is never used".to_string(),
+                        )],
+                    );
+                }
+                self.expression(expr)
+            }
+            TypeDef { .. } | Invalid(_) | Break | Continue => (),
+            Return(expr) => {
+                if let Some(expr) = expr {
+                    self.expression(expr)
+                }
+            }
+            Impl(imp) => {
+                self.variable(&imp.symbol);
+                self.dict(&imp.members);
+            }
+            If(i) => {
+                self.expression(&i.condition);
+                self.scoped_statements(&i.if_body);
+                if let Some(else_body) = &i.else_body {
+                    self.scoped_statements(else_body);
+                }
+            }
+            For(f) => {
+                self.expression(&f.array);
+                self.scopes.push(Scope::default());
+                if let [var] = f.symbol.values.as_slice() {
+                    if let Some(name) = simple_symbol(var) {
+                        self.define(name, var.pos, BindingKind::Argument);
+                    }
+                }
+                self.statements(&f.body);
+                self.pop_scope();
+            }
+            While(w) => {
+                self.expression(&w.condition);
+                self.scoped_statements(&w.body);
+            }
+            Error(e) => self.expression(&e.message),
+            Try(t) => {
+                self.scoped_statements(&t.try_body);
+                self.scopes.push(Scope::default());
+                if let Some(name) = t.catch_symbol.as_ref().and_then(simple_symbol) {
+                    let pos = t.catch_symbol.as_ref().unwrap().pos;
+                    self.define(name, pos, BindingKind::Argument);
+                }
+                self.statements(&t.catch_body);
+                self.pop_scope();
+            }
+            Extract(expr) => self.expression(expr),
+            ImportNames(i) => {
+                for name in &i.names {
+                    self.define(
+                        name.alias.unwrap_or(name.name),
+                        name.pos,
+                        BindingKind::Import,
+                    );
+                }
+            }
+            Sync(s) => match &s.part {
+                Some((part, body)) => {
+                    self.variable(&s.group);
+                    self.expression(part);
+                    self.scoped_statements(body);
+                }
+                None => {
+                    if let Some(name) = simple_symbol(&s.group) {
+                        self.define(name, s.group.pos, BindingKind::Argument);
+                    }
+                }
+            },
+        }
+
+        self.levels.truncate(self.levels.len() - pushed);
+    }
+
+    fn definition(&mut self, def: &ast::Definition) {
+        // the value is evaluated before the symbol is defined
+        if let Some(value) = &def.value {
+            self.expression(value);
+        }
+        let name = match simple_symbol(&def.symbol) {
+            Some(name) => name,
+            None => {
+                match &def.symbol.value.body {
+                    // destructuring isn't checked
+                    ast::ValueBody::Array(_) | ast::ValueBody::Dictionary(_) => (),
+                    _ => self.variable(&def.symbol),
+                }
+                return;
+            }
+        };
+        let pos = def.symbol.pos;
+        if def.mutable {
+            if let Some(prev) = self.lookup(name).map(|b| b.pos) {
+                let level = self.level(Lint::ShadowedLet);
+                self.warn(
+                    level,
+                    Lint::ShadowedLet,
+                    format!("`{}` shadows an earlier definition", name),
+                    vec![
+                        (pos, format!("`{}` is defined again here", name)),
+                        (prev, "The earlier definition is here".to_string()),
+                    ],
+                );
+            }
+        } else if self.lookup(name).is_some() {
+            // assigning to an existing variable, which might have side effects
+            // (like on counters), so it counts as a use
+            self.use_name(name);
+            return;
+        }
+        let kind = match &def.value {
+            Some(value) if is_trigger_function(value) => BindingKind::Trigger,
+            _ => BindingKind::Variable,
+        };
+        self.define(name, pos, kind);
+    }
+
+    fn dict(&mut self, defs: &[ast::DictDef]) {
+        for def in defs {
+            match def {
+                ast::DictDef::Def((_, expr)) | ast::DictDef::Extract(expr) => self.expression(expr),
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &ast::Expression) {
+        for var in &expr.values {
+            self.variable(var);
+        }
+    }
+
+    fn variable(&mut self, var: &ast::Variable) {
+        use ast::ValueBody::*;
+        match &var.value.body {
+            Symbol(name) => self.use_name(*name),
+            CmpStmt(cmp) => self.scoped_statements(&cmp.statements),
+            Dictionary(defs) => self.dict(defs),
+            Expression(expr) => self.expression(expr),
+            FormatStr(parts) => {
+                for part in parts {
+                    if let ast::FormatStrPart::Expr(expr) = part {
+                        self.expression(expr);
+                    }
+                }
+            }
+            Match(expr, cases) => {
+                self.expression(expr);
+                for case in cases {
+                    if let ast::CaseType::Pattern(pattern) = &case.typ {
+                        self.expression(pattern);
+                    }
+                    match &case.body {
+                        ast::CaseBody::Expr(expr) => self.expression(expr),
+                        ast::CaseBody::Block(body) => self.scoped_statements(body),
+                    }
+                }
+            }
+            Array(items) => {
+                for item in items {
+                    self.expression(&item.value);
+                }
+            }
+            ListComp(comp) => {
+                self.expression(&comp.iterator);
+                self.scopes.push(Scope::default());
+                self.define(comp.symbol, var.pos, BindingKind::Argument);
+                if let Some(condition) = &comp.condition {
+                    self.expression(condition);
+                }
+                self.expression(&comp.body);
+                self.pop_scope();
+            }
+            Obj(obj) => {
+                for (key, value) in &obj.props {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Macro(m) => {
+                for (_, default, _, typ, _, _) in &m.args {
+                    if let Some(default) = default {
+                        self.expression(default);
+                    }
+                    if let Some(typ) = typ {
+                        self.expression(typ);
+                    }
+                }
+                if let Some(ret) = &m.ret_type {
+                    self.expression(ret);
+                }
+                self.scopes.push(Scope::default());
+                for (name, _, _, _, pos, _) in &m.args {
+                    self.define(*name, *pos, BindingKind::Argument);
+                }
+                self.statements(&m.body.statements);
+                self.pop_scope();
+            }
+            MacroPattern(pattern) => {
+                for arg in &pattern.args {
+                    self.expression(arg);
+                }
+                self.expression(&pattern.ret);
+            }
+            Ternary(t) => {
+                self.expression(&t.condition);
+                self.expression(&t.if_expr);
+                self.expression(&t.else_expr);
+            }
+            Id(_) | Number(_) | Int(_) | Bool(_) | Str(_) | Import(..) | Resolved(_)
+            | TypeIndicator(_) | SelfVal | Null => (),
+        }
+        for path in &var.path {
+            match path {
+                ast::Path::Index(expr) => self.expression(expr),
+                ast::Path::NSlice(slices) => {
+                    for slice in slices {
+                        for expr in [&slice.left, &slice.right, &slice.step]
+                            .into_iter()
+                            .flatten()
+                        {
+                            self.expression(expr);
+                        }
+                    }
+                }
+                ast::Path::Call(args) => {
+                    for arg in args {
+                        self.expression(&arg.value);
+                    }
+                }
+                ast::Path::Constructor(defs) => self.dict(defs),
+                ast::Path::Member(_)
+                | ast::Path::Associated(_)
+                | ast::Path::Increment
+                | ast::Path::Decrement => (),
+            }
+        }
+    }
+}
@@ -0,0 +1,1010 @@
+//! An on-disk cache for `#[cache_output]` modules
+//!
+//! `Globals.prev_imports` only remembers a module's output for one build. This
+//! saves the output and implementations of a module to a file, together with a
+//! hash of every file that was read while compiling it, so later builds can
+//! load the module instead of compiling it again.
+//!
+//! The triggers and objects a module adds are saved too, and added to the level
+//! again when it's loaded. A module that makes sync groups, or adds triggers to
+//! functions it didn't make (other than the root one), has to be compiled every
+//! time. The same goes for modules that call built-in functions like
+//! `$.readfile` or `$.random`, since their output depends on more than their
+//! source, and on the permissions of the build.
+//!
+//! Arbitrary IDs the module made are saved relative to the first one, and new
+//! ones are taken when it's loaded, and the same goes for the functions,
+//! trigger order and unique ids of its triggers. Implementations the module got from the
+//! build it was imported in, like the standard library's, are saved by name and
+//! taken from the build that loads it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ahash::AHashMap;
+use errors::compiler_info::CodeArea;
+use internment::LocalIntern;
+use parser::ast;
+use serde::{Deserialize, Serialize};
+use shared::{FileRange, SpwnSource, StoredValue};
+
+use crate::builtins::{get_lib_file, Block, Builtin, Color, Group, Id, Item};
+use crate::compiler_types::{FunctionId, Implementations, TriggerOrder};
+use crate::globals::Globals;
+use crate::leveldata::{GdObj, ObjParam, ObjectOrigin};
+use crate::value::{Macro, MacroArgDef, MacroFuncData, Pattern, TriggerFunction, Value};
+use crate::value_storage::store_val_m;
+
+// cached modules from other versions of the compiler are never used
+const CACHE_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "/3");
+
+// indices into the arrays of arbitrary id counters
+const GROUP: usize = 0;
+const COLOR: usize = 1;
+const BLOCK: usize = 2;
+const ITEM: usize = 3;
+
+fn closed_ids(globals: &Globals) -> [u16; 4] {
+    [
+        globals.closed_groups,
+        globals.closed_colors,
+        globals.closed_blocks,
+        globals.closed_items,
+    ]
+}
+
+/// The state of the build before a module is compiled, to find out what the module added
+pub struct ModuleStart {
+    type_count: u16,
+    closed_ids: [u16; 4],
+    func_ids: usize,
+    triggers: usize,
+    root_triggers: usize,
+    objects: usize,
+    sync_groups: usize,
+    trigger_order: f64,
+    uid_counter: usize,
+    impure_calls: usize,
+    implementations: Implementations,
+}
+
+impl ModuleStart {
+    pub fn new(globals: &Globals) -> Self {
+        ModuleStart {
+            type_count: globals.type_id_count,
+            closed_ids: closed_ids(globals),
+            func_ids: globals.func_ids.len(),
+            triggers: globals.func_ids.iter().map(|f| f.obj_list.len()).sum(),
+            root_triggers: globals.func_ids[0].obj_list.len(),
+            objects: globals.objects.len(),
+            sync_groups: globals.sync_groups.len(),
+            trigger_order: globals.trigger_order,
+            uid_counter: globals.uid_counter,
+            impure_calls: globals.impure_calls,
+            implementations: globals.implementations.clone(),
+        }
+    }
+
+    /// If the module changed the level in a way that can't be replayed when it's loaded
+    pub fn has_side_effects(&self, globals: &Globals) -> bool {
+        // the root function is the only one from before the module that can get triggers
+        let old_triggers: usize = globals.func_ids[1..self.func_ids]
+            .iter()
+            .map(|f| f.obj_list.len())
+            .sum();
+        old_triggers != self.triggers - self.root_triggers
+            || globals.sync_groups.len() != self.sync_groups
+    }
+
+    // if the member was already implemented before the module was compiled
+    fn inherits(&self, typ: u16, name: LocalIntern<String>, v: StoredValue) -> bool {
+        let member = self
+            .implementations
+            .get(&typ)
+            .and_then(|imp| imp.get(&name));
+        matches!(member, Some((old, _)) if *old == v)
+    }
+
+    /// If the module called a built-in function whose result depends on more than its arguments
+    pub fn is_impure(&self, globals: &Globals) -> bool {
+        globals.impure_calls != self.impure_calls
+    }
+}
+
+/// Files that were read by an import, and a hash of their contents
+pub type ModuleFiles = Vec<(SpwnSource, u64)>;
+
+/// A hash of a file's contents that stays the same between builds
+pub fn hash_text(text: &str) -> u64 {
+    // FNV-1a, since the std hashers can change between Rust versions
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn read_source(source: &SpwnSource) -> Option<String> {
+    match source {
+        SpwnSource::File(path) => fs::read_to_string(path).ok(),
+        SpwnSource::BuiltIn(path) => get_lib_file(path)?.contents_utf8().map(String::from),
+        SpwnSource::String(s) => Some(s.to_string()),
+    }
+}
+
+fn cache_file(dir: &Path, module: &SpwnSource) -> PathBuf {
+    let key = format!("{}{:?}", CACHE_VERSION, module);
+    dir.join(format!("{:016x}.json", hash_text(&key)))
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedSource {
+    File(PathBuf),
+    BuiltIn(PathBuf),
+    String(String),
+}
+
+impl From<&SpwnSource> for CachedSource {
+    fn from(source: &SpwnSource) -> Self {
+        match source {
+            SpwnSource::File(p) => CachedSource::File(p.clone()),
+            SpwnSource::BuiltIn(p) => CachedSource::BuiltIn(p.clone()),
+            SpwnSource::String(s) => CachedSource::String(s.to_string()),
+        }
+    }
+}
+
+impl From<CachedSource> for SpwnSource {
+    fn from(source: CachedSource) -> Self {
+        match source {
+            CachedSource::File(p) => SpwnSource::File(p),
+            CachedSource::BuiltIn(p) => SpwnSource::BuiltIn(p),
+            CachedSource::String(s) => SpwnSource::String(LocalIntern::new(s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedArea {
+    file: CachedSource,
+    pos: FileRange,
+}
+
+impl From<CodeArea> for CachedArea {
+    fn from(area: CodeArea) -> Self {
+        CachedArea {
+            file: CachedSource::from(&*area.file),
+            pos: area.pos,
+        }
+    }
+}
+
+impl From<CachedArea> for CodeArea {
+    fn from(area: CachedArea) -> Self {
+        CodeArea {
+            file: LocalIntern::new(area.file.into()),
+            pos: area.pos,
+        }
+    }
+}
+
+// types are saved by name, since their ids depend on the order they were defined in
+#[derive(Serialize, Deserialize)]
+struct CachedType {
+    name: String,
+    area: CachedArea,
+    desc: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+enum CachedId {
+    Specific(u16),
+    // the nth arbitrary id the module made
+    Arbitrary(u16),
+}
+
+// values refer to other values by their index in `CachedModule::values`
+#[derive(Serialize, Deserialize)]
+enum CachedValue {
+    Group(CachedId),
+    Color(CachedId),
+    Block(CachedId),
+    Item(CachedId),
+    Number(f64),
+    Int(i64),
+    Bool(bool),
+    Dict(Vec<(String, usize)>),
+    Macro(Box<CachedMacro>),
+    BuiltinMacro(String),
+    Str(String),
+    Array(Vec<usize>),
+    Obj(Vec<(u16, CachedObjParam)>, ast::ObjectMode),
+    Builtins,
+    TypeIndicator(String),
+    Range(i32, i32, usize),
+    Pattern(CachedPattern),
+    TriggerFunc(CachedId),
+    Null,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedMacro {
+    args: Vec<CachedArg>,
+    def_variables: Vec<(String, usize)>,
+    def_file: CachedSource,
+    body: Vec<ast::Statement>,
+    tag: ast::Attribute,
+    arg_pos: FileRange,
+    ret_pattern: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedArg {
+    name: String,
+    default: Option<usize>,
+    attribute: ast::Attribute,
+    pattern: Option<usize>,
+    position: FileRange,
+    arg_type: ast::ArgType,
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedPattern {
+    Type(String),
+    Array(Vec<CachedPattern>),
+    Dict(Vec<(String, CachedPattern)>),
+    Either(Box<CachedPattern>, Box<CachedPattern>),
+    Both(Box<CachedPattern>, Box<CachedPattern>),
+    Not(Box<CachedPattern>),
+    Any,
+    Eq(usize),
+    NotEq(usize),
+    MoreThan(usize),
+    LessThan(usize),
+    MoreOrEq(usize),
+    LessOrEq(usize),
+    In(usize),
+    Macro {
+        args: Vec<CachedPattern>,
+        ret: Box<CachedPattern>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedObjParam {
+    Group(CachedId),
+    Color(CachedId),
+    Block(CachedId),
+    Item(CachedId),
+    Number(f64),
+    Bool(bool),
+    Text(String),
+    GroupList(Vec<CachedId>),
+    Epsilon,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedOrigin {
+    area: CachedArea,
+    call_stack: Vec<CachedArea>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedObject {
+    // the function the object is in (see `Saver::function`)
+    func: usize,
+    params: Vec<(u16, CachedObjParam)>,
+    mode: ast::ObjectMode,
+    // relative to the unique ids from before the module
+    unique_id: usize,
+    origin: Option<CachedOrigin>,
+}
+
+// the triggers and objects the module added, which are added again when it's loaded
+#[derive(Serialize, Deserialize)]
+struct CachedLevel {
+    // the parent of every function the module made
+    functions: Vec<usize>,
+    // with their trigger order, relative to the one from before the module
+    triggers: Vec<(CachedObject, f64)>,
+    objects: Vec<CachedObject>,
+    // how far the module moved the trigger order and the unique ids
+    trigger_order: f64,
+    unique_ids: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedValData {
+    val: CachedValue,
+    fn_context: CachedId,
+    mutable: bool,
+    private: bool,
+    def_area: CachedArea,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedModule {
+    version: String,
+    // every file that was read to compile the module, and a hash of its contents
+    files: Vec<(CachedSource, u64)>,
+    types: Vec<CachedType>,
+    // how many arbitrary groups, colors, blocks and items the module made
+    new_ids: [u16; 4],
+    values: Vec<CachedValData>,
+    output: usize,
+    implementations: Vec<(String, Vec<CachedMember>)>,
+    // members that are taken from the build that loads the module, by type name
+    inherited: Vec<(String, String)>,
+    level: CachedLevel,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedMember {
+    name: String,
+    value: usize,
+    in_scope: bool,
+}
+
+struct Saver<'a, 'b> {
+    globals: &'a Globals<'b>,
+    start: &'a ModuleStart,
+    type_names: AHashMap<u16, &'a str>,
+    indices: AHashMap<StoredValue, usize>,
+    values: Vec<Option<CachedValData>>,
+}
+
+impl<'a, 'b> Saver<'a, 'b> {
+    fn type_name(&self, id: u16) -> Option<String> {
+        self.type_names.get(&id).map(|s| s.to_string())
+    }
+
+    fn id(&self, id: Id, class: usize) -> Option<CachedId> {
+        match id {
+            Id::Specific(n) => Some(CachedId::Specific(n)),
+            // arbitrary ids from before the module belong to another module
+            Id::Arbitrary(n) if n > self.start.closed_ids[class] => {
+                Some(CachedId::Arbitrary(n - self.start.closed_ids[class]))
+            }
+            Id::Arbitrary(_) => None,
+        }
+    }
+
+    fn value(&mut self, v: StoredValue) -> Option<usize> {
+        if let Some(i) = self.indices.get(&v) {
+            return Some(*i);
+        }
+        // the index is reserved first, so values can refer to themselves
+        let index = self.values.len();
+        self.values.push(None);
+        self.indices.insert(v, index);
+
+        let data = self.globals.stored_values.map.get(v)?;
+        let cached = CachedValData {
+            val: self.convert(&data.val)?,
+            fn_context: self.id(data.fn_context.id, GROUP)?,
+            mutable: data.mutable,
+            private: data.private,
+            def_area: data.def_area.into(),
+        };
+        self.values[index] = Some(cached);
+        Some(index)
+    }
+
+    fn values<'c>(
+        &mut self,
+        map: impl Iterator<Item = (&'c LocalIntern<String>, &'c StoredValue)>,
+    ) -> Option<Vec<(String, usize)>> {
+        map.map(|(k, v)| Some((k.to_string(), self.value(*v)?)))
+            .collect()
+    }
+
+    fn convert(&mut self, val: &Value) -> Option<CachedValue> {
+        Some(match val {
+            Value::Group(g) => CachedValue::Group(self.id(g.id, GROUP)?),
+            Value::Color(c) => CachedValue::Color(self.id(c.id, COLOR)?),
+            Value::Block(b) => CachedValue::Block(self.id(b.id, BLOCK)?),
+            Value::Item(i) => CachedValue::Item(self.id(i.id, ITEM)?),
+            Value::Number(n) => CachedValue::Number(*n),
+            Value::Int(n) => CachedValue::Int(*n),
+            Value::Bool(b) => CachedValue::Bool(*b),
+            Value::Dict(d) => CachedValue::Dict(self.values(d.iter())?),
+            Value::Macro(Macro::FuncLike(m)) => {
+                let mut args = Vec::new();
+                for arg in &m.args {
+                    args.push(CachedArg {
+                        name: arg.name.to_string(),
+                        default: match arg.default {
+                            Some(v) => Some(self.value(v)?),
+                            None => None,
+                        },
+                        attribute: arg.attribute.clone(),
+                        pattern: match arg.pattern {
+                            Some(v) => Some(self.value(v)?),
+                            None => None,
+                        },
+                        position: arg.position,
+                        arg_type: arg.arg_type,
+                    });
+                }
+                CachedValue::Macro(Box::new(CachedMacro {
+                    args,
+                    def_variables: self.values(m.def_variables.iter())?,
+                    def_file: CachedSource::from(&*m.def_file),
+                    body: m.body.clone(),
+                    tag: m.tag.clone(),
+                    arg_pos: m.arg_pos,
+                    ret_pattern: match m.ret_pattern {
+                        Some(v) => Some(self.value(v)?),
+                        None => None,
+                    },
+                }))
+            }
+            Value::Macro(Macro::BuiltinLike(b)) => CachedValue::BuiltinMacro(String::from(*b)),
+            Value::Str(s) => CachedValue::Str(s.clone()),
+            Value::Array(a) => {
+                CachedValue::Array(a.iter().map(|v| self.value(*v)).collect::<Option<_>>()?)
+            }
+            Value::Obj(params, mode) => {
+                let mut cached = Vec::new();
+                for (key, param) in params {
+                    cached.push((*key, self.obj_param(param)?));
+                }
+                CachedValue::Obj(cached, *mode)
+            }
+            Value::Builtins => CachedValue::Builtins,
+            Value::TypeIndicator(t) => CachedValue::TypeIndicator(self.type_name(*t)?),
+            Value::Range(start, end, step) => CachedValue::Range(*start, *end, *step),
+            Value::Pattern(p) => CachedValue::Pattern(self.pattern(p)?),
+            Value::TriggerFunc(f) => CachedValue::TriggerFunc(self.id(f.start_group.id, GROUP)?),
+            Value::Null => CachedValue::Null,
+            // sync groups belong to the build that made them
+            Value::SyncGroup(_) => return None,
+        })
+    }
+
+    // the root function is 0, and the nth function the module made is n
+    fn function(&self, id: usize) -> Option<usize> {
+        match id {
+            0 => Some(0),
+            id if id >= self.start.func_ids => Some(id - self.start.func_ids + 1),
+            // functions from before the module belong to another module
+            _ => None,
+        }
+    }
+
+    fn object(&self, obj: &GdObj) -> Option<CachedObject> {
+        let mut params = Vec::new();
+        for (key, param) in &obj.params {
+            params.push((*key, self.obj_param(param)?));
+        }
+        let origin = self
+            .globals
+            .object_origins
+            .get(&obj.unique_id)
+            .map(|origin| CachedOrigin {
+                area: origin.area.into(),
+                call_stack: origin.call_stack.iter().map(|a| (*a).into()).collect(),
+            });
+        Some(CachedObject {
+            func: self.function(obj.func_id)?,
+            params,
+            mode: obj.mode,
+            unique_id: obj.unique_id.checked_sub(self.start.uid_counter)?,
+            origin,
+        })
+    }
+
+    fn level(&self) -> Option<CachedLevel> {
+        let new_functions = &self.globals.func_ids[self.start.func_ids..];
+        let functions = new_functions
+            .iter()
+            .map(|f| self.function(f.parent?))
+            .collect::<Option<_>>()?;
+
+        let root = &self.globals.func_ids[0].obj_list[self.start.root_triggers..];
+        let mut triggers = Vec::new();
+        for (obj, order) in root
+            .iter()
+            .chain(new_functions.iter().flat_map(|f| &f.obj_list))
+        {
+            triggers.push((self.object(obj)?, order.0 - self.start.trigger_order));
+        }
+        let objects = self.globals.objects[self.start.objects..]
+            .iter()
+            .map(|obj| self.object(obj))
+            .collect::<Option<_>>()?;
+
+        Some(CachedLevel {
+            functions,
+            triggers,
+            objects,
+            trigger_order: self.globals.trigger_order - self.start.trigger_order,
+            unique_ids: self.globals.uid_counter - self.start.uid_counter,
+        })
+    }
+
+    fn obj_param(&self, param: &ObjParam) -> Option<CachedObjParam> {
+        Some(match param {
+            ObjParam::Group(g) => CachedObjParam::Group(self.id(g.id, GROUP)?),
+            ObjParam::Color(c) => CachedObjParam::Color(self.id(c.id, COLOR)?),
+            ObjParam::Block(b) => CachedObjParam::Block(self.id(b.id, BLOCK)?),
+            ObjParam::Item(i) => CachedObjParam::Item(self.id(i.id, ITEM)?),
+            ObjParam::Number(n) => CachedObjParam::Number(*n),
+            ObjParam::Bool(b) => CachedObjParam::Bool(*b),
+            ObjParam::Text(t) => CachedObjParam::Text(t.clone()),
+            ObjParam::GroupList(list) => CachedObjParam::GroupList(
+                list.iter()
+                    .map(|g| self.id(g.id, GROUP))
+                    .collect::<Option<_>>()?,
+            ),
+            ObjParam::Epsilon => CachedObjParam::Epsilon,
+        })
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) -> Option<CachedPattern> {
+        Some(match pattern {
+            Pattern::Type(t) => CachedPattern::Type(self.type_name(*t)?),
+            Pattern::Array(a) => {
+                CachedPattern::Array(a.iter().map(|p| self.pattern(p)).collect::<Option<_>>()?)
+            }
+            Pattern::Dict(d) => CachedPattern::Dict(
+                d.iter()
+                    .map(|(k, p)| Some((k.to_string(), self.pattern(p)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            Pattern::Either(a, b) => {
+                CachedPattern::Either(Box::new(self.pattern(a)?), Box::new(self.pattern(b)?))
+            }
+            Pattern::Both(a, b) => {
+                CachedPattern::Both(Box::new(self.pattern(a)?), Box::new(self.pattern(b)?))
+            }
+            Pattern::Not(p) => CachedPattern::Not(Box::new(self.pattern(p)?)),
+            Pattern::Any => CachedPattern::Any,
+            Pattern::Eq(v) => CachedPattern::Eq(self.value(*v)?),
+            Pattern::NotEq(v) => CachedPattern::NotEq(self.value(*v)?),
+            Pattern::MoreThan(v) => CachedPattern::MoreThan(self.value(*v)?),
+            Pattern::LessThan(v) => CachedPattern::LessThan(self.value(*v)?),
+            Pattern::MoreOrEq(v) => CachedPattern::MoreOrEq(self.value(*v)?),
+            Pattern::LessOrEq(v) => CachedPattern::LessOrEq(self.value(*v)?),
+            Pattern::In(v) => CachedPattern::In(self.value(*v)?),
+            Pattern::Macro { args, ret } => CachedPattern::Macro {
+                args: args
+                    .iter()
+                    .map(|p| self.pattern(p))
+                    .collect::<Option<_>>()?,
+                ret: Box::new(self.pattern(ret)?),
+            },
+        })
+    }
+}
+
+/// Saves the output of a module to the cache directory, with the triggers and objects
+/// it added. Values that can't be saved, like sync groups, and errors writing the file are ignored, and the module
+/// is just compiled again next time.
+///
+/// `files` are the files that were read to compile the module, and `start` is
+/// the state of the build before it was compiled.
+pub fn save(
+    dir: &Path,
+    module: &SpwnSource,
+    files: &[(SpwnSource, u64)],
+    output: StoredValue,
+    implementations: &Implementations,
+    start: &ModuleStart,
+    globals: &Globals,
+) {
+    let mut saver = Saver {
+        globals,
+        start,
+        type_names: globals
+            .type_ids
+            .iter()
+            .map(|(name, (id, _))| (*id, name.as_str()))
+            .collect(),
+        indices: AHashMap::default(),
+        values: Vec::new(),
+    };
+
+    let output = match saver.value(output) {
+        Some(i) => i,
+        None => return,
+    };
+    let mut cached_impls = Vec::new();
+    let mut inherited = Vec::new();
+    for (typ, members) in implementations {
+        let type_name = match saver.type_name(*typ) {
+            Some(t) => t,
+            None => return,
+        };
+        let mut cached_members = Vec::new();
+        for (name, (v, in_scope)) in members {
+            if start.inherits(*typ, *name, *v) {
+                inherited.push((type_name.clone(), name.to_string()));
+                continue;
+            }
+            match saver.value(*v) {
+                Some(value) => cached_members.push(CachedMember {
+                    name: name.to_string(),
+                    value,
+                    in_scope: *in_scope,
+                }),
+                None => return,
+            }
+        }
+        cached_impls.push((type_name, cached_members));
+    }
+    let level = match saver.level() {
+        Some(l) => l,
+        None => return,
+    };
+    let values = match saver.values.into_iter().collect::<Option<Vec<_>>>() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let mut types = Vec::new();
+    for (name, (id, area)) in &globals.type_ids {
+        if *id > start.type_count {
+            types.push(CachedType {
+                name: name.clone(),
+                area: (*area).into(),
+                desc: globals.type_descriptions.get(id).cloned(),
+            });
+        }
+    }
+
+    let cached = CachedModule {
+        version: CACHE_VERSION.to_string(),
+        files: files
+            .iter()
+            .map(|(source, hash)| (source.into(), *hash))
+            .collect(),
+        types,
+        new_ids: {
+            let now = closed_ids(globals);
+            [0, 1, 2, 3].map(|i| now[i] - start.closed_ids[i])
+        },
+        values,
+        output,
+        implementations: cached_impls,
+        inherited,
+        level,
+    };
+
+    if let Ok(json) = serde_json::to_string(&cached) {
+        if fs::create_dir_all(dir).is_ok() {
+            // write to a temporary file first, so other builds never see half a file
+            let path = cache_file(dir, module);
+            let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+            if fs::write(&temp, json).is_ok() && fs::rename(&temp, &path).is_err() {
+                fs::remove_file(&temp).ok();
+            }
+        }
+    }
+}
+
+struct Loader<'a, 'b> {
+    globals: &'a mut Globals<'b>,
+    closed_ids: [u16; 4],
+    func_ids: usize,
+    trigger_order: f64,
+    uid_counter: usize,
+    keys: Vec<StoredValue>,
+}
+
+impl<'a, 'b> Loader<'a, 'b> {
+    fn id(&self, id: CachedId, class: usize) -> Id {
+        match id {
+            CachedId::Specific(n) => Id::Specific(n),
+            CachedId::Arbitrary(n) => Id::Arbitrary(self.closed_ids[class] + n),
+        }
+    }
+
+    fn key(&self, i: usize) -> Option<StoredValue> {
+        self.keys.get(i).copied()
+    }
+
+    fn type_id(&self, name: &str) -> Option<u16> {
+        self.globals.type_ids.get(name).map(|(id, _)| *id)
+    }
+
+    fn values(
+        &self,
+        list: Vec<(String, usize)>,
+    ) -> Option<AHashMap<LocalIntern<String>, StoredValue>> {
+        list.into_iter()
+            .map(|(k, v)| Some((LocalIntern::new(k), self.key(v)?)))
+            .collect()
+    }
+
+    fn optional(&self, i: Option<usize>) -> Option<Option<StoredValue>> {
+        match i {
+            Some(i) => Some(Some(self.key(i)?)),
+            None => Some(None),
+        }
+    }
+
+    fn convert(&self, val: CachedValue) -> Option<Value> {
+        Some(match val {
+            CachedValue::Group(id) => Value::Group(Group {
+                id: self.id(id, GROUP),
+            }),
+            CachedValue::Color(id) => Value::Color(Color {
+                id: self.id(id, COLOR),
+            }),
+            CachedValue::Block(id) => Value::Block(Block {
+                id: self.id(id, BLOCK),
+            }),
+            CachedValue::Item(id) => Value::Item(Item {
+                id: self.id(id, ITEM),
+            }),
+            CachedValue::Number(n) => Value::Number(n),
+            CachedValue::Int(n) => Value::Int(n),
+            CachedValue::Bool(b) => Value::Bool(b),
+            CachedValue::Dict(d) => Value::Dict(self.values(d)?),
+            CachedValue::Macro(m) => {
+                let m = *m;
+                let mut args = Vec::new();
+                for arg in m.args {
+                    args.push(MacroArgDef {
+                        name: LocalIntern::new(arg.name),
+                        default: self.optional(arg.default)?,
+                        attribute: arg.attribute,
+                        pattern: self.optional(arg.pattern)?,
+                        position: arg.position,
+                        arg_type: arg.arg_type,
+                    });
+                }
+                Value::Macro(Macro::FuncLike(MacroFuncData {
+                    args,
+                    def_variables: self.values(m.def_variables)?,
+                    def_file: LocalIntern::new(m.def_file.into()),
+                    body: m.body,
+                    tag: m.tag,
+                    arg_pos: m.arg_pos,
+                    ret_pattern: self.optional(m.ret_pattern)?,
+                }))
+            }
+            CachedValue::BuiltinMacro(name) => {
+                Value::Macro(Macro::BuiltinLike(name.parse::<Builtin>().ok()?))
+            }
+            CachedValue::Str(s) => Value::Str(s),
+            CachedValue::Array(a) => {
+                Value::Array(a.into_iter().map(|i| self.key(i)).collect::<Option<_>>()?)
+            }
+            CachedValue::Obj(params, mode) => Value::Obj(
+                params
+                    .into_iter()
+                    .map(|(k, p)| (k, self.obj_param(p)))
+                    .collect(),
+                mode,
+            ),
+            CachedValue::Builtins => Value::Builtins,
+            CachedValue::TypeIndicator(t) => Value::TypeIndicator(self.type_id(&t)?),
+            CachedValue::Range(start, end, step) => Value::Range(start, end, step),
+            CachedValue::Pattern(p) => Value::Pattern(self.pattern(p)?),
+            CachedValue::TriggerFunc(id) => Value::TriggerFunc(TriggerFunction {
+                start_group: Group {
+                    id: self.id(id, GROUP),
+                },
+            }),
+            CachedValue::Null => Value::Null,
+        })
+    }
+
+    fn function(&self, id: usize) -> usize {
+        match id {
+            0 => 0,
+            n => self.func_ids + n - 1,
+        }
+    }
+
+    fn object(&mut self, obj: CachedObject) -> GdObj {
+        let unique_id = self.uid_counter + obj.unique_id;
+        if let Some(origin) = obj.origin {
+            self.globals.object_origins.insert(
+                unique_id,
+                ObjectOrigin {
+                    area: origin.area.into(),
+                    call_stack: origin.call_stack.into_iter().map(|a| a.into()).collect(),
+                },
+            );
+        }
+        GdObj {
+            func_id: self.function(obj.func),
+            params: obj
+                .params
+                .into_iter()
+                .map(|(k, p)| (k, self.obj_param(p)))
+                .collect(),
+            mode: obj.mode,
+            unique_id,
+            merged_ids: Vec::new(),
+        }
+    }
+
+    // adds the triggers and objects the module made to this build
+    fn replay(&mut self, level: CachedLevel) {
+        for parent in level.functions {
+            let parent = self.function(parent);
+            self.globals.func_ids.push(FunctionId {
+                parent: Some(parent),
+                width: None,
+                obj_list: Vec::new(),
+            });
+        }
+        for (obj, order) in level.triggers {
+            let obj = self.object(obj);
+            let order = TriggerOrder(self.trigger_order + order);
+            self.globals.func_ids[obj.func_id]
+                .obj_list
+                .push((obj, order));
+        }
+        for obj in level.objects {
+            let obj = self.object(obj);
+            self.globals.objects.push(obj);
+        }
+        self.globals.trigger_order += level.trigger_order;
+        self.globals.uid_counter += level.unique_ids;
+    }
+
+    fn obj_param(&self, param: CachedObjParam) -> ObjParam {
+        match param {
+            CachedObjParam::Group(id) => ObjParam::Group(Group {
+                id: self.id(id, GROUP),
+            }),
+            CachedObjParam::Color(id) => ObjParam::Color(Color {
+                id: self.id(id, COLOR),
+            }),
+            CachedObjParam::Block(id) => ObjParam::Block(Block {
+                id: self.id(id, BLOCK),
+            }),
+            CachedObjParam::Item(id) => ObjParam::Item(Item {
+                id: self.id(id, ITEM),
+            }),
+            CachedObjParam::Number(n) => ObjParam::Number(n),
+            CachedObjParam::Bool(b) => ObjParam::Bool(b),
+            CachedObjParam::Text(t) => ObjParam::Text(t),
+            CachedObjParam::GroupList(list) => ObjParam::GroupList(
+                list.into_iter()
+                    .map(|id| Group {
+                        id: self.id(id, GROUP),
+                    })
+                    .collect(),
+            ),
+            CachedObjParam::Epsilon => ObjParam::Epsilon,
+        }
+    }
+
+    fn pattern(&self, pattern: CachedPattern) -> Option<Pattern> {
+        let boxed = |p| Some(Box::new(self.pattern(p)?));
+        Some(match pattern {
+            CachedPattern::Type(t) => Pattern::Type(self.type_id(&t)?),
+            CachedPattern::Array(a) => Pattern::Array(
+                a.into_iter()
+                    .map(|p| self.pattern(p))
+                    .collect::<Option<_>>()?,
+            ),
+            CachedPattern::Dict(d) => Pattern::Dict(
+                d.into_iter()
+                    .map(|(k, p)| Some((LocalIntern::new(k), boxed(p)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            CachedPattern::Either(a, b) => Pattern::Either(boxed(*a)?, boxed(*b)?),
+            CachedPattern::Both(a, b) => Pattern::Both(boxed(*a)?, boxed(*b)?),
+            CachedPattern::Not(p) => Pattern::Not(boxed(*p)?),
+            CachedPattern::Any => Pattern::Any,
+            CachedPattern::Eq(v) => Pattern::Eq(self.key(v)?),
+            CachedPattern::NotEq(v) => Pattern::NotEq(self.key(v)?),
+            CachedPattern::MoreThan(v) => Pattern::MoreThan(self.key(v)?),
+            CachedPattern::LessThan(v) => Pattern::LessThan(self.key(v)?),
+            CachedPattern::MoreOrEq(v) => Pattern::MoreOrEq(self.key(v)?),
+            CachedPattern::LessOrEq(v) => Pattern::LessOrEq(self.key(v)?),
+            CachedPattern::In(v) => Pattern::In(self.key(v)?),
+            CachedPattern::Macro { args, ret } => Pattern::Macro {
+                args: args
+                    .into_iter()
+                    .map(|p| self.pattern(p))
+                    .collect::<Option<_>>()?,
+                ret: boxed(*ret)?,
+            },
+        })
+    }
+}
+
+/// Loads a module from the cache directory, if it was saved by this version of the
+/// compiler and none of the files it read have changed since.
+///
+/// The triggers and objects the module added are added to this build again.
+/// Returns the module's output, its implementations and the files it read.
+pub fn load(
+    dir: &Path,
+    module: &SpwnSource,
+    globals: &mut Globals,
+) -> Option<(StoredValue, Implementations, ModuleFiles)> {
+    let json = fs::read_to_string(cache_file(dir, module)).ok()?;
+    let cached: CachedModule = serde_json::from_str(&json).ok()?;
+    if cached.version != CACHE_VERSION {
+        return None;
+    }
+
+    let mut files = Vec::new();
+    for (source, hash) in cached.files {
+        let source = SpwnSource::from(source);
+        if hash_text(&read_source(&source)?) != hash {
+            return None;
+        }
+        files.push((source, hash));
+    }
+
+    for typ in cached.types {
+        if !globals.type_ids.contains_key(&typ.name) {
+            globals.type_id_count += 1;
+            let id = globals.type_id_count;
+            globals.type_ids.insert(typ.name, (id, typ.area.into()));
+            if let Some(desc) = typ.desc {
+                globals.type_descriptions.insert(id, desc);
+            }
+        }
+    }
+
+    // the module's arbitrary ids come after the ones that are already used
+    let closed = closed_ids(globals);
+    globals.closed_groups += cached.new_ids[GROUP];
+    globals.closed_colors += cached.new_ids[COLOR];
+    globals.closed_blocks += cached.new_ids[BLOCK];
+    globals.closed_items += cached.new_ids[ITEM];
+
+    let mut loader = Loader {
+        func_ids: globals.func_ids.len(),
+        trigger_order: globals.trigger_order,
+        uid_counter: globals.uid_counter,
+        globals,
+        closed_ids: closed,
+        keys: Vec::new(),
+    };
+    // all the values are stored first, so they can refer to each other
+    for data in &cached.values {
+        let fn_context = Group {
+            id: loader.id(data.fn_context, GROUP),
+        };
+        let key = store_val_m(
+            Value::Null,
+            loader.globals,
+            fn_context,
+            !data.mutable,
+            CodeArea::new(),
+        );
+        loader.keys.push(key);
+    }
+
+    for (i, data) in cached.values.into_iter().enumerate() {
+        let val = loader.convert(data.val)?;
+        let key = loader.keys[i];
+        let stored = loader.globals.stored_values.map.get_mut(key)?;
+        stored.val = val;
+        stored.private = data.private;
+        stored.def_area = data.def_area.into();
+    }
+
+    let mut implementations = Implementations::default();
+    for (typ, members) in cached.implementations {
+        let mut imp = AHashMap::default();
+        for member in members {
+            imp.insert(
+                LocalIntern::new(member.name),
+                (loader.key(member.value)?, member.in_scope),
+            );
+        }
+        implementations.insert(loader.type_id(&typ)?, imp);
+    }
+    for (typ, name) in cached.inherited {
+        let typ = loader.type_id(&typ)?;
+        let name = LocalIntern::new(name);
+        let member = *loader.globals.implementations.get(&typ)?.get(&name)?;
+        implementations.entry(typ).or_default().insert(name, member);
+    }
+
+    let output = loader.key(cached.output)?;
+    // nothing can fail after this, so the level is never left half replayed
+    loader.replay(cached.level);
+    Some((output, implementations, files))
+}
@@ -0,0 +1,146 @@
+//! The builtins each pckp library is allowed to use
+//!
+//! A library can list the unsafe builtins it needs in the `permissions` of
+//! its `pckp.yaml`. When a library from the `pckp_libraries` folder is
+//! imported, it gets the permissions of the build narrowed down to those, and
+//! every builtin called from its code (or from code it called) is checked
+//! against them, even when the library's macros are called later by the script.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use ahash::AHashMap;
+use errors::compiler_info::CompilerInfo;
+use errors::{create_error, RuntimeError};
+use shared::SpwnSource;
+
+use crate::builtins::{Builtin, BuiltinPermissions, BUILTIN_LIST};
+
+/// The folder pckp installs libraries into
+pub const PACKAGE_DIR: &str = "pckp_libraries";
+/// The manifest of a library
+pub const MANIFEST_NAME: &str = "pckp.yaml";
+
+#[derive(Debug, Clone)]
+struct Package {
+    name: String,
+    permissions: BuiltinPermissions,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PackagePermissions {
+    // by the folder of the package
+    packages: AHashMap<PathBuf, Package>,
+}
+
+/// The folder of the pckp library the file is in, if it's in one
+pub fn package_root(file: &Path) -> Option<&Path> {
+    file.ancestors()
+        .find(|dir| dir.parent().and_then(Path::file_name) == Some(PACKAGE_DIR.as_ref()))
+}
+
+// the names in the `permissions` list of the manifest
+fn declared_builtins(manifest: &Path) -> Result<Vec<String>, String> {
+    let text = match fs::read_to_string(manifest) {
+        Ok(text) => text,
+        // a library without a manifest can't use any unsafe builtins
+        Err(_) => return Ok(Vec::new()),
+    };
+    let yaml: serde_yaml::Value = serde_yaml::from_str(&text).map_err(|e| e.to_string())?;
+    match yaml.get("permissions") {
+        None | Some(serde_yaml::Value::Null) => Ok(Vec::new()),
+        Some(serde_yaml::Value::Sequence(list)) => list
+            .iter()
+            .map(|val| match val.as_str() {
+                Some(name) => Ok(name.to_string()),
+                None => Err(format!(
+                    "Expected builtin names in `permissions`, found {:?}",
+                    val
+                )),
+            })
+            .collect(),
+        Some(other) => Err(format!(
+            "Expected `permissions` to be a list of builtin names, found {:?}",
+            other
+        )),
+    }
+}
+
+impl PackagePermissions {
+    /// Reads the manifest of the library `module` is in (if it's in one and it wasn't
+    /// read yet), and narrows the permissions of the build down to the ones it declares
+    #[allow(clippy::result_large_err)]
+    pub fn load(
+        &mut self,
+        module: &Path,
+        build: &BuiltinPermissions,
+        info: &CompilerInfo,
+    ) -> Result<(), RuntimeError> {
+        let root = match package_root(module) {
+            Some(root) if !self.packages.contains_key(root) => root,
+            _ => return Ok(()),
+        };
+        let manifest = root.join(MANIFEST_NAME);
+        let error = |message: String| {
+            RuntimeError::CustomError(create_error(
+                info.clone(),
+                &format!("Invalid manifest {}: {}", manifest.display(), message),
+                &[],
+                None,
+            ))
+        };
+        let mut permissions = build.clone();
+        let declared = declared_builtins(&manifest)
+            .map_err(error)?
+            .into_iter()
+            .map(|name| {
+                Builtin::from_str(&name).map_err(|_| error(format!("unknown builtin `{}`", name)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for b in BUILTIN_LIST {
+            if !permissions.is_safe(*b) && !declared.contains(b) {
+                permissions.set(*b, false);
+            }
+        }
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.packages
+            .insert(root.to_path_buf(), Package { name, permissions });
+        Ok(())
+    }
+
+    /// Returns an error message if the builtin is called from the code of a library
+    /// (somewhere in the call stack) that didn't declare it. Should be checked after
+    /// the permissions of the build, since those are narrowed down for the libraries.
+    pub fn check(&self, builtin: Builtin, info: &CompilerInfo) -> Result<(), String> {
+        if self.packages.is_empty() {
+            return Ok(());
+        }
+        let areas = info
+            .call_stack
+            .iter()
+            .chain(std::iter::once(&info.position));
+        for area in areas {
+            let file = match area.file.as_ref() {
+                SpwnSource::File(file) => file,
+                _ => continue,
+            };
+            let package = match package_root(file).and_then(|root| self.packages.get(root)) {
+                Some(package) => package,
+                None => continue,
+            };
+            if !package.permissions.is_allowed(builtin) {
+                return Err(format!(
+                    "The library `{}` uses this built-in function without declaring it, add `{}` to the `permissions` in its {}",
+                    package.name,
+                    String::from(builtin),
+                    MANIFEST_NAME
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+//! Limits the builtins that use the file system to some directories
+//!
+//! `--allow-read=DIR` and `--allow-write=DIR` allow the builtins that read or
+//! change the file system, but only for paths inside the given directories.
+//! Paths are compared after resolving `.`, `..` and symbolic links, so a path
+//! can't get out of a directory through them, even links to files that don't
+//! exist yet.
+
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::builtins::Builtin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsAccess {
+    Read,
+    Write,
+}
+
+impl FsAccess {
+    const READ: &'static [Builtin] = &[
+        Builtin::ReadFile,
+        Builtin::ReadDir,
+        Builtin::MetaData,
+        Builtin::FileExists,
+        Builtin::FileKind,
+    ];
+    const WRITE: &'static [Builtin] = &[
+        Builtin::WriteFile,
+        Builtin::DeleteFile,
+        Builtin::MkDir,
+        Builtin::RmDir,
+        Builtin::RmDirAll,
+    ];
+
+    /// How the builtin uses the paths it's given, if it can be limited to some directories
+    pub fn of(builtin: Builtin) -> Option<Self> {
+        if FsAccess::READ.contains(&builtin) {
+            Some(FsAccess::Read)
+        } else if FsAccess::WRITE.contains(&builtin) {
+            Some(FsAccess::Write)
+        } else {
+            None
+        }
+    }
+
+    /// The builtins that are allowed by `--allow-read` or `--allow-write`
+    pub fn builtins(self) -> &'static [Builtin] {
+        match self {
+            FsAccess::Read => FsAccess::READ,
+            FsAccess::Write => FsAccess::WRITE,
+        }
+    }
+
+    /// The command line flag that allows these builtins for a directory
+    pub fn flag(self) -> &'static str {
+        match self {
+            FsAccess::Read => "--allow-read",
+            FsAccess::Write => "--allow-write",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PathScopes {
+    // `None` if the builtins can use any path
+    read: Option<Vec<PathBuf>>,
+    write: Option<Vec<PathBuf>>,
+}
+
+impl PathScopes {
+    fn scopes(&self, access: FsAccess) -> &Option<Vec<PathBuf>> {
+        match access {
+            FsAccess::Read => &self.read,
+            FsAccess::Write => &self.write,
+        }
+    }
+
+    /// Adds a directory the builtins with this access can use. Once one is added,
+    /// the builtins can't use paths outside of them anymore.
+    pub fn allow(&mut self, access: FsAccess, dir: &Path) {
+        let scopes = match access {
+            FsAccess::Read => &mut self.read,
+            FsAccess::Write => &mut self.write,
+        };
+        scopes.get_or_insert_with(Vec::new).push(resolve(dir));
+    }
+
+    /// Returns an error message naming the path if the builtin can't use it
+    pub fn check(&self, builtin: Builtin, path: &Path) -> Result<(), String> {
+        let access = match FsAccess::of(builtin) {
+            Some(access) => access,
+            None => return Ok(()),
+        };
+        let scopes = match self.scopes(access) {
+            Some(scopes) => scopes,
+            None => return Ok(()),
+        };
+        let resolved = resolve(path);
+        if scopes.iter().any(|dir| resolved.starts_with(dir)) {
+            return Ok(());
+        }
+        let dirs: Vec<String> = scopes
+            .iter()
+            .map(|dir| format!("`{}`", dir.display()))
+            .collect();
+        Err(format!(
+            "Permission denied for `{}` ({}), since `{}` only allows {}",
+            path.display(),
+            resolved.display(),
+            access.flag(),
+            dirs.join(", ")
+        ))
+    }
+}
+
+// how many links inside links are followed, like the limit of most systems
+const MAX_LINKS: usize = 40;
+
+/// The absolute path, with `.`, `..` and the symbolic links in the part of it
+/// that exists resolved (relative paths start at the current directory, like
+/// in the builtins)
+pub fn resolve(path: &Path) -> PathBuf {
+    let cwd = env::current_dir()
+        .and_then(|dir| dir.canonicalize())
+        .unwrap_or_default();
+    resolve_from(cwd, path, 0)
+}
+
+fn resolve_from(mut resolved: PathBuf, path: &Path, links: usize) -> PathBuf {
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            // the path so far is already resolved, so this goes up from where a link points
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            // the root (or a prefix on windows) replaces the path so far
+            component => {
+                resolved.push(component);
+                match resolved.canonicalize() {
+                    Ok(real) => resolved = real,
+                    // a link to something that doesn't exist can't be canonicalized,
+                    // but writing to it creates the file it points to
+                    Err(_) => {
+                        if let Ok(target) = fs::read_link(&resolved) {
+                            // a loop of links, which isn't inside any directory
+                            if links == MAX_LINKS {
+                                return PathBuf::new();
+                            }
+                            resolved.pop();
+                            resolved = resolve_from(resolved, &target, links + 1);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    resolved
+}
@@ -0,0 +1,130 @@
+//! Records how long macro calls and imports take, for `--profile`
+//!
+//! Every macro call and import is a frame, and the profiler adds up the time
+//! and the number of values stored in each stack of frames, not counting the
+//! frames that were called from it. The stacks can be written as a
+//! flamegraph-compatible folded stack file.
+
+use std::time::{Duration, Instant};
+
+use ahash::AHashMap;
+use errors::compiler_info::CodeArea;
+use shared::ImportType;
+
+use crate::globals::Globals;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Frame {
+    Import(ImportType),
+    // the area of the call, like in `CompilerInfo::call_stack`
+    Macro(CodeArea),
+}
+
+/// The time spent in a stack of frames, and how many values were stored in it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    pub time: Duration,
+    pub values: u64,
+    pub calls: u64,
+}
+
+impl std::ops::AddAssign for Sample {
+    fn add_assign(&mut self, other: Sample) {
+        self.time += other.time;
+        self.values += other.values;
+        self.calls += other.calls;
+    }
+}
+
+struct OpenFrame {
+    start: Instant,
+    values: u64,
+    // the time and values of the frames called from this one, so they aren't counted twice
+    children: (Duration, u64),
+}
+
+impl OpenFrame {
+    fn new(values: u64) -> Self {
+        OpenFrame {
+            start: Instant::now(),
+            values,
+            children: (Duration::ZERO, 0),
+        }
+    }
+}
+
+pub struct Profiler {
+    frames: Vec<Frame>,
+    // one more than `frames`, since the script itself is also open
+    open: Vec<OpenFrame>,
+    samples: AHashMap<Vec<Frame>, Sample>,
+}
+
+impl Profiler {
+    pub fn new(values: u64) -> Self {
+        Profiler {
+            frames: Vec::new(),
+            open: vec![OpenFrame::new(values)],
+            samples: AHashMap::default(),
+        }
+    }
+
+    pub fn enter(&mut self, frame: Frame, values: u64) {
+        self.frames.push(frame);
+        self.open.push(OpenFrame::new(values));
+    }
+
+    pub fn exit(&mut self, values: u64) {
+        let open = match self.open.pop() {
+            Some(o) => o,
+            None => return,
+        };
+        let time = open.start.elapsed();
+        let values = values - open.values;
+
+        let sample = self.samples.entry(self.frames.clone()).or_default();
+        sample.time += time.saturating_sub(open.children.0);
+        sample.values += values - open.children.1;
+        sample.calls += 1;
+        self.frames.pop();
+
+        if let Some(parent) = self.open.last_mut() {
+            parent.children.0 += time;
+            parent.children.1 += values;
+        }
+    }
+
+    /// Closes all the frames, including the one for the script itself
+    pub fn finish(&mut self, values: u64) {
+        while !self.open.is_empty() {
+            self.exit(values);
+        }
+    }
+
+    /// Every stack of frames, and the time and values used in the last frame of
+    /// it. The empty stack is the script itself.
+    pub fn samples(&self) -> impl Iterator<Item = (&[Frame], Sample)> {
+        self.samples.iter().map(|(k, v)| (k.as_slice(), *v))
+    }
+}
+
+/// Runs `f` in a new frame if the profiler is on
+pub fn profile<T>(
+    globals: &mut Globals,
+    frame: impl FnOnce() -> Frame,
+    f: impl FnOnce(&mut Globals) -> T,
+) -> T {
+    let values = globals.stored_values.total_stored;
+    match &mut globals.profiler {
+        Some(profiler) => profiler.enter(frame(), values),
+        None => return f(globals),
+    }
+
+    // the frame is closed even if `f` fails, since the error can be caught
+    let out = f(globals);
+    let values = globals.stored_values.total_stored;
+    if let Some(profiler) = &mut globals.profiler {
+        profiler.exit(values);
+    }
+    out
+}
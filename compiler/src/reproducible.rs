@@ -0,0 +1,122 @@
+//! Makes the builtins that depend on the outside world give the same results
+//! every build
+//!
+//! With a seed (`--seed` or `#[seed(n)]`), `$.random` uses a seeded RNG
+//! instead of the thread's one. `--fixed-time` is returned by `$.time`, and
+//! with an input file `$.get_input` answers with its lines instead of asking
+//! the user. The same seed gives the same numbers with the same version of spwn.
+
+use std::collections::VecDeque;
+
+use errors::compiler_info::{CodeArea, CompilerInfo};
+use errors::{create_error, RuntimeError};
+use internment::LocalIntern;
+use parser::ast;
+use shared::SpwnSource;
+
+#[cfg(not(target_arch = "wasm32"))]
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[derive(Debug, Default)]
+pub struct Reproducible {
+    #[cfg(not(target_arch = "wasm32"))]
+    rng: Option<StdRng>,
+    fixed_time: Option<f64>,
+    // the answers that are left, `None` if the user is asked
+    inputs: Option<VecDeque<String>>,
+}
+
+impl Reproducible {
+    pub fn new(seed: Option<u64>, fixed_time: Option<f64>, inputs: Option<Vec<String>>) -> Self {
+        #[cfg(target_arch = "wasm32")]
+        let _ = seed;
+        Reproducible {
+            #[cfg(not(target_arch = "wasm32"))]
+            rng: seed.map(StdRng::seed_from_u64),
+            fixed_time,
+            inputs: inputs.map(VecDeque::from),
+        }
+    }
+
+    /// A number between 0 and 1
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn random_number(&mut self) -> f64 {
+        match &mut self.rng {
+            Some(rng) => rng.gen(),
+            None => rand::thread_rng().gen(),
+        }
+    }
+
+    /// A random index into a list of `len` elements, or `None` if it's empty
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn random_index(&mut self, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        Some(match &mut self.rng {
+            Some(rng) => rng.gen_range(0..len),
+            None => rand::thread_rng().gen_range(0..len),
+        })
+    }
+
+    /// The time `$.time` returns instead of the system time
+    pub fn fixed_time(&self) -> Option<f64> {
+        self.fixed_time
+    }
+
+    /// The next answer for `$.get_input`. `None` if the user should be asked,
+    /// and `Some(Err)` if there are no answers left.
+    pub fn next_input(&mut self) -> Option<Result<String, String>> {
+        let inputs = self.inputs.as_mut()?;
+        Some(
+            inputs
+                .pop_front()
+                .ok_or_else(|| "The input file has no answers left".to_string()),
+        )
+    }
+}
+
+/// The seed from the `#[seed(n)]` attribute of the file, if it has one
+#[allow(clippy::result_large_err)]
+pub fn file_seed(file_tag: &ast::Attribute, file: SpwnSource) -> Result<Option<u64>, RuntimeError> {
+    let args = match file_tag.get("seed") {
+        Some(args) => args,
+        None => return Ok(None),
+    };
+    let seed = match args.as_slice() {
+        [ast::Argument {
+            symbol: None,
+            value,
+            ..
+        }] => match value.values.as_slice() {
+            [ast::Variable {
+                value: ast::ValueLiteral { body },
+                operator: None,
+                path,
+                ..
+            }] if path.is_empty() => match body {
+                // small integers are parsed as numbers
+                ast::ValueBody::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as u64),
+                ast::ValueBody::Int(n) if *n >= 0 => Some(*n as u64),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    };
+    match seed {
+        Some(seed) => Ok(Some(seed)),
+        None => {
+            let pos = args.first().map(|arg| arg.pos).unwrap_or_default();
+            Err(RuntimeError::CustomError(create_error(
+                CompilerInfo::from_area(CodeArea {
+                    file: LocalIntern::new(file),
+                    pos,
+                }),
+                "Expected a single non-negative integer, like `#[seed(42)]`",
+                &[],
+                None,
+            )))
+        }
+    }
+}
@@ -0,0 +1,294 @@
+// sync groups
+//
+// the parts of a sync group never run at the same time, so a group that is
+// only used inside one part can be shared with a group from another part.
+// every part starts by toggling off all the shared triggers of its sync group,
+// and then toggling its own shared triggers back on:
+//
+// sync a
+// sync a[1] { ... } // part 1 uses 5?g
+// sync a[2] { ... } // part 2 uses 9?g, which becomes 5?g as well
+
+use ahash::{AHashMap, AHashSet};
+use errors::compiler_info::CompilerInfo;
+use parser::ast::ObjectMode;
+
+use crate::builtins::{context_trigger, ArbitraryId, Group, Id};
+use crate::compiler_types::{
+    FunctionId, SyncGroup, SyncGroupId, SyncPart, SyncPartId, TriggerOrder,
+};
+use crate::context::Context;
+use crate::globals::Globals;
+use crate::leveldata::{GdObj, ObjParam};
+
+const TOGGLE: f64 = 1049.0;
+const SPAWN: f64 = 1268.0;
+const TRIGGERS_WITH_ACTIVATE: [f64; 4] = [
+    1611.0, // count
+    1811.0, // instant count
+    1815.0, // collision
+    1812.0, // on death
+];
+
+const GROUPS: u16 = 57;
+const TARGET: u16 = 51;
+const ACTIVATE_GROUP: u16 = 56;
+
+pub fn new_sync_group(globals: &mut Globals) -> SyncGroupId {
+    let toggle_group = Group::next_free(&mut globals.closed_groups);
+    globals.sync_groups.push(SyncGroup {
+        parts: Vec::new(),
+        toggle_group,
+    });
+    globals.sync_groups.len() - 1
+}
+
+// finds the part with the given key, or makes a new one
+pub fn get_part(globals: &mut Globals, sync: SyncGroupId, key: u64) -> SyncPartId {
+    if let Some(i) = globals.sync_groups[sync]
+        .parts
+        .iter()
+        .position(|p| p.key == key)
+    {
+        return i;
+    }
+    let toggle_group = Group::next_free(&mut globals.closed_groups);
+    let parts = &mut globals.sync_groups[sync].parts;
+    parts.push(SyncPart {
+        key,
+        toggle_group,
+        groups_made: Vec::new(),
+        objects_made: Vec::new(),
+        start_triggers: Vec::new(),
+    });
+    parts.len() - 1
+}
+
+// adds the toggle triggers that start a part in this context
+pub fn start_part(
+    context: &Context,
+    globals: &mut Globals,
+    sync: SyncGroupId,
+    part: SyncPartId,
+    info: &CompilerInfo,
+) {
+    let targets = [
+        (globals.sync_groups[sync].toggle_group, false),
+        (globals.sync_groups[sync].parts[part].toggle_group, true),
+    ];
+    for (target, activate) in targets {
+        let mut params = AHashMap::default();
+        params.insert(1, ObjParam::Number(TOGGLE));
+        params.insert(TARGET, ObjParam::Group(target));
+        params.insert(ACTIVATE_GROUP, ObjParam::Bool(activate));
+        globals.trigger_order += 1.0;
+
+        let obj = GdObj {
+            params,
+            ..context_trigger(context, &mut globals.uid_counter)
+        }
+        .context_parameters(context);
+        globals.record_origin(obj.unique_id, info);
+        globals.sync_groups[sync].parts[part]
+            .start_triggers
+            .push(obj.unique_id);
+
+        globals.func_ids[context.func_id]
+            .obj_list
+            .push((obj, TriggerOrder(globals.trigger_order)))
+    }
+}
+
+// the groups that the start triggers toggle. these are only put on
+// triggers when the groups are shared, so the optimizer has to keep
+// the toggle triggers even though the groups seem empty
+pub fn toggle_groups(sync_groups: &[SyncGroup]) -> impl Iterator<Item = Id> + '_ {
+    sync_groups.iter().flat_map(|s| {
+        std::iter::once(s.toggle_group.id).chain(s.parts.iter().map(|p| p.toggle_group.id))
+    })
+}
+
+#[derive(Default)]
+struct GroupUse {
+    // a group can only be shared if it's only used to hold triggers and to spawn them
+    shareable: bool,
+    // unique ids of the triggers in the group
+    triggers: Vec<usize>,
+    // unique ids of the triggers that activate the group
+    activators: Vec<usize>,
+}
+
+fn group_uses(func_ids: &[FunctionId], objects: &[GdObj]) -> AHashMap<ArbitraryId, GroupUse> {
+    let mut uses = AHashMap::<ArbitraryId, GroupUse>::default();
+
+    let all_objects = func_ids
+        .iter()
+        .flat_map(|f| f.obj_list.iter().map(|(obj, _)| obj))
+        .chain(objects.iter());
+
+    for obj in all_objects {
+        let activates = match obj.params.get(&1) {
+            Some(ObjParam::Number(id)) if *id == SPAWN => true,
+            Some(ObjParam::Number(id)) if TRIGGERS_WITH_ACTIVATE.contains(id) => {
+                matches!(obj.params.get(&ACTIVATE_GROUP), Some(ObjParam::Bool(true)))
+            }
+            _ => false,
+        };
+        for (key, param) in &obj.params {
+            let groups = match param {
+                ObjParam::Group(g) => vec![*g],
+                ObjParam::GroupList(list) => list.clone(),
+                _ => continue,
+            };
+            for group in &groups {
+                let id = match group.id {
+                    Id::Arbitrary(id) => id,
+                    Id::Specific(_) => continue,
+                };
+                let group_use = uses.entry(id).or_insert_with(|| GroupUse {
+                    shareable: true,
+                    ..Default::default()
+                });
+                match (obj.mode, *key) {
+                    // triggers with more than one group would be toggled
+                    // by the other groups as well
+                    (ObjectMode::Trigger, GROUPS) if groups.len() == 1 => {
+                        group_use.triggers.push(obj.unique_id)
+                    }
+                    (ObjectMode::Trigger, TARGET) if activates => {
+                        group_use.activators.push(obj.unique_id)
+                    }
+                    _ => group_use.shareable = false,
+                }
+            }
+        }
+    }
+    uses
+}
+
+// the groups made in a part that can be shared with the other parts
+fn shareable_groups(
+    part: &SyncPart,
+    uses: &AHashMap<ArbitraryId, GroupUse>,
+    claimed: &AHashSet<ArbitraryId>,
+) -> Vec<ArbitraryId> {
+    let made_in_part = |uid: &usize| part.objects_made.iter().any(|r| r.contains(uid));
+
+    let mut groups: Vec<ArbitraryId> = part
+        .groups_made
+        .iter()
+        .flat_map(|r| r.clone())
+        .filter(|g| !claimed.contains(g))
+        .filter(|g| match uses.get(g) {
+            Some(u) => {
+                u.shareable
+                    && !u.triggers.is_empty()
+                    && u.triggers.iter().all(made_in_part)
+                    && u.activators.iter().all(made_in_part)
+            }
+            None => false,
+        })
+        .collect();
+    groups.sort_unstable();
+    groups.dedup();
+    groups
+}
+
+// lets the parts of every sync group share their groups.
+// this runs after optimization, right before the ids are assigned
+pub fn share_groups(func_ids: &mut [FunctionId], objects: &[GdObj], sync_groups: &[SyncGroup]) {
+    let uses = group_uses(func_ids, objects);
+
+    let existing: AHashSet<usize> = func_ids
+        .iter()
+        .flat_map(|f| f.obj_list.iter().map(|(obj, _)| obj.unique_id))
+        .collect();
+
+    let mut claimed = AHashSet::<ArbitraryId>::default();
+    let mut renamed = AHashMap::<ArbitraryId, ArbitraryId>::default();
+    // extra groups for the triggers in shared groups
+    let mut toggled_by = AHashMap::<usize, Vec<Group>>::default();
+    let mut unused_start_triggers = AHashSet::<usize>::default();
+
+    for sync in sync_groups {
+        let parts: Vec<Vec<ArbitraryId>> = sync
+            .parts
+            .iter()
+            .map(|part| {
+                // a part can't share anything if the optimizer removed its start triggers
+                if part.start_triggers.iter().all(|t| existing.contains(t)) {
+                    shareable_groups(part, &uses, &claimed)
+                } else {
+                    Vec::new()
+                }
+            })
+            .collect();
+
+        let mut shares = vec![false; parts.len()];
+        let most = parts.iter().map(|p| p.len()).max().unwrap_or(0);
+
+        // the nth shareable group of every part becomes the same group
+        for n in 0..most {
+            let sharing: Vec<usize> = (0..parts.len()).filter(|p| parts[*p].len() > n).collect();
+            if sharing.len() < 2 {
+                break;
+            }
+            let new_id = parts[sharing[0]][n];
+            for p in sharing {
+                let old_id = parts[p][n];
+                shares[p] = true;
+                claimed.insert(old_id);
+                renamed.insert(old_id, new_id);
+                for trigger in &uses[&old_id].triggers {
+                    toggled_by
+                        .entry(*trigger)
+                        .or_default()
+                        .extend([sync.toggle_group, sync.parts[p].toggle_group]);
+                }
+            }
+        }
+
+        for (part, shares) in sync.parts.iter().zip(shares) {
+            if !shares {
+                unused_start_triggers.extend(part.start_triggers.iter().copied());
+            }
+        }
+    }
+
+    for fn_id in func_ids {
+        fn_id
+            .obj_list
+            .retain(|(obj, _)| !unused_start_triggers.contains(&obj.unique_id));
+
+        for (obj, _) in &mut fn_id.obj_list {
+            for param in obj.params.values_mut() {
+                let groups = match param {
+                    ObjParam::Group(g) => std::slice::from_mut(g),
+                    ObjParam::GroupList(list) => &mut list[..],
+                    _ => continue,
+                };
+                for group in groups {
+                    if let Id::Arbitrary(id) = group.id {
+                        if let Some(new_id) = renamed.get(&id) {
+                            group.id = Id::Arbitrary(*new_id);
+                        }
+                    }
+                }
+            }
+
+            if let Some(extra) = toggled_by.get(&obj.unique_id) {
+                let mut groups = match obj.params.get(&GROUPS) {
+                    Some(ObjParam::Group(g)) => vec![*g],
+                    Some(ObjParam::GroupList(list)) => list.clone(),
+                    _ => Vec::new(),
+                };
+                for group in extra {
+                    if !groups.contains(group) {
+                        groups.push(*group);
+                    }
+                }
+                obj.params.insert(GROUPS, ObjParam::GroupList(groups));
+            }
+        }
+    }
+}
@@ -30,6 +30,8 @@ pub enum Value {
     Block(Block),
     Item(Item),
     Number(f64),
+    // exact integers, for things like big IDs and bit manipulation
+    Int(i64),
     Bool(bool),
     TriggerFunc(TriggerFunction),
     Dict(AHashMap<LocalIntern<String>, StoredValue>),
@@ -42,6 +44,7 @@ pub enum Value {
     TypeIndicator(TypeId),
     Range(i32, i32, usize), //start, end, step
     Pattern(Pattern),
+    SyncGroup(SyncGroupId),
     Null,
 }
 
@@ -343,11 +346,46 @@ pub fn default_value_equality(
                 (a, b) => set_return_bool(a == b, globals, contexts),
             }
         }
+        (Value::Int(i), Value::Number(n)) | (Value::Number(n), Value::Int(i)) => {
+            set_return_bool(int_equals_number(i, n), globals, contexts)
+        }
         (a, b) => set_return_bool(a == b, globals, contexts),
     };
     Ok(())
 }
 
+// `key: #[private] value` in a dictionary or impl
+pub fn marked_private(expr: &ast::Expression) -> bool {
+    expr.values
+        .first()
+        .is_some_and(|v| v.tag.get("private").is_some())
+}
+
+pub fn private_error(
+    name: &str,
+    val: StoredValue,
+    area: CodeArea,
+    globals: &Globals,
+    info: &CompilerInfo,
+) -> RuntimeError {
+    RuntimeError::CustomError(create_error(
+        info.clone(),
+        "Use of private member",
+        &[
+            (globals.get_area(val), "Defined as private here"),
+            (
+                area,
+                &format!("`{}` can only be used in the file it's defined in", name),
+            ),
+        ],
+        None,
+    ))
+}
+
+pub fn int_equals_number(i: i64, n: f64) -> bool {
+    n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 && n as i64 == i
+}
+
 pub fn strict_value_equality(val1: StoredValue, val2: StoredValue, globals: &Globals) -> bool {
     match (&globals.stored_values[val1], &globals.stored_values[val2]) {
         (Value::Array(a1), Value::Array(a2)) => {
@@ -395,6 +433,9 @@ pub fn strict_value_equality(val1: StoredValue, val2: StoredValue, globals: &Glo
                 _ => p1 == p2,
             }
         }
+        (Value::Int(i), Value::Number(n)) | (Value::Number(n), Value::Int(i)) => {
+            int_equals_number(*i, *n)
+        }
         (a, b) => a == b,
     }
 }
@@ -463,6 +504,12 @@ macro_rules! type_id {
     (epsilon) => {
         20
     };
+    (sync_group) => {
+        21
+    };
+    (int) => {
+        22
+    };
 }
 
 pub(crate) use type_id;
@@ -476,6 +523,7 @@ impl Value {
             Value::Block(_) => type_id!(block),
             Value::Item(_) => type_id!(item),
             Value::Number(_) => type_id!(number),
+            Value::Int(_) => type_id!(int),
             Value::Bool(_) => type_id!(bool),
             Value::TriggerFunc(_) => type_id!(trigger_function),
             Value::Dict(d) => match d.get(&globals.TYPE_MEMBER_NAME) {
@@ -499,6 +547,7 @@ impl Value {
             Value::Null => type_id!(NULL),
             Value::Range(_, _, _) => type_id!(range),
             Value::Pattern(_) => type_id!(pattern),
+            Value::SyncGroup(_) => type_id!(sync_group),
         }
     }
 
@@ -531,6 +580,7 @@ impl Value {
             Value::Block(v) => v.hash(state),
             Value::Item(v) => v.hash(state),
             Value::Number(v) => v.to_ne_bytes().hash(state),
+            Value::Int(v) => v.hash(state),
             Value::Bool(v) => v.hash(state),
             Value::TriggerFunc(v) => v.hash(state),
             Value::Dict(v) => {
@@ -561,6 +611,7 @@ impl Value {
                 st.hash(state);
             }
             Value::Pattern(v) => v.hash(state, globals),
+            Value::SyncGroup(v) => v.hash(state),
             Value::Null => "null".hash(state),
         }
     }
@@ -693,8 +744,11 @@ impl Value {
                     }
                 }
                 Pattern::Type(t) => {
+                    // @ints are also @numbers
+                    let is_type = self.to_num(globals) == t
+                        || (t == type_id!(number) && matches!(self, Value::Int(_)));
                     (*full_context.inner()).return_value = store_const_value(
-                        Value::Bool(self.to_num(globals) == t),
+                        Value::Bool(is_type),
                         globals,
                         full_context.inner().start_group,
                         info.position,
@@ -1008,6 +1062,7 @@ impl Value {
                 }) + "i"
             }
             Value::Number(n) => n.to_string(),
+            Value::Int(n) => n.to_string(),
             Value::Bool(b) => b.to_string(),
             Value::TriggerFunc(_) => "!{ /* trigger function */ }".to_string(),
             Value::Range(start, end, stepsize) => {
@@ -1025,6 +1080,8 @@ impl Value {
                 }
 
                 let mut d = dict_in.clone();
+                // private entries aren't shown, since they can be printed from any file
+                d.retain(|_, v| !globals.stored_values.map[*v].private);
                 if let Some(n) = d.get(&globals.TYPE_MEMBER_NAME) {
                     let val = globals.stored_values[*n].clone();
                     out += &display_inner(&val, globals)?;
@@ -1126,6 +1183,7 @@ impl Value {
                 out
             }
             Value::Builtins => "$".to_string(),
+            Value::SyncGroup(id) => format!("<sync group {}>", id),
             Value::Null => "null".to_string(),
             Value::TypeIndicator(id) => format!(
                 "@{}",
@@ -1235,6 +1293,31 @@ impl Value {
     }
 }
 
+// indexes and object keys are @numbers, so @ints are converted first
+fn int_to_number(val: StoredValue, globals: &mut Globals, start_group: Group) -> StoredValue {
+    match globals.stored_values[val] {
+        Value::Int(n) => {
+            let area = globals.get_area(val);
+            store_const_value(Value::Number(n as f64), globals, start_group, area)
+        }
+        _ => val,
+    }
+}
+
+// `as @int` doesn't round, so the number has to be an integer already
+#[allow(clippy::result_large_err)]
+fn number_to_int(n: f64, info: &CompilerInfo) -> Result<i64, RuntimeError> {
+    if n.fract() != 0.0 || n < i64::MIN as f64 || n >= i64::MAX as f64 {
+        return Err(RuntimeError::CustomError(create_error(
+            info.clone(),
+            &format!("Cannot convert {} to @int, since it isn't an integer", n),
+            &[],
+            Some("Use `$.floor`, `$.ceil` or `$.round` to get an integer"),
+        )));
+    }
+    Ok(n as i64)
+}
+
 pub fn convert_type(
     val: &Value,
     typ: TypeId,
@@ -1258,6 +1341,22 @@ pub fn convert_type(
         (Value::Number(n), type_id!(item)) => Value::Item(Item::new(*n as u16)),
         (Value::Number(n), type_id!(number)) => Value::Number(*n),
         (Value::Number(n), type_id!(bool)) => Value::Bool(*n != 0.0),
+        (Value::Number(n), type_id!(int)) => Value::Int(number_to_int(*n, info)?),
+
+        (Value::Int(n), type_id!(number)) => Value::Number(*n as f64),
+        (Value::Int(n), type_id!(bool)) => Value::Bool(*n != 0),
+        (Value::Int(n), _)
+            if [type_id!(group), type_id!(color), type_id!(block), type_id!(item)].contains(&typ) =>
+        {
+            convert_type(&Value::Number(*n as f64), typ, info, globals, context)?
+        }
+        (
+            Value::Group(_) | Value::Color(_) | Value::Block(_) | Value::Item(_) | Value::Bool(_),
+            type_id!(int),
+        ) => match convert_type(val, type_id!(number), info, globals, context)? {
+            Value::Number(n) => Value::Int(n as i64),
+            _ => unreachable!(),
+        },
 
         (Value::Group(g), type_id!(number)) => Value::Number(match g.id {
             Id::Specific(n) => n as f64,
@@ -1325,6 +1424,25 @@ pub fn convert_type(
         },
 
     
+        (Value::Str(s), type_id!(int)) => {
+            let (digits, radix) = match s.get(..2) {
+                Some("0x") => (&s[2..], 16),
+                Some("0b") => (&s[2..], 2),
+                Some("0o") => (&s[2..], 8),
+                _ => (&s[..], 10),
+            };
+            match i64::from_str_radix(digits, radix) {
+                Ok(n) => Value::Int(n),
+                Err(_) => {
+                    return Err(RuntimeError::CustomError(create_error(
+                        info.clone(),
+                        &format!("Cannot convert string '{}' to @int", s),
+                        &[],
+                        None,
+                    )))
+                }
+            }
+        }
         (Value::Str(s), type_id!(number)) => {
             if s.len() < 2 {
                 let out: std::result::Result<f64, _> = s.parse();
@@ -1799,6 +1917,14 @@ impl VariableFuncs for ast::Variable {
                         info.position,
                     )
                 }
+                ast::ValueBody::Int(num) => {
+                    full_context.inner().return_value = store_const_value(
+                        Value::Int(*num),
+                        globals,
+                        full_context.inner().start_group,
+                        info.position,
+                    )
+                }
                 ast::ValueBody::Dictionary(dict) => {
                     eval_dict(dict.clone(), full_context, globals, info.clone(), constant)?
                 }
@@ -1928,6 +2054,52 @@ impl VariableFuncs for ast::Variable {
                     )
                 }
 
+                ast::ValueBody::FormatStr(parts) => {
+                    let exprs = parts
+                        .iter()
+                        .filter_map(|part| match part {
+                            ast::FormatStrPart::Expr(e) => Some(e.clone()),
+                            ast::FormatStrPart::Str(_) => None,
+                        })
+                        .collect();
+                    let combinations =
+                        all_combinations(exprs, full_context, globals, info.clone(), constant)?;
+
+                    for (values, fc) in combinations {
+                        let mut values = values.into_iter();
+                        let mut out = String::new();
+                        for part in parts {
+                            let expr = match part {
+                                ast::FormatStrPart::Str(s) => {
+                                    out += s;
+                                    continue;
+                                }
+                                ast::FormatStrPart::Expr(e) => e,
+                            };
+                            let val = values.next().unwrap();
+                            if let Value::Str(s) = &globals.stored_values[val] {
+                                out += s;
+                                continue;
+                            }
+                            // displayed the same way as in $.print
+                            let mut info = info.clone();
+                            info.position.pos =
+                                (expr.values[0].pos.0, expr.values.last().unwrap().pos.1);
+                            handle_unary_operator(val, Builtin::DisplayOp, fc, globals, &info)?;
+                            match &globals.stored_values[fc.inner().return_value] {
+                                Value::Str(s) => out += s,
+                                a => out += &a.clone().to_str(globals),
+                            }
+                        }
+                        fc.inner().return_value = store_const_value(
+                            Value::Str(out),
+                            globals,
+                            fc.inner().start_group,
+                            info.position,
+                        )
+                    }
+                }
+
                 ast::ValueBody::ListComp(comp) => {
                     globals.push_new_preserved();
                     comp.iterator
@@ -2912,7 +3084,11 @@ impl VariableFuncs for ast::Variable {
                     for (expressions, context) in combinations {
                         let mut obj: Vec<(u16, ObjParam)> = Vec::new();
                         for i in 0..(o.props.len()) {
-                            let o_key = expressions[i * 2];
+                            let o_key = int_to_number(
+                                expressions[i * 2],
+                                globals,
+                                context.inner().start_group,
+                            );
                             let o_val = expressions[i * 2 + 1];
                             // hopefully self explanatory
 
@@ -3049,6 +3225,7 @@ impl VariableFuncs for ast::Variable {
 
                                             ObjParam::Number(*n)
                                         },
+                                        Value::Int(n) => ObjParam::Number(*n as f64),
                                         Value::Str(s) => ObjParam::Text(s.clone()),
                                         Value::TriggerFunc(g) => ObjParam::Group(g.start_group),
 
@@ -3177,6 +3354,15 @@ impl VariableFuncs for ast::Variable {
 
                         (*full_context.inner()).return_value =
                             match val.member(*m, full_context.inner(), globals, info.clone()) {
+                                Some(member) if globals.is_hidden(member, info.position.file) => {
+                                    return Err(private_error(
+                                        m,
+                                        member,
+                                        info.position,
+                                        globals,
+                                        &info,
+                                    ));
+                                }
                                 Some(m) => m,
                                 None => {
                                     return Err(RuntimeError::UndefinedErr {
@@ -3197,6 +3383,17 @@ impl VariableFuncs for ast::Variable {
                         (*full_context.inner()).return_value = match &val {
                             Value::TypeIndicator(t) => match globals.implementations.get(t) {
                                 Some(imp) => match imp.get(a) {
+                                    Some((val, _))
+                                        if globals.is_hidden(*val, info.position.file) =>
+                                    {
+                                        return Err(private_error(
+                                            a,
+                                            *val,
+                                            info.position,
+                                            globals,
+                                            &info,
+                                        ));
+                                    }
                                     Some((val, _)) => {
                                         if let Value::Macro(Macro::FuncLike(m)) = &globals.stored_values[*val] {
                                             if !m.args.is_empty()
@@ -3294,6 +3491,7 @@ impl VariableFuncs for ast::Variable {
                                     }
                                     Ok(Some(*n as isize))
                                 }
+                                Value::Int(n) => Ok(Some(*n as isize)),
                                 Value::Null => Ok(None),
                                 _ => {
                                     return Err(RuntimeError::TypeError {
@@ -3373,7 +3571,11 @@ impl VariableFuncs for ast::Variable {
                         i.eval(full_context, globals, info.clone(), constant)?;
 
                         for full_context in full_context.iter() {
-                            let index_ptr = full_context.inner().return_value;
+                            let index_ptr = int_to_number(
+                                full_context.inner().return_value,
+                                globals,
+                                full_context.inner().start_group,
+                            );
                             match &val {
                                 Value::Array(arr) => {
                                     match &globals.stored_values[index_ptr] {
@@ -3425,7 +3627,17 @@ impl VariableFuncs for ast::Variable {
                                                 desc: "dictionary key".to_string(),
                                             });
                                         }
-                                        full_context.inner().return_value = d[&intern];
+                                        let member = d[&intern];
+                                        if globals.is_hidden(member, info.position.file) {
+                                            return Err(private_error(
+                                                s,
+                                                member,
+                                                info.position,
+                                                globals,
+                                                &info,
+                                            ));
+                                        }
+                                        full_context.inner().return_value = member;
                                     }
                                     _ => {
                                         return Err(RuntimeError::TypeError {
@@ -3794,6 +4006,7 @@ impl VariableFuncs for ast::Variable {
                     val_ptr,
                     match o {
                         UnaryOperator::Minus => Builtin::NegOp,
+                        UnaryOperator::BitNot => Builtin::BitNotOp,
                         UnaryOperator::Increment => Builtin::PreIncrOp,
                         UnaryOperator::Decrement => Builtin::PreDecrOp,
                         UnaryOperator::Not => Builtin::NotOp,
@@ -3837,7 +4050,7 @@ impl VariableFuncs for ast::Variable {
             }
         }
 
-        merge_all_contexts(contexts, globals, true);
+        merge_all_contexts(contexts, globals, true, &info);
 
         Ok(())
     }
@@ -3955,6 +4168,9 @@ impl VariableFuncs for ast::Variable {
                     ast::Path::Member(m) => {
                         let val = globals.stored_values[current_ptr].clone();
                         match val.member(*m, full_context.inner(), globals, info.clone()) {
+                            Some(s) if globals.is_hidden(s, info.position.file) => {
+                                return Err(private_error(m, s, info.position, globals, info));
+                            }
                             Some(s) => current_ptr = s,
                             None => {
                                 if !globals.is_mutable(current_ptr) {
@@ -4107,6 +4323,19 @@ impl VariableFuncs for ast::Variable {
                     ast::Path::Associated(m) => {
                         match &globals.stored_values[current_ptr] {
                             Value::TypeIndicator(t) => {
+                                if let Some((val, _)) =
+                                    globals.implementations.get(t).and_then(|imp| imp.get(m))
+                                {
+                                    if globals.is_hidden(*val, info.position.file) {
+                                        return Err(private_error(
+                                            m,
+                                            *val,
+                                            info.position,
+                                            globals,
+                                            info,
+                                        ));
+                                    }
+                                }
                                 match (*globals).implementations.get_mut(t) {
                                     Some(imp) => {
                                         if let Some((val, _)) = imp.get(m) {
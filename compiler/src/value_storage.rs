@@ -16,6 +16,8 @@ pub struct ValStorage {
     pub map: SlotMap<StoredValue, StoredValData>,
     pub preserved_stack: Vec<Vec<StoredValue>>,
     pub prev_value_count: u32,
+    // how many values have been stored, including the ones that were collected
+    pub total_stored: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +26,8 @@ pub struct StoredValData {
     pub fn_context: Group,
     pub mutable: bool,
     pub def_area: CodeArea,
+    // marked with #[private], so it can only be used in the file it's defined in
+    pub private: bool,
     marked: bool,
 }
 
@@ -117,6 +121,11 @@ impl ValStorage {
         }
     }
 
+    fn insert(&mut self, data: StoredValData) -> StoredValue {
+        self.total_stored += 1;
+        self.map.insert(data)
+    }
+
     fn marked(&self, root: StoredValue) -> bool {
         self.map
             .get(root)
@@ -131,6 +140,7 @@ impl ValStorage {
             fn_context: Group::new(0),
             mutable: false,
             def_area: CodeArea::new(),
+            private: false,
             marked: false,
         });
         let null_storage = map.insert(StoredValData {
@@ -138,6 +148,7 @@ impl ValStorage {
             fn_context: Group::new(0),
             mutable: false,
             def_area: CodeArea::new(),
+            private: false,
             marked: false,
         });
 
@@ -146,6 +157,7 @@ impl ValStorage {
                 map,
                 preserved_stack: Vec::new(),
                 prev_value_count: 100,
+                total_stored: 0,
             },
             builtin_storage,
             null_storage,
@@ -317,11 +329,12 @@ pub fn clone_value(
     //do the thing
     //bing bang
     //profit
-    (*globals).stored_values.map.insert(StoredValData {
+    globals.stored_values.insert(StoredValData {
         val: old_val,
         fn_context,
         mutable: !constant,
         def_area: area,
+        private: false,
         marked: false,
     })
 }
@@ -338,12 +351,13 @@ pub fn clone_value_preserve_area(
     //do the thing
     //bing bang
     //profit
-    (*globals).stored_values.map.insert(StoredValData {
+    globals.stored_values.insert(StoredValData {
         val: old_val,
         fn_context,
         mutable: !constant,
 
         def_area: globals.get_area(index),
+        private: globals.stored_values.map[index].private,
         marked: false,
     })
 }
@@ -384,11 +398,12 @@ pub fn store_const_value(
     fn_context: Group,
     area: CodeArea,
 ) -> StoredValue {
-    (*globals).stored_values.map.insert(StoredValData {
+    globals.stored_values.insert(StoredValData {
         val,
         fn_context,
         mutable: false,
         def_area: area,
+        private: false,
         marked: false,
     })
 }
@@ -400,11 +415,12 @@ pub fn store_val_m(
     constant: bool,
     area: CodeArea,
 ) -> StoredValue {
-    (*globals).stored_values.map.insert(StoredValData {
+    globals.stored_values.insert(StoredValData {
         val,
         fn_context,
         mutable: !constant,
         def_area: area,
+        private: false,
         marked: false,
     })
 }
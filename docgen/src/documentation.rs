@@ -48,6 +48,7 @@ pub fn document_lib(path: &str) -> Result<(), RuntimeError> {
     );
 
     let mut start_context = FullContext::new(&globals);
+    start_context.set_root();
 
     // store_value(Value::Builtins, 1, &mut globals, &start_context);
     // store_value(Value::Null, 1, &mut globals, &start_context);
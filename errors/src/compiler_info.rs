@@ -46,7 +46,7 @@ impl Default for CompilerInfo {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct CodeArea {
     pub file: LocalIntern<shared::SpwnSource>,
     pub pos: FileRange,
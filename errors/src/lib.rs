@@ -70,6 +70,98 @@ pub enum RuntimeError {
         dropped: CodeArea,
         reason: String,
     },
+
+    // a value passed to `throw`, one message per context
+    ThrownError {
+        messages: Vec<String>,
+        info: CompilerInfo,
+    },
+
+    // more contexts than `--max-contexts`, with the areas that split the most
+    TooManyContexts {
+        limit: usize,
+        origins: Vec<(CodeArea, usize)>,
+        info: CompilerInfo,
+    },
+}
+
+impl RuntimeError {
+    pub fn info(&self) -> &CompilerInfo {
+        match self {
+            RuntimeError::UndefinedErr { info, .. }
+            | RuntimeError::PackageSyntaxError { info, .. }
+            | RuntimeError::PackageError { info, .. }
+            | RuntimeError::TypeError { info, .. }
+            | RuntimeError::PatternMismatchError { info, .. }
+            | RuntimeError::BuiltinError { info, .. }
+            | RuntimeError::MutabilityError { info, .. }
+            | RuntimeError::ContextChangeMutateError { info, .. }
+            | RuntimeError::ContextChangeError { info, .. }
+            | RuntimeError::BreakNeverUsedError { info, .. }
+            | RuntimeError::ThrownError { info, .. }
+            | RuntimeError::TooManyContexts { info, .. } => info,
+            RuntimeError::CustomError(report) => &report.info,
+        }
+    }
+
+    // short name for the kind of error, used by `try`/`catch`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RuntimeError::UndefinedErr { .. } => "undefined",
+            RuntimeError::PackageSyntaxError { .. } => "package_syntax",
+            RuntimeError::PackageError { .. } => "package",
+            RuntimeError::TypeError { .. } => "type",
+            RuntimeError::PatternMismatchError { .. } => "pattern_mismatch",
+            RuntimeError::CustomError(_) => "custom",
+            RuntimeError::BuiltinError { .. } => "builtin",
+            RuntimeError::MutabilityError { .. } => "mutability",
+            RuntimeError::ContextChangeMutateError { .. }
+            | RuntimeError::ContextChangeError { .. } => "context_change",
+            RuntimeError::BreakNeverUsedError { .. } => "break_never_used",
+            RuntimeError::ThrownError { .. } => "throw",
+            RuntimeError::TooManyContexts { .. } => "too_many_contexts",
+        }
+    }
+
+    // the error message without any formatting
+    pub fn message(&self) -> String {
+        match self {
+            RuntimeError::UndefinedErr {
+                undefined, desc, ..
+            } => format!("Use of undefined {} '{}'", desc, undefined),
+            RuntimeError::PackageSyntaxError { err, .. } => err.message(),
+            RuntimeError::PackageError { err, .. } => err.message(),
+            RuntimeError::TypeError {
+                expected, found, ..
+            } => format!("Type mismatch: expected {}, found {}", expected, found),
+            RuntimeError::PatternMismatchError { pattern, val, .. } => {
+                format!("Pattern mismatch: this {} is not {}", val, pattern)
+            }
+            RuntimeError::CustomError(report) => report.message.clone(),
+            RuntimeError::BuiltinError { message, .. } => message.clone(),
+            RuntimeError::MutabilityError { .. } => {
+                "Attempted to change immutable variable".to_string()
+            }
+            RuntimeError::ContextChangeMutateError { .. } => {
+                "Attempted to change a variable defined in a different trigger function context"
+                    .to_string()
+            }
+            RuntimeError::ContextChangeError { message, .. } => message.clone(),
+            RuntimeError::BreakNeverUsedError { breaktype, .. } => format!(
+                "{} statement never used",
+                match breaktype {
+                    BreakType::ContinueLoop => "Continue",
+                    BreakType::Loop => "Break",
+                    BreakType::Macro(_, _) => "Return",
+                    BreakType::Switch(_) => "Switch",
+                }
+            ),
+            RuntimeError::ThrownError { messages, .. } => messages.join("\n"),
+            RuntimeError::TooManyContexts { limit, .. } => {
+                format!("More than {} contexts are being compiled at once", limit)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -95,7 +187,7 @@ impl RainbowColorGenerator {
         let x = c * (1.0 - (h % 2.0 - 1.0).abs());
         let m = hsl.b - c * 0.5;
 
-        let (red, green, blue) = if h >= 0.0 && h < 0.0 {
+        let (red, green, blue) = if (0.0..1.0).contains(&h) {
             (c, x, 0.0)
         } else if (1.0..2.0).contains(&h) {
             (x, c, 0.0)
@@ -424,10 +516,135 @@ impl From<RuntimeError> for ErrorReport {
                 ],
                 None,
             ),
+
+            RuntimeError::ThrownError { messages, info } => create_error(
+                info.clone(),
+                "Runtime Error",
+                &messages
+                    .iter()
+                    .map(|m| (info.position, m.as_str()))
+                    .collect::<Vec<_>>(),
+                None,
+            ),
+
+            RuntimeError::TooManyContexts {
+                limit,
+                origins,
+                info,
+            } => {
+                let splits: Vec<_> = origins
+                    .iter()
+                    .map(|(area, added)| {
+                        (
+                            *area,
+                            format!(
+                                "Split into {} more context{} here",
+                                added,
+                                if *added == 1 { "" } else { "s" }
+                            ),
+                        )
+                    })
+                    .collect();
+                let mut labels = vec![(info.position, "Too many contexts after this")];
+                labels.extend(splits.iter().map(|(a, l)| (*a, l.as_str())));
+                create_error(
+                    info.clone(),
+                    &format!("More than {} contexts are being compiled at once", limit),
+                    &labels,
+                    Some("Every macro that returns in more than one context (for example with a `return` inside a trigger function) multiplies the contexts that the code after it is compiled in. The limit can be changed with `--max-contexts`"),
+                )
+            }
         }
     }
 }
 
+// the kinds of warnings, which can be turned off with `#[allow(name)]`
+// or turned into errors with `#[deny(name)]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariables,
+    ShadowedLet,
+    UnusedImports,
+    UnreachableCode,
+    UnusedTriggers,
+    // too many contexts at once, found while compiling instead of by the linter
+    ContextExplosion,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 6] = [
+        Lint::UnusedVariables,
+        Lint::ShadowedLet,
+        Lint::UnusedImports,
+        Lint::UnreachableCode,
+        Lint::UnusedTriggers,
+        Lint::ContextExplosion,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariables => "unused_variables",
+            Lint::ShadowedLet => "shadowed_let",
+            Lint::UnusedImports => "unused_imports",
+            Lint::UnreachableCode => "unreachable_code",
+            Lint::UnusedTriggers => "unused_triggers",
+            Lint::ContextExplosion => "context_explosion",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.iter().copied().find(|l| l.name() == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub lint: Lint,
+    pub message: String,
+    pub position: CodeArea,
+    pub labels: Vec<(CodeArea, String)>,
+}
+
+impl Warning {
+    // the error for a warning that is denied with `#[deny(...)]`
+    pub fn into_error(self) -> RuntimeError {
+        let labels: Vec<_> = self.labels.iter().map(|(a, l)| (*a, l.as_str())).collect();
+        RuntimeError::CustomError(create_error(
+            CompilerInfo::from_area(self.position),
+            &self.message,
+            &labels,
+            Some(&format!("`{}` is denied in this file", self.lint.name())),
+        ))
+    }
+}
+
+pub fn create_warning_report(warning: Warning) -> ariadne::Report<CodeArea> {
+    use ariadne::{Label, Report, ReportKind};
+
+    let mut colors = RainbowColorGenerator::new(30.0, 1.5, 0.8);
+    let mut report = Report::build(
+        ReportKind::Warning,
+        warning.position.file.as_ref().clone(),
+        warning.position.pos.0,
+    )
+    .with_message(&warning.message);
+
+    for (i, (area, label)) in warning.labels.into_iter().enumerate() {
+        report = report.with_label(
+            Label::new(area)
+                .with_message(label)
+                .with_order(i as i32)
+                .with_color(colors.next()),
+        );
+    }
+    report
+        .with_note(format!(
+            "add `#[allow({})]` to hide this warning",
+            warning.lint.name().fg(ariadne::Color::Yellow)
+        ))
+        .finish()
+}
+
 #[derive(Debug)]
 pub enum SyntaxError {
     ExpectedErr {
@@ -449,6 +666,28 @@ pub enum SyntaxError {
     CustomError(ErrorReport),
 }
 
+impl SyntaxError {
+    pub fn pos(&self) -> FileRange {
+        match self {
+            SyntaxError::ExpectedErr { pos, .. }
+            | SyntaxError::UnexpectedErr { pos, .. }
+            | SyntaxError::SyntaxError { pos, .. } => *pos,
+            SyntaxError::CustomError(report) => report.info.position.pos,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            SyntaxError::ExpectedErr {
+                expected, found, ..
+            } => format!("Expected {}, found {}", expected, found),
+            SyntaxError::UnexpectedErr { found, .. } => format!("Unexpected {}", found),
+            SyntaxError::SyntaxError { message, .. } => message.clone(),
+            SyntaxError::CustomError(report) => report.message.clone(),
+        }
+    }
+}
+
 impl From<SyntaxError> for ErrorReport {
     fn from(err: SyntaxError) -> ErrorReport {
         //write!(f, "SuperErrorSideKick is here!")
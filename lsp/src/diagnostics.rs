@@ -0,0 +1,281 @@
+// compiles a file in the background and turns the errors into lsp diagnostics
+//
+// the compiler uses thread-local interned strings, so everything it makes has to
+// stay on the compiling thread, and only plain data is sent back.
+// the compile stops at the next statement once `cancel` is set, which is done
+// when it takes too long or when the file has changed since
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use compiler::builtins::{Builtin, BuiltinPermissions, BUILTIN_NAMES};
+use compiler::compiler::{compile_script, CompileOptions};
+use compiler::globals::Globals;
+use compiler::value::{Macro, Value};
+use errors::compiler_info::CompilerInfo;
+use errors::{create_error, ErrorReport, Warning};
+use lsp_types::{Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, Url};
+use parser::parser::parse_spwn_partial;
+use shared::SpwnSource;
+
+use crate::document::Document;
+
+const COMPILE_STACK_SIZE: usize = 8 * 1024 * 1024;
+const COMPILE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Default)]
+pub struct TypeInfo {
+    pub desc: Option<String>,
+    // name, description
+    pub members: Vec<(String, Option<String>)>,
+}
+
+#[derive(Debug, Default)]
+pub struct CompileResult {
+    pub diagnostics: Vec<Diagnostic>,
+    // only filled in when the compile succeeded
+    pub types: HashMap<String, TypeInfo>,
+}
+
+/// Compiles the file, or returns `None` if it was cancelled before it finished
+pub fn compile(
+    text: String,
+    path: PathBuf,
+    include_paths: Vec<PathBuf>,
+    cancel: Arc<AtomicBool>,
+) -> Option<CompileResult> {
+    let (sender, receiver) = mpsc::channel();
+    let thread_path = path.clone();
+    let thread_text = text.clone();
+    let thread_cancel = cancel.clone();
+
+    let spawned = std::thread::Builder::new()
+        .name("spwn compile".to_string())
+        .stack_size(COMPILE_STACK_SIZE)
+        .spawn(move || {
+            let result = compile_on_thread(thread_text, thread_path, include_paths, thread_cancel);
+            // the receiver is gone if the compile took too long
+            sender.send(result).ok();
+        });
+
+    let message = match spawned {
+        Ok(_) => match receiver.recv_timeout(COMPILE_TIMEOUT) {
+            Ok(_) if cancel.load(Ordering::Relaxed) => return None,
+            Ok(result) => return Some(result),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                // stops the compiling thread
+                cancel.store(true, Ordering::Relaxed);
+                format!(
+                    "compilation took longer than {} seconds, so it was not checked",
+                    COMPILE_TIMEOUT.as_secs()
+                )
+            }
+            // the compiler panicked
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                "the compiler crashed while checking this file".to_string()
+            }
+        },
+        Err(e) => format!("could not start the compiler: {}", e),
+    };
+    let document = Document::new(&text);
+    Some(CompileResult {
+        diagnostics: vec![Diagnostic {
+            range: document.range((0, 0)),
+            severity: Some(DiagnosticSeverity::WARNING),
+            source: Some("spwn".to_string()),
+            message,
+            ..Default::default()
+        }],
+        types: HashMap::new(),
+    })
+}
+
+fn compile_on_thread(
+    text: String,
+    path: PathBuf,
+    include_paths: Vec<PathBuf>,
+    cancel: Arc<AtomicBool>,
+) -> CompileResult {
+    let document = Document::new(&text);
+    let source = SpwnSource::File(path.clone());
+
+    let (statements, notes, errors) = parse_spwn_partial(text, source.clone(), BUILTIN_NAMES);
+    if !errors.is_empty() {
+        return CompileResult {
+            diagnostics: errors
+                .into_iter()
+                .map(|e| to_diagnostic(ErrorReport::from(e), &path, &document))
+                .collect(),
+            types: HashMap::new(),
+        };
+    }
+
+    // the editor can't answer questions on stdin
+    let mut permissions = BuiltinPermissions::new();
+    permissions.set(Builtin::GetInput, false);
+
+    let mut std_out = std::io::sink();
+    match compile_script(
+        statements,
+        source,
+        include_paths,
+        CompileOptions {
+            cancel: Some(cancel),
+            ..Default::default()
+        },
+        notes,
+        permissions,
+        "".to_string(),
+        &mut std_out,
+    ) {
+        Ok(globals) => CompileResult {
+            diagnostics: globals
+                .warnings
+                .iter()
+                .filter(|w| matches!(w.position.file.as_ref(), SpwnSource::File(f) if f == &path))
+                .map(|w| warning_diagnostic(w, &path, &document))
+                .collect(),
+            types: type_info(&globals),
+        },
+        Err(e) => CompileResult {
+            diagnostics: vec![to_diagnostic(ErrorReport::from(e), &path, &document)],
+            types: HashMap::new(),
+        },
+    }
+}
+
+fn warning_diagnostic(warning: &Warning, path: &Path, document: &Document) -> Diagnostic {
+    let labels: Vec<_> = warning
+        .labels
+        .iter()
+        .map(|(a, l)| (*a, l.as_str()))
+        .collect();
+    let report = create_error(
+        CompilerInfo::from_area(warning.position),
+        &warning.message,
+        &labels,
+        Some(&format!(
+            "add `#[allow({})]` to hide this warning",
+            warning.lint.name()
+        )),
+    );
+    Diagnostic {
+        severity: Some(DiagnosticSeverity::WARNING),
+        ..to_diagnostic(report, path, document)
+    }
+}
+
+fn type_info(globals: &Globals) -> HashMap<String, TypeInfo> {
+    let mut types = HashMap::new();
+    for (name, (id, _)) in &globals.type_ids {
+        let mut members: Vec<(String, Option<String>)> = globals
+            .implementations
+            .get(id)
+            .map(|members| {
+                members
+                    .iter()
+                    .map(|(member, (value, _))| {
+                        let desc = match &globals.stored_values[*value] {
+                            Value::Macro(Macro::FuncLike(m)) => m.tag.get_desc(),
+                            _ => None,
+                        };
+                        (member.to_string(), desc)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        members.sort();
+        types.insert(
+            name.clone(),
+            TypeInfo {
+                desc: globals
+                    .type_descriptions
+                    .get(id)
+                    .map(|d| d.trim().to_string()),
+                members,
+            },
+        );
+    }
+    types
+}
+
+// the error messages are colored for the terminal
+fn strip_colors(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // skip until the end of the escape sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn to_diagnostic(report: ErrorReport, path: &Path, document: &Document) -> Diagnostic {
+    let in_document = |file: &SpwnSource| matches!(file, SpwnSource::File(f) if f == path);
+
+    let range = if in_document(&report.info.position.file) {
+        document.range(report.info.position.pos)
+    } else {
+        // the error happened in another file, so show it where this file first appears
+        report
+            .labels
+            .iter()
+            .find(|(area, _)| in_document(&area.file))
+            .map(|(area, _)| document.range(area.pos))
+            .unwrap_or_else(|| document.range((0, 0)))
+    };
+
+    let mut other_documents = HashMap::<PathBuf, Document>::new();
+    let related = report
+        .labels
+        .iter()
+        .filter_map(|(area, message)| {
+            let file = match area.file.as_ref() {
+                SpwnSource::File(f) => f,
+                _ => return None,
+            };
+            let range = if file == path {
+                document.range(area.pos)
+            } else {
+                if !other_documents.contains_key(file) {
+                    let text = std::fs::read_to_string(file).ok()?;
+                    other_documents.insert(file.clone(), Document::new(&text));
+                }
+                other_documents[file].range(area.pos)
+            };
+            Some(DiagnosticRelatedInformation {
+                location: Location::new(Url::from_file_path(file).ok()?, range),
+                message: strip_colors(message),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut message = strip_colors(&report.message);
+    if let Some(note) = &report.note {
+        message += &format!("\nnote: {}", strip_colors(note));
+    }
+
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("spwn".to_string()),
+        message,
+        related_information: if related.is_empty() {
+            None
+        } else {
+            Some(related)
+        },
+        ..Default::default()
+    }
+}
@@ -0,0 +1,90 @@
+// the text of an open file, with conversions between the byte offsets
+// used by the parser and the line/column positions used by the editor
+
+use lsp_types::{Position, Range};
+use shared::FileRange;
+
+pub struct Document {
+    pub text: String,
+    // byte offset of the start of every line
+    line_starts: Vec<usize>,
+}
+
+pub fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Document {
+    pub fn new(text: &str) -> Self {
+        // the parser does the same, so the offsets line up
+        let text = text.replace("\r\n", "\n");
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Document { text, line_starts }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let start = self.line_starts[line];
+        // editors count columns in utf-16 code units
+        let character = self.text[start..]
+            .char_indices()
+            .take_while(|(i, _)| start + i < offset)
+            .map(|(_, c)| c.len_utf16())
+            .sum::<usize>();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn offset(&self, position: Position) -> usize {
+        let start = match self.line_starts.get(position.line as usize) {
+            Some(start) => *start,
+            None => return self.text.len(),
+        };
+        let mut column = 0;
+        for (i, c) in self.text[start..].char_indices() {
+            if column >= position.character as usize || c == '\n' {
+                return start + i;
+            }
+            column += c.len_utf16();
+        }
+        self.text.len()
+    }
+
+    pub fn range(&self, (start, end): FileRange) -> Range {
+        Range::new(self.position(start), self.position(end))
+    }
+
+    // the identifier around an offset, and where it starts
+    pub fn word_at(&self, offset: usize) -> Option<(usize, &str)> {
+        let offset = offset.min(self.text.len());
+        let start = self.text[..offset]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_ident_char(*c))
+            .last()
+            .map(|(i, _)| i)
+            .unwrap_or(offset);
+        let end = self.text[offset..]
+            .char_indices()
+            .find(|(_, c)| !is_ident_char(*c))
+            .map(|(i, _)| offset + i)
+            .unwrap_or(self.text.len());
+        if start == end {
+            None
+        } else {
+            Some((start, &self.text[start..end]))
+        }
+    }
+
+    // what comes right before an offset on the same line, like `$.` or `@counter::`
+    pub fn line_before(&self, offset: usize) -> &str {
+        let offset = offset.min(self.text.len());
+        let start = self.text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        &self.text[start..offset]
+    }
+}
@@ -0,0 +1,9 @@
+// a language server for spwn, started with `spwn lsp`.
+// it talks to the editor over stdin and stdout
+pub mod diagnostics;
+pub mod document;
+pub mod server;
+pub mod symbols;
+mod worker;
+
+pub use server::run;
@@ -0,0 +1,408 @@
+// the language server loop, which answers the editor's requests
+// using the open documents and the last compile of every file
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use compiler::builtins::{BUILTIN_DESCRIPTIONS, BUILTIN_NAMES};
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+    Notification as _,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, Location, MarkupContent, MarkupKind, OneOf,
+    Position, Range, SaveOptions, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Url,
+};
+use parser::parser::parse_spwn_partial;
+use serde::de::DeserializeOwned;
+use shared::SpwnSource;
+
+use crate::document::{is_ident_char, Document};
+use crate::symbols::{resolve_import, DefinitionKind, Symbols};
+use crate::worker::{Types, Worker};
+
+type ServerError = Box<dyn Error + Sync + Send>;
+
+struct OpenFile {
+    document: Document,
+    symbols: Symbols,
+}
+
+struct Server {
+    connection: Connection,
+    include_paths: Vec<PathBuf>,
+    files: HashMap<Url, OpenFile>,
+    // the types from the last successful compile, with their members
+    types: Types,
+    worker: Worker,
+}
+
+pub fn run() -> Result<(), ServerError> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::FULL),
+                save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                    include_text: Some(true),
+                })),
+                ..Default::default()
+            },
+        )),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string(), ":".to_string(), "@".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let params: InitializeParams =
+        serde_json::from_value(connection.initialize(serde_json::to_value(capabilities)?)?)?;
+
+    // the same places `spwn build` looks for libraries
+    let mut include_paths = Vec::new();
+    #[allow(deprecated)]
+    if let Some(root) = params.root_uri.and_then(|u| u.to_file_path().ok()) {
+        include_paths.push(root);
+    }
+    if let Ok(dir) = std::env::current_dir() {
+        include_paths.push(dir);
+    }
+    if let Some(dir) = std::env::current_exe()
+        .ok()
+        .and_then(|e| e.parent().map(|p| p.to_path_buf()))
+    {
+        include_paths.push(dir);
+    }
+
+    let types = Arc::new(Mutex::new(HashMap::new()));
+    let worker = Worker::new(connection.sender.clone(), types.clone());
+    let mut server = Server {
+        connection,
+        include_paths,
+        files: HashMap::new(),
+        types,
+        worker,
+    };
+    server.main_loop()?;
+    // the writer thread stops once the connection is gone
+    drop(server);
+    io_threads.join()?;
+    Ok(())
+}
+
+impl Server {
+    fn main_loop(&mut self) -> Result<(), ServerError> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    self.request(request)?
+                }
+                Message::Notification(notification) => self.notification(notification)?,
+                Message::Response(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn respond<T: serde::Serialize>(&self, id: RequestId, result: T) -> Result<(), ServerError> {
+        let response = Response::new_ok(id, result);
+        self.connection.sender.send(Message::Response(response))?;
+        Ok(())
+    }
+
+    fn respond_err(
+        &self,
+        id: RequestId,
+        code: lsp_server::ErrorCode,
+        message: String,
+    ) -> Result<(), ServerError> {
+        let response = Response::new_err(id, code as i32, message);
+        self.connection.sender.send(Message::Response(response))?;
+        Ok(())
+    }
+
+    // answers the request with the result of `f`, or with an error if the params can't be read
+    fn answer<P: DeserializeOwned, T: serde::Serialize>(
+        &self,
+        request: Request,
+        f: impl FnOnce(&Self, P) -> T,
+    ) -> Result<(), ServerError> {
+        match serde_json::from_value(request.params) {
+            Ok(params) => self.respond(request.id, f(self, params)),
+            Err(e) => self.respond_err(
+                request.id,
+                lsp_server::ErrorCode::InvalidParams,
+                format!("invalid params for {}: {}", request.method, e),
+            ),
+        }
+    }
+
+    fn request(&mut self, request: Request) -> Result<(), ServerError> {
+        match request.method.as_str() {
+            GotoDefinition::METHOD => self.answer(request, |s, p: GotoDefinitionParams| {
+                s.definition(p.text_document_position_params)
+            }),
+            HoverRequest::METHOD => self.answer(request, |s, p: HoverParams| {
+                s.hover(p.text_document_position_params)
+            }),
+            Completion::METHOD => self.answer(request, |s, p: CompletionParams| {
+                s.completion(p.text_document_position)
+            }),
+            _ => {
+                let message = format!("unsupported request: {}", request.method);
+                self.respond_err(request.id, lsp_server::ErrorCode::MethodNotFound, message)
+            }
+        }
+    }
+
+    fn notification(&mut self, notification: Notification) -> Result<(), ServerError> {
+        // notifications can't be answered, so the ones that can't be read are ignored
+        fn params<P: DeserializeOwned>(notification: Notification) -> Option<P> {
+            serde_json::from_value(notification.params).ok()
+        }
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                if let Some(params) = params::<lsp_types::DidOpenTextDocumentParams>(notification) {
+                    let uri = params.text_document.uri;
+                    self.update(&uri, &params.text_document.text);
+                    self.check(&uri);
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                if let Some(params) = params::<lsp_types::DidChangeTextDocumentParams>(notification)
+                {
+                    let uri = params.text_document.uri;
+                    // the whole text is sent on every change
+                    if let Some(change) = params.content_changes.into_iter().last() {
+                        self.update(&uri, &change.text);
+                        self.check(&uri);
+                    }
+                }
+            }
+            DidSaveTextDocument::METHOD => {
+                if let Some(params) = params::<lsp_types::DidSaveTextDocumentParams>(notification) {
+                    let uri = params.text_document.uri;
+                    if let Some(text) = params.text {
+                        self.update(&uri, &text);
+                    }
+                    self.check(&uri);
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                if let Some(params) = params::<lsp_types::DidCloseTextDocumentParams>(notification)
+                {
+                    self.files.remove(&params.text_document.uri);
+                    self.worker.cancel(&params.text_document.uri);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // keeps the text and finds the definitions in it
+    fn update(&mut self, uri: &Url, text: &str) {
+        let document = Document::new(text);
+        let source = match uri.to_file_path() {
+            Ok(path) => SpwnSource::File(path),
+            Err(_) => SpwnSource::String(internment::LocalIntern::new(uri.to_string())),
+        };
+        // the statements with syntax errors are skipped, so the rest still works
+        let (statements, _, _) = parse_spwn_partial(document.text.clone(), source, BUILTIN_NAMES);
+        let symbols = Symbols::from_statements(&statements);
+        self.files
+            .insert(uri.clone(), OpenFile { document, symbols });
+    }
+
+    // compiles a file in the background, which sends the errors to the editor
+    fn check(&mut self, uri: &Url) {
+        let (file, path) = match (self.files.get(uri), uri.to_file_path()) {
+            (Some(file), Ok(path)) => (file, path),
+            _ => return,
+        };
+        self.worker.check(
+            uri.clone(),
+            file.document.text.clone(),
+            path,
+            self.include_paths.clone(),
+        );
+    }
+
+    fn definition(
+        &self,
+        params: lsp_types::TextDocumentPositionParams,
+    ) -> Option<GotoDefinitionResponse> {
+        let uri = params.text_document.uri;
+        let file = self.files.get(&uri)?;
+        let offset = file.document.offset(params.position);
+
+        if let Some(import) = file.symbols.import_at(offset) {
+            let path = resolve_import(import, &uri.to_file_path().ok()?, &self.include_paths)?;
+            let start = Range::new(Position::new(0, 0), Position::new(0, 0));
+            return Some(GotoDefinitionResponse::Scalar(Location::new(
+                Url::from_file_path(path).ok()?,
+                start,
+            )));
+        }
+
+        let (start, word) = file.document.word_at(offset)?;
+        let kind = if file.document.line_before(start).ends_with('@') {
+            DefinitionKind::Type
+        } else {
+            DefinitionKind::Variable
+        };
+        let def = file.symbols.definition(word, kind, offset)?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            uri.clone(),
+            file.document.range(def.pos),
+        )))
+    }
+
+    fn hover(&self, params: lsp_types::TextDocumentPositionParams) -> Option<Hover> {
+        let file = self.files.get(&params.text_document.uri)?;
+        let offset = file.document.offset(params.position);
+        let (start, word) = file.document.word_at(offset)?;
+        let before = file.document.line_before(start);
+
+        let (title, desc) = if before.ends_with("$.") {
+            let desc = BUILTIN_DESCRIPTIONS
+                .iter()
+                .find(|(name, _)| *name == word)?
+                .1;
+            (format!("$.{}", word), Some(desc.to_string()))
+        } else if let Some(typ) = type_before(before) {
+            let desc = self
+                .types
+                .lock()
+                .unwrap()
+                .get(typ)?
+                .members
+                .iter()
+                .find(|(name, _)| name == word)?
+                .1
+                .clone();
+            (format!("@{}::{}", typ, word), desc)
+        } else if before.ends_with('@') {
+            let desc = match file.symbols.definition(word, DefinitionKind::Type, offset) {
+                Some(def) => def.desc.clone(),
+                None => self.types.lock().unwrap().get(word)?.desc.clone(),
+            };
+            (format!("@{}", word), desc)
+        } else {
+            let def = file
+                .symbols
+                .definition(word, DefinitionKind::Variable, offset)?;
+            let title = match def.kind {
+                DefinitionKind::Argument => format!("(argument) {}", word),
+                _ => word.to_string(),
+            };
+            (title, def.desc.clone())
+        };
+
+        let mut value = format!("```spwn\n{}\n```", title);
+        if let Some(desc) = desc {
+            value += &format!("\n\n{}", desc);
+        }
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: None,
+        })
+    }
+
+    fn completion(
+        &self,
+        params: lsp_types::TextDocumentPositionParams,
+    ) -> Option<CompletionResponse> {
+        let file = self.files.get(&params.text_document.uri)?;
+        let offset = file.document.offset(params.position);
+        // what comes before the word that is being typed
+        let before = file
+            .document
+            .line_before(offset)
+            .trim_end_matches(is_ident_char);
+        let types = self.types.lock().unwrap();
+
+        let items = if before.ends_with("$.") {
+            BUILTIN_DESCRIPTIONS
+                .iter()
+                .map(|(name, desc)| item(name, CompletionItemKind::FUNCTION, Some(desc)))
+                .collect()
+        } else if let Some(typ) = type_before(before) {
+            types
+                .get(typ)?
+                .members
+                .iter()
+                .map(|(name, desc)| item(name, CompletionItemKind::METHOD, desc.as_deref()))
+                .collect()
+        } else if before.ends_with('@') {
+            let mut items: Vec<CompletionItem> = types
+                .iter()
+                .map(|(name, info)| item(name, CompletionItemKind::CLASS, info.desc.as_deref()))
+                .collect();
+            for def in file.symbols.visible(offset) {
+                if def.kind == DefinitionKind::Type && !types.contains_key(&def.name) {
+                    items.push(item(
+                        &def.name,
+                        CompletionItemKind::CLASS,
+                        def.desc.as_deref(),
+                    ))
+                }
+            }
+            items
+        } else if before.ends_with('.') || before.ends_with(':') {
+            // members of values whose type isn't known
+            return None;
+        } else {
+            file.symbols
+                .visible(offset)
+                .into_iter()
+                .filter(|def| def.kind != DefinitionKind::Type)
+                .map(|def| {
+                    let kind = match def.kind {
+                        DefinitionKind::Argument => CompletionItemKind::VARIABLE,
+                        _ => CompletionItemKind::VALUE,
+                    };
+                    item(&def.name, kind, def.desc.as_deref())
+                })
+                .collect()
+        };
+        Some(CompletionResponse::Array(items))
+    }
+}
+
+fn item(label: &str, kind: CompletionItemKind, desc: Option<&str>) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(kind),
+        documentation: desc.map(|d| lsp_types::Documentation::String(d.to_string())),
+        ..Default::default()
+    }
+}
+
+// the type in `@type::` or `@type.` at the end of some text
+fn type_before(text: &str) -> Option<&str> {
+    let text = text.strip_suffix("::").or_else(|| text.strip_suffix('.'))?;
+    let name = &text[text.trim_end_matches(is_ident_char).len()..];
+    if !name.is_empty() && text[..text.len() - name.len()].ends_with('@') {
+        Some(name)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,347 @@
+// finds the definitions and imports in a parsed file
+//
+// every definition gets the range of the code it can be used in (its scope),
+// which is the whole file for top level definitions, and the macro,
+// trigger function or statement body for everything else
+
+use std::path::{Path, PathBuf};
+
+use parser::ast::{self, StatementBody, ValueBody};
+use shared::{FileRange, ImportType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Variable,
+    Argument,
+    Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    pub pos: FileRange,
+    pub scope: FileRange,
+    pub desc: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Symbols {
+    pub definitions: Vec<Definition>,
+    pub imports: Vec<(FileRange, ImportType)>,
+}
+
+const WHOLE_FILE: FileRange = (0, usize::MAX);
+
+fn contains(range: FileRange, offset: usize) -> bool {
+    range.0 <= offset && offset <= range.1
+}
+
+impl Symbols {
+    pub fn from_statements(statements: &[ast::Statement]) -> Self {
+        let mut symbols = Symbols::default();
+        symbols.statements(statements, WHOLE_FILE);
+        symbols
+    }
+
+    // the definition a name refers to at an offset:
+    // the closest one before it that is in scope
+    pub fn definition(
+        &self,
+        name: &str,
+        kind: DefinitionKind,
+        offset: usize,
+    ) -> Option<&Definition> {
+        let visible = self
+            .definitions
+            .iter()
+            .filter(|d| {
+                d.name == name && (d.kind == DefinitionKind::Type) == (kind == DefinitionKind::Type)
+            })
+            .filter(|d| contains(d.scope, offset));
+        let first = visible.clone().next();
+        visible
+            .filter(|d| d.pos.0 <= offset)
+            .max_by_key(|d| d.pos.0)
+            .or(first)
+    }
+
+    // every name that can be used at an offset
+    pub fn visible(&self, offset: usize) -> Vec<&Definition> {
+        let mut out: Vec<&Definition> = Vec::new();
+        for def in self
+            .definitions
+            .iter()
+            .filter(|d| contains(d.scope, offset))
+        {
+            if !out.iter().any(|d| d.name == def.name) {
+                out.push(def);
+            }
+        }
+        out
+    }
+
+    pub fn import_at(&self, offset: usize) -> Option<&ImportType> {
+        self.imports
+            .iter()
+            .find(|(pos, _)| contains(*pos, offset))
+            .map(|(_, i)| i)
+    }
+
+    fn define(
+        &mut self,
+        name: &str,
+        kind: DefinitionKind,
+        pos: FileRange,
+        scope: FileRange,
+        desc: Option<String>,
+    ) {
+        self.definitions.push(Definition {
+            name: name.to_string(),
+            kind,
+            pos,
+            scope,
+            desc,
+        })
+    }
+
+    // the symbols a value is assigned to (`a = ...`, `[a, b] = ...`)
+    fn define_pattern(&mut self, var: &ast::Variable, scope: FileRange, desc: Option<String>) {
+        if !var.path.is_empty() {
+            return;
+        }
+        match &var.value.body {
+            ValueBody::Symbol(name) => {
+                self.define(name, DefinitionKind::Variable, var.pos, scope, desc)
+            }
+            ValueBody::Array(elements) => {
+                for el in elements {
+                    for v in &el.value.values {
+                        self.define_pattern(v, scope, None)
+                    }
+                }
+            }
+            ValueBody::Expression(e) => {
+                for v in &e.values {
+                    self.define_pattern(v, scope, None)
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn statements(&mut self, statements: &[ast::Statement], scope: FileRange) {
+        for statement in statements {
+            self.statement(statement, scope)
+        }
+    }
+
+    fn statement(&mut self, statement: &ast::Statement, scope: FileRange) {
+        match &statement.body {
+            StatementBody::Definition(def) => {
+                let desc = def.value.as_ref().and_then(value_desc);
+                self.define_pattern(&def.symbol, scope, desc);
+                if let Some(value) = &def.value {
+                    self.expression(value)
+                }
+            }
+            StatementBody::Call(call) => self.variable(&call.function),
+            StatementBody::Expr(e) | StatementBody::Extract(e) => self.expression(e),
+            StatementBody::Error(e) => self.expression(&e.message),
+            StatementBody::Return(e) => {
+                if let Some(e) = e {
+                    self.expression(e)
+                }
+            }
+            StatementBody::TypeDef { name, attr } => self.define(
+                name,
+                DefinitionKind::Type,
+                statement.pos,
+                scope,
+                attr.get_desc(),
+            ),
+            StatementBody::Impl(imp) => {
+                self.variable(&imp.symbol);
+                self.dict(&imp.members);
+            }
+            StatementBody::If(i) => {
+                self.expression(&i.condition);
+                self.statements(&i.if_body, statement.pos);
+                if let Some(body) = &i.else_body {
+                    self.statements(body, statement.pos);
+                }
+            }
+            StatementBody::For(f) => {
+                self.expression(&f.array);
+                for v in &f.symbol.values {
+                    self.define_pattern(v, statement.pos, None);
+                }
+                self.statements(&f.body, statement.pos);
+            }
+            StatementBody::While(w) => {
+                self.expression(&w.condition);
+                self.statements(&w.body, statement.pos);
+            }
+            StatementBody::ImportNames(i) => {
+                for name in &i.names {
+                    let symbol = name.alias.unwrap_or(name.name);
+                    self.define(&symbol, DefinitionKind::Variable, name.pos, scope, None);
+                }
+            }
+            StatementBody::Try(t) => {
+                self.statements(&t.try_body, statement.pos);
+                if let Some(symbol) = &t.catch_symbol {
+                    self.define_pattern(symbol, statement.pos, None);
+                }
+                self.statements(&t.catch_body, statement.pos);
+            }
+            StatementBody::Sync(s) => match &s.part {
+                None => self.define_pattern(&s.group, scope, None),
+                Some((part, body)) => {
+                    self.variable(&s.group);
+                    self.expression(part);
+                    self.statements(body, statement.pos);
+                }
+            },
+            StatementBody::Break | StatementBody::Continue | StatementBody::Invalid(_) => (),
+        }
+    }
+
+    fn expression(&mut self, expr: &ast::Expression) {
+        for v in &expr.values {
+            self.variable(v)
+        }
+    }
+
+    fn dict(&mut self, defs: &[ast::DictDef]) {
+        for def in defs {
+            match def {
+                ast::DictDef::Def((_, e)) | ast::DictDef::Extract(e) => self.expression(e),
+            }
+        }
+    }
+
+    fn variable(&mut self, var: &ast::Variable) {
+        match &var.value.body {
+            ValueBody::Macro(m) => {
+                for (name, default, attr, pattern, pos, _) in &m.args {
+                    self.define(
+                        name,
+                        DefinitionKind::Argument,
+                        *pos,
+                        var.pos,
+                        attr.get_desc(),
+                    );
+                    for e in default.iter().chain(pattern) {
+                        self.expression(e)
+                    }
+                }
+                if let Some(ret) = &m.ret_type {
+                    self.expression(ret)
+                }
+                self.statements(&m.body.statements, var.pos);
+            }
+            ValueBody::CmpStmt(c) => self.statements(&c.statements, var.pos),
+            ValueBody::Dictionary(defs) => self.dict(defs),
+            ValueBody::Array(elements) => {
+                for el in elements {
+                    self.expression(&el.value)
+                }
+            }
+            ValueBody::Expression(e) => self.expression(e),
+            ValueBody::Import(import, _) => self.imports.push((var.pos, import.clone())),
+            ValueBody::Match(e, cases) => {
+                self.expression(e);
+                for case in cases {
+                    if let ast::CaseType::Pattern(p) = &case.typ {
+                        self.expression(p)
+                    }
+                    match &case.body {
+                        ast::CaseBody::Expr(e) => self.expression(e),
+                        ast::CaseBody::Block(b) => self.statements(b, var.pos),
+                    }
+                }
+            }
+            ValueBody::ListComp(c) => {
+                self.define(&c.symbol, DefinitionKind::Variable, var.pos, var.pos, None);
+                self.expression(&c.iterator);
+                if let Some(cond) = &c.condition {
+                    self.expression(cond)
+                }
+                self.expression(&c.body);
+            }
+            ValueBody::Obj(o) => {
+                for (k, v) in &o.props {
+                    self.expression(k);
+                    self.expression(v);
+                }
+            }
+            ValueBody::Ternary(t) => {
+                self.expression(&t.condition);
+                self.expression(&t.if_expr);
+                self.expression(&t.else_expr);
+            }
+            ValueBody::MacroPattern(p) => {
+                for a in &p.args {
+                    self.expression(a)
+                }
+                self.expression(&p.ret);
+            }
+            ValueBody::FormatStr(parts) => {
+                for part in parts {
+                    if let ast::FormatStrPart::Expr(e) = part {
+                        self.expression(e)
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        for path in &var.path {
+            match path {
+                ast::Path::Index(e) => self.expression(e),
+                ast::Path::Call(args) => {
+                    for arg in args {
+                        self.expression(&arg.value)
+                    }
+                }
+                ast::Path::Constructor(defs) => self.dict(defs),
+                ast::Path::NSlice(slices) => {
+                    for s in slices {
+                        for e in s.left.iter().chain(&s.right).chain(&s.step) {
+                            self.expression(e)
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+// the `#[desc(...)]` of a defined value, which can be on the definition or on the macro itself
+fn value_desc(value: &ast::Expression) -> Option<String> {
+    let first = value.values.first()?;
+    first.tag.get_desc().or(match &first.value.body {
+        ValueBody::Macro(m) => m.properties.get_desc(),
+        _ => None,
+    })
+}
+
+// the file an import refers to
+pub fn resolve_import(
+    import: &ImportType,
+    file: &Path,
+    include_paths: &[PathBuf],
+) -> Option<PathBuf> {
+    match import {
+        ImportType::Script(p) => {
+            let path = file.parent()?.join(p);
+            path.exists().then_some(path)
+        }
+        ImportType::Lib(name) => include_paths
+            .iter()
+            .map(|p| p.join("libraries").join(name).join("lib.spwn"))
+            .find(|p| p.exists()),
+    }
+}
@@ -0,0 +1,131 @@
+// compiles the open files on a background thread, so the server keeps answering
+// requests while a file is being checked.
+//
+// a file is only compiled once it hasn't changed for a moment, and a compile
+// that is still running when a newer version of the file is queued is cancelled
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use lsp_server::{Message, Notification};
+use lsp_types::notification::{Notification as _, PublishDiagnostics};
+use lsp_types::{PublishDiagnosticsParams, Url};
+
+use crate::diagnostics::{compile, TypeInfo};
+
+// how long a file has to stay the same before it is compiled
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+// the types from the last successful compile, with their members
+pub type Types = Arc<Mutex<HashMap<String, TypeInfo>>>;
+
+struct Job {
+    uri: Url,
+    text: String,
+    path: PathBuf,
+    include_paths: Vec<PathBuf>,
+    cancel: Arc<AtomicBool>,
+    ready_at: Instant,
+}
+
+pub struct Worker {
+    jobs: mpsc::Sender<Job>,
+    // the cancel flag of the newest compile of every file
+    latest: HashMap<Url, Arc<AtomicBool>>,
+}
+
+impl Worker {
+    /// Starts the thread, which sends the diagnostics to the editor and updates `types`
+    pub fn new(sender: crossbeam_channel::Sender<Message>, types: Types) -> Self {
+        let (jobs, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("spwn check".to_string())
+            .spawn(move || run(receiver, sender, types))
+            .expect("could not start the compile worker");
+        Worker {
+            jobs,
+            latest: HashMap::new(),
+        }
+    }
+
+    /// Compiles the text of a file once it hasn't changed for a moment,
+    /// instead of any older version that is waiting or being compiled
+    pub fn check(&mut self, uri: Url, text: String, path: PathBuf, include_paths: Vec<PathBuf>) {
+        self.cancel(&uri);
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.latest.insert(uri.clone(), cancel.clone());
+        // the thread only stops when the worker is dropped
+        self.jobs
+            .send(Job {
+                uri,
+                text,
+                path,
+                include_paths,
+                cancel,
+                ready_at: Instant::now() + DEBOUNCE,
+            })
+            .ok();
+    }
+
+    /// Stops checking a file
+    pub fn cancel(&mut self, uri: &Url) {
+        if let Some(cancel) = self.latest.remove(uri) {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // so the thread doesn't keep the connection open until its compile is done
+        for cancel in self.latest.values() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+fn run(receiver: mpsc::Receiver<Job>, sender: crossbeam_channel::Sender<Message>, types: Types) {
+    // at most one job for every file, the newest one
+    let mut waiting: Vec<Job> = Vec::new();
+    loop {
+        let next = match waiting.iter().map(|job| job.ready_at).min() {
+            Some(ready_at) => {
+                receiver.recv_timeout(ready_at.saturating_duration_since(Instant::now()))
+            }
+            None => receiver
+                .recv()
+                .map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+        };
+        match next {
+            Ok(job) => {
+                waiting.retain(|j| j.uri != job.uri);
+                waiting.push(job);
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => (),
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+        waiting.retain(|job| !job.cancel.load(Ordering::Relaxed));
+
+        let now = Instant::now();
+        let (ready, rest): (Vec<Job>, Vec<Job>) =
+            waiting.into_iter().partition(|job| job.ready_at <= now);
+        waiting = rest;
+        for job in ready {
+            let result = match compile(job.text, job.path, job.include_paths, job.cancel) {
+                Some(result) => result,
+                // a newer version is on its way
+                None => continue,
+            };
+            types.lock().unwrap().extend(result.types);
+
+            let params = PublishDiagnosticsParams::new(job.uri, result.diagnostics, None);
+            let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+            if sender.send(Message::Notification(notification)).is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use ahash::AHashMap;
+use compiler::builtins::{Group, Id, Item};
+use compiler::compiler_types::{FunctionId, TriggerOrder};
+use compiler::leveldata::{GdObj, ObjParam};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use optimizer::optimize::optimize;
+use optimizer::ReservedIds;
+use parser::ast::ObjectMode;
+
+const TRIGGERS: usize = 100_000;
+// every step of a chain adds 5 triggers
+const TRIGGERS_PER_STEP: usize = 5;
+
+struct Network {
+    triggers: Vec<(GdObj, TriggerOrder)>,
+    closed_group: u16,
+}
+
+impl Network {
+    fn add(&mut self, params: &[(u16, ObjParam)]) {
+        let unique_id = self.triggers.len();
+        self.triggers.push((
+            GdObj {
+                func_id: 0,
+                params: params.iter().cloned().collect::<AHashMap<_, _>>(),
+                mode: ObjectMode::Trigger,
+                unique_id,
+                merged_ids: Vec::new(),
+            },
+            TriggerOrder(unique_id as f64),
+        ));
+    }
+
+    fn next_group(&mut self) -> Group {
+        self.closed_group += 1;
+        Group {
+            id: Id::Arbitrary(self.closed_group),
+        }
+    }
+}
+
+// a chain of groups that each move an object, spawn the next group,
+// and check a counter with two instant count triggers that can be merged
+fn add_chain(network: &mut Network, chain: u16, steps: usize) {
+    let start = Group {
+        id: Id::Specific(chain),
+    };
+    let object = ObjParam::Group(Group {
+        id: Id::Specific(chain + 5000),
+    });
+    let item = ObjParam::Item(Item {
+        id: Id::Arbitrary(chain),
+    });
+
+    let mut current = start;
+    for step in 0..steps {
+        let next = network.next_group();
+        let checked = network.next_group();
+        let group = ObjParam::Group(current);
+
+        network.add(&[
+            (1, ObjParam::Number(1268.0)),
+            (51, ObjParam::Group(next)),
+            (57, group.clone()),
+            (63, ObjParam::Number(0.0)),
+        ]);
+        network.add(&[
+            (1, ObjParam::Number(901.0)),
+            (51, object.clone()),
+            (57, group.clone()),
+            (28, ObjParam::Number(10.0)),
+        ]);
+        for comparison in [0.0, 1.0] {
+            network.add(&[
+                (1, ObjParam::Number(1811.0)),
+                (80, item.clone()),
+                (77, ObjParam::Number(step as f64)),
+                (88, ObjParam::Number(comparison)),
+                (51, ObjParam::Group(checked)),
+                (56, ObjParam::Bool(true)),
+                (57, group.clone()),
+            ]);
+        }
+        network.add(&[
+            (1, ObjParam::Number(1007.0)),
+            (51, object.clone()),
+            (57, ObjParam::Group(checked)),
+            (35, ObjParam::Number(0.5)),
+        ]);
+
+        current = next;
+    }
+}
+
+// a network with `TRIGGERS` triggers, made of `chains` chains that don't share any groups
+fn synthetic_network(chains: u16) -> (Vec<FunctionId>, u16) {
+    let mut network = Network {
+        triggers: Vec::new(),
+        closed_group: 0,
+    };
+    let steps = TRIGGERS / TRIGGERS_PER_STEP / chains as usize;
+    for chain in 1..=chains {
+        add_chain(&mut network, chain, steps);
+    }
+
+    (
+        vec![FunctionId {
+            parent: None,
+            width: None,
+            obj_list: network.triggers,
+        }],
+        network.closed_group,
+    )
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut bmg = c.benchmark_group("optimize");
+    bmg.sample_size(10);
+    bmg.throughput(Throughput::Elements(TRIGGERS as u64));
+
+    for chains in [10, 1000] {
+        let (func_ids, closed_group) = synthetic_network(chains);
+        let reserved = ReservedIds::from_objects(&[], &func_ids);
+
+        bmg.bench_with_input(
+            BenchmarkId::new("100k_triggers", format!("{}_components", chains)),
+            &func_ids,
+            |b, func_ids| b.iter(|| optimize(func_ids.clone(), closed_group, reserved.clone())),
+        );
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
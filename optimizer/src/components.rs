@@ -0,0 +1,169 @@
+// triggers can only affect each other through the groups they use,
+// so triggers that don't share any groups (directly or through other triggers)
+// can be optimized separately. this splits the triggers into connected
+// components, and packs them into a few parts that can be optimized in parallel
+
+use ahash::{AHashMap, AHashSet};
+use compiler::builtins::Group;
+use compiler::compiler_types::FunctionId;
+use compiler::leveldata::{GdObj, ObjParam};
+
+use crate::{obj_props, NO_GROUP};
+
+struct UnionFind {
+    parents: AHashMap<Group, Group>,
+}
+
+impl UnionFind {
+    fn find(&mut self, g: Group) -> Group {
+        let mut current = g;
+        while let Some(parent) = self.parents.get(&current).copied() {
+            if parent == current {
+                break;
+            }
+            // path halving
+            let grandparent = self.parents[&parent];
+            self.parents.insert(current, grandparent);
+            current = grandparent;
+        }
+        self.parents.entry(current).or_insert(current);
+        current
+    }
+
+    fn union(&mut self, a: Group, b: Group) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a != b {
+            self.parents.insert(b, a);
+        }
+    }
+}
+
+fn groups_used(obj: &GdObj) -> impl Iterator<Item = Group> + '_ {
+    obj.params.values().flat_map(|param| match param {
+        ObjParam::Group(g) => vec![*g],
+        ObjParam::GroupList(list) => list.clone(),
+        _ => Vec::new(),
+    })
+}
+
+// groups only connect triggers if there are triggers in them.
+// all the top level triggers are in group 0, which would put almost
+// everything in the same component, so group 0 is left out as well
+// (group 0 can't be toggled, so it doesn't need to have all its triggers in the same place)
+fn linked_groups<'a>(
+    obj: &'a GdObj,
+    trigger_groups: &'a AHashSet<Group>,
+) -> impl Iterator<Item = Group> + 'a {
+    groups_used(obj).filter(move |g| *g != NO_GROUP && trigger_groups.contains(g))
+}
+
+// the group that decides which component a trigger is in
+fn anchor_group(obj: &GdObj, trigger_groups: &AHashSet<Group>) -> Group {
+    match obj.params.get(&obj_props::GROUPS) {
+        Some(ObjParam::Group(g)) if *g != NO_GROUP => *g,
+        _ => linked_groups(obj, trigger_groups)
+            .next()
+            .unwrap_or(NO_GROUP),
+    }
+}
+
+// how many parts the triggers are split into. this is fixed instead of depending
+// on the number of threads, since the order of the merged triggers (and the
+// arbitrary groups given out later) depends on how they were split
+pub(crate) const PARTS: usize = 16;
+
+// splits the triggers into at most `parts` lists, so that no two lists
+// contain triggers from the same connected component.
+// every list has the same function id structure as the input
+pub(crate) fn split_components(objects: &[FunctionId], parts: usize) -> Vec<Vec<FunctionId>> {
+    let mut trigger_groups = AHashSet::<Group>::default();
+    for fn_id in objects {
+        for (obj, _) in &fn_id.obj_list {
+            match obj.params.get(&obj_props::GROUPS) {
+                Some(ObjParam::Group(g)) => {
+                    trigger_groups.insert(*g);
+                }
+                Some(ObjParam::GroupList(list)) => trigger_groups.extend(list.iter().copied()),
+                _ => (),
+            }
+        }
+    }
+
+    let mut components = UnionFind {
+        parents: AHashMap::default(),
+    };
+
+    for fn_id in objects {
+        for (obj, _) in &fn_id.obj_list {
+            let anchor = anchor_group(obj, &trigger_groups);
+            components.find(anchor);
+            for g in linked_groups(obj, &trigger_groups) {
+                components.union(anchor, g)
+            }
+        }
+    }
+
+    // count the triggers in each component, in the order they're first found
+    // so the result doesn't depend on hash map order
+    let mut sizes = Vec::<(Group, usize)>::new();
+    let mut indexes = AHashMap::<Group, usize>::default();
+    for fn_id in objects {
+        for (obj, _) in &fn_id.obj_list {
+            let root = components.find(anchor_group(obj, &trigger_groups));
+            let i = *indexes.entry(root).or_insert_with(|| {
+                sizes.push((root, 0));
+                sizes.len() - 1
+            });
+            sizes[i].1 += 1;
+        }
+    }
+
+    let parts = parts.min(sizes.len()).max(1);
+
+    // biggest components first, each into the part with the fewest triggers
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|a, b| sizes[*b].1.cmp(&sizes[*a].1).then(a.cmp(b)));
+    let mut part_sizes = vec![0; parts];
+    let mut part_of = AHashMap::<Group, usize>::default();
+    for i in order {
+        let (root, size) = sizes[i];
+        let (part, _) = part_sizes
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, s)| (**s, *i))
+            .unwrap();
+        part_sizes[part] += size;
+        part_of.insert(root, part);
+    }
+
+    let empty: Vec<FunctionId> = objects
+        .iter()
+        .map(|f| FunctionId {
+            parent: f.parent,
+            width: f.width,
+            obj_list: Vec::new(),
+        })
+        .collect();
+    let mut out = vec![empty; parts];
+
+    for (i, fn_id) in objects.iter().enumerate() {
+        for (obj, order) in &fn_id.obj_list {
+            let part = part_of[&components.find(anchor_group(obj, &trigger_groups))];
+            out[part][i].obj_list.push((obj.clone(), *order));
+        }
+    }
+    out
+}
+
+// puts the parts back into one list
+pub(crate) fn merge_components(parts: Vec<Vec<FunctionId>>) -> Vec<FunctionId> {
+    let mut parts = parts.into_iter();
+    let mut out = parts.next().unwrap_or_default();
+    for part in parts {
+        for (fn_id, other) in out.iter_mut().zip(part) {
+            fn_id.obj_list.extend(other.obj_list);
+        }
+    }
+    out
+}
@@ -20,7 +20,10 @@ pub fn group_toggling(
     closed_group: &mut u16,
 ) {
     let mut visited = AHashSet::default();
-    for group in network.map.clone().keys() {
+    // sorted so the new arbitrary groups don't depend on hash map order
+    let mut groups: Vec<Group> = network.map.keys().copied().collect();
+    groups.sort();
+    for group in &groups {
         if is_start_group(*group, reserved) {
             intraframe_grouping(
                 network,
@@ -427,6 +430,7 @@ pub fn create_toggle_trigger(
         func_id: obj.0,
         mode: ObjectMode::Trigger,
         unique_id: objects[obj].0.unique_id,
+        merged_ids: objects[obj].0.merged_ids.clone(),
     };
 
     (*objects.list)[obj.0]
@@ -0,0 +1,559 @@
+use ahash::AHashMap;
+use compiler::builtins::{Group, Item};
+use compiler::compiler_types::TriggerOrder;
+use compiler::leveldata::{GdObj, ObjParam};
+
+use crate::optimize::{is_start_group, ToggleGroups};
+use crate::{
+    get_role, obj_ids, obj_props, ObjPtr, ReservedIds, Trigger, TriggerNetwork, TriggerRole,
+    Triggerlist,
+};
+
+// instant count optimisation
+
+// counter comparisons (and everything built on them, like match) compile to
+// a bunch of instant count triggers that check the same item. this pass
+// - merges instant count triggers in the same group that check the same item
+//   and activate the same group, if their conditions can be written as one (== 5 or < 5 -> < 6)
+// - folds instant count triggers into the group they activate, if that group only
+//   contains instant count triggers for the same item (> 3 then < 5 -> == 4)
+//
+// instant count triggers that toggle their target off are merged with each other the
+// same way, and can be folded into the trigger that activates their group
+//
+// item values are always integers, so conditions are handled as integer ranges
+
+pub(crate) const ITEM: u16 = 80;
+pub(crate) const COUNT: u16 = 77;
+pub(crate) const COMPARISON: u16 = 88;
+
+const EQUAL_TO: u8 = 0;
+const LARGER_THAN: u8 = 1;
+const SMALLER_THAN: u8 = 2;
+
+// inclusive range of item values, None means unbounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct Range {
+    pub(crate) start: Option<i64>,
+    pub(crate) end: Option<i64>,
+}
+
+impl Range {
+    pub(crate) fn from_comparison(mode: u8, n: i64) -> Option<Self> {
+        Some(match mode {
+            EQUAL_TO => Range {
+                start: Some(n),
+                end: Some(n),
+            },
+            LARGER_THAN => Range {
+                start: Some(n + 1),
+                end: None,
+            },
+            SMALLER_THAN => Range {
+                start: None,
+                end: Some(n - 1),
+            },
+            _ => return None,
+        })
+    }
+
+    // None if this range can't be checked with a single instant count trigger,
+    // Some(None) if it doesn't need to be checked at all
+    fn to_comparison(self) -> Option<Option<(u8, i64)>> {
+        match (self.start, self.end) {
+            (None, None) => Some(None),
+            (Some(a), Some(b)) if a == b => Some(Some((EQUAL_TO, a))),
+            (Some(a), None) => Some(Some((LARGER_THAN, a - 1))),
+            (None, Some(b)) => Some(Some((SMALLER_THAN, b + 1))),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn is_empty(self) -> bool {
+        matches!((self.start, self.end), (Some(a), Some(b)) if a > b)
+    }
+
+    pub(crate) fn intersection(self, other: Self) -> Self {
+        Range {
+            start: match (self.start, other.start) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                (a, b) => a.or(b),
+            },
+            end: match (self.end, other.end) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+
+    // the union of two ranges, if it is a single range
+    pub(crate) fn union(self, other: Self) -> Option<Self> {
+        let (first, second) =
+            if self.start.map_or(i64::MIN, |a| a) <= other.start.map_or(i64::MIN, |a| a) {
+                (self, other)
+            } else {
+                (other, self)
+            };
+        match (first.end, second.start) {
+            (Some(a), Some(b)) if a + 1 < b => None,
+            _ => Some(Range {
+                start: first.start,
+                end: match (first.end, second.end) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    _ => None,
+                },
+            }),
+        }
+    }
+
+    fn overlaps(self, other: Self) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    pub(crate) fn contains(self, other: Self) -> bool {
+        self.intersection(other) == other
+    }
+}
+
+#[derive(Debug, Clone)]
+struct InstantCount {
+    trigger: Trigger,
+    item: Item,
+    target: Group,
+    range: Range,
+    // `false` if the target is toggled off instead
+    activate: bool,
+    // all the other properties, which have to be the same for triggers to be combined
+    rest: Vec<(u16, ObjParam)>,
+}
+
+fn get_instant_count(trigger: Trigger, objects: &Triggerlist) -> Option<InstantCount> {
+    let params = &objects[trigger.obj].0.params;
+    match params.get(&1) {
+        Some(ObjParam::Number(n)) if *n as u16 == obj_ids::INSTANT_COUNT => (),
+        _ => return None,
+    };
+    let activate = matches!(
+        params.get(&obj_props::ACTIVATE_GROUP),
+        Some(ObjParam::Bool(true))
+    );
+    let item = match params.get(&ITEM) {
+        Some(ObjParam::Item(i)) => *i,
+        _ => return None,
+    };
+    let target = match params.get(&obj_props::TARGET) {
+        Some(ObjParam::Group(g)) => *g,
+        _ => return None,
+    };
+    let count = match params.get(&COUNT) {
+        Some(ObjParam::Number(n)) if n.fract() == 0.0 => *n as i64,
+        None => 0,
+        _ => return None,
+    };
+    let mode = match params.get(&COMPARISON) {
+        Some(ObjParam::Number(n)) => *n as u8,
+        None => EQUAL_TO,
+        _ => return None,
+    };
+    let mut rest: Vec<(u16, ObjParam)> = params
+        .iter()
+        .filter(|(k, _)| {
+            !matches!(
+                **k,
+                1 | ITEM
+                    | COUNT
+                    | COMPARISON
+                    | obj_props::TARGET
+                    | obj_props::GROUPS
+                    | obj_props::ACTIVATE_GROUP
+            )
+        })
+        .map(|(k, v)| (*k, v.clone()))
+        .collect();
+    rest.sort_by_key(|(k, _)| *k);
+    Some(InstantCount {
+        trigger,
+        item,
+        target,
+        range: Range::from_comparison(mode, count)?,
+        activate,
+        rest,
+    })
+}
+
+// changes the instant count trigger to activate (or toggle off) `target` when the item
+// is in `range` (the range has to be representable)
+fn rewrite_trigger(obj: &mut GdObj, target: Group, range: Range, activate: bool) -> TriggerRole {
+    obj.params
+        .insert(obj_props::TARGET, ObjParam::Group(target));
+    obj.params
+        .insert(obj_props::ACTIVATE_GROUP, ObjParam::Bool(activate));
+    match range.to_comparison().unwrap() {
+        Some((mode, n)) => {
+            obj.params.insert(COUNT, ObjParam::Number(n as f64));
+            obj.params.insert(COMPARISON, ObjParam::Number(mode as f64));
+        }
+        None => {
+            // always true, so this is just a spawn trigger
+            obj.params
+                .insert(1, ObjParam::Number(obj_ids::SPAWN as f64));
+            for prop in [ITEM, COUNT, COMPARISON, obj_props::ACTIVATE_GROUP] {
+                obj.params.remove(&prop);
+            }
+        }
+    }
+    get_role(obj)
+}
+
+pub(crate) fn instant_count_optimization(
+    network: &mut TriggerNetwork,
+    objects: &mut Triggerlist,
+    reserved: &ReservedIds,
+    toggle_groups: &ToggleGroups,
+) {
+    let mut groups: Vec<Group> = network.map.keys().copied().collect();
+    groups.sort();
+    for group in groups.iter().copied() {
+        merge_instant_counts(network, objects, group);
+    }
+    for group in groups {
+        fold_instant_counts(network, objects, reserved, toggle_groups, group);
+    }
+}
+
+// where each trigger is in the gang, so they can be changed without searching the whole gang
+fn trigger_positions(network: &TriggerNetwork, group: Group) -> AHashMap<ObjPtr, usize> {
+    match network.map.get(&group) {
+        Some(gang) => gang
+            .triggers
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.obj, i))
+            .collect(),
+        None => AHashMap::default(),
+    }
+}
+
+fn set_trigger(
+    network: &mut TriggerNetwork,
+    group: Group,
+    positions: &AHashMap<ObjPtr, usize>,
+    ptr: ObjPtr,
+    f: impl Fn(&mut Trigger),
+) {
+    if let (Some(gang), Some(i)) = (network.map.get_mut(&group), positions.get(&ptr)) {
+        f(&mut gang.triggers[*i])
+    }
+}
+
+fn combinable(a: &InstantCount, b: &InstantCount) -> bool {
+    a.item == b.item && a.target == b.target && a.activate == b.activate && a.rest == b.rest
+}
+
+// instant count triggers right after each other in the same group,
+// checking the same item and activating the same group
+fn merge_instant_counts(network: &mut TriggerNetwork, objects: &mut Triggerlist, group: Group) {
+    let positions = trigger_positions(network, group);
+    let mut sorted: Vec<Trigger> = network.map[&group]
+        .triggers
+        .iter()
+        .filter(|t| !t.deleted)
+        .copied()
+        .collect();
+    sorted.sort_by(|a, b| objects[a.obj].1.partial_cmp(&objects[b.obj].1).unwrap());
+
+    // split into runs of instant count triggers that aren't interrupted by other triggers,
+    // since those might change the item
+    let mut runs: Vec<Vec<InstantCount>> = vec![Vec::new()];
+    for trigger in sorted {
+        match get_instant_count(trigger, objects) {
+            Some(ic) => runs.last_mut().unwrap().push(ic),
+            None => runs.push(Vec::new()),
+        }
+    }
+
+    for run in runs {
+        let mut done = vec![false; run.len()];
+        for i in 0..run.len() {
+            if done[i] {
+                continue;
+            }
+            let mut similar: Vec<InstantCount> = run[i..]
+                .iter()
+                .zip(&done[i..])
+                .filter(|(ic, done)| !**done && combinable(ic, &run[i]))
+                .map(|(ic, _)| ic.clone())
+                .collect();
+            for (j, ic) in run.iter().enumerate().skip(i) {
+                if combinable(ic, &run[i]) {
+                    done[j] = true;
+                }
+            }
+            if similar.len() < 2 {
+                continue;
+            }
+
+            // if more than one of them can be true at the same time,
+            // the group is activated multiple times
+            let disjoint = similar.iter().enumerate().all(|(a, ic1)| {
+                similar
+                    .iter()
+                    .skip(a + 1)
+                    .all(|ic2| !ic1.range.overlaps(ic2.range))
+            });
+            if !disjoint {
+                continue;
+            }
+
+            similar.sort_by_key(|ic| ic.range.start.unwrap_or(i64::MIN));
+
+            // merge neighbouring ranges
+            let mut merged: Vec<(Range, Vec<InstantCount>)> = Vec::new();
+            for ic in similar {
+                if let Some((range, ics)) = merged.last_mut() {
+                    if let Some(union) = range.union(ic.range) {
+                        // a toggle off that is always true can't stay an instant count trigger
+                        if union
+                            .to_comparison()
+                            .is_some_and(|c| c.is_some() || ic.activate)
+                        {
+                            *range = union;
+                            ics.push(ic);
+                            continue;
+                        }
+                    }
+                }
+                merged.push((ic.range, vec![ic]));
+            }
+
+            for (range, ics) in merged {
+                if ics.len() < 2 {
+                    continue;
+                }
+                // keep the first one (in trigger order), and delete the rest
+                let first = ics
+                    .iter()
+                    .min_by(|a, b| {
+                        objects[a.trigger.obj]
+                            .1
+                            .partial_cmp(&objects[b.trigger.obj].1)
+                            .unwrap()
+                    })
+                    .unwrap()
+                    .trigger
+                    .obj;
+                let role =
+                    rewrite_trigger(&mut objects[first].0, ics[0].target, range, ics[0].activate);
+                set_trigger(network, group, &positions, first, |t| t.role = role);
+                for ic in ics.iter().filter(|ic| ic.trigger.obj != first) {
+                    set_trigger(network, group, &positions, ic.trigger.obj, |t| {
+                        t.deleted = true
+                    });
+                    objects.merge_origins(first, ic.trigger.obj);
+                }
+            }
+        }
+    }
+}
+
+// instant count triggers activating a group that only checks the same item again
+fn fold_instant_counts(
+    network: &mut TriggerNetwork,
+    objects: &mut Triggerlist,
+    reserved: &ReservedIds,
+    toggle_groups: &ToggleGroups,
+    group: Group,
+) {
+    let triggers: Vec<Trigger> = match network.map.get(&group) {
+        Some(gang) => gang
+            .triggers
+            .iter()
+            .filter(|t| !t.deleted)
+            .copied()
+            .collect(),
+        None => return,
+    };
+    // new triggers are only added at the end, so this stays correct
+    let positions = trigger_positions(network, group);
+    for trigger in triggers {
+        let first = match get_instant_count(trigger, objects) {
+            Some(ic) if ic.activate => ic,
+            _ => continue,
+        };
+        let target = first.target;
+        // toggling a group on only matters if it can also be toggled off,
+        // and every instant count toggles its own target on
+        if target == group
+            || is_start_group(target, reserved)
+            || toggle_groups.toggles_off.contains_key(&target)
+            || toggle_groups.stops.contains_key(&target)
+        {
+            continue;
+        }
+        let gang = match network.map.get(&target) {
+            Some(g) => g,
+            None => continue,
+        };
+        if gang.connections_in != 1 {
+            continue;
+        }
+
+        let mut next = Vec::new();
+        for t in gang.triggers.iter().filter(|t| !t.deleted) {
+            match get_instant_count(*t, objects) {
+                Some(ic) if ic.item == first.item && ic.rest == first.rest => next.push(ic),
+                _ => {
+                    next.clear();
+                    break;
+                }
+            }
+        }
+        if next.is_empty() {
+            continue;
+        }
+
+        let ranges: Vec<Range> = next
+            .iter()
+            .map(|ic| first.range.intersection(ic.range))
+            .collect();
+        if ranges
+            .iter()
+            .any(|r| !r.is_empty() && r.to_comparison().is_none())
+        {
+            continue;
+        }
+
+        let order: TriggerOrder = objects[first.trigger.obj].1;
+        let target_positions = trigger_positions(network, target);
+        let mut new_triggers = Vec::new();
+        for (ic, range) in next.iter().zip(ranges) {
+            set_trigger(network, target, &target_positions, ic.trigger.obj, |t| {
+                t.deleted = true
+            });
+            if range.is_empty() {
+                continue;
+            }
+            let mut obj = objects[first.trigger.obj].0.clone();
+            obj.merge_origins(objects[ic.trigger.obj].0.origin_ids());
+            let role = rewrite_trigger(&mut obj, ic.target, range, ic.activate);
+            new_triggers.push((obj, role));
+        }
+
+        // the first new trigger replaces the old one
+        match new_triggers.first() {
+            Some((obj, role)) => {
+                objects[first.trigger.obj].0 = obj.clone();
+                let role = *role;
+                set_trigger(network, group, &positions, first.trigger.obj, |t| {
+                    t.role = role
+                });
+            }
+            None => set_trigger(network, group, &positions, first.trigger.obj, |t| {
+                t.deleted = true
+            }),
+        }
+        for (obj, role) in new_triggers.into_iter().skip(1) {
+            let fn_id = first.trigger.obj.0;
+            objects.list[fn_id].obj_list.push((obj, order));
+            let ptr = ObjPtr(fn_id, objects.list[fn_id].obj_list.len() - 1);
+            network.map.get_mut(&group).unwrap().triggers.push(Trigger {
+                obj: ptr,
+                role,
+                deleted: false,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: Option<i64>, end: Option<i64>) -> Range {
+        Range { start, end }
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(
+            Range::from_comparison(EQUAL_TO, 5),
+            Some(range(Some(5), Some(5)))
+        );
+        assert_eq!(
+            Range::from_comparison(LARGER_THAN, 5),
+            Some(range(Some(6), None))
+        );
+        assert_eq!(
+            Range::from_comparison(SMALLER_THAN, 5),
+            Some(range(None, Some(4)))
+        );
+        assert_eq!(Range::from_comparison(3, 5), None);
+
+        assert_eq!(
+            range(Some(4), Some(4)).to_comparison(),
+            Some(Some((EQUAL_TO, 4)))
+        );
+        assert_eq!(
+            range(Some(4), None).to_comparison(),
+            Some(Some((LARGER_THAN, 3)))
+        );
+        assert_eq!(
+            range(None, Some(4)).to_comparison(),
+            Some(Some((SMALLER_THAN, 5)))
+        );
+        assert_eq!(range(None, None).to_comparison(), Some(None));
+        assert_eq!(range(Some(2), Some(4)).to_comparison(), None);
+    }
+
+    #[test]
+    fn intersection() {
+        // > 3 and < 5
+        assert_eq!(
+            range(Some(4), None).intersection(range(None, Some(4))),
+            range(Some(4), Some(4))
+        );
+        assert_eq!(
+            range(Some(2), Some(8)).intersection(range(Some(5), None)),
+            range(Some(5), Some(8))
+        );
+        assert_eq!(
+            range(None, None).intersection(range(None, Some(1))),
+            range(None, Some(1))
+        );
+        // > 5 and < 2
+        let empty = range(Some(6), None).intersection(range(None, Some(1)));
+        assert_eq!(empty, range(Some(6), Some(1)));
+        assert!(empty.is_empty());
+        assert!(!range(Some(3), Some(3)).is_empty());
+    }
+
+    #[test]
+    fn union() {
+        // == 5 or < 5
+        assert_eq!(
+            range(Some(5), Some(5)).union(range(None, Some(4))),
+            Some(range(None, Some(5)))
+        );
+        assert_eq!(
+            range(Some(6), None).union(range(Some(3), Some(7))),
+            Some(range(Some(3), None))
+        );
+        assert_eq!(
+            range(Some(1), Some(2)).union(range(Some(3), Some(4))),
+            Some(range(Some(1), Some(4)))
+        );
+        assert_eq!(range(Some(1), Some(2)).union(range(Some(4), Some(5))), None);
+        assert_eq!(range(None, Some(1)).union(range(Some(7), None)), None);
+        assert_eq!(
+            range(None, Some(1)).union(range(Some(0), None)),
+            Some(range(None, None))
+        );
+    }
+
+    #[test]
+    fn contains() {
+        assert!(range(None, Some(5)).contains(range(Some(2), Some(3))));
+        assert!(range(Some(2), Some(3)).contains(range(Some(2), Some(3))));
+        assert!(!range(Some(2), Some(3)).contains(range(Some(2), None)));
+    }
+}
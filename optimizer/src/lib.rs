@@ -5,11 +5,14 @@ use compiler::{
 };
 use ahash::{AHashMap, AHashSet};
 
+mod components;
 mod dead_code;
 mod group_toggling;
+mod instant_count;
 pub mod optimize;
 mod spawn_optimisation;
 mod trigger_dedup;
+pub mod verify;
 
 pub type Swaps = AHashMap<Group, (Group, TriggerOrder)>;
 
@@ -61,7 +64,7 @@ pub enum TriggerRole {
     Func,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ReservedIds {
     pub object_groups: AHashSet<Id>,
     pub trigger_groups: AHashSet<Id>, // only includes the obj_props::GROUPS prop
@@ -171,6 +174,14 @@ pub struct Triggerlist<'a> {
     list: &'a mut Vec<FunctionId>,
 }
 
+impl<'a> Triggerlist<'a> {
+    // keeps where a trigger came from when it's merged into another one
+    fn merge_origins(&mut self, into: ObjPtr, from: ObjPtr) {
+        let ids: Vec<usize> = self[from].0.origin_ids().collect();
+        self[into].0.merge_origins(ids);
+    }
+}
+
 impl<'a> std::ops::Index<ObjPtr> for Triggerlist<'a> {
     type Output = (GdObj, TriggerOrder);
 
@@ -230,25 +241,26 @@ pub fn get_role(obj: &GdObj) -> TriggerRole {
                 }
             }
             obj_ids::COUNT | obj_ids::COLLISION | obj_ids::INSTANT_COUNT | obj_ids::ON_DEATH => {
-                if let Some(ObjParam::Bool(false)) | None =
-                    obj.params.get(&obj_props::ACTIVATE_GROUP)
-                {
-                    // will toggle a group off
-                    TriggerRole::Output
-                } else if let Some(ObjParam::Group(g)) = obj.params.get(&obj_props::TARGET) {
-                    if let Id::Specific(_) = g.id {
-                        // might interact with triggers in the editor
-                        TriggerRole::Output
-                    } else {
-                        TriggerRole::Func
+                match obj.params.get(&obj_props::TARGET) {
+                    // activates (or toggles off) group 0, which doesn't do anything,
+                    // so it's removed like a function trigger that leads nowhere
+                    None => TriggerRole::Func,
+                    Some(ObjParam::Group(g)) => {
+                        if let Some(ObjParam::Bool(false)) | None =
+                            obj.params.get(&obj_props::ACTIVATE_GROUP)
+                        {
+                            // will toggle a group off, which stops the triggers in it from being spawned,
+                            // so it has to stay even if the group only contains triggers.
+                            // the instant count pass still merges and folds these
+                            TriggerRole::Output
+                        } else if let Id::Specific(_) = g.id {
+                            // might interact with triggers in the editor
+                            TriggerRole::Output
+                        } else {
+                            TriggerRole::Func
+                        }
                     }
-                    // else if toggle_groups.contains(g) {
-                    //     // might toggle a group on
-                    //     TriggerRole::Output
-                    // }
-                } else {
-                    // the user didnt provide a target group, so fuck them no optimization for you >:D
-                    TriggerRole::Output
+                    _ => TriggerRole::Output,
                 }
             }
             _ => TriggerRole::Output,
@@ -3,24 +3,139 @@ use compiler::compiler_types::{FunctionId, TriggerOrder};
 use parser::ast::ObjectMode;
 
 use crate::{
-    dead_code, get_role, group_toggling, obj_ids, obj_props, spawn_optimisation, trigger_dedup,
-    ObjPtr, ReservedIds, Swaps, Trigger, TriggerGang, TriggerNetwork, TriggerRole, Triggerlist,
-    NO_GROUP,
+    components, dead_code, get_role, group_toggling, instant_count, obj_ids, obj_props,
+    spawn_optimisation, trigger_dedup, ObjPtr, ReservedIds, Swaps, Trigger, TriggerGang,
+    TriggerNetwork, TriggerRole, Triggerlist, NO_GROUP,
 };
 
-//mod icalgebra;
 use compiler::leveldata::{GdObj, ObjParam};
 
 use ahash::AHashMap;
 
+// the rounds stop early once a round doesn't change anything
+const MAX_ROUNDS: usize = 10;
+
 pub fn optimize(
+    obj_in: Vec<FunctionId>,
+    closed_group: u16,
+    reserved: ReservedIds,
+) -> Vec<FunctionId> {
+    let (obj_in, reserved) = optimize_triggers(obj_in, reserved);
+    toggle_groups(obj_in, closed_group, &reserved)
+}
+
+// everything except group toggling, which orders the triggers in a frame with
+// toggle triggers that `verify` can't follow the conditions through
+pub fn optimize_triggers(
+    obj_in: Vec<FunctionId>,
+    mut reserved: ReservedIds,
+) -> (Vec<FunctionId>, ReservedIds) {
+    // parts of the network that aren't connected can't affect each other,
+    // so they are optimized in parallel. the parts don't depend on the number of
+    // threads, so the output is the same on every machine
+    let parts = components::split_components(&obj_in, components::PARTS);
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(parts.len());
+
+    let optimized: Vec<Vec<FunctionId>> = if threads <= 1 {
+        parts
+            .into_iter()
+            .map(|part| optimize_part(part, &reserved))
+            .collect()
+    } else {
+        let queue = std::sync::Mutex::new(parts.into_iter().enumerate());
+        let mut results: Vec<(usize, Vec<FunctionId>)> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..threads)
+                .map(|_| {
+                    let (queue, reserved) = (&queue, &reserved);
+                    s.spawn(move || {
+                        let mut done = Vec::new();
+                        loop {
+                            // the lock is released before the part is optimized
+                            let next = queue.lock().unwrap().next();
+                            match next {
+                                Some((i, part)) => done.push((i, optimize_part(part, reserved))),
+                                None => break done,
+                            }
+                        }
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        });
+        // back in the order of the parts, no matter which thread finished first
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, part)| part).collect()
+    };
+
+    let mut obj_in = components::merge_components(optimized);
+    let mut network = build_network(&obj_in);
+    let mut objects = Triggerlist { list: &mut obj_in };
+
+    update_reserved(&mut network, &mut objects, &mut reserved);
+
+    clean_network(&mut network, &objects, false);
+
+    trigger_dedup::dedup_triggers(&mut network, &mut objects, &reserved);
+
+    (rebuild(&network, &obj_in), reserved)
+}
+
+pub fn toggle_groups(
     mut obj_in: Vec<FunctionId>,
     mut closed_group: u16,
-    mut reserved: ReservedIds,
+    reserved: &ReservedIds,
 ) -> Vec<FunctionId> {
-    let mut network = TriggerNetwork::default();
+    let mut network = build_network(&obj_in);
+    let mut objects = Triggerlist { list: &mut obj_in };
 
-    let toggle_groups = get_toggle_groups(&obj_in);
+    clean_network(&mut network, &objects, false);
+
+    group_toggling::group_toggling(&mut network, &mut objects, reserved, &mut closed_group);
+    //dbg!(&network);
+
+    let zero_group = Group {
+        id: Id::Specific(0),
+    };
+    if let Some(gang) = network.map.get(&zero_group) {
+        if gang.triggers.len() > 1 {
+            closed_group += 1;
+            let new_start_group = Group {
+                id: Id::Arbitrary(closed_group),
+            };
+
+            let mut swaps = Swaps::default();
+            swaps.insert(zero_group, (new_start_group, TriggerOrder(0.0)));
+
+            replace_groups(swaps, &mut objects);
+
+            create_spawn_trigger(
+                Trigger {
+                    obj: ObjPtr(0, 0), // arbitrary object
+                    role: TriggerRole::Spawn,
+                    deleted: false,
+                },
+                new_start_group,
+                zero_group,
+                0.0,
+                &mut objects,
+                &mut network,
+                TriggerRole::Spawn,
+                false,
+            );
+        }
+    }
+
+    rebuild(&network, &obj_in)
+}
+
+fn build_network(obj_in: &[FunctionId]) -> TriggerNetwork {
+    let mut network = TriggerNetwork::default();
 
     // sort all triggers by their group
     for (f, fnid) in obj_in.iter().enumerate() {
@@ -55,6 +170,16 @@ pub fn optimize(
             //}
         }
     }
+    network
+}
+
+// runs the rounds of optimization on a part of the network,
+// and returns the triggers that are left
+fn optimize_part(mut obj_in: Vec<FunctionId>, reserved: &ReservedIds) -> Vec<FunctionId> {
+    let mut reserved = reserved.clone();
+
+    let toggle_groups = get_toggle_groups(&obj_in);
+    let mut network = build_network(&obj_in);
 
     let mut objects = Triggerlist { list: &mut obj_in };
 
@@ -70,7 +195,9 @@ pub fn optimize(
     // round 1
     //dbg!(&objects.list);
 
-    for _ in 0..10 {
+    let mut last_fingerprint = None;
+
+    for _ in 0..MAX_ROUNDS {
         clean_network(&mut network, &objects, true);
 
         dead_code::dead_code_optimization(&mut network, &mut objects, &reserved);
@@ -88,51 +215,50 @@ pub fn optimize(
         clean_network(&mut network, &objects, false);
         //dbg!(&objects.list);
 
-        update_reserved(&mut network, &mut objects, &mut reserved);
-    }
-
-    clean_network(&mut network, &objects, false);
-
-    trigger_dedup::dedup_triggers(&mut network, &mut objects, &reserved);
+        instant_count::instant_count_optimization(
+            &mut network,
+            &mut objects,
+            &reserved,
+            &toggle_groups,
+        );
 
-    clean_network(&mut network, &objects, false);
+        clean_network(&mut network, &objects, false);
 
-    group_toggling::group_toggling(&mut network, &mut objects, &reserved, &mut closed_group);
-    //dbg!(&network);
+        update_reserved(&mut network, &mut objects, &mut reserved);
 
-    let zero_group = Group {
-        id: Id::Specific(0),
-    };
-    if let Some(gang) = network.map.get(&zero_group) {
-        if gang.triggers.len() > 1 {
-            closed_group += 1;
-            let new_start_group = Group {
-                id: Id::Arbitrary(closed_group),
-            };
+        let fingerprint = Some(network_fingerprint(&network, &objects));
+        if fingerprint == last_fingerprint {
+            break;
+        }
+        last_fingerprint = fingerprint;
+    }
 
-            let mut swaps = Swaps::default();
-            swaps.insert(zero_group, (new_start_group, TriggerOrder(0.0)));
+    rebuild(&network, &obj_in)
+}
 
-            replace_groups(swaps, &mut objects);
+// a summary of all the triggers in the network, used to check
+// if a round of optimization changed anything
+fn network_fingerprint(network: &TriggerNetwork, objects: &Triggerlist) -> (usize, u64) {
+    use std::hash::{Hash, Hasher};
 
-            create_spawn_trigger(
-                Trigger {
-                    obj: ObjPtr(0, 0), // arbitrary object
-                    role: TriggerRole::Spawn,
-                    deleted: false,
-                },
-                new_start_group,
-                zero_group,
-                0.0,
-                &mut objects,
-                &mut network,
-                TriggerRole::Spawn,
-                false,
-            );
+    let mut count = 0;
+    let mut sum = 0u64;
+    for gang in network.map.values() {
+        for trigger in gang.triggers.iter().filter(|t| !t.deleted) {
+            let (obj, order) = &objects[trigger.obj];
+            let mut params: Vec<_> = obj.params.iter().collect();
+            params.sort_by_key(|(k, _)| **k);
+
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            params.hash(&mut hasher);
+            order.0.to_bits().hash(&mut hasher);
+
+            count += 1;
+            // the order of the triggers doesn't matter
+            sum = sum.wrapping_add(hasher.finish());
         }
     }
-
-    rebuild(&network, &obj_in)
+    (count, sum)
 }
 
 pub fn is_start_group(g: Group, reserved: &ReservedIds) -> bool {
@@ -270,43 +396,37 @@ pub fn clean_network(network: &mut TriggerNetwork, objects: &Triggerlist, delete
     //         .insert(trigger.obj);
     // }
 
-    for (_, gang) in new_network.map.clone() {
-        for trigger in gang.triggers {
+    let mut connections = Vec::new();
+    for gang in new_network.map.values() {
+        for trigger in &gang.triggers {
             let obj = &objects[trigger.obj].0;
             if let (TriggerRole::Func | TriggerRole::Spawn, Some(ObjParam::Group(id))) =
                 (trigger.role, obj.params.get(&obj_props::TARGET))
             {
-                if let Some(gang) = new_network.map.get_mut(&id) {
-                    (*gang).connections_in += 1;
+                connections.push((*id, *trigger));
+            }
+        }
+    }
 
-                    if trigger.role != TriggerRole::Spawn {
-                        (*gang).non_spawn_triggers_in = true;
-                    }
-                }
+    for (id, trigger) in connections {
+        if let Some(gang) = new_network.map.get_mut(&id) {
+            gang.connections_in += 1;
 
-                new_network
-                    .connectors
-                    .entry(*id)
-                    .or_default()
-                    .insert(trigger.obj);
+            if trigger.role != TriggerRole::Spawn {
+                gang.non_spawn_triggers_in = true;
             }
         }
+
+        new_network
+            .connectors
+            .entry(id)
+            .or_default()
+            .insert(trigger.obj);
     }
 
     *network = new_network;
 }
 
-// fn instant_count_optimization(
-//     network: &mut TriggerNetwork,
-//     objects: &mut Triggerlist,
-//     closed_group: &mut u16,
-// ) {
-//     use icalgebra::{build_ic_connections, get_all_ic_connections};
-//     let c = get_all_ic_connections(network, &objects);
-//     let swaps = build_ic_connections(network, objects, closed_group, c);
-//     replace_groups(swaps, network, objects);
-// }
-
 pub fn replace_groups(table: Swaps, objects: &mut Triggerlist) {
     let mut map: AHashMap<Group, (Vec<ObjPtr>, Group, TriggerOrder)> = table
         .into_iter()
@@ -361,7 +481,10 @@ fn rebuild(network: &TriggerNetwork, orig_structure: &[FunctionId]) -> Vec<Funct
         (*el).obj_list.clear();
     }
 
-    for gang in network.map.values() {
+    // sorted so the order of the triggers doesn't depend on hash map order
+    let mut groups: Vec<&Group> = network.map.keys().collect();
+    groups.sort();
+    for gang in groups.into_iter().map(|g| &network.map[g]) {
         for trigger in &gang.triggers {
             //assert!(trigger.optimized);
             if trigger.deleted {
@@ -393,7 +516,7 @@ pub fn create_spawn_trigger(
     network: &mut TriggerNetwork,
     role: TriggerRole,
     deleted: bool,
-) {
+) -> ObjPtr {
     let mut new_obj_map = AHashMap::default();
     new_obj_map.insert(1, ObjParam::Number(1268.0));
     new_obj_map.insert(obj_props::TARGET, ObjParam::Group(target_group));
@@ -408,6 +531,7 @@ pub fn create_spawn_trigger(
         func_id: trigger.obj.0,
         mode: ObjectMode::Trigger,
         unique_id: objects[trigger.obj].0.unique_id,
+        merged_ids: objects[trigger.obj].0.merged_ids.clone(),
     };
 
     (*objects.list)[trigger.obj.0]
@@ -444,4 +568,5 @@ pub fn create_spawn_trigger(
             }
         }
     }
+    obj_index
 }
@@ -14,7 +14,7 @@ use compiler::builtins::Group;
 use compiler::leveldata::ObjParam;
 use ahash::{AHashMap, AHashSet};
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
 struct SpawnDelay {
     pub(crate) delay: u32,
     pub(crate) epsiloned: bool,
@@ -26,6 +26,8 @@ struct Connection {
     end_group: Group,
     delay: SpawnDelay,
     trigger: Trigger,
+    // every spawn trigger on the way, which are all replaced by one
+    chain: Vec<Trigger>,
 }
 
 #[derive(Debug)]
@@ -137,7 +139,10 @@ pub(crate) fn spawn_optimisation(
 
     //bg!(&spawn_connections, &all);
 
-    for start in inputs.clone() {
+    // sorted so the result doesn't depend on hash map order
+    let mut starts: Vec<Group> = inputs.iter().copied().collect();
+    starts.sort();
+    for start in starts {
         let mut visited = Vec::new();
         look_for_cycle(
             start,
@@ -160,7 +165,9 @@ pub(crate) fn spawn_optimisation(
     // go from every trigger in an input group and get every possible path to an
     // output group (stopping if it reaches a group already visited)
 
-    for start in inputs {
+    let mut starts: Vec<Group> = inputs.into_iter().collect();
+    starts.sort();
+    for start in starts {
         //println!("<{:?}>", start);
         let mut visited = Vec::new();
         traverse(
@@ -175,6 +182,7 @@ pub(crate) fn spawn_optimisation(
             &cycle_points,
             &spawn_connections,
             &mut visited,
+            &mut Vec::new(),
             &mut all,
         );
         //println!("</{:?}>", start);
@@ -182,40 +190,51 @@ pub(crate) fn spawn_optimisation(
 
     //dbg!(&all);
 
-    let mut deduped = AHashMap::default();
+    let mut deduped = AHashMap::<_, (Trigger, Vec<Trigger>)>::default();
 
     for Connection {
         start_group,
         end_group,
         delay,
         trigger,
+        chain,
     } in all
     {
-        deduped.insert((start_group, end_group, delay), trigger);
+        let entry = deduped
+            .entry((start_group, end_group, delay))
+            .or_insert_with(|| (trigger, Vec::new()));
+        entry.0 = trigger;
+        entry.1.extend(chain);
     }
 
     let mut swaps = Swaps::default();
 
-    // let mut start_counts = FnvHashMap::default();
-    // let mut end_counts = FnvHashMap::default();
+    // a group can only be merged into another if this is the only
+    // connection going out of it (or into it), otherwise the other
+    // connections would end up in the merged group too
+    let mut start_counts = AHashMap::<Group, usize>::default();
+    let mut end_counts = AHashMap::<Group, usize>::default();
 
-    // for ((start, end, _), _) in deduped.iter() {
-    //     start_counts
-    //         .entry(start)
-    //         .and_modify(|c| *c += 1)
-    //         .or_insert(1);
+    for (start, end, _) in deduped.keys() {
+        *start_counts.entry(*start).or_default() += 1;
+        *end_counts.entry(*end).or_default() += 1;
+    }
 
-    //     end_counts.entry(end).and_modify(|c| *c += 1).or_insert(1);
-    // }
+    let mut deduped: Vec<_> = deduped.into_iter().collect();
+    deduped.sort_by_key(|(key, _)| *key);
 
-    for ((start, end, delay), trigger) in deduped {
+    for ((start, end, delay), (trigger, chain)) in deduped {
         let d = if delay.delay < 50 && delay.epsiloned {
             50
         } else {
             delay.delay
         };
+        // a group can only be swapped with one other group
+        let start_swapped = swaps.contains_key(&start);
+        let end_swapped = swaps.contains_key(&end);
+
         let mut plain_trigger = |network| {
-            create_spawn_trigger(
+            let new_trigger = create_spawn_trigger(
                 trigger,
                 end,
                 start,
@@ -224,7 +243,10 @@ pub(crate) fn spawn_optimisation(
                 network,
                 TriggerRole::Spawn,
                 false,
-            )
+            );
+            for t in &chain {
+                objects.merge_origins(new_trigger, t.obj);
+            }
         };
 
         let mut insert_to_swaps = |a: Group, b: Group, objects: &mut Triggerlist| {
@@ -260,11 +282,15 @@ pub(crate) fn spawn_optimisation(
             || toggle_groups.stops.contains_key(&end)
         {
             plain_trigger(network)
-        } else if d == 0 && !is_start_group(end, reserved) && network.map[&end].connections_in == 1
+        } else if d == 0
+            && !end_swapped
+            && end_counts[&end] == 1
+            && !is_start_group(end, reserved)
+            && network.map[&end].connections_in == 1
         {
             //dbg!(end, start);
             insert_to_swaps(end, start, objects);
-        } else if d == 0 && !is_start_group(start, reserved)
+        } else if d == 0 && !start_swapped && start_counts[&start] == 1 && !is_start_group(start, reserved)
                 && network.map[&start].connections_in == 1 //??
                 && (network.map[&start].triggers.is_empty()
                     || network.map[&start].triggers.iter().all(|t| t.deleted))
@@ -305,6 +331,7 @@ fn look_for_cycle(
                     end_group: *g,
                     delay: *delay,
                     trigger: *trigger,
+                    chain: vec![*trigger],
                 });
                 cycle_points.insert(current);
 
@@ -330,6 +357,7 @@ fn traverse(
     cycle_points: &AHashSet<Group>,
     spawn_connections: &AHashMap<Group, Vec<SpawnTrigger>>,
     visited: &mut Vec<Group>,
+    path: &mut Vec<Trigger>, // the spawn triggers from the origin to the current trigger
     all: &mut Vec<Connection>,
 ) {
     if visited.contains(&current) {
@@ -349,12 +377,14 @@ fn traverse(
                 epsiloned: total_delay.epsiloned || d.epsiloned,
             };
             visited.push(current);
+            path.push(*t2);
             if outputs.contains(g) {
                 all.push(Connection {
                     start_group: origin,
                     end_group: *g,
                     delay: new_delay,
                     trigger: trigger.unwrap_or(*t2),
+                    chain: path.clone(),
                 });
 
                 // avoid infinite loop
@@ -371,6 +401,7 @@ fn traverse(
                         cycle_points,
                         spawn_connections,
                         visited,
+                        &mut Vec::new(),
                         all,
                     );
                 }
@@ -384,9 +415,11 @@ fn traverse(
                     cycle_points,
                     spawn_connections,
                     visited,
+                    path,
                     all,
                 );
             }
+            path.pop();
             assert_eq!(visited.pop(), Some(current));
         }
     } else if let Some(t) = trigger {
@@ -395,6 +428,7 @@ fn traverse(
             end_group: current,
             delay: total_delay,
             trigger: t,
+            chain: path.clone(),
         }) //?
     } else {
         //unreachable!();
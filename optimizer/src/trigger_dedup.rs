@@ -172,8 +172,14 @@ pub(crate) fn dedup_triggers(
     loop {
         let mut swaps = Swaps::default();
         let mut representative_groups = Vec::<(TriggerGangBehavior, Group, TriggerOrder)>::new();
-
-        for (group, gang) in network.map.iter_mut() {
+        // the deleted triggers, and the group with the triggers that replace them
+        let mut merged = Vec::<(Trigger, Group)>::new();
+
+        // sorted so the representative groups don't depend on hash map order
+        let mut groups: Vec<Group> = network.map.keys().copied().collect();
+        groups.sort();
+        for group in &groups {
+            let gang = network.map.get_mut(group).unwrap();
             if is_start_group(*group, reserved) {
                 continue;
             }
@@ -196,6 +202,7 @@ pub(crate) fn dedup_triggers(
                 if b == &behavior {
                     for trigger in &mut gang.triggers {
                         (*trigger).deleted = true;
+                        merged.push((*trigger, *repr));
                     }
                     //dbg!(behavior, repr, group, &representative_groups);
                     assert!(swaps.insert(*group, (*repr, *order)).is_none());
@@ -220,6 +227,16 @@ pub(crate) fn dedup_triggers(
         if swaps.is_empty() {
             break;
         }
+        for (trigger, repr) in merged {
+            let behavior = get_trigger_behavior(trigger, objects);
+            let same = network.map[&repr]
+                .triggers
+                .iter()
+                .find(|t| !t.deleted && get_trigger_behavior(**t, objects) == behavior);
+            if let Some(same) = same {
+                objects.merge_origins(same.obj, trigger.obj);
+            }
+        }
         replace_groups(swaps, objects);
         clean_network(network, objects, false);
     }
@@ -0,0 +1,511 @@
+// checks that the optimizer didn't change what a level actually does
+//
+// every start group is followed through spawn and function triggers,
+// and every output trigger that can be reached is recorded together with
+// the start group, each total spawn delay it can take to get there and
+// the conditions of the count, instant count and collision triggers on the way.
+// if the optimizer did its job, this set is the same before and after.
+//
+// loops of spawn triggers would make the set infinite, so it only goes up to
+// the sum of all the spawn delays (before or after optimizing, whichever is
+// more). no path gets further than that without going around a loop
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::fmt;
+
+use ahash::AHashMap;
+use compiler::builtins::{Group, Id};
+use compiler::compiler_types::FunctionId;
+use compiler::leveldata::{GdObj, ObjParam};
+
+use crate::instant_count::{Range, COMPARISON, COUNT, ITEM};
+use crate::optimize::{is_start_group, optimize_triggers, toggle_groups};
+use crate::trigger_dedup::param_identifier;
+use crate::{get_role, obj_ids, obj_props, ReservedIds, TriggerRole, NO_GROUP};
+
+const SPAWN_DELAY: u16 = 63;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Effect {
+    pub start_group: String,
+    // in milliseconds, so it can be compared exactly
+    pub delay: i64,
+    // what has to be true for the output to be reached
+    pub conditions: Vec<String>,
+    pub output: String,
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "group {} +{:.3}s",
+            self.start_group,
+            self.delay as f64 / 1000.0
+        )?;
+        if !self.conditions.is_empty() {
+            write!(f, " if {}", self.conditions.join(" and "))?;
+        }
+        write!(f, ": {}", self.output)
+    }
+}
+
+// the conditions on a path through the triggers. the values of the items are
+// only known until a pickup trigger changes them, after that they're forgotten
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Conditions {
+    // instant count checks, as the values each item has to have.
+    // the optimizer merges and folds these, so they're compared as ranges
+    items: BTreeMap<String, Range>,
+    // count, collision and death triggers, which wait for something to happen
+    events: BTreeSet<String>,
+}
+
+impl Conditions {
+    // the conditions after also checking that the item is in the range, `None` if it never can be
+    fn check_item(&self, item: String, range: Range) -> Option<Self> {
+        let mut new = self.clone();
+        let range = match new.items.get(&item) {
+            Some(old) => old.intersection(range),
+            None => range,
+        };
+        if range.is_empty() {
+            return None;
+        }
+        if range.start.is_none() && range.end.is_none() {
+            new.items.remove(&item);
+        } else {
+            new.items.insert(item, range);
+        }
+        Some(new)
+    }
+
+    // true if these conditions are true whenever the other ones are
+    fn covers(&self, other: &Self) -> bool {
+        self.events == other.events
+            && self.items.iter().all(|(item, range)| {
+                other
+                    .items
+                    .get(item)
+                    .is_some_and(|other| range.contains(*other))
+            })
+    }
+
+    // the conditions that are true when either of these are, if they can be written as one
+    // (like the ones of instant count triggers the optimizer merged)
+    fn union(&self, other: &Self) -> Option<Self> {
+        if self.events != other.events
+            || self.items.len() != other.items.len()
+            || self.items.keys().ne(other.items.keys())
+        {
+            return None;
+        }
+        let mut different = self
+            .items
+            .iter()
+            .zip(other.items.values())
+            .filter(|((_, a), b)| a != b);
+        let ((item, a), b) = different.next()?;
+        if different.next().is_some() {
+            return None;
+        }
+        let mut new = self.clone();
+        new.items.insert(item.clone(), a.union(*b)?);
+        Some(new)
+    }
+
+    fn describe(&self) -> Vec<String> {
+        let items = self
+            .items
+            .iter()
+            .map(|(item, range)| match (range.start, range.end) {
+                (Some(a), Some(b)) if a == b => format!("item {} == {}", item, a),
+                (Some(a), Some(b)) => format!("item {} in {}..={}", item, a, b),
+                (Some(a), None) => format!("item {} >= {}", item, a),
+                (None, Some(b)) => format!("item {} <= {}", item, b),
+                (None, None) => unreachable!(),
+            });
+        items.chain(self.events.iter().cloned()).collect()
+    }
+}
+
+// removes the paths that are covered by other ones, and combines the ones that
+// only differ in the value of one item
+fn simplify(paths: Vec<Conditions>) -> Vec<Conditions> {
+    let mut paths = paths;
+    'simplify: loop {
+        for i in 0..paths.len() {
+            for j in 0..paths.len() {
+                if i == j {
+                    continue;
+                }
+                if paths[i].covers(&paths[j]) {
+                    paths.remove(j);
+                    continue 'simplify;
+                }
+                if let Some(union) = paths[i].union(&paths[j]) {
+                    paths[i] = union;
+                    paths.remove(j);
+                    continue 'simplify;
+                }
+            }
+        }
+        break paths;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    // effects that were there before optimizing, but not after
+    pub missing: Vec<Effect>,
+    // effects that only showed up after optimizing
+    pub added: Vec<Effect>,
+}
+
+impl VerifyReport {
+    pub fn is_equivalent(&self) -> bool {
+        self.missing.is_empty() && self.added.is_empty()
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_equivalent() {
+            return write!(f, "optimized triggers are equivalent to the original");
+        }
+        write!(f, "optimized triggers differ from the original:")?;
+        for effect in &self.missing {
+            write!(f, "\n  - {}", effect)?;
+        }
+        for effect in &self.added {
+            write!(f, "\n  + {}", effect)?;
+        }
+        Ok(())
+    }
+}
+
+// optimizes the triggers, and checks that it didn't change what they do.
+// group toggling turns conditions into toggle triggers, so the conditions are
+// compared before it, and only the rest of the effects after it
+pub fn optimize_and_verify(
+    obj_in: Vec<FunctionId>,
+    closed_group: u16,
+    reserved: ReservedIds,
+) -> (Vec<FunctionId>, VerifyReport) {
+    let (optimized, optimized_reserved) = optimize_triggers(obj_in.clone(), reserved.clone());
+    let mut report = verify_optimization(&obj_in, &optimized, &reserved, true);
+
+    let toggled = toggle_groups(optimized.clone(), closed_group, &optimized_reserved);
+    let toggling = verify_optimization(&optimized, &toggled, &reserved, false);
+    report.missing.extend(toggling.missing);
+    report.added.extend(toggling.added);
+
+    (toggled, report)
+}
+
+// compares the reachable effects of the triggers before and after optimization
+pub fn verify_optimization(
+    before: &[FunctionId],
+    after: &[FunctionId],
+    reserved: &ReservedIds,
+    conditions: bool,
+) -> VerifyReport {
+    // the same for both, so effects from loops are cut off at the same time
+    let horizon = total_delay(before).max(total_delay(after));
+    let before_effects = reachable_effects(before, reserved, conditions, horizon);
+    let after_effects = reachable_effects(after, reserved, conditions, horizon);
+
+    VerifyReport {
+        missing: before_effects.difference(&after_effects).cloned().collect(),
+        added: after_effects.difference(&before_effects).cloned().collect(),
+    }
+}
+
+struct EffectSearch<'a> {
+    gangs: AHashMap<Group, Vec<&'a GdObj>>,
+    reserved: &'a ReservedIds,
+    // if the conditions of the paths are recorded
+    conditions: bool,
+    // the longest delay that is followed, in milliseconds
+    horizon: i64,
+}
+
+pub fn reachable_effects(
+    func_ids: &[FunctionId],
+    reserved: &ReservedIds,
+    conditions: bool,
+    horizon: i64,
+) -> BTreeSet<Effect> {
+    let mut gangs = AHashMap::<Group, Vec<&GdObj>>::default();
+    for fn_id in func_ids {
+        for (obj, _) in &fn_id.obj_list {
+            // a trigger with multiple groups is activated by any of them
+            match obj.params.get(&obj_props::GROUPS) {
+                Some(ObjParam::Group(g)) => gangs.entry(*g).or_default().push(obj),
+                Some(ObjParam::GroupList(list)) => {
+                    for g in list {
+                        gangs.entry(*g).or_default().push(obj)
+                    }
+                }
+                _ => gangs.entry(NO_GROUP).or_default().push(obj),
+            }
+        }
+    }
+
+    let mut starts: Vec<Group> = gangs
+        .keys()
+        .copied()
+        .filter(|g| is_start_group(*g, reserved))
+        .collect();
+    starts.sort_by_key(|g| group_identifier(*g, reserved));
+
+    let search = EffectSearch {
+        gangs,
+        reserved,
+        conditions,
+        horizon,
+    };
+
+    let mut paths = Paths::new();
+    for start in starts {
+        search.follow(start, &mut paths);
+    }
+
+    let mut effects = BTreeSet::new();
+    for ((start_group, delay, output), conditions) in paths {
+        for conditions in simplify(conditions) {
+            effects.insert(Effect {
+                start_group: start_group.clone(),
+                delay,
+                conditions: conditions.describe(),
+                output: output.clone(),
+            });
+        }
+    }
+    effects
+}
+
+type Paths = BTreeMap<(String, i64, String), Vec<Conditions>>;
+
+impl<'a> EffectSearch<'a> {
+    // finds every delay (up to the horizon) that each group can be reached
+    // with from the start group, and records the output triggers in those groups.
+    // a group is only followed again with the same delay if it's reached with
+    // conditions that aren't covered by the ones it was reached with before
+    fn follow(&self, start: Group, paths: &mut Paths) {
+        let start_name = group_identifier(start, self.reserved);
+        let mut reached = AHashMap::<(Group, i64), Vec<Conditions>>::default();
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((0, start, Conditions::default())));
+
+        while let Some(Reverse((delay, group, mut conditions))) = queue.pop() {
+            let before = reached.entry((group, delay)).or_default();
+            if before.iter().any(|c| c.covers(&conditions)) {
+                continue;
+            }
+            // like both branches of an if, which continue in the same group
+            while let Some(i) = before.iter().position(|c| c.union(&conditions).is_some()) {
+                let other = before.remove(i);
+                conditions = other.union(&conditions).unwrap();
+            }
+            before.push(conditions.clone());
+
+            let gang = match self.gangs.get(&group) {
+                Some(g) => g,
+                None => continue,
+            };
+
+            // the items might be different when they're checked again after this
+            let next = if gang.iter().any(|obj| is_pickup(obj)) {
+                Conditions {
+                    items: BTreeMap::new(),
+                    events: conditions.events.clone(),
+                }
+            } else {
+                conditions.clone()
+            };
+
+            for obj in gang {
+                let target = match obj.params.get(&obj_props::TARGET) {
+                    Some(ObjParam::Group(g)) => Some(*g),
+                    _ => None,
+                };
+                match (get_role(obj), target) {
+                    (TriggerRole::Spawn, Some(target)) => {
+                        let delay = delay + spawn_delay(obj);
+                        if delay <= self.horizon {
+                            queue.push(Reverse((delay, target, next.clone())))
+                        }
+                    }
+                    (TriggerRole::Func, Some(target)) => {
+                        if let Some(conditions) = self.add_condition(obj, &next) {
+                            queue.push(Reverse((delay, target, conditions)))
+                        }
+                    }
+                    // activates group 0
+                    (TriggerRole::Func, None) => (),
+                    _ => {
+                        if self.is_control_flow(obj) {
+                            continue;
+                        }
+                        // the optimizer merges and folds instant counts that toggle
+                        // a group off, so their comparison is a condition like the others
+                        let (output, conditions) = if self.conditions && is_instant_count(obj) {
+                            match self.add_condition(obj, &conditions) {
+                                Some(c) => (self.output_identifier(obj, &[COUNT, COMPARISON]), c),
+                                None => continue,
+                            }
+                        } else {
+                            (self.output_identifier(obj, &[]), conditions.clone())
+                        };
+                        paths
+                            .entry((start_name.clone(), delay, output))
+                            .or_default()
+                            .push(conditions);
+                    }
+                }
+            }
+        }
+    }
+
+    // toggle and stop triggers that only target other triggers are treated
+    // like count triggers: they decide which paths are taken, but they don't
+    // have any visible effect by themselves
+    // (group toggling also makes a lot of these)
+    fn is_control_flow(&self, obj: &GdObj) -> bool {
+        matches!(
+            obj.params.get(&1),
+            Some(ObjParam::Number(n)) if matches!(*n as u16, obj_ids::TOGGLE | obj_ids::STOP)
+        ) && matches!(
+            obj.params.get(&obj_props::TARGET),
+            Some(ObjParam::Group(Group { id: id @ Id::Arbitrary(_) }))
+                if !self.reserved.object_groups.contains(id)
+        )
+    }
+
+    // the conditions after going through a function trigger, `None` if it can never activate its target
+    fn add_condition(&self, obj: &GdObj, conditions: &Conditions) -> Option<Conditions> {
+        if !self.conditions {
+            return Some(conditions.clone());
+        }
+        let obj_id = match obj.params.get(&1) {
+            Some(ObjParam::Number(n)) => *n as u16,
+            _ => return Some(conditions.clone()),
+        };
+        if obj_id == obj_ids::INSTANT_COUNT {
+            let count = match obj.params.get(&COUNT) {
+                Some(ObjParam::Number(n)) => Some(*n),
+                None => Some(0.0),
+                _ => None,
+            };
+            let mode = match obj.params.get(&COMPARISON) {
+                Some(ObjParam::Number(n)) => *n as u8,
+                _ => 0,
+            };
+            let item = obj.params.get(&ITEM).map(param_identifier);
+            if let (Some(item), Some(range)) = (item, count.and_then(|n| item_range(mode, n))) {
+                return conditions.check_item(item, range);
+            }
+        } else if !matches!(
+            obj_id,
+            obj_ids::COUNT | obj_ids::COLLISION | obj_ids::ON_DEATH | obj_ids::INSTANT_COUNT
+        ) {
+            // spawn and touch triggers don't check anything
+            return Some(conditions.clone());
+        }
+        let mut conditions = conditions.clone();
+        conditions
+            .events
+            .insert(self.output_identifier(obj, &[obj_props::TARGET, obj_props::ACTIVATE_GROUP]));
+        Some(conditions)
+    }
+
+    // the properties of the trigger, without its groups and the ones in `skip`
+    fn output_identifier(&self, obj: &GdObj, skip: &[u16]) -> String {
+        let mut params: Vec<_> = obj
+            .params
+            .iter()
+            .filter(|(k, _)| **k != obj_props::GROUPS && !skip.contains(k))
+            .map(|(k, v)| {
+                let val = match v {
+                    // trigger groups might be renamed by the optimizer
+                    ObjParam::Group(g) => group_identifier(*g, self.reserved),
+                    ObjParam::GroupList(list) => list
+                        .iter()
+                        .map(|g| group_identifier(*g, self.reserved))
+                        .collect::<Vec<_>>()
+                        .join("."),
+                    _ => param_identifier(v),
+                };
+                (*k, val)
+            })
+            .collect();
+        params.sort();
+
+        params
+            .iter()
+            .map(|(k, v)| format!("{},{}", k, v))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+// the values of an item an instant count trigger activates its target for.
+// items are integers, so comparisons with other numbers are rounded
+fn item_range(mode: u8, count: f64) -> Option<Range> {
+    if count.fract() == 0.0 {
+        return Range::from_comparison(mode, count as i64);
+    }
+    Some(match mode {
+        // never true
+        0 => Range {
+            start: Some(1),
+            end: Some(0),
+        },
+        1 => Range {
+            start: Some(count.floor() as i64 + 1),
+            end: None,
+        },
+        2 => Range {
+            start: None,
+            end: Some(count.ceil() as i64 - 1),
+        },
+        _ => return None,
+    })
+}
+
+// the delays of all the spawn triggers added together
+fn total_delay(func_ids: &[FunctionId]) -> i64 {
+    func_ids
+        .iter()
+        .flat_map(|fn_id| &fn_id.obj_list)
+        .filter(|(obj, _)| get_role(obj) == TriggerRole::Spawn)
+        .map(|(obj, _)| spawn_delay(obj))
+        .sum()
+}
+
+// in milliseconds
+fn spawn_delay(obj: &GdObj) -> i64 {
+    match obj.params.get(&SPAWN_DELAY) {
+        Some(ObjParam::Number(n)) => (n * 1000.0).round() as i64,
+        Some(ObjParam::Epsilon) => 50,
+        _ => 0,
+    }
+}
+
+fn is_instant_count(obj: &GdObj) -> bool {
+    matches!(obj.params.get(&1), Some(ObjParam::Number(n)) if *n as u16 == obj_ids::INSTANT_COUNT)
+}
+
+fn is_pickup(obj: &GdObj) -> bool {
+    matches!(obj.params.get(&1), Some(ObjParam::Number(n)) if *n as u16 == obj_ids::PICKUP)
+}
+
+fn group_identifier(g: Group, reserved: &ReservedIds) -> String {
+    match g.id {
+        Id::Specific(id) => format!("{}", id),
+        Id::Arbitrary(id) if reserved.object_groups.contains(&g.id) => format!("?{}", id),
+        Id::Arbitrary(_) => "?".to_string(),
+    }
+}
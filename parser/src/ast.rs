@@ -3,6 +3,7 @@
 use ahash::AHashSet;
 
 use internment::LocalIntern;
+use serde::{Deserialize, Serialize};
 
 use crate::fmt::SpwnFmt;
 use shared::FileRange;
@@ -10,20 +11,20 @@ use shared::ImportType;
 use shared::StoredValue;
 
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum DictDef {
     Def((LocalIntern<String>, Expression)),
     Extract(Expression),
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum ArrayPrefix {
     Collect,
     Spread,
     // future-proofing
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct ArrayDef {
     pub value: Expression,
     pub operator: Option<ArrayPrefix>,
@@ -31,7 +32,7 @@ pub struct ArrayDef {
 
 //pub type Comment = (Option<String>, Option<String>);
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Statement {
     pub body: StatementBody,
     pub arrow: bool, /*context changing */
@@ -39,7 +40,7 @@ pub struct Statement {
     //pub comment: Comment,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum StatementBody {
     //Definition(Definition),
     Call(Call),
@@ -54,7 +55,12 @@ pub enum StatementBody {
     For(For),
     While(While),
     Error(Error),
+    Try(Try),
     Extract(Expression),
+    ImportNames(ImportNames),
+    Sync(Sync),
+    // a statement with a syntax error, with its source code
+    Invalid(String),
 
     Break,
     Continue,
@@ -63,13 +69,13 @@ pub enum StatementBody {
 
 // TODO: implement this in parser and compiler
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Definition {
     pub symbol: Variable,
     pub value: Option<Expression>,
     pub mutable: bool,
 }
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct ValueLiteral {
     pub body: ValueBody,
     //pub comment: Comment,
@@ -84,22 +90,26 @@ impl ValueLiteral {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum ValueBody {
     Id(Id),
     Number(f64),
+    Int(i64),
     CmpStmt(CompoundStatement),
     Dictionary(Vec<DictDef>),
     Symbol(LocalIntern<String>),
     Bool(bool),
     Expression(Expression),
     Str(StrInner),
+    FormatStr(Vec<FormatStrPart>),
     Import(ImportType, bool),
     Match(Expression, Vec<Case>),
     Array(Vec<ArrayDef>),
     ListComp(Comprehension),
     Obj(ObjectLiteral),
     Macro(Macro),
+    // only created by the compiler
+    #[serde(skip)]
     Resolved(StoredValue),
     TypeIndicator(String),
     MacroPattern(MacroPattern),
@@ -108,7 +118,7 @@ pub enum ValueBody {
     Null,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MacroPattern {
     pub args: Vec<Expression>,
     pub ret: Expression,
@@ -127,32 +137,39 @@ impl ValueBody {
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Copy, Hash)]
+#[derive(Clone, PartialEq, Debug, Copy, Hash, Serialize, Deserialize)]
 pub enum ObjectMode {
     Object,
     Trigger,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct ObjectLiteral {
     pub props: Vec<(Expression, Expression)>,
     pub mode: ObjectMode,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct StrInner {
     pub inner: String,
     pub flags: Option<StringFlags>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+// f"x = {x}": the text between the embedded expressions, and the expressions
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FormatStrPart {
+    Str(String),
+    Expr(Expression),
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum StringFlags {
     Base64,
     Raw,
     Unindent,
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Operator {
     Or,
     And,
@@ -173,6 +190,12 @@ pub enum Operator {
     Minus,
     Modulo,
 
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+
     Either,
     Both,
 
@@ -190,10 +213,11 @@ pub enum Operator {
     Swap,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum UnaryOperator {
     Not,
     Minus,
+    BitNot,
     Increment,
     Decrement,
 
@@ -206,7 +230,7 @@ pub enum UnaryOperator {
     InPattern,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum IdClass {
     Group,
     Color,
@@ -214,7 +238,7 @@ pub enum IdClass {
     Block,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Attribute {
     pub tags: Vec<(String, Vec<Argument>)>,
 }
@@ -298,7 +322,7 @@ pub trait CountSymbols {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Path {
     Member(LocalIntern<String>),
     Associated(LocalIntern<String>),
@@ -310,14 +334,14 @@ pub enum Path {
     Decrement,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Argument {
     pub symbol: Option<LocalIntern<String>>,
     pub value: Expression,
     pub pos: FileRange,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Slice {
     pub left: Option<Expression>,
     pub right: Option<Expression>,
@@ -351,7 +375,7 @@ pub struct Event {
     pub func: Variable,
 }*/
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Call {
     pub function: Variable,
 }
@@ -362,7 +386,7 @@ pub struct Native {
     pub args: Vec<Argument>,
 }*/
 //     name     def value     props     type ind.     location in file     is reference
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum ArgType {
     Ref,
     Mut,
@@ -376,7 +400,7 @@ pub type ArgDef = (
     FileRange,
     ArgType,
 );
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Macro {
     pub args: Vec<ArgDef>,
     pub body: CompoundStatement,
@@ -385,51 +409,81 @@ pub struct Macro {
     pub ret_type: Option<Expression>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct For {
     pub symbol: Expression,
     pub array: Expression,
     pub body: Vec<Statement>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+// `sync a` defines a new sync group,
+// `sync a[part] { ... }` runs the body as a part of sync group `a`
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Sync {
+    pub group: Variable,
+    pub part: Option<(Expression, Vec<Statement>)>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct While {
     pub condition: Expression,
     pub body: Vec<Statement>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum CaseType {
 
     Pattern(Expression),
     Default,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum CaseBody {
 
     Expr(Expression),
     Block(Vec<Statement>),
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Case {
     pub typ: CaseType,
     pub body: CaseBody,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Match {
     pub value: Expression,
     pub cases: Vec<Case>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Error {
     pub message: Expression,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+// import { a, b as c } from "module.spwn"
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ImportNames {
+    pub names: Vec<ImportName>,
+    pub module: ImportType,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ImportName {
+    pub name: LocalIntern<String>,
+    pub alias: Option<LocalIntern<String>>,
+    pub pos: FileRange,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Try {
+    pub try_body: Vec<Statement>,
+    // the symbol after `catch` that the error is stored in
+    pub catch_symbol: Option<Variable>,
+    pub catch_body: Vec<Statement>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Variable {
     pub operator: Option<UnaryOperator>,
     pub value: ValueLiteral,
@@ -452,13 +506,13 @@ impl Variable {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Expression {
     pub values: Vec<Variable>,
     pub operators: Vec<Operator>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Ternary {
     pub condition: Expression,
     pub if_expr: Expression,
@@ -466,7 +520,7 @@ pub struct Ternary {
     pub is_pattern: bool,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Comprehension {
     pub symbol: LocalIntern<String>,
     pub iterator: Expression,
@@ -493,25 +547,25 @@ impl Expression {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct CompoundStatement {
     pub statements: Vec<Statement>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Implementation {
     pub symbol: Variable,
     pub members: Vec<DictDef>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct If {
     pub condition: Expression,
     pub if_body: Vec<Statement>,
     pub else_body: Option<Vec<Statement>>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Id {
     pub number: u16,
     pub unspecified: bool,
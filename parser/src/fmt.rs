@@ -1,240 +1,1041 @@
 // tools for automatically formatting spwn files
+//
+// when the formatter has the source code, it keeps the comments and blank lines,
+// and the parts of the layout that were chosen by the author: how literals are
+// written, parentheses, and whether a list is on one line or one element per line.
+// without the source code (like in error messages), the syntax tree is printed as it is
+
+use logos::Logos;
 
 use crate::ast::*;
+use crate::parser::Token;
+
+const NUMBER_LITERALS: &[Token] = &[
+    Token::Number,
+    Token::BinaryLiteral,
+    Token::HexLiteral,
+    Token::OctalLiteral,
+];
 
 pub trait SpwnFmt {
-    fn fmt(&self, ind: Indent) -> String;
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String;
+
+    fn fmt(&self, ind: Indent) -> String {
+        self.fmt_with(&mut Formatter::default(), ind)
+    }
 }
 
 type Indent = u16;
 
-fn tabs(mut num: Indent) -> String {
+fn tabs(num: Indent) -> String {
+    " ".repeat(num as usize)
+}
+
+// formats a parsed file, keeping the comments from its source code
+pub fn format(statements: &[Statement], file_tag: &Attribute, source: &str) -> String {
+    let mut f = Formatter::new(source);
     let mut out = String::new();
-    while num > 4 {
-        out += "\t";
-        num -= 4;
+    let mut start = 0;
+    let mut has_prev = false;
+
+    if !file_tag.tags.is_empty() {
+        // the file attribute is the first thing in the file
+        let tag_start = f.tokens.first().map(|t| t.start).unwrap_or(0);
+        f.gap(0, tag_start, 0, &mut out, &mut has_prev, false);
+        out += &file_tag.fmt_with(&mut f, 0);
+        start = f
+            .tokens
+            .get(1)
+            .and_then(|t| t.pair)
+            .map(|close| f.tokens[close].end)
+            .unwrap_or(tag_start);
+        if let Some(comment) = f.trailing_comment(start) {
+            out += &format!(" {}", comment.0);
+            start = comment.1;
+        }
+        out += "\n";
+        has_prev = true;
     }
 
-    for _ in 0..num {
-        out += " ";
+    let end = f.source.as_ref().map(|s| s.len()).unwrap_or(0);
+    out += &f.statements(statements, start, end, 0, has_prev);
+    out
+}
+
+struct Comment {
+    text: String,
+    start: usize,
+    end: usize,
+    taken: bool,
+}
+
+struct SourceToken {
+    token: Token,
+    start: usize,
+    end: usize,
+    // the index of the matching bracket
+    pair: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct Formatter {
+    source: Option<String>,
+    // every token except statement separators
+    tokens: Vec<SourceToken>,
+    comments: Vec<Comment>,
+}
+
+// the comments in the space between two tokens
+fn find_comments(gap: &str, offset: usize, out: &mut Vec<Comment>) {
+    let mut i = 0;
+    while let Some(j) = gap[i..].find('/') {
+        let start = i + j;
+        let rest = &gap[start..];
+        let len = if rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else {
+            rest.get(2..)
+                .and_then(|r| r.find("*/"))
+                .map(|e| e + 4)
+                .unwrap_or(rest.len())
+        };
+        out.push(Comment {
+            text: rest[..len].trim_end().to_string(),
+            start: offset + start,
+            end: offset + start + len,
+            taken: false,
+        });
+        i = start + len;
     }
+}
 
-    out
+fn closing_bracket(open: Token) -> Option<Token> {
+    match open {
+        Token::OpenCurlyBracket => Some(Token::ClosingCurlyBracket),
+        Token::OpenSquareBracket => Some(Token::ClosingSquareBracket),
+        Token::OpenBracket => Some(Token::ClosingBracket),
+        _ => None,
+    }
 }
 
-pub fn _format(input: Vec<Statement>) -> String {
-    let mut out = String::new();
-    for s in input {
-        out += &s.fmt(0)
+fn bracket_token(bracket: char) -> Token {
+    match bracket {
+        '{' => Token::OpenCurlyBracket,
+        '[' => Token::OpenSquareBracket,
+        _ => Token::OpenBracket,
+    }
+}
+
+// a dictionary key, with quotes if it isn't a valid name
+fn dict_key(name: &str) -> String {
+    let mut lexer = Token::lexer(name);
+    let first = lexer.next();
+    let whole = lexer.span() == (0..name.len());
+    match (first, lexer.next()) {
+        (Some(Token::Symbol | Token::Type), None) if whole => name.to_string(),
+        _ => format!("{:?}", name),
     }
-    out
 }
 
-pub fn _format2(input: &ValueBody) -> String {
-    input.fmt(0)
+// where a list is in the source: its brackets, and the first token, last token
+// and comma of every element
+struct ListLayout {
+    open: usize,
+    close: usize,
+    elements: Vec<(usize, usize, Option<usize>)>,
 }
 
-fn element_list(elements: &[impl SpwnFmt], open: char, closing: char, ind: Indent) -> String {
-    if elements.is_empty() {
-        return format!("{}{}", open, closing);
+impl Formatter {
+    pub fn new(source: &str) -> Self {
+        // the parser does the same, so the positions line up
+        let source = source.replace("\r\n", "\n");
+        let mut tokens = Vec::new();
+        let mut comments = Vec::new();
+
+        let mut lexer = Token::lexer(&source);
+        let mut last_end = 0;
+        while let Some(token) = lexer.next() {
+            let span = lexer.span();
+            find_comments(&source[last_end..span.start], last_end, &mut comments);
+            last_end = span.end;
+            if token != Token::StatementSeparator {
+                tokens.push(SourceToken {
+                    token,
+                    start: span.start,
+                    end: span.end,
+                    pair: None,
+                });
+            }
+        }
+        find_comments(&source[last_end..], last_end, &mut comments);
+
+        let mut open = Vec::<usize>::new();
+        for i in 0..tokens.len() {
+            let token = tokens[i].token;
+            if closing_bracket(token).is_some() {
+                open.push(i);
+            } else if let Some(&o) = open.last() {
+                if closing_bracket(tokens[o].token) == Some(token) {
+                    open.pop();
+                    tokens[o].pair = Some(i);
+                    tokens[i].pair = Some(o);
+                }
+            }
+        }
+
+        Formatter {
+            source: Some(source),
+            tokens,
+            comments,
+        }
     }
 
-    let mut elem_text = Vec::<String>::new();
-    let mut sum = 0;
+    fn has_source(&self) -> bool {
+        self.source.is_some()
+    }
 
-    let last = elements.len() - 1;
+    fn text(&self, start: usize, end: usize) -> &str {
+        match &self.source {
+            Some(s) => s.get(start..end.max(start)).unwrap_or(""),
+            None => "",
+        }
+    }
 
-    for (_i, el) in elements.iter().enumerate() {
-        let text = el.fmt(0);
+    fn newlines(&self, start: usize, end: usize) -> usize {
+        self.text(start, end).matches('\n').count()
+    }
 
-        sum += text.lines().next().unwrap().len();
+    // the index of the first token that starts at or after an offset
+    fn first_token(&self, offset: usize) -> usize {
+        self.tokens.partition_point(|t| t.start < offset)
+    }
 
-        elem_text.push(text)
+    fn span(&self, index: usize) -> (usize, usize) {
+        (self.tokens[index].start, self.tokens[index].end)
     }
 
-    let vertical = if elements.len() == 1 {
-        sum > 150
-    } else {
-        elem_text.iter().enumerate().any(|(i, x)| {
-            if i != last {
-                x.len() > 50 || x.contains('\n')
+    fn token_is(&self, index: usize, token: Token) -> bool {
+        matches!(self.tokens.get(index), Some(t) if t.token == token)
+    }
+
+    // the index of a token that is one of the next few tokens after an offset
+    fn find_token(&self, offset: usize, token: Token, within: usize) -> Option<usize> {
+        let first = self.first_token(offset);
+        (first..(first + within).min(self.tokens.len())).find(|i| self.tokens[*i].token == token)
+    }
+
+    // the text of the token a literal was parsed from
+    fn literal(&self, var: &Variable, expected: &[Token]) -> Option<String> {
+        let index = self.first_token(var.pos.0) + var.operator.is_some() as usize;
+        let token = self.tokens.get(index)?;
+        if expected.contains(&token.token) {
+            Some(self.text(token.start, token.end).to_string())
+        } else {
+            None
+        }
+    }
+
+    // takes the comments that start between two offsets
+    fn take_comments(&mut self, start: usize, end: usize) -> Vec<(String, usize, usize)> {
+        self.comments
+            .iter_mut()
+            .filter(|c| !c.taken && start <= c.start && c.start < end)
+            .map(|c| {
+                c.taken = true;
+                (c.text.clone(), c.start, c.end)
+            })
+            .collect()
+    }
+
+    fn has_comments(&self, start: usize, end: usize) -> bool {
+        self.comments
+            .iter()
+            .any(|c| !c.taken && start <= c.start && c.start < end)
+    }
+
+    // a comment at the end of the line something ends on
+    fn trailing_comment(&mut self, end: usize) -> Option<(String, usize)> {
+        let next_token = self.tokens.get(self.first_token(end)).map(|t| t.start);
+        let source = self.source.as_ref()?;
+        let comment = self
+            .comments
+            .iter_mut()
+            .find(|c| !c.taken && c.start >= end)?;
+        if source[end..comment.start].contains('\n')
+            || matches!(next_token, Some(t) if t < comment.start)
+        {
+            return None;
+        }
+        comment.taken = true;
+        Some((comment.text.clone(), comment.end))
+    }
+
+    // prints the comments between two things, keeping a blank line
+    // wherever the source has one
+    fn gap(
+        &mut self,
+        start: usize,
+        end: usize,
+        ind: Indent,
+        out: &mut String,
+        has_prev: &mut bool,
+        at_end: bool,
+    ) {
+        if !self.has_source() {
+            return;
+        }
+        let mut prev = start;
+        for (text, c_start, c_end) in self.take_comments(start, end) {
+            if *has_prev && self.newlines(prev, c_start) >= 2 {
+                *out += "\n";
+            }
+            *out += &format!("{}{}\n", tabs(ind), text);
+            prev = c_end;
+            *has_prev = true;
+        }
+        if !at_end && *has_prev && self.newlines(prev, end) >= 2 {
+            *out += "\n";
+        }
+    }
+
+    // where a statement starts, including its attribute
+    fn statement_start(&self, statement: &Statement, min: usize) -> usize {
+        let first = self.first_token(statement.pos.0);
+        if first > 0 && self.token_is(first - 1, Token::ClosingSquareBracket) {
+            if let Some(open) = self.tokens[first - 1].pair {
+                if open > 0 && self.token_is(open - 1, Token::Hash) {
+                    let start = self.tokens[open - 1].start;
+                    if start >= min {
+                        return start;
+                    }
+                }
+            }
+        }
+        statement.pos.0
+    }
+
+    // a list of statements, one per line. statements with comments that
+    // can't be placed anywhere are kept as they are written
+    fn statements(
+        &mut self,
+        statements: &[Statement],
+        start: usize,
+        end: usize,
+        ind: Indent,
+        mut has_prev: bool,
+    ) -> String {
+        let mut out = String::new();
+        let mut cursor = start;
+        for statement in statements {
+            let stmt_start = self.statement_start(statement, cursor);
+            self.gap(cursor, stmt_start, ind, &mut out, &mut has_prev, false);
+
+            let mut text = statement.fmt_with(self, ind);
+            let stmt_end = statement.pos.1.max(stmt_start);
+            if self.has_source() {
+                if self.has_comments(stmt_start, stmt_end) {
+                    self.take_comments(stmt_start, stmt_end);
+                    text = self.text(stmt_start, stmt_end).to_string();
+                }
+                cursor = stmt_end;
+            }
+            out += &format!("{}{}", tabs(ind), text);
+            if let Some((comment, comment_end)) = self.trailing_comment(stmt_end) {
+                out += &format!(" {}", comment);
+                cursor = comment_end;
+            }
+            out += "\n";
+            has_prev = true;
+        }
+        self.gap(cursor, end, ind, &mut out, &mut has_prev, true);
+        out
+    }
+
+    // a block of statements in curly brackets, which starts with one of the
+    // next few tokens after an offset. also returns where the block ends
+    fn block(&mut self, statements: &[Statement], after: usize, ind: Indent) -> (String, usize) {
+        let brackets = self
+            .find_token(after, Token::OpenCurlyBracket, 3)
+            .and_then(|open| Some((open, self.tokens[open].pair?)));
+        match brackets {
+            Some((open, close)) => {
+                let (start, end) = (self.tokens[open].end, self.tokens[close].start);
+                let block_end = self.tokens[close].end;
+                if statements.is_empty() && !self.has_comments(start, end) {
+                    return ("{}".to_string(), block_end);
+                }
+                let inner = self.statements(statements, start, end, ind + 4, false);
+                (format!("{{\n{}{}}}", inner, tabs(ind)), block_end)
+            }
+            None => {
+                if statements.is_empty() {
+                    return ("{}".to_string(), after);
+                }
+                let inner = self.statements(statements, 0, 0, ind + 4, false);
+                (format!("{{\n{}{}}}", inner, tabs(ind)), after)
+            }
+        }
+    }
+
+    fn list_layout(&self, first_start: usize, bracket: char) -> Option<ListLayout> {
+        // the innermost bracket around the first element
+        let mut i = self.first_token(first_start);
+        let open = loop {
+            i = i.checked_sub(1)?;
+            let token = &self.tokens[i];
+            match token.pair {
+                Some(close) if close > i && self.tokens[close].start >= first_start => break i,
+                Some(close) if close > i => (),
+                Some(o) => i = o,
+                None => (),
+            }
+        };
+        if self.tokens[open].token != bracket_token(bracket) {
+            return None;
+        }
+        let close = self.tokens[open].pair?;
+
+        let mut elements = Vec::new();
+        let mut first = None;
+        let mut i = open + 1;
+        while i < close {
+            let token = &self.tokens[i];
+            if token.token == Token::Comma {
+                elements.push((first?, i - 1, Some(i)));
+                first = None;
             } else {
-                sum > 100
+                first.get_or_insert(i);
+                if let Some(pair) = token.pair {
+                    i = pair;
+                }
             }
+            i += 1;
+        }
+        if let Some(first) = first {
+            elements.push((first, close - 1, None));
+        }
+        Some(ListLayout {
+            open,
+            close,
+            elements,
         })
-    };
+    }
+
+    fn element_list(
+        &mut self,
+        elements: &[impl ListElement],
+        open: char,
+        closing: char,
+        ind: Indent,
+    ) -> String {
+        if elements.is_empty() {
+            return format!("{}{}", open, closing);
+        }
+        let layout = elements[0]
+            .start()
+            .and_then(|start| self.list_layout(start, open))
+            .filter(|layout| layout.elements.len() == elements.len());
+        match layout {
+            Some(layout) => self.source_list(elements, layout, open, closing, ind),
+            None => self.plain_list(elements, open, closing, ind),
+        }
+    }
+
+    // a list laid out like it is in the source: one element per line
+    // if there is a line break after the opening bracket or if it has comments
+    fn source_list(
+        &mut self,
+        elements: &[impl ListElement],
+        layout: ListLayout,
+        open: char,
+        closing: char,
+        ind: Indent,
+    ) -> String {
+        let first_start = self.span(layout.elements[0].0).0;
+        let mut vertical = self.newlines(self.span(layout.open).1, first_start) > 0;
+        let mut between_start = self.span(layout.open).1;
+        for (first, last, _) in &layout.elements {
+            vertical |= self.has_comments(between_start, self.span(*first).0);
+            between_start = self.span(*last).1;
+        }
+        vertical |= self.has_comments(between_start, self.span(layout.close).0);
+
+        if !vertical {
+            let texts: Vec<String> = elements.iter().map(|e| e.fmt_with(self, ind)).collect();
+            return format!("{}{}{}", open, texts.join(", "), closing);
+        }
 
-    if vertical {
         let mut out = format!("{}\n", open);
+        let mut cursor = self.span(layout.open).1;
+        let mut has_prev = false;
+        for (el, (first, last, comma)) in elements.iter().zip(&layout.elements) {
+            self.gap(
+                cursor,
+                self.span(*first).0,
+                ind + 4,
+                &mut out,
+                &mut has_prev,
+                false,
+            );
+            out += &format!("{}{},", tabs(ind + 4), el.fmt_with(self, ind + 4));
+            cursor = self.span(comma.unwrap_or(*last)).1;
+            if let Some((comment, comment_end)) = self.trailing_comment(cursor) {
+                out += &format!(" {}", comment);
+                cursor = comment_end;
+            }
+            out += "\n";
+            has_prev = true;
+        }
+        self.gap(
+            cursor,
+            self.span(layout.close).0,
+            ind + 4,
+            &mut out,
+            &mut has_prev,
+            true,
+        );
+        out + &format!("{}{}", tabs(ind), closing)
+    }
+
+    // a list without source code, which goes on multiple lines when it is long
+    fn plain_list(
+        &mut self,
+        elements: &[impl ListElement],
+        open: char,
+        closing: char,
+        ind: Indent,
+    ) -> String {
+        let mut elem_text: Vec<String> = elements.iter().map(|e| e.fmt_with(self, ind)).collect();
+        let sum: usize = elem_text
+            .iter()
+            .map(|t| t.lines().next().unwrap_or("").len())
+            .sum();
+        let last = elements.len() - 1;
+
+        let vertical = if elements.len() == 1 {
+            sum > 150
+        } else {
+            elem_text.iter().enumerate().any(|(i, x)| {
+                if i != last {
+                    x.len() > 50 || x.contains('\n')
+                } else {
+                    sum > 100
+                }
+            })
+        };
 
-        for el in &elem_text {
-            for line in el.lines() {
-                out += &format!("{}{}\n", tabs(ind + 4), line);
+        if vertical {
+            let mut out = format!("{}\n", open);
+            for el in &elem_text {
+                for line in el.lines() {
+                    out += &format!("{}{}\n", tabs(4), line);
+                }
+                out.pop();
+                out += ",\n";
+            }
+            out + &format!("{}{}", tabs(ind), closing)
+        } else {
+            let last_elem = elem_text.pop().unwrap();
+            let mut out = open.to_string();
+            for el in elem_text {
+                out += &format!("{}, ", el);
             }
-            out.pop();
-            out += ",\n";
+            out += &last_elem;
+            out.push(closing);
+            out
         }
-        /*if elements.len() == 1 {
-            out.pop();
-            out.pop();
-            out += "\n";
-        }*/
+    }
 
-        out + &format!("{}{}", tabs(ind), closing)
-    } else {
-        let mut out = format!("{}", open);
-        let last_elem = elem_text.pop().unwrap();
-        let iter = elem_text.iter();
+    // what goes between an attribute and the thing after it
+    fn attr_separator(&self, attr: &str, after: usize, ind: Indent) -> String {
+        let newline = if self.has_source() {
+            let first = self.first_token(after);
+            first > 0 && self.newlines(self.tokens[first - 1].end, after) > 0
+        } else {
+            attr.len() > 60
+        };
+        if newline {
+            format!("\n{}", tabs(ind))
+        } else {
+            " ".to_string()
+        }
+    }
+
+    fn variable(&mut self, var: &Variable, ind: Indent) -> String {
+        let mut out = String::new();
+
+        if !var.tag.tags.is_empty() {
+            let tag = var.tag.fmt_with(self, ind);
+            out += &self.attr_separator(&tag, var.pos.0, ind);
+            out.insert_str(0, &tag);
+        }
+
+        if let Some(op) = &var.operator {
+            out += &op.fmt_with(self, ind);
+            if let UnaryOperator::Not
+            | UnaryOperator::Minus
+            | UnaryOperator::BitNot
+            | UnaryOperator::Increment
+            | UnaryOperator::Decrement = op
+            {
+            } else {
+                out += " ";
+            }
+        }
+
+        out += &self.value(&var.value.body, Some(var), ind);
+
+        for p in &var.path {
+            out += &p.fmt_with(self, ind);
+        }
+        out
+    }
+
+    // a value, which is part of a variable when there is source code
+    fn value(&mut self, body: &ValueBody, var: Option<&Variable>, ind: Indent) -> String {
+        let var = var.filter(|_| self.has_source());
+        use ValueBody::*;
+        match body {
+            Id(x) => var
+                .and_then(|v| self.literal(v, &[Token::Id]))
+                .unwrap_or_else(|| x.fmt_with(self, ind)),
+            Number(x) => var
+                .and_then(|v| self.literal(v, NUMBER_LITERALS))
+                .unwrap_or_else(|| format!("{}", x)),
+            Int(x) => var
+                .and_then(|v| self.literal(v, NUMBER_LITERALS))
+                .unwrap_or_else(|| format!("{}", x)),
+            Str(x) => var
+                .and_then(|v| self.literal(v, &[Token::StringLiteral]))
+                .unwrap_or_else(|| format!("{:?}", x.inner)),
+            FormatStr(parts) => var
+                .and_then(|v| self.literal(v, &[Token::StringLiteral]))
+                .unwrap_or_else(|| {
+                    let mut out = String::from("f\"");
+                    for part in parts {
+                        match part {
+                            FormatStrPart::Str(text) => {
+                                let escaped = format!("{:?}", text);
+                                out += &escaped[1..escaped.len() - 1]
+                                    .replace('{', "{{")
+                                    .replace('}', "}}");
+                            }
+                            FormatStrPart::Expr(e) => {
+                                out += &format!("{{{}}}", e.fmt_with(self, ind))
+                            }
+                        }
+                    }
+                    out + "\""
+                }),
+            CmpStmt(x) => {
+                let after = var.map(|v| v.pos.0).unwrap_or(0);
+                format!("!{}", self.block(&x.statements, after, ind).0)
+            }
+            Dictionary(x) => self.element_list(x, '{', '}', ind),
+            Array(x) => self.element_list(x, '[', ']', ind),
+            Symbol(x) => x.to_string(),
+            Bool(x) => format!("{}", x),
+            Expression(x) => match var {
+                Some(v) => self.parenthesized(x, v, ind),
+                None => format!("({})", x.fmt_with(self, ind)),
+            },
+            Import(x, forced) => {
+                format!("import{} {}", if *forced { "!" } else { "" }, import_path(x))
+            }
+            Obj(x) => {
+                (match x.mode {
+                    ObjectMode::Object => "obj ".to_string(),
+                    ObjectMode::Trigger => "trigger ".to_string(),
+                }) + &self.element_list(&x.props, '{', '}', ind)
+            }
+            Macro(x) => self.macro_def(x, var, ind),
+            Resolved(_) => "<val>".to_string(),
+            TypeIndicator(x) => format!("@{}", x),
+            Null => "null".to_string(),
+            SelfVal => "self".to_string(),
+            Ternary(t) => format!(
+                "{} if {}{} else {}",
+                t.if_expr.fmt_with(self, ind),
+                if t.is_pattern { "is " } else { "" },
+                t.condition.fmt_with(self, ind),
+                t.else_expr.fmt_with(self, ind)
+            ),
+            ListComp(c) => {
+                let mut out = format!(
+                    "[{} for {} in {}",
+                    c.body.fmt_with(self, ind),
+                    c.symbol,
+                    c.iterator.fmt_with(self, ind)
+                );
+                if let Some(cond) = &c.condition {
+                    out += &format!(", if {}", cond.fmt_with(self, ind));
+                }
+                out + "]"
+            }
+            Match(value, cases) => self.match_expr(value, cases, ind),
+            MacroPattern(p) => {
+                let short = p.args.len() == 1
+                    && var.is_some_and(|v| {
+                        !self.token_is(self.first_token(v.pos.0), Token::OpenBracket)
+                    });
+                let args: Vec<String> = p.args.iter().map(|a| a.fmt_with(self, ind)).collect();
+                let ret = p.ret.fmt_with(self, ind);
+                if short {
+                    format!("{} -> {}", args[0], ret)
+                } else {
+                    format!("({}) -> {}", args.join(", "), ret)
+                }
+            }
+        }
+    }
+
+    // an expression in a variable, which is either in parentheses or
+    // was split from a longer expression by operator precedence
+    fn parenthesized(&mut self, expr: &Expression, var: &Variable, ind: Indent) -> String {
+        let first = self.first_token(var.pos.0) + var.operator.is_some() as usize;
+        if self.token_is(first, Token::OpenSquareBracket)
+            && self.token_is(first + 1, Token::OpenSquareBracket)
+        {
+            if let Some(text) = self.decorator(expr, ind) {
+                return text;
+            }
+        }
+        let in_brackets = self.token_is(first, Token::OpenBracket)
+            && (!var.path.is_empty()
+                || var.operator.is_some()
+                || matches!(self.tokens[first].pair, Some(close) if self.tokens[close].end == var.pos.1));
+        if in_brackets {
+            format!("({})", expr.fmt_with(self, ind))
+        } else {
+            expr.fmt_with(self, ind)
+        }
+    }
+
+    // `[[decorator]] (args) { ... }`, which is parsed as `decorator(..., (args) { ... })`
+    fn decorator(&mut self, expr: &Expression, ind: Indent) -> Option<String> {
+        let mut decorator = expr.values.first()?.clone();
+        let mut args = match decorator.path.pop() {
+            Some(Path::Call(args)) => args,
+            _ => return None,
+        };
+        let function = args.pop()?.value.values.first()?.clone();
+        if !args.is_empty() {
+            decorator.path.push(Path::Call(args));
+        }
+        let untagged = Variable {
+            tag: Attribute::new(),
+            ..function
+        };
+        Some(format!(
+            "[[{}]] {}",
+            self.variable(&decorator, ind),
+            self.variable(&untagged, ind)
+        ))
+    }
+
+    fn macro_def(&mut self, m: &Macro, var: Option<&Variable>, ind: Indent) -> String {
+        let mut out = String::new();
+        if var.is_none() && !m.properties.tags.is_empty() {
+            // a macro in a variable has the same properties as the variable's tag
+            out += &m.properties.fmt_with(self, ind);
+            out += " ";
+        }
+
+        let returned = match m.body.statements.as_slice() {
+            [Statement {
+                body: StatementBody::Return(Some(e)),
+                arrow: false,
+                ..
+            }] => Some(e),
+            _ => None,
+        };
 
-        for el in iter {
-            out += &format!("{}, ", el);
+        // `a => ...`
+        if let (Some(v), Some(e)) = (var, returned) {
+            let first = self.first_token(v.pos.0);
+            if m.args.len() <= 1
+                && m.ret_type.is_none()
+                && self.token_is(first, Token::Symbol)
+                && self.token_is(first + 1, Token::ThickArrow)
+            {
+                let arg = m.args.first().map(|a| a.0.to_string());
+                return format!(
+                    "{} => {}",
+                    arg.unwrap_or_else(|| "_".to_string()),
+                    e.fmt_with(self, ind)
+                );
+            }
+        }
+
+        out += &self.element_list(&m.args, '(', ')', ind);
+        let mut after = m.arg_pos.1;
+        if let Some(ret) = &m.ret_type {
+            out += &format!(" -> {}", ret.fmt_with(self, ind));
+            after = expr_end(ret);
+        }
+
+        // `(a) => ...`
+        if let Some(e) = returned {
+            if self.has_source() && self.token_is(self.first_token(after), Token::ThickArrow) {
+                return out + &format!(" => {}", e.fmt_with(self, ind));
+            }
         }
 
-        let mut last_elem_lines = last_elem.lines();
-        out += last_elem_lines.next().unwrap();
+        out + " " + &self.block(&m.body.statements, after, ind).0
+    }
+
+    fn match_expr(&mut self, value: &Expression, cases: &[Case], ind: Indent) -> String {
+        let mut out = format!("match {} {{\n", value.fmt_with(self, ind));
+
+        let brackets = self
+            .find_token(expr_end(value), Token::OpenCurlyBracket, 1)
+            .and_then(|open| Some((open, self.tokens[open].pair?)));
+        let (mut cursor, end) = match brackets {
+            Some((open, close)) => (self.tokens[open].end, self.tokens[close].start),
+            None => (0, 0),
+        };
+        let source = self.has_source() && brackets.is_some();
+
+        let mut has_prev = false;
+        for case in cases {
+            let start = match &case.typ {
+                CaseType::Pattern(p) => p.get_pos().0,
+                CaseType::Default => self
+                    .find_token(cursor, Token::Else, 1)
+                    .map(|i| self.tokens[i].start)
+                    .unwrap_or(cursor),
+            };
+            if source {
+                self.gap(cursor, start, ind + 4, &mut out, &mut has_prev, false);
+            }
 
-        for line in last_elem_lines {
-            out += &format!("\n{}{}", tabs(ind), line);
+            let (pattern, pattern_end) = match &case.typ {
+                CaseType::Pattern(p) => (p.fmt_with(self, ind + 4), expr_end(p)),
+                CaseType::Default => ("else".to_string(), start),
+            };
+            let (body, body_end) = match &case.body {
+                CaseBody::Expr(e) => (e.fmt_with(self, ind + 4), expr_end(e)),
+                CaseBody::Block(b) => self.block(b, pattern_end, ind + 4),
+            };
+            out += &format!("{}{}: {},", tabs(ind + 4), pattern, body);
+
+            if source {
+                cursor = match self.find_token(body_end, Token::Comma, 1) {
+                    Some(comma) => self.tokens[comma].end,
+                    None => body_end,
+                };
+                if let Some((comment, comment_end)) = self.trailing_comment(cursor) {
+                    out += &format!(" {}", comment);
+                    cursor = comment_end;
+                }
+            }
+            out += "\n";
+            has_prev = true;
         }
+        if source {
+            self.gap(cursor, end, ind + 4, &mut out, &mut has_prev, true);
+        }
+        out + &tabs(ind) + "}"
+    }
 
-        out.push(closing);
+    fn try_statement(&mut self, statement: &Try, start: usize, ind: Indent) -> String {
+        let (try_body, try_end) = self.block(&statement.try_body, start, ind);
+        let (symbol, catch_end) = match &statement.catch_symbol {
+            Some(symbol) => (format!("{} ", symbol.fmt_with(self, ind)), symbol.pos.1),
+            None => (
+                String::new(),
+                match self.find_token(try_end, Token::Catch, 1) {
+                    Some(i) => self.tokens[i].end,
+                    None => try_end,
+                },
+            ),
+        };
+        let (catch_body, _) = self.block(&statement.catch_body, catch_end, ind);
+        format!("try {} catch {}{}", try_body, symbol, catch_body)
+    }
+
+    fn if_statement(&mut self, statement: &If, ind: Indent) -> String {
+        let (body, body_end) = self.block(&statement.if_body, expr_end(&statement.condition), ind);
+        let mut out = format!("if {} {}", statement.condition.fmt_with(self, ind), body);
+
+        if let Some(else_body) = &statement.else_body {
+            let else_token = self.find_token(body_end, Token::Else, 1);
+            let else_if = match else_body.as_slice() {
+                [Statement {
+                    body: StatementBody::If(inner),
+                    arrow: false,
+                    ..
+                }] => {
+                    // `else { if ... }` is kept as it is
+                    let written_as_else_if = match else_token {
+                        Some(i) => self.token_is(i + 1, Token::If),
+                        None => !self.has_source(),
+                    };
+                    written_as_else_if.then_some(inner)
+                }
+                _ => None,
+            };
+            match else_if {
+                Some(inner) => out += &format!(" else {}", self.if_statement(inner, ind)),
+                None => {
+                    let after = else_token.map(|i| self.tokens[i].end).unwrap_or(body_end);
+                    out += &format!(" else {}", self.block(else_body, after, ind).0);
+                }
+            }
+        }
         out
     }
 }
 
+fn import_path(module: &shared::ImportType) -> String {
+    match module {
+        shared::ImportType::Script(p) => format!("{:?}", p.to_string_lossy()),
+        shared::ImportType::Lib(name) => name.clone(),
+    }
+}
+
+// where an expression ends in the source
+fn expr_end(expr: &Expression) -> usize {
+    let last = expr.values.last().unwrap();
+    match &last.value.body {
+        // the position of a ternary ends at the start of its last token
+        ValueBody::Ternary(t) if last.path.is_empty() => expr_end(&t.else_expr),
+        _ => last.pos.1,
+    }
+}
+
+// an element of a comma separated list
+trait ListElement: SpwnFmt {
+    // where the element starts in the source
+    fn start(&self) -> Option<usize>;
+}
+
+impl ListElement for DictDef {
+    fn start(&self) -> Option<usize> {
+        match self {
+            DictDef::Def((_, expr)) | DictDef::Extract(expr) => Some(expr.get_pos().0),
+        }
+    }
+}
+
+impl ListElement for ArrayDef {
+    fn start(&self) -> Option<usize> {
+        Some(self.value.get_pos().0)
+    }
+}
+
+impl ListElement for Argument {
+    fn start(&self) -> Option<usize> {
+        Some(self.pos.0)
+    }
+}
+
+impl ListElement for ArgDef {
+    fn start(&self) -> Option<usize> {
+        Some(self.4 .0)
+    }
+}
+
+impl ListElement for (Expression, Expression) {
+    fn start(&self) -> Option<usize> {
+        Some(self.0.get_pos().0)
+    }
+}
+
+impl ListElement for (String, Vec<Argument>) {
+    fn start(&self) -> Option<usize> {
+        None
+    }
+}
+
 impl SpwnFmt for DictDef {
-    fn fmt(&self, ind: Indent) -> String {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
         match self {
-            DictDef::Def((name, expr)) => format!("{}{}: {}", tabs(ind), name, expr.fmt(ind)),
-            DictDef::Extract(expr) => format!("{}..{}", tabs(ind), expr.fmt(ind)),
-        }
-    }
-}
-
-// fn trim_start_tabs(string: &str) -> (&str, Indent) {
-//     //https://doc.rust-lang.org/src/core/str/mod.rs.html#4082-4090
-//     let mut ind = 0;
-//     for (i, c) in string.chars().enumerate() {
-//         match c {
-//             '\t' => ind += 4,
-//             ' ' => ind += 1,
-//             _ => return (unsafe { string.get_unchecked(i..string.len()) }, ind),
-//         }
-//     }
-//     ("", ind)
-// }
-
-// fn indent_comment(comment: &str, ind: Indent) -> String {
-//     let mut in_comment = false;
-//     let mut current_off = 0;
-//     let mut out = String::new();
-//     for line in comment.lines() {
-//         let (trimmed, ind_offset) = trim_start_tabs(line);
-//         if !in_comment {
-//             if trimmed.starts_with("//") {
-//                 out += &format!("{}{}\r\n", tabs(ind), trimmed);
-//             } else if trimmed.starts_with("/*") {
-//                 in_comment = true;
-//                 current_off = ind_offset;
-//                 out += &format!("{}{}\r\n", tabs(ind), trimmed);
-//             }
-//         } else {
-//             out += &format!("{}{}\r\n", tabs(ind_offset - current_off), trimmed);
-//         }
-
-//         if line.trim_end().ends_with("*/") {
-//             in_comment = false
-//         }
-//     }
-//     out
-// }
-
-/*#[cfg(test)]
-mod tests {
-    use super::*;
-    #[test]
-    fn test() {
-        println!(
-            "{}",
-            indent_comment(
-                &String::from(
-                    "
-//hello
-                /*
-                a = {
-                    b = 2
-                    c = 3
-                    a = {
-                        b = 2
-                        c = 3
-                        a = {
-                            b = 2
-                            c = 3
-                        }
-                    }
+            DictDef::Def((name, expr)) => {
+                // `{a}` is short for `{a: a}`
+                let value_start = f.first_token(expr.get_pos().0);
+                let shorthand = f.has_source()
+                    && !(value_start > 0 && f.token_is(value_start - 1, Token::Colon))
+                    && matches!(
+                        expr.values.as_slice(),
+                        [Variable {
+                            value: ValueLiteral { body: ValueBody::Symbol(s) },
+                            operator: None,
+                            path,
+                            tag,
+                            ..
+                        }] if s == name && path.is_empty() && tag.tags.is_empty()
+                    );
+                if shorthand {
+                    name.to_string()
+                } else {
+                    format!("{}: {}", dict_key(name), expr.fmt_with(f, ind))
                 }
-                */
-
-//bruh
-//bruh
-//bruh
-        "
-                ),
-                0
-            )
-        )
+            }
+            DictDef::Extract(expr) => format!("..{}", expr.fmt_with(f, ind)),
+        }
     }
-}*/
+}
 
 impl SpwnFmt for Statement {
-    fn fmt(&self, ind: Indent) -> String {
-        let mut out = String::new();
-        // if let Some(comment) = &self.comment.0 {
-        //     //out += "[stmt pre]";
-
-        //     out += &indent_comment(comment, ind);
-        //     //
-        //     if !comment.ends_with('\n') {
-        //         out += "\n";
-        //     }
-        //     out += &tabs(ind);
-        // }
-        out += &if self.arrow {
-            format!("-> {}\n", self.body.fmt(ind))
-        } else {
-            format!("{}\n", self.body.fmt(ind))
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        let body = match &self.body {
+            StatementBody::TypeDef { name, attr } if !attr.tags.is_empty() => {
+                let tag = attr.fmt_with(f, ind);
+                let separator = f.attr_separator(&tag, self.pos.0, ind);
+                format!("{}{}type @{}", tag, separator, name)
+            }
+            StatementBody::Try(x) => f.try_statement(x, self.pos.0, ind),
+            StatementBody::Definition(def) if def.mutable && !def.symbol.tag.tags.is_empty() => {
+                let tag = def.symbol.tag.fmt_with(f, ind);
+                let separator = f.attr_separator(&tag, self.pos.0, ind);
+                let untagged = Definition {
+                    symbol: Variable {
+                        tag: Attribute::new(),
+                        ..def.symbol.clone()
+                    },
+                    ..def.clone()
+                };
+                format!("{}{}{}", tag, separator, untagged.fmt_with(f, ind))
+            }
+            body => body.fmt_with(f, ind),
         };
-        // if let Some(comment) = &self.comment.1 {
-        //     if !comment.starts_with('\n') {
-        //         out += "\n";
-        //     }
-
-        //     //out += &tabs(ind);
-        //     //out += "[stmt post]";
-        //     out += &indent_comment(comment, ind);
-        // }
-        out
+        if self.arrow {
+            format!("-> {}", body)
+        } else {
+            body
+        }
     }
 }
 
 impl SpwnFmt for StatementBody {
-    fn fmt(&self, ind: Indent) -> String {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
         match self {
-            //StatementBody::Definition(def) => format!("{}", def.fmt(ind)),
-            StatementBody::Call(call) => call.fmt(ind),
-            StatementBody::Expr(x) => x.fmt(ind),
-            StatementBody::TypeDef { name, .. } => format!("type {}", name),
+            StatementBody::Call(call) => call.fmt_with(f, ind),
+            StatementBody::Expr(x) => x.fmt_with(f, ind),
+            StatementBody::TypeDef { name, attr } => {
+                if attr.tags.is_empty() {
+                    format!("type @{}", name)
+                } else {
+                    format!("{} type @{}", attr.fmt_with(f, ind), name)
+                }
+            }
             StatementBody::Return(x) => match x {
-                Some(expr) => format!("return {}", expr.fmt(ind)),
+                Some(expr) => format!("return {}", expr.fmt_with(f, ind)),
                 None => "return".to_string(),
             },
-            StatementBody::Definition(x) => x.fmt(ind),
-            StatementBody::Impl(x) => x.fmt(ind),
-            StatementBody::If(x) => x.fmt(ind),
-            StatementBody::For(x) => x.fmt(ind),
-            StatementBody::While(_) => "While loop lol".to_string(),
-            StatementBody::Error(x) => x.fmt(ind),
-            StatementBody::Extract(x) => format!("extract {}", x.fmt(ind)),
+            StatementBody::Definition(x) => x.fmt_with(f, ind),
+            StatementBody::Impl(x) => x.fmt_with(f, ind),
+            StatementBody::If(x) => x.fmt_with(f, ind),
+            StatementBody::For(x) => x.fmt_with(f, ind),
+            StatementBody::While(x) => x.fmt_with(f, ind),
+            StatementBody::Error(x) => x.fmt_with(f, ind),
+            // without a position the blocks are found from the AST alone
+            StatementBody::Try(x) => f.try_statement(x, usize::MAX, ind),
+            StatementBody::Extract(x) => format!("extract {}", x.fmt_with(f, ind)),
+            StatementBody::ImportNames(x) => x.fmt_with(f, ind),
+            StatementBody::Sync(x) => x.fmt_with(f, ind),
+            StatementBody::Invalid(text) => text.clone(),
             StatementBody::Break => String::from("break"),
             StatementBody::Continue => String::from("continue"),
         }
@@ -243,83 +1044,35 @@ impl SpwnFmt for StatementBody {
 
 //for object def
 impl SpwnFmt for (Expression, Expression) {
-    fn fmt(&self, ind: Indent) -> String {
-        format!("{}: {}", self.0.fmt(ind), self.1.fmt(ind))
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        format!("{}: {}", self.0.fmt_with(f, ind), self.1.fmt_with(f, ind))
     }
 }
 
 impl SpwnFmt for ArrayDef {
-    fn fmt(&self, ind: Indent) -> String {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
         match &self.operator {
-            Some(ArrayPrefix::Collect) => format!("*{}", self.value.fmt(ind)),
-            Some(ArrayPrefix::Spread) => format!("..{}", self.value.fmt(ind)),
-            None => self.value.fmt(ind),
+            Some(ArrayPrefix::Collect) => format!("*{}", self.value.fmt_with(f, ind)),
+            Some(ArrayPrefix::Spread) => format!("..{}", self.value.fmt_with(f, ind)),
+            None => self.value.fmt_with(f, ind),
         }
     }
 }
 
 impl SpwnFmt for ValueBody {
-    fn fmt(&self, ind: Indent) -> String {
-        use ValueBody::*;
-        match self {
-            Id(x) => x.fmt(ind),
-            Number(x) => format!("{}", x),
-            CmpStmt(x) => format!("!{{\n{}\n{}}}", x.fmt(ind + 4), tabs(ind)),
-            Dictionary(x) => element_list(x, '{', '}', ind),
-            Array(x) => element_list(x, '[', ']', ind),
-            Symbol(x) => x.to_string(),
-            Bool(x) => format!("{}", x),
-            Expression(x) => format!("({})", x.fmt(ind)),
-            Str(x) => format!("\"{}\"", x.inner),
-            Import(x, f) => format!("import{} {:?}", if *f { "!" } else { "" }, x),
-            Obj(x) => {
-                (match x.mode {
-                    ObjectMode::Object => "obj".to_string(),
-                    ObjectMode::Trigger => "trigger".to_string(),
-                }) + &element_list(&x.props, '{', '}', ind)
-            }
-            Macro(x) => x.fmt(ind),
-            Resolved(_) => "<val>".to_string(),
-            TypeIndicator(x) => format!("@{}", x),
-            Null => "null".to_string(),
-            SelfVal => "self".to_string(),
-            Ternary(t) => {
-                if !t.is_pattern {
-                    format!(
-                        "{} if is {} else {}",
-                        t.if_expr.fmt(ind),
-                        t.condition.fmt(ind),
-                        t.else_expr.fmt(ind)
-                    )
-                } else {
-                    format!(
-                        "{} if {} else {}",
-                        t.if_expr.fmt(ind),
-                        t.condition.fmt(ind),
-                        t.else_expr.fmt(ind)
-                    )
-                }
-            }
-            ListComp(c) => format!(
-                "{} for {} in {}",
-                c.body.fmt(ind),
-                c.symbol,
-                c.iterator.fmt(ind)
-            ),
-            Match(_, _) => todo!(),
-            MacroPattern(_) => todo!(),
-        }
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        f.value(self, None, ind)
     }
 }
 
 impl SpwnFmt for ValueLiteral {
-    fn fmt(&self, ind: Indent) -> String {
-        self.body.fmt(ind)
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        self.body.fmt_with(f, ind)
     }
 }
 
 impl SpwnFmt for IdClass {
-    fn fmt(&self, _ind: Indent) -> String {
+    fn fmt_with(&self, _f: &mut Formatter, _ind: Indent) -> String {
         match self {
             IdClass::Group => "g",
             IdClass::Color => "c",
@@ -330,15 +1083,36 @@ impl SpwnFmt for IdClass {
     }
 }
 
+impl SpwnFmt for Slice {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        let mut out = String::new();
+        if let Some(left) = &self.left {
+            out += &left.fmt_with(f, ind);
+        }
+        out += ":";
+        // the step is written before the end
+        if let Some(step) = &self.step {
+            out += &format!("{}:", step.fmt_with(f, ind));
+        }
+        if let Some(right) = &self.right {
+            out += &right.fmt_with(f, ind);
+        }
+        out
+    }
+}
+
 impl SpwnFmt for Path {
-    fn fmt(&self, ind: Indent) -> String {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
         match self {
             Path::Member(def) => format!(".{}", def),
             Path::Associated(def) => format!("::{}", def),
-            Path::NSlice(_def) => "[its a slice ok]".to_string(),
-            Path::Constructor(dict) => format!("::{}", element_list(dict, '{', '}', ind)),
-            Path::Index(call) => format!("[{}]", call.fmt(ind)),
-            Path::Call(x) => element_list(x, '(', ')', ind),
+            Path::NSlice(slices) => {
+                let slices: Vec<String> = slices.iter().map(|s| s.fmt_with(f, ind)).collect();
+                format!("[{}]", slices.join(", "))
+            }
+            Path::Constructor(dict) => format!("::{}", f.element_list(dict, '{', '}', ind)),
+            Path::Index(call) => format!("[{}]", call.fmt_with(f, ind)),
+            Path::Call(x) => f.element_list(x, '(', ')', ind),
             Path::Increment => "++".to_string(),
             Path::Decrement => "--".to_string(),
         }
@@ -346,103 +1120,97 @@ impl SpwnFmt for Path {
 }
 
 impl SpwnFmt for Argument {
-    fn fmt(&self, ind: Indent) -> String {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
         if let Some(symbol) = &self.symbol {
-            format!("{} = {}", symbol, self.value.fmt(ind))
+            format!("{} = {}", symbol, self.value.fmt_with(f, ind))
         } else {
-            self.value.fmt(ind)
+            self.value.fmt_with(f, ind)
         }
     }
 }
 
 impl SpwnFmt for Call {
-    fn fmt(&self, ind: Indent) -> String {
-        format!("{}!", self.function.fmt(ind))
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        format!("{}!", self.function.fmt_with(f, ind))
     }
 }
 
 impl SpwnFmt for For {
-    fn fmt(&self, ind: Indent) -> String {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        let (body, _) = f.block(&self.body, expr_end(&self.array), ind);
         format!(
-            "for {} in {} {{\n{}\n{}}}",
-            self.symbol.fmt(ind),
-            self.array.fmt(ind),
-            CompoundStatement {
-                statements: self.body.clone()
-            }
-            .fmt(ind + 4),
-            tabs(ind)
+            "for {} in {} {}",
+            self.symbol.fmt_with(f, ind),
+            self.array.fmt_with(f, ind),
+            body
         )
     }
 }
 
-impl SpwnFmt for Variable {
-    fn fmt(&self, ind: Indent) -> String {
-        let mut out = String::new();
-
-        // if let Some(comment) = &self.comment.0 {
-        //     //out += "[var pre]";
-        //     out += &indent_comment(comment, ind);
-
-        //     if comment.ends_with('\n') {
-        //         out += &tabs(ind);
-        //     }
-        // }
-
-        if let Some(op) = &self.operator {
-            out += &op.fmt(ind);
-        }
-
-        out += &self.value.fmt(ind);
-
-        for p in &self.path {
-            out += &p.fmt(ind).to_string();
-        }
-
-        // if let Some(comment) = &self.comment.1 {
-        //     //out += "[var post]";
-
-        //     out += &indent_comment(comment, ind);
+impl SpwnFmt for ImportNames {
+    fn fmt_with(&self, _f: &mut Formatter, _ind: Indent) -> String {
+        let names: Vec<String> = self
+            .names
+            .iter()
+            .map(|n| match n.alias {
+                Some(alias) => format!("{} as {}", n.name, alias),
+                None => n.name.to_string(),
+            })
+            .collect();
+        format!(
+            "import {{ {} }} from {}",
+            names.join(", "),
+            import_path(&self.module)
+        )
+    }
+}
 
-        //     /*if comment.ends_with("\n") {
-        //         out += &tabs(ind);
-        //     }*/
-        // }
+impl SpwnFmt for While {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        let (body, _) = f.block(&self.body, expr_end(&self.condition), ind);
+        format!("while {} {}", self.condition.fmt_with(f, ind), body)
+    }
+}
 
-        out
+impl SpwnFmt for Variable {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        f.variable(self, ind)
     }
 }
 
 impl SpwnFmt for Expression {
-    fn fmt(&self, ind: Indent) -> String {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
         let mut out = String::new();
         for (i, op) in self.operators.iter().enumerate() {
-            if let Operator::Range|Operator::InclRange = op {
-                out += &format!("{}{}", self.values[i].fmt(ind), (*op).fmt(ind));
+            if let Operator::Range | Operator::InclRange = op {
+                out += &format!("{}{}", self.values[i].fmt_with(f, ind), op.fmt_with(f, ind));
             } else {
-                out += &format!("{} {} ", self.values[i].fmt(ind), (*op).fmt(ind));
+                out += &format!(
+                    "{} {} ",
+                    self.values[i].fmt_with(f, ind),
+                    op.fmt_with(f, ind)
+                );
             }
         }
 
-        out += &self.values.last().unwrap().fmt(ind);
+        out += &self.values.last().unwrap().fmt_with(f, ind);
 
         out
     }
 }
 
 impl SpwnFmt for Id {
-    fn fmt(&self, ind: Indent) -> String {
-        format!(
-            "{}{}{}",
-            self.number,
-            if self.unspecified { "?" } else { "" },
-            self.class_name.fmt(ind),
-        )
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        if self.unspecified {
+            format!("?{}", self.class_name.fmt_with(f, ind))
+        } else {
+            format!("{}{}", self.number, self.class_name.fmt_with(f, ind))
+        }
     }
 }
 
 impl SpwnFmt for Operator {
-    fn fmt(&self, _ind: Indent) -> String {
+    fn fmt_with(&self, _f: &mut Formatter, _ind: Indent) -> String {
         match self {
             Operator::Or => "||",
             Operator::And => "&&",
@@ -461,6 +1229,11 @@ impl SpwnFmt for Operator {
             Operator::Plus => "+",
             Operator::Minus => "-",
             Operator::Modulo => "%",
+            Operator::BitAnd => ".&",
+            Operator::BitOr => ".|",
+            Operator::BitXor => ".^",
+            Operator::ShiftLeft => "<<",
+            Operator::ShiftRight => ">>",
             Operator::Assign => "=",
             Operator::Add => "+=",
             Operator::Subtract => "-=",
@@ -481,10 +1254,11 @@ impl SpwnFmt for Operator {
 }
 
 impl SpwnFmt for UnaryOperator {
-    fn fmt(&self, _ind: Indent) -> String {
+    fn fmt_with(&self, _f: &mut Formatter, _ind: Indent) -> String {
         match self {
             UnaryOperator::Not => "!",
             UnaryOperator::Minus => "-",
+            UnaryOperator::BitNot => "~",
             UnaryOperator::Decrement => "--",
             UnaryOperator::Increment => "++",
             UnaryOperator::EqPattern => "==",
@@ -500,13 +1274,13 @@ impl SpwnFmt for UnaryOperator {
 }
 
 impl SpwnFmt for Definition {
-    fn fmt(&self, ind: Indent) -> String {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
         format!(
             "{}{}{}",
             if self.mutable { "let " } else { "" },
-            self.symbol.fmt(ind),
+            self.symbol.fmt_with(f, ind),
             if let Some(value) = &self.value {
-                format!(" = {}", value.fmt(ind))
+                format!(" = {}", value.fmt_with(f, ind))
             } else {
                 String::new()
             }
@@ -515,113 +1289,94 @@ impl SpwnFmt for Definition {
 }
 
 impl SpwnFmt for Error {
-    fn fmt(&self, ind: Indent) -> String {
-        format!("error {}", self.message.fmt(ind))
-    }
-}
-
-fn trim_newline(s: &mut String) {
-    if s.ends_with('\n') {
-        s.pop();
-        if s.ends_with('\r') {
-            s.pop();
-        }
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        format!("throw {}", self.message.fmt_with(f, ind))
     }
 }
 
 impl SpwnFmt for CompoundStatement {
-    fn fmt(&self, ind: Indent) -> String {
-        let mut out = String::new();
-
-        for s in &self.statements {
-            out += &format!("{}{}", tabs(ind), s.fmt(ind));
-        }
-
-        trim_newline(&mut out);
-
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        let mut out = f.statements(&self.statements, 0, 0, ind, false);
+        out.pop();
         out
     }
 }
 
 impl SpwnFmt for Implementation {
-    fn fmt(&self, ind: Indent) -> String {
-        format!("impl {} ", self.symbol.fmt(ind)) + &element_list(&self.members, '{', '}', ind)
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        format!("impl {} ", self.symbol.fmt_with(f, ind))
+            + &f.element_list(&self.members, '{', '}', ind)
     }
 }
 
-impl SpwnFmt for If {
-    fn fmt(&self, ind: Indent) -> String {
-        let mut out = format!(
-            "if {} {{\n{}\n{}}}",
-            self.condition.fmt(ind),
-            CompoundStatement {
-                statements: self.if_body.clone()
-            }
-            .fmt(ind + 4),
-            tabs(ind)
-        );
-
-        if let Some(body) = &self.else_body {
-            out += &format!(
-                " else {{\n{}\n{}}}",
-                CompoundStatement {
-                    statements: body.clone()
-                }
-                .fmt(ind + 4),
-                tabs(ind)
-            );
+impl SpwnFmt for Sync {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        match &self.part {
+            Some((part, body)) => {
+                let (body, _) = f.block(body, expr_end(part), ind);
+                format!(
+                    "sync {}[{}] {}",
+                    self.group.fmt_with(f, ind),
+                    part.fmt_with(f, ind),
+                    body
+                )
+            }
+            None => format!("sync {}", self.group.fmt_with(f, ind)),
         }
+    }
+}
 
-        out
+impl SpwnFmt for If {
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        f.if_statement(self, ind)
     }
 }
 
 impl SpwnFmt for ArgDef {
-    fn fmt(&self, ind: Indent) -> String {
-        let (name, value, tag, typ, _, _) = self;
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        let (name, value, tag, typ, _, arg_type) = self;
 
-        let mut out = tag.fmt(ind);
+        let mut out = String::new();
+        if !tag.tags.is_empty() {
+            out += &tag.fmt_with(f, ind);
+            out += " ";
+        }
+        out += match arg_type {
+            ArgType::Ref => "&",
+            ArgType::Mut => "let ",
+            ArgType::Const => "",
+        };
         out += name;
         if let Some(expr) = typ {
-            out += &format!(": {}", expr.fmt(ind));
+            out += &format!(": {}", expr.fmt_with(f, ind));
         }
 
         if let Some(expr) = value {
-            out += &format!(" = {}", expr.fmt(ind));
+            out += &format!(" = {}", expr.fmt_with(f, ind));
         }
         out
     }
 }
 
 impl SpwnFmt for Macro {
-    fn fmt(&self, ind: Indent) -> String {
-        let mut out = String::new();
-
-        out += &self.properties.fmt(ind);
-
-        out += &element_list(&self.args, '(', ')', ind);
-        out += &format!(" {{\n{}\n{}}}", &self.body.fmt(ind + 4), tabs(ind));
-        out
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        f.macro_def(self, None, ind)
     }
 }
 
 impl SpwnFmt for (String, Vec<Argument>) {
-    fn fmt(&self, ind: Indent) -> String {
-        self.0.clone() + &element_list(&self.1, '(', ')', ind)
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        if self.1.is_empty() {
+            self.0.clone()
+        } else {
+            self.0.clone() + &f.element_list(&self.1, '(', ')', ind)
+        }
     }
 }
 
 impl SpwnFmt for Attribute {
-    fn fmt(&self, ind: Indent) -> String {
-        if self.tags.is_empty() {
-            return String::new();
-        }
-
-        let text = String::from("#") + &element_list(&self.tags, '[', ']', ind);
-        if text.len() > 60 {
-            text + "\n" + &tabs(ind)
-        } else {
-            text + " "
-        }
+    fn fmt_with(&self, f: &mut Formatter, ind: Indent) -> String {
+        let tags: Vec<String> = self.tags.iter().map(|t| t.fmt_with(f, ind)).collect();
+        format!("#[{}]", tags.join(", "))
     }
 }
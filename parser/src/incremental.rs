@@ -0,0 +1,348 @@
+//! Incremental reparsing for editor tooling
+//!
+//! A [`ParsedFile`] keeps the text and statements of a file, and [`ParsedFile::apply_edit`]
+//! only reparses the top-level statements around an edit instead of the whole file.
+
+use crate::ast;
+use crate::parser::{parse_region, parse_spwn_partial, ParseNotes};
+
+use errors::SyntaxError;
+use shared::FileRange;
+use shared::SpwnSource;
+
+/// Replaces the text in `range` (byte offsets into the current text) with `text`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEdit {
+    pub range: FileRange,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: FileRange, text: &str) -> Self {
+        TextEdit {
+            range,
+            text: text.to_string(),
+        }
+    }
+}
+
+pub struct ParsedFile {
+    /// The source code, with `\r\n` line breaks replaced by `\n`
+    pub text: String,
+    pub statements: Vec<ast::Statement>,
+    pub notes: ParseNotes,
+    pub errors: Vec<SyntaxError>,
+}
+
+impl ParsedFile {
+    pub fn parse(text: String, source: SpwnSource, builtin_list: &[&'static str]) -> Self {
+        let text = text.replace("\r\n", "\n");
+        let (statements, notes, errors) = parse_spwn_partial(text.clone(), source, builtin_list);
+        ParsedFile {
+            text,
+            statements,
+            notes,
+            errors,
+        }
+    }
+
+    /// Applies an edit to the text, and updates the statements to be the same as
+    /// parsing the new text from scratch.
+    ///
+    /// Returns the range of the new text that was reparsed, or `None` if the whole
+    /// file had to be reparsed. Panics if the edit range isn't in the text.
+    pub fn apply_edit(&mut self, edit: &TextEdit) -> Option<FileRange> {
+        let new_text = edit.text.replace("\r\n", "\n");
+        let delta = new_text.len() as isize - (edit.range.1 - edit.range.0) as isize;
+        self.text
+            .replace_range(edit.range.0..edit.range.1, &new_text);
+
+        let region = if self.errors.is_empty() {
+            self.affected_statements(edit.range)
+        } else {
+            // statements after a syntax error can't be trusted
+            None
+        };
+        let (first, last) = match region {
+            Some(r) => r,
+            None => {
+                self.parse_all();
+                return None;
+            }
+        };
+
+        // the region starts after the statement before it, so attributes
+        // in front of the first statement are included
+        let start = self.statements[first - 1].pos.1;
+        let is_last = last == self.statements.len() - 1;
+        let end = if is_last {
+            self.text.len()
+        } else {
+            shift(self.statements[last].pos.1, delta)
+        };
+
+        let (reparsed, errors) = parse_region(&self.text, start, end, &mut self.notes);
+
+        // if the last statement doesn't end where it used to, the edit
+        // changed how the statements after the region are parsed
+        let ends_at_region = is_last || reparsed.last().map(|s| s.pos.1) == Some(end);
+        if !errors.is_empty() || !ends_at_region {
+            self.parse_all();
+            return None;
+        }
+
+        let mut after = self.statements.split_off(last + 1);
+        for statement in &mut after {
+            statement.shift(delta);
+        }
+        self.statements.truncate(first);
+        self.statements.extend(reparsed);
+        self.statements.extend(after);
+        Some((start, end))
+    }
+
+    // the first and last statement to reparse for an edit, including
+    // one statement on each side of the ones the edit touches
+    fn affected_statements(&self, (edit_start, edit_end): FileRange) -> Option<(usize, usize)> {
+        let first = self
+            .statements
+            .iter()
+            .position(|s| s.pos.1 >= edit_start)
+            .unwrap_or(self.statements.len());
+        let last = self.statements.iter().rposition(|s| s.pos.0 <= edit_end)?;
+
+        // the file's tag and the first statement are parsed differently,
+        // so edits at the top of the file reparse everything
+        if first < 2 {
+            return None;
+        }
+        Some((first - 1, (last + 1).min(self.statements.len() - 1)))
+    }
+
+    fn parse_all(&mut self) {
+        let builtins: Vec<&'static str> = self.notes.builtins.iter().copied().collect();
+        let (statements, notes, errors) =
+            parse_spwn_partial(self.text.clone(), self.notes.file.clone(), &builtins);
+        self.statements = statements;
+        self.notes = notes;
+        self.errors = errors;
+    }
+}
+
+fn shift(pos: usize, delta: isize) -> usize {
+    (pos as isize + delta) as usize
+}
+
+// moves all the positions in a part of the AST that comes after an edit
+trait ShiftPositions {
+    fn shift(&mut self, delta: isize);
+}
+
+impl ShiftPositions for FileRange {
+    fn shift(&mut self, delta: isize) {
+        self.0 = shift(self.0, delta);
+        self.1 = shift(self.1, delta);
+    }
+}
+
+impl<T: ShiftPositions> ShiftPositions for Vec<T> {
+    fn shift(&mut self, delta: isize) {
+        for item in self {
+            item.shift(delta);
+        }
+    }
+}
+
+impl<T: ShiftPositions> ShiftPositions for Option<T> {
+    fn shift(&mut self, delta: isize) {
+        if let Some(item) = self {
+            item.shift(delta);
+        }
+    }
+}
+
+impl ShiftPositions for ast::Statement {
+    fn shift(&mut self, delta: isize) {
+        self.pos.shift(delta);
+        self.body.shift(delta);
+    }
+}
+
+impl ShiftPositions for ast::StatementBody {
+    fn shift(&mut self, delta: isize) {
+        use ast::StatementBody::*;
+        match self {
+            Call(call) => call.function.shift(delta),
+            Expr(expr) | Extract(expr) => expr.shift(delta),
+            Definition(def) => {
+                def.symbol.shift(delta);
+                def.value.shift(delta);
+            }
+            TypeDef { attr, .. } => attr.shift(delta),
+            Return(expr) => expr.shift(delta),
+            Impl(imp) => {
+                imp.symbol.shift(delta);
+                imp.members.shift(delta);
+            }
+            If(i) => {
+                i.condition.shift(delta);
+                i.if_body.shift(delta);
+                i.else_body.shift(delta);
+            }
+            For(f) => {
+                f.symbol.shift(delta);
+                f.array.shift(delta);
+                f.body.shift(delta);
+            }
+            While(w) => {
+                w.condition.shift(delta);
+                w.body.shift(delta);
+            }
+            Error(e) => e.message.shift(delta),
+            Try(t) => {
+                t.try_body.shift(delta);
+                t.catch_symbol.shift(delta);
+                t.catch_body.shift(delta);
+            }
+            ImportNames(i) => {
+                for name in &mut i.names {
+                    name.pos.shift(delta);
+                }
+            }
+            Sync(s) => {
+                s.group.shift(delta);
+                if let Some((part, body)) = &mut s.part {
+                    part.shift(delta);
+                    body.shift(delta);
+                }
+            }
+            Invalid(_) | Break | Continue => (),
+        }
+    }
+}
+
+impl ShiftPositions for ast::Expression {
+    fn shift(&mut self, delta: isize) {
+        self.values.shift(delta);
+    }
+}
+
+impl ShiftPositions for ast::Variable {
+    fn shift(&mut self, delta: isize) {
+        self.pos.shift(delta);
+        self.value.body.shift(delta);
+        self.path.shift(delta);
+        self.tag.shift(delta);
+    }
+}
+
+impl ShiftPositions for ast::Attribute {
+    fn shift(&mut self, delta: isize) {
+        for (_, args) in &mut self.tags {
+            args.shift(delta);
+        }
+    }
+}
+
+impl ShiftPositions for ast::Argument {
+    fn shift(&mut self, delta: isize) {
+        self.pos.shift(delta);
+        self.value.shift(delta);
+    }
+}
+
+impl ShiftPositions for ast::DictDef {
+    fn shift(&mut self, delta: isize) {
+        match self {
+            ast::DictDef::Def((_, expr)) | ast::DictDef::Extract(expr) => expr.shift(delta),
+        }
+    }
+}
+
+impl ShiftPositions for ast::Path {
+    fn shift(&mut self, delta: isize) {
+        use ast::Path::*;
+        match self {
+            Index(expr) => expr.shift(delta),
+            NSlice(slices) => {
+                for slice in slices {
+                    slice.left.shift(delta);
+                    slice.right.shift(delta);
+                    slice.step.shift(delta);
+                }
+            }
+            Call(args) => args.shift(delta),
+            Constructor(defs) => defs.shift(delta),
+            Member(_) | Associated(_) | Increment | Decrement => (),
+        }
+    }
+}
+
+impl ShiftPositions for ast::ValueBody {
+    fn shift(&mut self, delta: isize) {
+        use ast::ValueBody::*;
+        match self {
+            CmpStmt(cmp) => cmp.statements.shift(delta),
+            Dictionary(defs) => defs.shift(delta),
+            Expression(expr) => expr.shift(delta),
+            FormatStr(parts) => {
+                for part in parts {
+                    if let ast::FormatStrPart::Expr(expr) = part {
+                        expr.shift(delta);
+                    }
+                }
+            }
+            Match(expr, cases) => {
+                expr.shift(delta);
+                for case in cases {
+                    if let ast::CaseType::Pattern(pattern) = &mut case.typ {
+                        pattern.shift(delta);
+                    }
+                    match &mut case.body {
+                        ast::CaseBody::Expr(expr) => expr.shift(delta),
+                        ast::CaseBody::Block(body) => body.shift(delta),
+                    }
+                }
+            }
+            Array(items) => {
+                for item in items {
+                    item.value.shift(delta);
+                }
+            }
+            ListComp(comp) => {
+                comp.iterator.shift(delta);
+                comp.condition.shift(delta);
+                comp.body.shift(delta);
+            }
+            Obj(obj) => {
+                for (key, value) in &mut obj.props {
+                    key.shift(delta);
+                    value.shift(delta);
+                }
+            }
+            Macro(m) => {
+                for (_, default, attr, typ, pos, _) in &mut m.args {
+                    default.shift(delta);
+                    attr.shift(delta);
+                    typ.shift(delta);
+                    pos.shift(delta);
+                }
+                m.body.statements.shift(delta);
+                m.properties.shift(delta);
+                m.arg_pos.shift(delta);
+                m.ret_type.shift(delta);
+            }
+            MacroPattern(pattern) => {
+                pattern.args.shift(delta);
+                pattern.ret.shift(delta);
+            }
+            Ternary(t) => {
+                t.condition.shift(delta);
+                t.if_expr.shift(delta);
+                t.else_expr.shift(delta);
+            }
+            Id(_) | Number(_) | Int(_) | Symbol(_) | Bool(_) | Str(_) | Import(..)
+            | Resolved(_) | TypeIndicator(_) | SelfVal | Null => (),
+        }
+    }
+}
@@ -1,3 +1,4 @@
 pub mod ast;
 pub mod fmt;
+pub mod incremental;
 pub mod parser;
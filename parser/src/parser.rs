@@ -130,6 +130,24 @@ pub enum Token {
     #[token("/%")]
     IntDividedBy,
 
+    #[token(".&")]
+    BitAnd,
+
+    #[token(".|")]
+    BitOr,
+
+    #[token(".^")]
+    BitXor,
+
+    #[token("~")]
+    BitNot,
+
+    #[token("<<")]
+    ShiftLeft,
+
+    #[token(">>")]
+    ShiftRight,
+
     #[token("!")]
     Exclamation,
 
@@ -260,6 +278,12 @@ pub enum Token {
     #[token("else")]
     Else,
 
+    #[token("try")]
+    Try,
+
+    #[token("catch")]
+    Catch,
+
     #[token("switch")]
     Switch,
 
@@ -320,9 +344,8 @@ impl Token {
             Or | And | Equal | NotEqual | MoreOrEqual | LessOrEqual | MoreThan | LessThan
             | Star | Modulo | Power | Plus | Minus | Slash | Exclamation | Assign | Add
             | Subtract | Multiply | Divide | IntDividedBy | IntDivide | As | In | Either
-            | Ampersand | DoubleStar | Exponate | Modulate | Increment | Decrement | Swap | Is => {
-                "operator"
-            }
+            | Ampersand | DoubleStar | Exponate | Modulate | Increment | Decrement | Swap | Is
+            | BitAnd | BitOr | BitXor | BitNot | ShiftLeft | ShiftRight => "operator",
             Symbol => "identifier",
             Number | BinaryLiteral | HexLiteral | OctalLiteral => "number literal",
             StringLiteral => "string literal",
@@ -333,11 +356,11 @@ impl Token {
             | ClosingSquareBracket | OpenBracket | ClosingBracket | Colon | DoubleColon
             | Period | DotDot | DotDotEq | At | Hash | Arrow | ThickArrow => "terminator",
 
-            Sync => "reserved keyword (not currently in use, but may be used in future updates)",
             Switch => "Deprecated keyword, use `match` instead",
 
-            Return | Implement | For | ErrorStatement | If | Else | Object | Trigger | Import
-            | Extract | Null | Type | Let | SelfVal | Break | Continue | Match | While => "keyword",
+            Return | Implement | For | ErrorStatement | If | Else | Try | Catch | Object
+            | Trigger | Import | Extract | Null | Type | Let | SelfVal | Break | Continue
+            | Match | While | Sync => "keyword",
             //Comment | MultiCommentStart | MultiCommentEnd => "comment",
             StatementSeparator => "statement separator",
             Error => "unknown",
@@ -349,6 +372,9 @@ pub struct ParseNotes {
     pub tag: ast::Attribute,
     pub file: SpwnSource,
     pub builtins: AHashSet<&'static str>,
+    // syntax errors that the parser recovered from
+    errors: Vec<SyntaxError>,
+    reached_end: bool,
 }
 
 impl ParseNotes {
@@ -357,8 +383,32 @@ impl ParseNotes {
             tag: ast::Attribute::new(),
             file: path,
             builtins: builtins.iter().copied().collect(),
+            errors: Vec::new(),
+            reached_end: false,
         }
     }
+
+    // once an error skips to the end of the file, the errors after it
+    // are caused by the missing code, so only the first one is kept
+    fn add_error(&mut self, err: SyntaxError, skipped: &SkippedTo) {
+        if !self.reached_end {
+            self.errors.push(err);
+        }
+        if let SkippedTo::End = skipped {
+            self.reached_end = true;
+        }
+    }
+
+    // the errors so far, so the ones from a parse that is thrown away
+    // (after parsing ahead on a copy of the tokens) can be removed again
+    fn checkpoint(&self) -> (usize, bool) {
+        (self.errors.len(), self.reached_end)
+    }
+
+    fn restore(&mut self, (len, reached_end): (usize, bool)) {
+        self.errors.truncate(len);
+        self.reached_end = reached_end;
+    }
 }
 
 #[derive(Clone)]
@@ -530,6 +580,19 @@ impl<'a> Tokens<'a> {
         self.stack[self.stack.len() - self.index - 1].1.clone()
     }
 
+    // how many tokens have been read, to be able to go back to this point
+    fn read_count(&self) -> usize {
+        self.stack.len() - self.index
+    }
+
+    fn rewind(&mut self, read_count: usize) {
+        self.index = self.stack.len() - read_count;
+    }
+
+    fn source_between(&self, start: usize, end: usize) -> &str {
+        &self.iter.source()[start..end]
+    }
+
     fn position(&self) -> (usize, usize) {
         if self.stack.len() - self.index == 0 {
             return (0, 0);
@@ -551,19 +614,115 @@ impl<'a> Tokens<'a> {
 
 const STATEMENT_SEPARATOR_DESC: &str = "Statement separator (line-break or ';')";
 
+// where skipping a statement with a syntax error stopped
+enum SkippedTo {
+    Separator,
+    ClosingBracket,
+    End,
+}
+
+// goes back to the start of a statement with a syntax error, and skips
+// to the first statement separator or unmatched '}' after the error
+// that isn't inside brackets, so parsing can go on from there
+fn skip_statement(tokens: &mut Tokens, start: usize, err: &SyntaxError) -> SkippedTo {
+    let err_start = err.pos().0;
+    tokens.rewind(start);
+
+    let mut open = Vec::<Token>::new();
+    loop {
+        let token = tokens.next(true);
+        let after_err = tokens.position().0 >= err_start;
+
+        // closes the last bracket of a type, and everything opened after it
+        let mut close = |opening| match open.iter().rposition(|t| *t == opening) {
+            Some(i) => {
+                open.truncate(i);
+                true
+            }
+            None => false,
+        };
+        match token {
+            None => return SkippedTo::End,
+            Some(t @ Token::OpenCurlyBracket)
+            | Some(t @ Token::OpenSquareBracket)
+            | Some(t @ Token::OpenBracket) => open.push(t),
+            Some(Token::ClosingCurlyBracket) if !close(Token::OpenCurlyBracket) && after_err => {
+                return SkippedTo::ClosingBracket
+            }
+            Some(Token::ClosingSquareBracket) => {
+                close(Token::OpenSquareBracket);
+            }
+            Some(Token::ClosingBracket) => {
+                close(Token::OpenBracket);
+            }
+            Some(Token::StatementSeparator) if open.is_empty() && after_err => {
+                return SkippedTo::Separator
+            }
+            _ => (),
+        }
+    }
+}
+
+// the placeholder for a statement that was skipped because of a syntax error
+fn invalid_statement(tokens: &Tokens, start: usize, skipped: &SkippedTo) -> ast::Statement {
+    let end = match skipped {
+        // the separator or bracket is not part of the statement
+        SkippedTo::Separator | SkippedTo::ClosingBracket => tokens.position().0,
+        SkippedTo::End => tokens.iter.source().len(),
+    }
+    .max(start);
+    let text = tokens.source_between(start, end);
+    let start = start + (text.len() - text.trim_start().len());
+    ast::Statement {
+        body: ast::StatementBody::Invalid(text.trim().to_string()),
+        arrow: false,
+        pos: (start, end),
+    }
+}
+
+// parses a file, and returns the first syntax error if there are any
 pub fn parse_spwn(
-    mut unparsed: String,
+    unparsed: String,
     source: SpwnSource,
     builtin_list: &[&'static str],
 ) -> Result<(Vec<ast::Statement>, ParseNotes), SyntaxError> {
+    let (statements, notes, mut errors) = parse_spwn_partial(unparsed, source, builtin_list);
+    if errors.is_empty() {
+        Ok((statements, notes))
+    } else {
+        Err(errors.remove(0))
+    }
+}
+
+// how many brackets are opened and not closed yet, so the REPL knows
+// when an input continues on the next line
+pub fn open_brackets(code: &str) -> usize {
+    let mut open = 0usize;
+    for token in Token::lexer(code) {
+        match token {
+            Token::OpenCurlyBracket | Token::OpenSquareBracket | Token::OpenBracket => open += 1,
+            Token::ClosingCurlyBracket | Token::ClosingSquareBracket | Token::ClosingBracket => {
+                open = open.saturating_sub(1)
+            }
+            _ => (),
+        }
+    }
+    open
+}
+
+// parses a file, and keeps going after syntax errors. statements that couldn't
+// be parsed become `StatementBody::Invalid`, and all the errors are returned
+pub fn parse_spwn_partial(
+    mut unparsed: String,
+    source: SpwnSource,
+    builtin_list: &[&'static str],
+) -> (Vec<ast::Statement>, ParseNotes, Vec<SyntaxError>) {
     unparsed = unparsed.replace("\r\n", "\n");
 
     let tokens_iter = Token::lexer(&unparsed);
 
     let mut tokens = Tokens::new(tokens_iter);
 
-    let mut statements = Vec::<ast::Statement>::new();
-
     let mut notes = ParseNotes::new(source, builtin_list);
 
     let mut line_breaks = Vec::<u32>::new();
@@ -577,8 +736,36 @@ pub fn parse_spwn(
 
     tokens.line_breaks = line_breaks;
 
-    let start_tag = check_for_tag(&mut tokens, &mut notes)?;
-    notes.tag = start_tag;
+    match check_for_tag(&mut tokens, &mut notes) {
+        Ok(start_tag) => notes.tag = start_tag,
+        Err(e) => return (Vec::new(), notes, vec![e]),
+    }
+    let statements = parse_top_level(&mut tokens, &mut notes);
+
+    let errors = std::mem::take(&mut notes.errors);
+    (statements, notes, errors)
+}
+
+// parses only the top-level statements between two byte offsets of a file.
+// the lexer starts at `start` instead of the beginning of the file, so the
+// positions in the statements are the same as in a full parse
+pub(crate) fn parse_region(
+    text: &str,
+    start: usize,
+    end: usize,
+    notes: &mut ParseNotes,
+) -> (Vec<ast::Statement>, Vec<SyntaxError>) {
+    let mut tokens_iter = Token::lexer(&text[..end]);
+    tokens_iter.bump(start);
+    let mut tokens = Tokens::new(tokens_iter);
+
+    notes.reached_end = false;
+    let statements = parse_top_level(&mut tokens, notes);
+    (statements, std::mem::take(&mut notes.errors))
+}
+
+fn parse_top_level(tokens: &mut Tokens, notes: &mut ParseNotes) -> Vec<ast::Statement> {
+    let mut statements = Vec::<ast::Statement>::new();
     loop {
         //+ do something if we have tokens. if no more tokens, leave loop
         match tokens.next(false) {
@@ -587,33 +774,55 @@ pub fn parse_spwn(
                 tokens.previous_no_ignore(false); //bring tokens back to original
 
                 //+ we are going to parse the tokens
-                let parsed = parse_statement(&mut tokens, &mut notes)?;
-                // if parsed.comment.0 == None && !statements.is_empty() {
-                //     parsed.comment.0 = statements.last().unwrap().comment.1.clone();
-                //     (*statements.last_mut().unwrap()).comment.1 = None;
-                // }
-
-                statements.push(parsed)
+                let start = tokens.read_count();
+                match parse_statement(tokens, notes) {
+                    Ok(parsed) => statements.push(parsed),
+                    Err(e) => {
+                        let start_pos = tokens_end(tokens, start);
+                        let skipped = skip_statement(tokens, start, &e);
+                        statements.push(invalid_statement(tokens, start_pos, &skipped));
+                        notes.add_error(e, &skipped);
+                        match skipped {
+                            SkippedTo::End => break,
+                            // a stray '}' at the top level
+                            SkippedTo::ClosingBracket | SkippedTo::Separator => continue,
+                        }
+                    }
+                }
             }
             None => break, //+ no more tokens, probably end of file
         }
 
         //+ can't find any more tokens that are valid syntax, checking for line separator
+        let end = tokens.read_count();
         match tokens.next(true) {
             Some(Token::StatementSeparator) => {}
             Some(a) => {
-                return Err(SyntaxError::ExpectedErr {
+                let e = SyntaxError::ExpectedErr {
                     expected: STATEMENT_SEPARATOR_DESC.to_string(),
                     found: format!("{}: \"{}\"", a.typ(), tokens.slice()),
                     pos: tokens.position(),
-                    file: notes.file,
-                })
+                    file: notes.file.clone(),
+                };
+                let skipped = skip_statement(tokens, end, &e);
+                notes.add_error(e, &skipped);
+                if let SkippedTo::End = skipped {
+                    break;
+                }
             }
             None => break,
         }
     }
+    statements
+}
 
-    Ok((statements, notes))
+// the byte offset where the token before a read count ends
+fn tokens_end(tokens: &Tokens, read_count: usize) -> usize {
+    if read_count == 0 {
+        0
+    } else {
+        tokens.stack[read_count - 1].2.end
+    }
 }
 
 fn parse_cmp_stmt(
@@ -622,34 +831,62 @@ fn parse_cmp_stmt(
 ) -> Result<Vec<ast::Statement>, SyntaxError> {
     let mut statements = Vec::<ast::Statement>::new();
     let opening_bracket = tokens.position();
+    let unmatched = |notes: &ParseNotes| SyntaxError::SyntaxError {
+        message: "Couldn't find matching '}' for this '{'".to_string(),
+        pos: opening_bracket,
+        file: notes.file.clone(),
+    };
     loop {
         match tokens.next(false) {
             Some(Token::ClosingCurlyBracket) => break,
             Some(_) => {
                 tokens.previous_no_ignore(false);
 
-                let parsed = parse_statement(tokens, notes)?;
-                // if parsed.comment.0 == None && !statements.is_empty() {
-                //     parsed.comment.0 = statements.last().unwrap().comment.1.clone();
-                //     (*statements.last_mut().unwrap()).comment.1 = None;
-                // }
-
-                statements.push(parsed) // add to big statement list
-                                        //println!("statement done");
-            }
-            None => {
-                return Err(SyntaxError::SyntaxError {
-                    message: "Couldn't find matching '}' for this '{'".to_string(),
-                    pos: opening_bracket,
-                    file: notes.file.clone(),
-                })
+                let start = tokens.read_count();
+                match parse_statement(tokens, notes) {
+                    Ok(parsed) => statements.push(parsed), // add to big statement list
+                    Err(e) => {
+                        let start_pos = tokens_end(tokens, start);
+                        let skipped = skip_statement(tokens, start, &e);
+                        statements.push(invalid_statement(tokens, start_pos, &skipped));
+                        notes.add_error(e, &skipped);
+                        match skipped {
+                            SkippedTo::Separator => continue,
+                            SkippedTo::ClosingBracket => break,
+                            SkippedTo::End => return Err(unmatched(notes)),
+                        }
+                    }
+                }
             }
+            None => return Err(unmatched(notes)),
         }
 
+        let end = tokens.read_count();
         match tokens.next(true) {
             Some(Token::StatementSeparator) => {}
             Some(Token::ClosingCurlyBracket) => break,
-            a => expected!(STATEMENT_SEPARATOR_DESC.to_string(), tokens, notes, a),
+            a => {
+                let e = SyntaxError::ExpectedErr {
+                    expected: STATEMENT_SEPARATOR_DESC.to_string(),
+                    found: format!(
+                        "{}: \"{}\"",
+                        match a {
+                            Some(t) => t.typ(),
+                            None => "EOF",
+                        },
+                        tokens.slice()
+                    ),
+                    pos: tokens.position(),
+                    file: notes.file.clone(),
+                };
+                let skipped = skip_statement(tokens, end, &e);
+                notes.add_error(e, &skipped);
+                match skipped {
+                    SkippedTo::Separator => (),
+                    SkippedTo::ClosingBracket => break,
+                    SkippedTo::End => return Err(unmatched(notes)),
+                }
+            }
         }
     }
     //tokens.next(false, false);
@@ -711,7 +948,11 @@ pub fn parse_statement(
             // definition statement (at last)
             // this branch only handles the immutable case, the immutable case is handled in the expression branch,
             // because its equivalent to an assign expression
-            let symbol = parse_variable(tokens, notes, false, None)?;
+            let mut symbol = parse_variable(tokens, notes, false, None)?;
+            // `#[allow(...)] let a = ...` is the same as `let #[allow(...)] a = ...`
+            if symbol.tag.tags.is_empty() {
+                symbol.tag = attr;
+            }
             let value = match tokens.next(false) {
                 Some(Token::Assign) => Some(parse_expr(tokens, notes, false, true, None)?),
                 _ => {
@@ -887,6 +1128,40 @@ pub fn parse_statement(
             //i dont think a summary is needed for this
         }
 
+        Some(Token::Try) => {
+            match tokens.next(false) {
+                Some(Token::OpenCurlyBracket) => (),
+                a => expected!("'{'".to_string(), tokens, notes, a),
+            }
+            let try_body = parse_cmp_stmt(tokens, notes)?;
+
+            match tokens.next(false) {
+                Some(Token::Catch) => (),
+                a => expected!("keyword 'catch'".to_string(), tokens, notes, a),
+            }
+            // the error can be stored in a variable, or ignored
+            let catch_symbol = match tokens.next(false) {
+                Some(Token::Symbol) => {
+                    let symbol = LocalIntern::new(tokens.slice());
+                    let variable = ast::ValueBody::Symbol(symbol).to_variable(tokens.position());
+                    match tokens.next(false) {
+                        Some(Token::OpenCurlyBracket) => (),
+                        a => expected!("'{'".to_string(), tokens, notes, a),
+                    }
+                    Some(variable)
+                }
+                Some(Token::OpenCurlyBracket) => None,
+                a => expected!("identifier or '{'".to_string(), tokens, notes, a),
+            };
+            let catch_body = parse_cmp_stmt(tokens, notes)?;
+
+            ast::StatementBody::Try(ast::Try {
+                try_body,
+                catch_symbol,
+                catch_body,
+            })
+        }
+
         Some(Token::Type) => {
             // defining a new type
             match tokens.next(false) {
@@ -937,6 +1212,94 @@ pub fn parse_statement(
             // honestly this shouldn't deserve a summary its so basic
         }
 
+        Some(Token::Sync) => {
+            let mut group = parse_variable(tokens, notes, false, None)?;
+
+            match tokens.next(false) {
+                // sync part
+                Some(Token::OpenCurlyBracket) => match group.path.pop() {
+                    Some(ast::Path::Index(part)) => {
+                        let body = parse_cmp_stmt(tokens, notes)?;
+                        ast::StatementBody::Sync(ast::Sync {
+                            group,
+                            part: Some((part, body)),
+                        })
+                    }
+                    _ => {
+                        return Err(SyntaxError::SyntaxError {
+                            message: "expected a sync part, like `sync a[part] { ... }`"
+                                .to_string(),
+                            pos: group.pos,
+                            file: notes.file.clone(),
+                        })
+                    }
+                },
+                // sync group definition
+                _ => {
+                    tokens.previous();
+                    if !group.path.is_empty() || group.operator.is_some() {
+                        return Err(SyntaxError::SyntaxError {
+                            message: "expected a variable name for the sync group".to_string(),
+                            pos: group.pos,
+                            file: notes.file.clone(),
+                        });
+                    }
+                    ast::StatementBody::Sync(ast::Sync { group, part: None })
+                }
+            }
+        }
+
+        Some(Token::Import)
+            if {
+                let read = tokens.read_count();
+                let names = tokens.next(false) == Some(Token::OpenCurlyBracket);
+                tokens.rewind(read);
+                names
+            } =>
+        {
+            tokens.next(false);
+            let mut names = Vec::new();
+            loop {
+                match tokens.next(false) {
+                    Some(Token::ClosingCurlyBracket) => break,
+                    Some(Token::Symbol) => {
+                        let name = LocalIntern::new(tokens.slice());
+                        let start = tokens.position().0;
+                        let alias = match tokens.next(false) {
+                            Some(Token::As) => match tokens.next(false) {
+                                Some(Token::Symbol) => Some(LocalIntern::new(tokens.slice())),
+                                a => expected!("identifier".to_string(), tokens, notes, a),
+                            },
+                            _ => {
+                                tokens.previous();
+                                None
+                            }
+                        };
+                        names.push(ast::ImportName {
+                            name,
+                            alias,
+                            pos: (start, tokens.position().1),
+                        });
+                        match tokens.next(false) {
+                            Some(Token::Comma) => (),
+                            Some(Token::ClosingCurlyBracket) => break,
+                            a => expected!("',' or '}'".to_string(), tokens, notes, a),
+                        }
+                    }
+                    a => expected!("identifier or '}'".to_string(), tokens, notes, a),
+                }
+            }
+            // `from` is only a keyword here
+            match tokens.next(false) {
+                Some(Token::Symbol) if tokens.slice() == "from" => (),
+                a => expected!("'from'".to_string(), tokens, notes, a),
+            }
+            let first = tokens.next(false);
+            let module = parse_import_path(first, tokens, notes)?;
+
+            ast::StatementBody::ImportNames(ast::ImportNames { names, module })
+        }
+
         Some(Token::Extract) => {
             let expr = parse_expr(tokens, notes, true, true, None)?;
             // its an expression because dicts can also be extracted alongside imported modules
@@ -1041,12 +1404,16 @@ macro_rules! op_precedence {
 }
 
 op_precedence! { // make sure the highest precedence is at the top
-    12, Left => As,
-    11, Left => Both,
-    10, Left => Either,
-    9, Right => Power,
-    8, Left => Modulo Star Slash IntDividedBy,
-    7, Left => Plus Minus,
+    16, Left => As,
+    15, Left => Both,
+    14, Left => Either,
+    13, Right => Power,
+    12, Left => Modulo Star Slash IntDividedBy,
+    11, Left => Plus Minus,
+    10, Left => ShiftLeft ShiftRight,
+    9, Left => BitAnd,
+    8, Left => BitXor,
+    7, Left => BitOr,
     6, Left => Range InclRange,
     5, Left => LessOrEqual MoreOrEqual,
     4, Left => Less More,
@@ -1433,6 +1800,11 @@ fn parse_operator(token: &Token) -> Option<ast::Operator> {
         Token::Slash => Some(ast::Operator::Slash),
         Token::IntDividedBy => Some(ast::Operator::IntDividedBy),
         Token::Modulo => Some(ast::Operator::Modulo),
+        Token::BitAnd => Some(ast::Operator::BitAnd),
+        Token::BitOr => Some(ast::Operator::BitOr),
+        Token::BitXor => Some(ast::Operator::BitXor),
+        Token::ShiftLeft => Some(ast::Operator::ShiftLeft),
+        Token::ShiftRight => Some(ast::Operator::ShiftRight),
 
         Token::Either => Some(ast::Operator::Either),
         Token::Ampersand => Some(ast::Operator::Both),
@@ -1907,6 +2279,32 @@ fn check_for_tag(
     }
 }
 
+// the file or library after `import`
+#[allow(clippy::result_large_err)]
+fn parse_import_path(
+    first: Option<Token>,
+    tokens: &mut Tokens,
+    notes: &mut ParseNotes,
+) -> Result<ImportType, SyntaxError> {
+    match first {
+        Some(Token::StringLiteral) => {
+            let (content, flag) = str_content(tokens.slice(), tokens, notes)?;
+
+            if flag.is_some() {
+                return Err(SyntaxError::UnexpectedErr {
+                    file: notes.file.to_owned(),
+                    pos: tokens.position(),
+                    found: format!("string flag ({:?})", flag),
+                });
+            }
+
+            Ok(ImportType::Script(PathBuf::from(content)))
+        }
+        Some(Token::Symbol) => Ok(ImportType::Lib(tokens.slice())),
+        a => expected!("literal string".to_string(), tokens, notes, a),
+    }
+}
+
 fn char_escape(
     chars: &mut Chars<'_>,
     tokens: &Tokens,
@@ -2103,7 +2501,95 @@ pub fn str_content(
     Ok(out)
 }
 
-fn check_if_slice(mut tokens: Tokens, notes: &mut ParseNotes) -> Result<bool, SyntaxError> {
+// f"x = {x}". the expressions in braces are lexed from where they are in the
+// file, so their positions (and the positions of errors in them) are correct
+#[allow(clippy::result_large_err)]
+fn parse_format_str(
+    tokens: &mut Tokens,
+    notes: &mut ParseNotes,
+) -> Result<Vec<ast::FormatStrPart>, SyntaxError> {
+    let (start, end) = tokens.position();
+    let source = tokens.iter.source();
+    // skip the flag and the quotes
+    let content_end = end - 1;
+    let mut i = start + 2;
+
+    let mut parts = Vec::new();
+    let mut text = String::new();
+
+    while i < content_end {
+        let mut chars = source[i..content_end].chars();
+        match chars.next().unwrap() {
+            '\\' => text.push(char_escape(&mut chars, tokens, notes)?),
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => {
+                return Err(SyntaxError::SyntaxError {
+                    message: "Unmatched '}' in format string (use '}}' for a '}')".to_string(),
+                    pos: (i, i + 1),
+                    file: notes.file.clone(),
+                })
+            }
+            '{' => {
+                if !text.is_empty() {
+                    parts.push(ast::FormatStrPart::Str(std::mem::take(&mut text)));
+                }
+
+                let mut expr_tokens = Tokens::new(Token::lexer(&source[..content_end]));
+                expr_tokens.iter.bump(i + 1);
+
+                let expr = parse_expr(&mut expr_tokens, notes, true, true, None)?;
+                match expr_tokens.next(false) {
+                    Some(Token::ClosingCurlyBracket) => (),
+                    Some(a) => {
+                        return Err(SyntaxError::ExpectedErr {
+                            expected: "'}'".to_string(),
+                            found: format!("{}: \"{}\"", a.typ(), expr_tokens.slice()),
+                            pos: expr_tokens.position(),
+                            file: notes.file.clone(),
+                        })
+                    }
+                    None => {
+                        return Err(SyntaxError::SyntaxError {
+                            message: "Unclosed '{' in format string (use '{{' for a '{')"
+                                .to_string(),
+                            pos: (i, i + 1),
+                            file: notes.file.clone(),
+                        })
+                    }
+                }
+                parts.push(ast::FormatStrPart::Expr(expr));
+                i = expr_tokens.position().1;
+                continue;
+            }
+            c => text.push(c),
+        }
+        i = content_end - chars.as_str().len();
+    }
+
+    if !text.is_empty() {
+        parts.push(ast::FormatStrPart::Str(text));
+    }
+    Ok(parts)
+}
+
+#[allow(clippy::result_large_err)]
+fn check_if_slice(tokens: Tokens, notes: &mut ParseNotes) -> Result<bool, SyntaxError> {
+    // the tokens are parsed again afterwards
+    let checkpoint = notes.checkpoint();
+    let result = check_if_slice_inner(tokens, notes);
+    notes.restore(checkpoint);
+    result
+}
+
+#[allow(clippy::result_large_err)]
+fn check_if_slice_inner(mut tokens: Tokens, notes: &mut ParseNotes) -> Result<bool, SyntaxError> {
     loop {
         match tokens.next(false) {
             Some(Token::Colon) => {
@@ -2157,6 +2643,35 @@ fn try_parse_macro_pattern(
     Ok(ast::MacroPattern { args, ret })
 }
 
+// tries to parse a macro definition, and then a macro pattern. the errors from
+// the attempts that are thrown away are removed, so they aren't shown twice
+#[allow(clippy::result_large_err)]
+fn parse_macro_or_pattern(
+    tokens: &mut Tokens,
+    notes: &mut ParseNotes,
+    parse_macro_def: impl FnOnce(&mut Tokens, &mut ParseNotes) -> Result<ast::ValueBody, SyntaxError>,
+) -> Result<ast::ValueBody, SyntaxError> {
+    let checkpoint = notes.checkpoint();
+    let mut test_tokens = tokens.clone();
+    match parse_macro_def(&mut test_tokens, notes) {
+        Ok(v) => {
+            (*tokens) = test_tokens;
+            Ok(v)
+        }
+        Err(e) => {
+            notes.restore(checkpoint);
+            match try_parse_macro_pattern(tokens, notes) {
+                Ok(pat) => Ok(ast::ValueBody::MacroPattern(pat)),
+                // return macro error, since its more likely that they were trying to make a normal macro
+                Err(_) => {
+                    notes.restore(checkpoint);
+                    Err(e)
+                }
+            }
+        }
+    }
+}
+
 fn try_parse_macro(
     tokens: &mut Tokens,
     notes: &mut ParseNotes,
@@ -2236,53 +2751,39 @@ fn try_parse_macro(
             }
         };
 
+    let checkpoint = notes.checkpoint();
     let mut test_tokens = tokens.clone();
 
     return match parse_expr(&mut test_tokens, notes, true, true, None) {
         Ok(expr) => {
             //macro def
-            Ok(match test_tokens.next(false) {
-                Some(Token::ClosingBracket) => match test_tokens.next(false) {
+            let next = test_tokens.next(false);
+            let after = if let Some(Token::ClosingBracket) = next {
+                test_tokens.next(false)
+            } else {
+                None
+            };
+            // everything except a parenthesized expression is parsed again from the start
+            let is_expression = matches!(next, Some(Token::ClosingBracket))
+                && !matches!(
+                    after,
+                    Some(Token::OpenCurlyBracket | Token::ThickArrow | Token::Arrow)
+                );
+            if !is_expression {
+                notes.restore(checkpoint);
+            }
+            Ok(match next {
+                Some(Token::ClosingBracket) => match after {
                     Some(Token::OpenCurlyBracket) => parse_macro_def(tokens, notes)?,
                     Some(Token::ThickArrow) => parse_macro_def(tokens, notes)?,
-                    Some(Token::Arrow) => {
-                        let mut test_tokens = tokens.clone();
-                        match parse_macro_def(&mut test_tokens, notes) {
-                            Ok(v) => {
-                                (*tokens) = test_tokens;
-                                v
-                            }
-                            Err(e) => {
-                                match try_parse_macro_pattern(tokens, notes) {
-                                    Ok(pat) => ast::ValueBody::MacroPattern(pat),
-                                    // return macro error, since its more likely that they were trying to make a normal macro
-                                    Err(_) => return Err(e),
-                                }
-                            }
-                        }
-                    }
+                    Some(Token::Arrow) => parse_macro_or_pattern(tokens, notes, parse_macro_def)?,
                     _ => {
                         test_tokens.previous();
                         (*tokens) = test_tokens;
                         ast::ValueBody::Expression(expr)
                     }
                 },
-                Some(Token::Comma) => {
-                    let mut test_tokens = tokens.clone();
-                    match parse_macro_def(&mut test_tokens, notes) {
-                        Ok(v) => {
-                            (*tokens) = test_tokens;
-                            v
-                        }
-                        Err(e) => {
-                            match try_parse_macro_pattern(tokens, notes) {
-                                Ok(pat) => ast::ValueBody::MacroPattern(pat),
-                                // return macro error, since its more likely that they were trying to make a normal macro
-                                Err(_) => return Err(e),
-                            }
-                        }
-                    }
-                }
+                Some(Token::Comma) => parse_macro_or_pattern(tokens, notes, parse_macro_def)?,
                 Some(Token::Colon) => parse_macro_def(tokens, notes)?,
                 a => {
                     return Err(SyntaxError::ExpectedErr {
@@ -2296,22 +2797,15 @@ fn try_parse_macro(
         }
 
         Err(_) => {
-            let mut test_tokens = tokens.clone();
-            match parse_macro_def(&mut test_tokens, notes) {
-                Ok(v) => {
-                    (*tokens) = test_tokens;
-                    Ok(v)
-                }
-                Err(e) => match try_parse_macro_pattern(tokens, notes) {
-                    Ok(pat) => Ok(ast::ValueBody::MacroPattern(pat)),
-                    // return macro error, since its more likely that they were trying to make a normal macro
-                    Err(_) => Err(e),
-                },
-            }
+            notes.restore(checkpoint);
+            parse_macro_or_pattern(tokens, notes, parse_macro_def)
         }
     };
 }
 
+// 2^53, the biggest integer that every smaller integer can be stored exactly in an f64
+const MAX_EXACT_FLOAT_INT: u64 = 1 << 53;
+
 fn parse_number_radix(
     tokens: &mut Tokens,
     notes: &mut ParseNotes,
@@ -2319,7 +2813,9 @@ fn parse_number_radix(
     prefix: &str,
 ) -> Result<ast::ValueBody, SyntaxError> {
     match u128::from_str_radix(&tokens.slice().replace('_', "").replace(prefix, ""), radix) {
-        Ok(n) => Ok(ast::ValueBody::Number(n as f64)),
+        // too big for @int, so it is rounded to a @number
+        Ok(n) if n > i64::MAX as u128 => Ok(ast::ValueBody::Number(n as f64)),
+        Ok(n) => Ok(ast::ValueBody::Int(n as i64)),
         Err(err) => return Err(SyntaxError::SyntaxError {
             message: format!("Error when parsing number: {}", err),
             pos: tokens.position(),
@@ -2374,6 +2870,11 @@ fn parse_variable(
             Some(ast::UnaryOperator::Increment)
         }
 
+        Some(Token::BitNot) => {
+            first_token = tokens.next(false);
+            Some(ast::UnaryOperator::BitNot)
+        }
+
         Some(Token::DotDot) => {
             return Err(SyntaxError::SyntaxError {
                 message:
@@ -2433,17 +2934,22 @@ fn parse_variable(
     let value = match first_token {
         // what kind of variable is it?
         Some(Token::Number) => {
-            ast::ValueBody::Number(match tokens.slice().replace('_', "").parse() {
-                Ok(n) => n, // its a valid number
-                Err(err) => {
-                    return Err(SyntaxError::SyntaxError {
-                        message: format!("Error when parsing number: {}", err),
+            let text = tokens.slice().replace('_', "");
+            match text.parse::<i64>() {
+                // integers that a @number can't store exactly are @ints
+                Ok(n) if n.unsigned_abs() > MAX_EXACT_FLOAT_INT => ast::ValueBody::Int(n),
+                _ => ast::ValueBody::Number(match text.parse() {
+                    Ok(n) => n, // its a valid number
+                    Err(err) => {
+                        return Err(SyntaxError::SyntaxError {
+                            message: format!("Error when parsing number: {}", err),
 
-                        pos: tokens.position(),
-                        file: notes.file.clone(),
-                    });
-                }
-            })
+                            pos: tokens.position(),
+                            file: notes.file.clone(),
+                        });
+                    }
+                }),
+            }
         }
         Some(Token::BinaryLiteral) => {
             match parse_number_radix(tokens, notes, 2, "0b") {
@@ -2463,6 +2969,9 @@ fn parse_variable(
                 Err(err) => return Err(err),
             }
         }
+        Some(Token::StringLiteral) if tokens.slice().starts_with('f') => {
+            ast::ValueBody::FormatStr(parse_format_str(tokens, notes)?)
+        }
         Some(Token::StringLiteral) => {
             // is a string
 
@@ -2565,6 +3074,7 @@ fn parse_variable(
         Some(Token::OpenSquareBracket) => {
             let mut potential_macro: Option<ast::ValueBody> = None;
 
+            let checkpoint = notes.checkpoint();
             if let Some(Token::OpenSquareBracket) = tokens.next(false) {
                 let mut test_tokens = tokens.clone();
                 if let Ok(mut v) = parse_variable(&mut test_tokens, notes, false, None) {
@@ -2640,6 +3150,8 @@ fn parse_variable(
             match potential_macro {
                 Some(x) => x,
                 None => {
+                    // it's parsed again as an array
+                    notes.restore(checkpoint);
                     tokens.previous_no_ignore(false);
 
                     //Array
@@ -2809,25 +3321,7 @@ fn parse_variable(
                 forced = true;
                 first = tokens.next(false);
             }
-            match first {
-                Some(Token::StringLiteral) => {
-                    let (content, flag) = str_content(tokens.slice(), tokens, notes)?;
-
-                    if flag.is_some() {
-                        return Err(SyntaxError::UnexpectedErr {
-                            file: notes.file.to_owned(),
-                            pos: tokens.position(),
-                            found: format!("string flag ({:?})", flag),
-                        });
-                    }
-
-                    ast::ValueBody::Import(ImportType::Script(PathBuf::from(content)), forced)
-                }
-                Some(Token::Symbol) => {
-                    ast::ValueBody::Import(ImportType::Lib(tokens.slice()), forced)
-                }
-                a => expected!("literal string".to_string(), tokens, notes, a),
-            }
+            ast::ValueBody::Import(parse_import_path(first, tokens, notes)?, forced)
         }
 
         Some(Token::At) => {
@@ -275,7 +275,37 @@ pub fn config_to_package(cfg: PathBuf) -> Result<Option<Package>, PckpError> {
                  .map(Package::dependency)
                  .collect::<Vec<_>>();
 
-                Ok(Some(Package::local(package_name, version, folders, depends)))
+                let mut permissions = Vec::new();
+                if let Some(list) =
+                    ensure_variant!(ymap, "list" = Array, "permissions"? from "root")?
+                {
+                    for v in list {
+                        match v {
+                            Yaml::String(name) => {
+                                if let Some(bad) = check_invalid(name) {
+                                    return Err(PckpError::config(
+                                        format!("Invalid character {} in permission {}", bad, name),
+                                        cfg,
+                                        None,
+                                    ));
+                                }
+                                permissions.push(name.clone())
+                            }
+                            b => {
+                                return Err(PckpError::config(
+                                    format!(
+                                        "Expected permission {:?} to be the name of a builtin",
+                                        b
+                                    ),
+                                    cfg,
+                                    None,
+                                ))
+                            }
+                        }
+                    }
+                }
+
+                Ok(Some(Package::local(package_name, version, folders, depends, permissions)))
             },
             Err(_) => {
                 Err(PckpError::config("Could not open configuration file".to_string(), cfg, None))
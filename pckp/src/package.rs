@@ -29,6 +29,8 @@ pub struct LocalPackage {
     pub version: String,
     pub paths: Vec<PathBuf>,
     pub dependencies: Vec<Package>,
+    // the unsafe builtins the package uses
+    pub permissions: Vec<String>,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -48,6 +50,7 @@ impl Package {
         version: String,
         paths: Vec<PathBuf>,
         dependencies: Vec<Package>,
+        permissions: Vec<String>,
     ) -> Package {
         Package {
             internal: PackageType::Local(LocalPackage {
@@ -55,6 +58,7 @@ impl Package {
                 version,
                 paths,
                 dependencies,
+                permissions,
             }),
         }
     }
@@ -125,6 +129,22 @@ impl Package {
                         fs_dir::copy(folder, &dest, &opts).unwrap();
                     }
 
+                    // the compiler reads the permissions from here, and the config file
+                    // isn't copied if it's not in one of the folders
+                    let cfg = get_config(Some(dest.clone()));
+                    if !cfg.exists() {
+                        fs::write(
+                            cfg,
+                            format!(
+                                "name: {}\nversion: {}\npermissions: [{}]\n",
+                                p.name,
+                                p.version,
+                                p.permissions.join(", ")
+                            ),
+                        )
+                        .unwrap();
+                    }
+
                     version_info.push((p.name.clone(), p.version.clone()));
                     //println!("package {:#?}", p);
                 }
@@ -1,13 +1,14 @@
 use std::path::PathBuf;
 
 use internment::LocalIntern;
+use serde::{Deserialize, Serialize};
 use slotmap::new_key_type;
 new_key_type! {
     pub struct StoredValue;
 } //index to stored value in globals.stored_values
 pub type FileRange = (usize, usize);
 
-#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+#[derive(PartialEq, Eq, Debug, Clone, Hash, Serialize, Deserialize)]
 pub enum ImportType {
     Script(PathBuf),
     Lib(String),
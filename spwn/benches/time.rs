@@ -1,5 +1,6 @@
-use criterion::{criterion_group, criterion_main, Criterion, Throughput};
-use internment::{LocalIntern};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use internment::LocalIntern;
+use parser::incremental::{ParsedFile, TextEdit};
 use shared::SpwnSource;
 use std::fs;
 use spwn::parse_spwn;
@@ -9,7 +10,7 @@ fn test_all(input: &str, src: SpwnSource) {
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
-    let input = fs::read_to_string("test/test.spwn").unwrap();
+    let input = fs::read_to_string("../test/test.spwn").unwrap();
     let src = LocalIntern::new("".to_string());
 
     let mut bmg = c.benchmark_group("main");
@@ -17,5 +18,25 @@ fn criterion_benchmark(c: &mut Criterion) {
     bmg.bench_function("test_all", |b| b.iter(|| test_all(&input, SpwnSource::String(src))));
 }
 
-criterion_group!(benches, criterion_benchmark);
+fn reparse_benchmark(c: &mut Criterion) {
+    let input = fs::read_to_string("../test/bf/bf_interpreter.spwn").unwrap();
+    let src = SpwnSource::String(LocalIntern::new("".to_string()));
+
+    // a one character edit in the middle of the file
+    let start = input.find("counter(555i)").unwrap() + "counter(55".len();
+    let edit = TextEdit::new((start, start + 1), "6");
+
+    let mut bmg = c.benchmark_group("bf_interpreter");
+    bmg.throughput(Throughput::Bytes(input.len() as u64));
+    bmg.bench_function("full_parse", |b| b.iter(|| test_all(&input, src.clone())));
+    bmg.bench_function("reparse_edit", |b| {
+        b.iter_batched(
+            || ParsedFile::parse(input.clone(), src.clone(), &[]),
+            |mut file| assert!(file.apply_edit(&edit).is_some()),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, criterion_benchmark, reparse_benchmark);
 criterion_main!(benches);
@@ -0,0 +1,649 @@
+// a debug adapter for `spwn dap`, so editors like vs code can stop the build at
+// breakpoints, step through the statements and look at the variables
+//
+// it talks the debug adapter protocol over stdin and stdout. the build runs on
+// the same thread, and while it's stopped the editor's requests are answered
+// from inside `DebugClient::paused`
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use ::compiler::builtins::{Builtin, BuiltinPermissions, BUILTIN_NAMES};
+use ::compiler::compiler::{compile_script, CompileOptions};
+use ::compiler::debugger::{DebugClient, Debugger, Paused, Step, StopReason};
+use ::compiler::globals::Globals;
+use ::compiler::value::Value;
+use ariadne::Cache;
+use errors::compiler_info::CodeArea;
+use errors::{create_report, create_warning_report, ErrorReport};
+use internment::LocalIntern;
+use serde_json::{json, Value as Json};
+use shared::{SpwnSource, StoredValue};
+
+use crate::parse_spwn;
+use crate::SpwnCache;
+
+// the build is the only thread
+const THREAD_ID: i64 = 1;
+
+struct Request {
+    seq: i64,
+    command: String,
+    arguments: Json,
+}
+
+struct Connection {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    seq: i64,
+    // the editor disconnected, so nothing more is sent
+    closed: bool,
+}
+
+impl Connection {
+    // returns `None` when the editor closes the input
+    fn read(&mut self) -> io::Result<Option<Request>> {
+        loop {
+            let mut length = None;
+            loop {
+                let mut line = String::new();
+                if self.input.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+                let line = line.trim();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some(len) = line.strip_prefix("Content-Length:") {
+                    length = len.trim().parse::<usize>().ok();
+                }
+            }
+            let length = length.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+            })?;
+            let mut content = vec![0; length];
+            self.input.read_exact(&mut content)?;
+            let message: Json = serde_json::from_slice(&content)?;
+
+            // the editor's responses to our requests aren't needed
+            if message["type"] == "request" {
+                return Ok(Some(Request {
+                    seq: message["seq"].as_i64().unwrap_or(0),
+                    command: message["command"].as_str().unwrap_or_default().to_string(),
+                    arguments: message["arguments"].clone(),
+                }));
+            }
+        }
+    }
+
+    fn send(&mut self, mut message: Json) -> io::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let content = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )?;
+        self.output.flush()
+    }
+
+    fn respond(&mut self, request: &Request, body: Json) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "success": true,
+            "command": request.command,
+            "body": body,
+        }))
+    }
+
+    fn fail(&mut self, request: &Request, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "success": false,
+            "command": request.command,
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+
+    fn output(&mut self, category: &str, output: &str) -> io::Result<()> {
+        self.event("output", json!({ "category": category, "output": output }))
+    }
+}
+
+// sends `$.print` to the editor's debug console
+struct Output(Rc<RefCell<Connection>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .borrow_mut()
+            .output("stdout", &String::from_utf8_lossy(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// what a `variablesReference` points to, while the build is stopped
+enum Reference {
+    // the variables of the nth context
+    Context(usize),
+    // the members of a dictionary or array
+    Value(StoredValue),
+}
+
+struct Session {
+    connection: Rc<RefCell<Connection>>,
+    lines_start_at_1: bool,
+    columns_start_at_1: bool,
+    // the lines with breakpoints in each file, counted from 0
+    breakpoints: HashMap<PathBuf, HashSet<usize>>,
+    // the canonical path of every file the compiler has been in
+    paths: HashMap<LocalIntern<SpwnSource>, Option<PathBuf>>,
+    sources: SpwnCache,
+    references: Vec<Reference>,
+}
+
+impl Session {
+    fn path(&mut self, file: LocalIntern<SpwnSource>) -> Option<&PathBuf> {
+        self.paths
+            .entry(file)
+            .or_insert_with(|| match file.as_ref() {
+                SpwnSource::File(path) => {
+                    Some(path.canonicalize().unwrap_or_else(|_| path.clone()))
+                }
+                _ => None,
+            })
+            .as_ref()
+    }
+
+    // the line and column of an offset, counted from 0
+    fn position(&mut self, file: &SpwnSource, offset: usize) -> Option<(usize, usize)> {
+        let source = self.sources.fetch(file).ok()?;
+        let (_, line, column) = source.get_offset_line(offset)?;
+        Some((line, column))
+    }
+
+    fn source(&mut self, file: &SpwnSource) -> Json {
+        match file {
+            SpwnSource::File(path) => json!({
+                "name": path.file_name().map(|n| n.to_string_lossy().to_string()),
+                "path": self.path(LocalIntern::new(file.clone())),
+            }),
+            // the libraries built into spwn can't be opened by the editor
+            SpwnSource::BuiltIn(path) => json!({
+                "name": path.display().to_string(),
+                "presentationHint": "deemphasize",
+            }),
+            SpwnSource::String(_) => json!({ "name": "<source>" }),
+        }
+    }
+
+    fn line_base(&self) -> usize {
+        self.lines_start_at_1 as usize
+    }
+
+    // answers the requests that don't need the build to be stopped
+    fn handle(&mut self, request: &Request) -> io::Result<()> {
+        let args = &request.arguments;
+        let body = match request.command.as_str() {
+            "initialize" => {
+                self.lines_start_at_1 = args["linesStartAt1"].as_bool().unwrap_or(true);
+                self.columns_start_at_1 = args["columnsStartAt1"].as_bool().unwrap_or(true);
+                let mut connection = self.connection.borrow_mut();
+                connection.respond(request, json!({ "supportsConfigurationDoneRequest": true }))?;
+                return connection.event("initialized", json!({}));
+            }
+            "setBreakpoints" => {
+                let path = PathBuf::from(args["source"]["path"].as_str().unwrap_or_default());
+                let path = path.canonicalize().unwrap_or(path);
+                let lines: Vec<usize> = args["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|b| b["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect();
+                let base = self.line_base();
+                self.breakpoints.insert(
+                    path,
+                    lines.iter().map(|line| line.saturating_sub(base)).collect(),
+                );
+                json!({
+                    "breakpoints": lines
+                        .iter()
+                        .map(|line| json!({ "verified": true, "line": line }))
+                        .collect::<Vec<_>>(),
+                })
+            }
+            "setExceptionBreakpoints" | "launch" | "configurationDone" => json!({}),
+            "threads" => json!({ "threads": [{ "id": THREAD_ID, "name": "build" }] }),
+            _ => {
+                return self
+                    .connection
+                    .borrow_mut()
+                    .fail(request, "the build isn't stopped")
+            }
+        };
+        self.connection.borrow_mut().respond(request, body)
+    }
+
+    fn stack_trace(&mut self, paused: &Paused) -> Json {
+        // the innermost frame is where the build stopped, and the others are the
+        // macro calls that led there
+        let mut areas = vec![paused.area];
+        areas.extend(paused.call_stack.iter().rev());
+        let outer = match paused
+            .call_stack
+            .first()
+            .unwrap_or(&paused.area)
+            .file
+            .as_ref()
+        {
+            SpwnSource::File(path) => path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            _ => "<source>".to_string(),
+        };
+
+        let mut frames = Vec::new();
+        for (id, area) in areas.iter().enumerate() {
+            // the name of a frame is the call it's inside of
+            let name = match paused.call_stack.len().checked_sub(id + 1) {
+                Some(call) => self.snippet(paused.call_stack[call]),
+                None => outer.clone(),
+            };
+            let (line, column) = self.position(&area.file, area.pos.0).unwrap_or((0, 0));
+            frames.push(json!({
+                "id": id,
+                "name": name,
+                "source": self.source(&area.file),
+                "line": line + self.line_base(),
+                "column": column + self.columns_start_at_1 as usize,
+            }));
+        }
+        json!({ "stackFrames": frames, "totalFrames": areas.len() })
+    }
+
+    // the first line of the code at `area`
+    fn snippet(&mut self, area: CodeArea) -> String {
+        let source = match self.sources.fetch(&area.file) {
+            Ok(source) => source,
+            Err(_) => return String::new(),
+        };
+        match source.get_offset_line(area.pos.0) {
+            Some((line, _, column)) => line
+                .chars()
+                .skip(column)
+                .take(area.pos.1.saturating_sub(area.pos.0))
+                .take_while(|c| *c != '\n')
+                .collect::<String>()
+                .trim()
+                .to_string(),
+            None => String::new(),
+        }
+    }
+
+    fn scopes(&mut self, paused: &mut Paused, frame: i64) -> Json {
+        // only the variables where the build stopped can be looked at, since the
+        // callers' contexts aren't kept around
+        if frame != 0 {
+            return json!({ "scopes": [] });
+        }
+        let groups: Vec<_> = paused
+            .contexts
+            .iter()
+            .map(|c| c.inner().start_group)
+            .collect();
+        let single = groups.len() == 1;
+        let mut scopes = Vec::new();
+        for (i, group) in groups.into_iter().enumerate() {
+            let name = if single {
+                "Variables".to_string()
+            } else {
+                format!(
+                    "Context {} ({})",
+                    i + 1,
+                    Value::Group(group).to_str(paused.globals)
+                )
+            };
+            self.references.push(Reference::Context(i));
+            scopes.push(json!({
+                "name": name,
+                "variablesReference": self.references.len(),
+                "expensive": false,
+            }));
+        }
+        json!({ "scopes": scopes })
+    }
+
+    fn variable(&mut self, name: String, val: StoredValue, globals: &mut Globals) -> Json {
+        let value = &globals.stored_values[val];
+        let has_members = match value {
+            Value::Dict(d) => !d.is_empty(),
+            Value::Array(a) => !a.is_empty(),
+            _ => false,
+        };
+        let reference = if has_members {
+            self.references.push(Reference::Value(val));
+            self.references.len()
+        } else {
+            0
+        };
+        json!({
+            "name": name,
+            "type": format!("@{}", value.get_type_str(globals)),
+            "value": value.clone().to_str(globals),
+            "variablesReference": reference,
+        })
+    }
+
+    fn variables(&mut self, paused: &mut Paused, reference: usize) -> Json {
+        let members: Vec<(String, StoredValue)> = match reference
+            .checked_sub(1)
+            .and_then(|i| self.references.get(i))
+        {
+            Some(Reference::Context(i)) => match paused.contexts.iter().nth(*i) {
+                Some(context) => {
+                    let mut vars: Vec<_> = context
+                        .inner()
+                        .get_variables()
+                        .iter()
+                        .filter_map(|(name, stack)| Some((name.to_string(), stack.last()?.val)))
+                        .collect();
+                    vars.sort();
+                    vars
+                }
+                None => Vec::new(),
+            },
+            Some(Reference::Value(val)) => match &paused.globals.stored_values[*val] {
+                Value::Dict(dict) => {
+                    let mut members: Vec<_> =
+                        dict.iter().map(|(k, v)| (k.to_string(), *v)).collect();
+                    members.sort();
+                    members
+                }
+                Value::Array(array) => array
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (i.to_string(), *v))
+                    .collect(),
+                _ => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+        let variables: Vec<_> = members
+            .into_iter()
+            .map(|(name, val)| self.variable(name, val, paused.globals))
+            .collect();
+        json!({ "variables": variables })
+    }
+
+    // answers the editor's requests until it says how to carry on, or returns
+    // `None` if it disconnects
+    fn wait(&mut self, paused: &mut Paused) -> io::Result<Option<Step>> {
+        loop {
+            let request = match self.connection.borrow_mut().read()? {
+                Some(request) => request,
+                None => return Ok(None),
+            };
+            let args = &request.arguments;
+            let (step, body) = match request.command.as_str() {
+                "continue" => (Some(Step::Continue), json!({ "allThreadsContinued": true })),
+                "next" => (Some(Step::Over), json!({})),
+                "stepIn" => (Some(Step::In), json!({})),
+                "stepOut" => (Some(Step::Out), json!({})),
+                "disconnect" => {
+                    self.connection.borrow_mut().respond(&request, json!({}))?;
+                    return Ok(None);
+                }
+                "stackTrace" => (None, self.stack_trace(paused)),
+                "scopes" => {
+                    let frame = args["frameId"].as_i64().unwrap_or(0);
+                    (None, self.scopes(paused, frame))
+                }
+                "variables" => {
+                    let reference = args["variablesReference"].as_u64().unwrap_or(0);
+                    (None, self.variables(paused, reference as usize))
+                }
+                _ => {
+                    self.handle(&request)?;
+                    continue;
+                }
+            };
+            self.connection.borrow_mut().respond(&request, body)?;
+            if step.is_some() {
+                return Ok(step);
+            }
+        }
+    }
+
+    fn detach(&mut self) {
+        self.breakpoints.clear();
+        self.connection.borrow_mut().closed = true;
+    }
+}
+
+impl DebugClient for Session {
+    fn has_breakpoint(&mut self, area: CodeArea) -> bool {
+        if self.breakpoints.is_empty() {
+            return false;
+        }
+        let path = match self.path(area.file) {
+            Some(path) => path.clone(),
+            None => return false,
+        };
+        if !self.breakpoints.contains_key(&path) {
+            return false;
+        }
+        match self.position(&area.file, area.pos.0) {
+            Some((line, _)) => self.breakpoints[&path].contains(&line),
+            None => false,
+        }
+    }
+
+    // stepping skips the libraries built into spwn, since the editor can't show them
+    fn can_stop(&mut self, area: CodeArea) -> bool {
+        !self.connection.borrow().closed && matches!(area.file.as_ref(), SpwnSource::File(_))
+    }
+
+    fn paused(&mut self, mut paused: Paused) -> Step {
+        if self.connection.borrow().closed {
+            return Step::Continue;
+        }
+        self.references.clear();
+        let (reason, description) = match paused.reason {
+            StopReason::Entry => ("entry", "Paused on entry"),
+            StopReason::Breakpoint => ("breakpoint", "Paused on breakpoint"),
+            StopReason::BreakpointCall => ("breakpoint", "Paused on $.breakpoint()"),
+            StopReason::Step => ("step", "Paused"),
+        };
+        let stopped = self.connection.borrow_mut().event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "description": description,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        );
+        match stopped.and_then(|_| self.wait(&mut paused)) {
+            Ok(Some(step)) => step,
+            // the editor is gone, so the build just finishes
+            _ => {
+                self.detach();
+                Step::Continue
+            }
+        }
+    }
+}
+
+/// Starts a debug adapter that talks over stdin and stdout
+pub fn run(
+    included_paths: Vec<PathBuf>,
+    options: CompileOptions,
+    permissions: BuiltinPermissions,
+) -> io::Result<()> {
+    run_with(
+        Box::new(io::BufReader::new(io::stdin())),
+        Box::new(io::stdout()),
+        included_paths,
+        options,
+        permissions,
+    )
+}
+
+/// Runs a debug adapter session with the editor on the other side of `input` and `output`
+pub fn run_with(
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    included_paths: Vec<PathBuf>,
+    options: CompileOptions,
+    permissions: BuiltinPermissions,
+) -> io::Result<()> {
+    let connection = Rc::new(RefCell::new(Connection {
+        input,
+        output,
+        seq: 0,
+        closed: false,
+    }));
+    let session = Rc::new(RefCell::new(Session {
+        connection: connection.clone(),
+        lines_start_at_1: true,
+        columns_start_at_1: true,
+        breakpoints: HashMap::new(),
+        paths: HashMap::new(),
+        sources: SpwnCache::default(),
+        references: Vec::new(),
+    }));
+
+    // the build starts once the editor has launched it and set the breakpoints
+    let mut launch = None;
+    let mut configured = false;
+    loop {
+        let request = match connection.borrow_mut().read()? {
+            Some(request) => request,
+            None => return Ok(()),
+        };
+        match request.command.as_str() {
+            "launch" => launch = Some(request.arguments.clone()),
+            "configurationDone" => configured = true,
+            "disconnect" => return connection.borrow_mut().respond(&request, json!({})),
+            _ => (),
+        }
+        session.borrow_mut().handle(&request)?;
+
+        if configured {
+            if let Some(launch) = launch.take() {
+                let mut options = options.clone();
+                let client: Rc<RefCell<dyn DebugClient>> = session.clone();
+                options.debugger = Some(Debugger::new(
+                    client,
+                    launch["stopOnEntry"].as_bool().unwrap_or(false),
+                ));
+                let program = PathBuf::from(launch["program"].as_str().unwrap_or_default());
+                let exit_code = build(
+                    program,
+                    included_paths.clone(),
+                    options,
+                    permissions.clone(),
+                    &connection,
+                )?;
+                if connection.borrow().closed {
+                    return Ok(());
+                }
+                let mut connection = connection.borrow_mut();
+                connection.event("terminated", json!({}))?;
+                connection.event("exited", json!({ "exitCode": exit_code }))?;
+            }
+        }
+    }
+}
+
+// builds the script and sends the errors and warnings to the debug console,
+// and returns the exit code
+fn build(
+    program: PathBuf,
+    included_paths: Vec<PathBuf>,
+    options: CompileOptions,
+    mut permissions: BuiltinPermissions,
+    connection: &Rc<RefCell<Connection>>,
+) -> io::Result<i64> {
+    // stdin belongs to the editor, so the answers have to come from --input-file
+    if options.inputs.is_none() {
+        permissions.set(Builtin::GetInput, false);
+    }
+    let mut cache = SpwnCache::default();
+    let report = |report: ariadne::Report<CodeArea>, cache: &mut SpwnCache| {
+        let mut text = Vec::new();
+        report.write(cache, &mut text).ok();
+        connection
+            .borrow_mut()
+            .output("stderr", &String::from_utf8_lossy(&text))
+    };
+
+    let code = match fs::read_to_string(&program) {
+        Ok(code) => code,
+        Err(e) => {
+            connection.borrow_mut().output(
+                "stderr",
+                &format!("Could not read {}: {}\n", program.display(), e),
+            )?;
+            return Ok(1);
+        }
+    };
+    let source = SpwnSource::File(program);
+    let (statements, notes) = match parse_spwn(code, source.clone(), BUILTIN_NAMES) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            report(create_report(ErrorReport::from(err)), &mut cache)?;
+            return Ok(1);
+        }
+    };
+
+    let mut std_out = Output(connection.clone());
+    let compiled = compile_script(
+        statements,
+        source,
+        included_paths,
+        options,
+        notes,
+        permissions,
+        String::new(),
+        &mut std_out,
+    );
+    match compiled {
+        Ok(mut globals) => {
+            for warning in std::mem::take(&mut globals.warnings) {
+                report(create_warning_report(warning), &mut cache)?;
+            }
+            Ok(0)
+        }
+        Err(err) => {
+            report(create_report(ErrorReport::from(err)), &mut cache)?;
+            Ok(1)
+        }
+    }
+}
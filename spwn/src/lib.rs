@@ -7,6 +7,8 @@ pub use ::compiler::compiler_types;
 pub use ::compiler::context;
 pub use ::compiler::globals;
 pub use ::compiler::leveldata;
+pub use ::compiler::profiler;
+pub use ::compiler::sync_groups;
 pub use ::compiler::value;
 pub use ::compiler::value_storage;
 pub use ::docgen::documentation;
@@ -32,6 +34,12 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
+pub mod dap;
+pub mod profile;
+pub mod repl;
+pub mod source_map;
+pub mod trigger_report;
+
 #[derive(Default)]
 pub struct SpwnCache {
     files: HashMap<SpwnSource, Source>,
@@ -62,12 +70,12 @@ impl ariadne::Cache<SpwnSource> for SpwnCache {
     }
 }
 
-pub fn run_spwn(
+fn compile_source<'a>(
     code: String,
+    source: SpwnSource,
     included: Vec<PathBuf>,
-    optimize: bool,
-) -> Result<[String; 2], String> {
-    let source = SpwnSource::String(LocalIntern::new(code.clone()));
+    std_out: &'a mut Vec<u8>,
+) -> Result<globals::Globals<'a>, String> {
     let cache = SpwnCache::default();
     let (statements, notes) = match parse_spwn(code, source.clone(), BUILTIN_NAMES) {
         Ok(a) => a,
@@ -80,36 +88,63 @@ pub fn run_spwn(
         }
     };
 
-    let mut std_out = Vec::<u8>::new();
-
-    let mut compiled = match compiler::compile_spwn(
+    match compiler::compile_spwn(
         statements,
         source,
         included,
+        Default::default(),
         notes,
         Default::default(),
         "".to_string(),
-        &mut std_out,
+        std_out,
     ) {
-        Ok(a) => a,
+        Ok(a) => Ok(a),
         Err(e) => {
             let mut out = Vec::<u8>::new();
             create_report(ErrorReport::from(e))
                 .write(cache, &mut out)
                 .unwrap();
-            return Err(String::from_utf8_lossy(&out).to_string());
+            Err(String::from_utf8_lossy(&out).to_string())
         }
-    };
+    }
+}
+
+// the ids the optimizer is not allowed to remove
+pub fn reserved_ids(compiled: &globals::Globals) -> optimizer::ReservedIds {
+    let mut reserved = optimizer::ReservedIds::from_objects(&compiled.objects, &compiled.func_ids);
+    // the sync group toggles seem useless until the groups are shared
+    reserved
+        .object_groups
+        .extend(sync_groups::toggle_groups(&compiled.sync_groups));
+    reserved
+}
+
+pub fn run_spwn(
+    code: String,
+    included: Vec<PathBuf>,
+    optimize: bool,
+) -> Result<[String; 2], String> {
+    let source = SpwnSource::String(LocalIntern::new(code.clone()));
+
+    let mut std_out = Vec::<u8>::new();
+
+    let mut compiled = compile_source(code, source, included, &mut std_out)?;
 
     let has_stuff = compiled.func_ids.iter().any(|x| !x.obj_list.is_empty());
 
-    let reserved = optimizer::ReservedIds::from_objects(&compiled.objects, &compiled.func_ids);
+    let reserved = reserved_ids(&compiled);
 
     if has_stuff && optimize {
         compiled.func_ids =
             optimizer::optimize::optimize(compiled.func_ids, compiled.closed_groups, reserved);
     }
 
+    sync_groups::share_groups(
+        &mut compiled.func_ids,
+        &compiled.objects,
+        &compiled.sync_groups,
+    );
+
     let mut objects = leveldata::apply_fn_ids(&compiled.func_ids);
 
     objects.extend(compiled.objects);
@@ -118,6 +153,106 @@ pub fn run_spwn(
 
     Ok([String::from_utf8_lossy(&std_out).to_string(), new_ls])
 }
+
+// compiles the code, optimizes the triggers and checks that the
+// optimized triggers have the same effects as the original ones
+pub fn verify_optimization(
+    code: String,
+    source: SpwnSource,
+    included: Vec<PathBuf>,
+) -> Result<optimizer::verify::VerifyReport, String> {
+    let mut std_out = Vec::<u8>::new();
+
+    let compiled = compile_source(code, source, included, &mut std_out)?;
+
+    let reserved = reserved_ids(&compiled);
+
+    let (_, report) =
+        optimizer::verify::optimize_and_verify(compiled.func_ids, compiled.closed_groups, reserved);
+    Ok(report)
+}
+
+// the syntax tree of a file without the positions, to compare two versions of it
+fn syntax_tree(statements: &[ast::Statement], notes: &parser::ParseNotes) -> String {
+    let text = format!("{:?} {:?}", statements, notes.tag);
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(i) = rest.find('(') {
+        out += &rest[..i];
+        rest = &rest[i..];
+        // positions are written as `(start, end)`
+        let is_pos = rest.find(')').is_some_and(|end| {
+            rest[1..end]
+                .split(", ")
+                .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+                && rest[1..end].contains(", ")
+        });
+        if is_pos {
+            rest = &rest[rest.find(')').unwrap() + 1..];
+        } else {
+            out.push('(');
+            rest = &rest[1..];
+        }
+    }
+    out + rest
+}
+
+fn syntax_errors(errors: Vec<errors::SyntaxError>) -> String {
+    let mut cache = SpwnCache::default();
+    let mut out = Vec::<u8>::new();
+    for e in errors {
+        create_report(ErrorReport::from(e))
+            .write(&mut cache, &mut out)
+            .unwrap();
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+// formats a file, and checks that the formatted code is parsed the same way
+pub fn format_spwn(code: String, source: SpwnSource) -> Result<String, String> {
+    let (statements, notes, errors) =
+        parser::parse_spwn_partial(code.clone(), source.clone(), BUILTIN_NAMES);
+    if !errors.is_empty() {
+        return Err(syntax_errors(errors));
+    }
+    let formatted = fmt::format(&statements, &notes.tag, &code);
+
+    let (new_statements, new_notes, errors) =
+        parser::parse_spwn_partial(formatted.clone(), source, BUILTIN_NAMES);
+    if !errors.is_empty() {
+        return Err(format!(
+            "the formatted code has a syntax error, which is a bug in the formatter:\n{}",
+            syntax_errors(errors)
+        ));
+    }
+    if syntax_tree(&statements, &notes) != syntax_tree(&new_statements, &new_notes) {
+        return Err(
+            "the formatted code does something different, which is a bug in the formatter"
+                .to_string(),
+        );
+    }
+    Ok(formatted)
+}
+
+// the syntax tree of a file as json, for external tools
+pub fn ast_json(code: String, source: SpwnSource) -> Result<String, String> {
+    #[derive(serde::Serialize)]
+    struct Ast<'a> {
+        file_tag: &'a ast::Attribute,
+        statements: &'a [ast::Statement],
+    }
+
+    let (statements, notes, errors) = parser::parse_spwn_partial(code, source, BUILTIN_NAMES);
+    if !errors.is_empty() {
+        return Err(syntax_errors(errors));
+    }
+    serde_json::to_string_pretty(&Ast {
+        file_tag: &notes.tag,
+        statements: &statements,
+    })
+    .map_err(|e| e.to_string())
+}
+
 #[cfg(test)]
 mod tests;
 
@@ -141,6 +276,7 @@ pub fn run_all_doc_examples() {
     globals.includes.push(PathBuf::from("./"));
 
     let mut start_context = context::FullContext::new(&globals);
+    start_context.set_root();
 
     let info = compiler_info::CompilerInfo::new();
 
@@ -221,7 +357,7 @@ pub fn run_all_doc_examples() {
         globals.objects.clear();
 
         let mut contexts = start_context.clone();
-        contexts.inner().root_context_ptr = &mut contexts;
+        contexts.set_root();
 
         match compiler::compile_scope(&statements, &mut contexts, &mut globals, info.clone()) {
             Ok(_) => (),
@@ -256,7 +392,7 @@ fn add_tests(
     all_tests: &mut Vec<(String, String)>,
 ) {
     match val {
-        value::Value::Macro(m) => {
+        value::Value::Macro(value::Macro::FuncLike(m)) => {
             if let Some(example) = m.tag.get_example(true) {
                 all_tests.push((name, example));
             }
@@ -9,20 +9,27 @@ use std::io::Read;
 use ::docgen::documentation;
 
 use ::compiler::leveldata;
+use ::compiler::path_scopes::FsAccess;
+use ::compiler::sync_groups;
 
 use optimizer::optimize;
 
 use ariadne::Cache;
 
 use optimize::optimize;
+use optimizer::verify::optimize_and_verify;
 
 use ::parser::parser::*;
 use builtins::BuiltinPermissions;
 
 use shared::SpwnSource;
+use spwn::profile::profile;
+use spwn::reserved_ids;
+use spwn::source_map::source_map;
+use spwn::trigger_report::trigger_report;
 use spwn::SpwnCache;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use editorlive::editorlive::editor_paste;
 use std::fs;
@@ -36,7 +43,7 @@ use clap::Command;
 use std::io::Write;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
-use errors::{create_report, ErrorReport};
+use errors::{create_report, create_warning_report, ErrorReport, Lint, Warning};
 
 fn print_with_color(text: &str, color: Color) {
     let mut stdout = StandardStream::stdout(ColorChoice::Always);
@@ -56,18 +63,54 @@ fn eprint_with_color(text: &str, color: termcolor::Color) {
     stdout.set_color(&ColorSpec::new()).unwrap();
 }
 
+// loading a module from the cache runs its code, so the cache is kept in the
+// user's own cache directory, where only they can change it
+fn module_cache_dir() -> Option<PathBuf> {
+    let dir = dirs::cache_dir()?.join("spwn");
+    fs::create_dir_all(&dir).ok()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // this also fails if the directory belongs to someone else
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).ok()?;
+    }
+    Some(dir)
+}
+
+// the warnings that are shown while building, see `CompileOptions::show_warning`
+fn show_warning_now(warning: &Warning) {
+    create_warning_report(warning.clone())
+        .eprint(SpwnCache::default())
+        .unwrap();
+}
+
 pub struct BuildOptions<'a> {
     permissions: BuiltinPermissions,
     include_paths: Vec<PathBuf>,
     gd_enabled: bool,
     opti_enabled: bool,
+    verify_opt: bool,
+    trigger_report: bool,
     level_name: Option<String>,
     live_editor: bool,
     save_file: Option<&'a str>,
+    deny_warnings: bool,
+    compile_options: compiler::CompileOptions,
+    // where the folded stacks from the profiler are written
+    profile_file: Option<PathBuf>,
+    // `Some(None)` if the source map goes next to the script
+    source_map: Option<Option<PathBuf>>,
 }
 
-impl<'a> BuildOptions<'a> {
-    fn from(build_cmd: &'a clap::ArgMatches) -> Result<Self, std::io::Error> {
+// the options for compiling, which `repl` shares with `build` and `eval`
+struct CompileSettings {
+    permissions: BuiltinPermissions,
+    include_paths: Vec<PathBuf>,
+    compile_options: compiler::CompileOptions,
+}
+
+impl CompileSettings {
+    fn from(cmd: &clap::ArgMatches) -> Result<Self, std::io::Error> {
         let mut permissions = BuiltinPermissions::new();
         let mut include_paths = vec![
             std::env::current_dir().expect("Cannot access current directory"),
@@ -77,47 +120,122 @@ impl<'a> BuildOptions<'a> {
                 .to_path_buf(),
         ];
 
+        let context_count = |name| {
+            cmd.value_of(name).map(|val| {
+                val.parse::<usize>()
+                    .unwrap_or_else(|_| panic!("Invalid number of contexts: {}", val))
+            })
+        };
+        let compile_options = compiler::CompileOptions {
+            module_cache: if cmd.is_present("no-cache") {
+                None
+            } else {
+                module_cache_dir()
+            },
+            profile: false,
+            context_warning: match context_count("context-warning") {
+                Some(0) => None,
+                Some(n) => Some(n),
+                None => compiler::CompileOptions::default().context_warning,
+            },
+            max_contexts: context_count("max-contexts"),
+            show_warning: None,
+            debugger: None,
+            seed: cmd.value_of("seed").map(|val| {
+                val.parse::<u64>()
+                    .unwrap_or_else(|_| panic!("Invalid seed: {}", val))
+            }),
+            fixed_time: cmd.value_of("fixed-time").map(|val| {
+                val.parse::<f64>()
+                    .unwrap_or_else(|_| panic!("Invalid time: {}", val))
+            }),
+            inputs: match cmd.value_of("input-file") {
+                Some(file) => Some(
+                    std::fs::read_to_string(file)?
+                        .lines()
+                        .map(String::from)
+                        .collect(),
+                ),
+                None => None,
+            },
+            cancel: None,
+        };
+
+        cmd.values_of("include-path")
+            .unwrap_or_default()
+            .for_each(|val| include_paths.push(val.into()));
+
+        cmd.values_of("allow").unwrap_or_default().for_each(|val| {
+            permissions.set(
+                val.parse()
+                    .unwrap_or_else(|_| panic!("Invalid builtin name: {}", val)),
+                true,
+            )
+        });
+
+        cmd.values_of("deny").unwrap_or_default().for_each(|val| {
+            permissions.set(
+                val.parse()
+                    .unwrap_or_else(|_| panic!("Invalid builtin name: {}", val)),
+                false,
+            )
+        });
+        for (name, access) in [
+            ("allow-read", FsAccess::Read),
+            ("allow-write", FsAccess::Write),
+        ] {
+            cmd.values_of(name)
+                .unwrap_or_default()
+                .for_each(|dir| permissions.allow_path(access, Path::new(dir)));
+        }
+        Ok(CompileSettings {
+            permissions,
+            include_paths,
+            compile_options,
+        })
+    }
+}
+
+impl<'a> BuildOptions<'a> {
+    fn from(build_cmd: &'a clap::ArgMatches) -> Result<Self, std::io::Error> {
+        let CompileSettings {
+            permissions,
+            include_paths,
+            mut compile_options,
+        } = CompileSettings::from(build_cmd)?;
+
         let gd_enabled =
             !build_cmd.is_present("no-level") && !build_cmd.is_present("console-output");
         let opti_enabled = !build_cmd.is_present("no-optimize");
+        let verify_opt = build_cmd.is_present("verify-opt");
+        let trigger_report = build_cmd.is_present("trigger-report");
         let level_name = build_cmd.value_of("level-name").map(str::to_string);
         let live_editor = build_cmd.is_present("live-editor");
         let save_file = build_cmd.value_of("save-file");
+        let deny_warnings = build_cmd.is_present("deny-warnings");
+        let profile_file = build_cmd.value_of("profile").map(PathBuf::from);
+        compile_options.profile = profile_file.is_some();
+        compile_options.show_warning = Some(show_warning_now);
+        let source_map = if build_cmd.is_present("source-map") {
+            Some(build_cmd.value_of("source-map").map(PathBuf::from))
+        } else {
+            None
+        };
 
-        build_cmd
-            .values_of("include-path")
-            .unwrap_or_default()
-            .for_each(|val| include_paths.push(val.into()));
-
-        build_cmd
-            .values_of("allow")
-            .unwrap_or_default()
-            .for_each(|val| {
-                permissions.set(
-                    val.parse()
-                        .unwrap_or_else(|_| panic!("Invalid builtin name: {}", val)),
-                    true,
-                )
-            });
-
-        build_cmd
-            .values_of("deny")
-            .unwrap_or_default()
-            .for_each(|val| {
-                permissions.set(
-                    val.parse()
-                        .unwrap_or_else(|_| panic!("Invalid builtin name: {}", val)),
-                    false,
-                )
-            });
         Ok(BuildOptions {
             permissions,
             include_paths,
             gd_enabled,
             opti_enabled,
+            verify_opt,
+            trigger_report,
             level_name,
             live_editor,
             save_file,
+            deny_warnings,
+            compile_options,
+            profile_file,
+            source_map,
         })
     }
 }
@@ -135,12 +253,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     arg!(-c --"console-output" "Makes the script print the created level into the console instead of writing it to your save file"),
                     arg!(-l --"no-level" "Only compiles the script, no level creation at all"),
                     arg!(-o --"no-optimize" "Removes post-optimization of triggers, making the output more readable, while also using a lot more objects and groups"),
+                    arg!(--"verify-opt" "Checks that the optimized triggers have the same effects as the original ones"),
+                    arg!(--"trigger-report" "Shows which files, lines and macro calls the objects, groups and items come from, before and after optimization"),
                     arg!(-n --"level-name" [NAME] "Targets a specific level"),
                     arg!(-e --"live-editor" "Instead of writing the level to the save file, the script will use a live editor library if it's installed (Currently works only for MacOS)"),
                     arg!(-s --"save-file" [FILE] "Chooses a specific save file to write to"),
                     arg!(-i --"include-path" "Adds a search path to look for libraries").takes_value(true).multiple_occurrences(true).min_values(0),
                     arg!(-a --allow "Allow the use of a builtin").takes_value(true).multiple_occurrences(true).min_values(0),
                     arg!(-d --deny "Deny the use of a builtin").takes_value(true).multiple_occurrences(true).min_values(0),
+                    arg!(--"allow-read" <DIR> "Allows the builtins that read files and directories, but only inside DIR").required(false).multiple_occurrences(true),
+                    arg!(--"allow-write" <DIR> "Allows the builtins that write and delete files and directories, but only inside DIR").required(false).multiple_occurrences(true),
+                    arg!(--"deny-warnings" "Fails the build if there are any warnings"),
+                    arg!(--"no-cache" "Compiles every library again instead of using the ones saved by earlier builds"),
+                    arg!(--profile [FILE] "Writes the time spent in every macro call and import to a folded stack file, for flamegraph tools"),
+                    arg!(--"source-map" [FILE] "Writes which lines of code made each object in the level to a JSON file (next to the script by default)"),
+                    arg!(--"context-warning" [N] "Warns when code is compiled in more than N contexts at once, and shows where they were split (0 turns the warning off)"),
+                    arg!(--"max-contexts" [N] "Fails the build when code is compiled in more than N contexts at once, instead of getting slower and slower"),
+                    arg!(--seed <N> "Seeds $.random so it gives the same values every build, instead of the #[seed] of the script").required(false),
+                    arg!(--"fixed-time" <SECONDS> "Makes $.time return this time instead of the system time").required(false),
+                    arg!(--"input-file" <FILE> "Answers $.get_input with the lines of this file instead of asking").required(false),
                 ]),
 
             Command::new("eval")
@@ -150,12 +281,41 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     arg!(-c --"console-output" "Makes the script print the created level into the console instead of writing it to your save file"),
                     arg!(-l --"no-level" "Only compiles the script, no level creation at all"),
                     arg!(-o --"no-optimize" "Removes post-optimization of triggers, making the output more readable, while also using a lot more objects and groups"),
+                    arg!(--"verify-opt" "Checks that the optimized triggers have the same effects as the original ones"),
+                    arg!(--"trigger-report" "Shows which files, lines and macro calls the objects, groups and items come from, before and after optimization"),
                     arg!(-n --"level-name" [NAME] "Targets a specific level"),
                     arg!(-e --"live-editor" "Instead of writing the level to the save file, the script will use a live editor library if it's installed (Currently works only for MacOS)"),
                     arg!(-s --"save-file" [FILE] "Chooses a specific save file to write to"),
                     arg!(-i --"include-path" "Adds a search path to look for libraries").takes_value(true).multiple_occurrences(true).min_values(0),
                     arg!(-a --allow "Allow the use of a builtin").takes_value(true).multiple_occurrences(true).min_values(0),
                     arg!(-d --deny "Deny the use of a builtin").takes_value(true).multiple_occurrences(true).min_values(0),
+                    arg!(--"allow-read" <DIR> "Allows the builtins that read files and directories, but only inside DIR").required(false).multiple_occurrences(true),
+                    arg!(--"allow-write" <DIR> "Allows the builtins that write and delete files and directories, but only inside DIR").required(false).multiple_occurrences(true),
+                    arg!(--"deny-warnings" "Fails the build if there are any warnings"),
+                    arg!(--"no-cache" "Compiles every library again instead of using the ones saved by earlier builds"),
+                    arg!(--profile [FILE] "Writes the time spent in every macro call and import to a folded stack file, for flamegraph tools"),
+                    arg!(--"source-map" [FILE] "Writes which lines of code made each object in the level to a JSON file (next to the script by default)"),
+                    arg!(--"context-warning" [N] "Warns when code is compiled in more than N contexts at once, and shows where they were split (0 turns the warning off)"),
+                    arg!(--"max-contexts" [N] "Fails the build when code is compiled in more than N contexts at once, instead of getting slower and slower"),
+                    arg!(--seed <N> "Seeds $.random so it gives the same values every build, instead of the #[seed] of the script").required(false),
+                    arg!(--"fixed-time" <SECONDS> "Makes $.time return this time instead of the system time").required(false),
+                    arg!(--"input-file" <FILE> "Answers $.get_input with the lines of this file instead of asking").required(false),
+                ]),
+
+            Command::new("repl")
+                .about("Starts an interactive session, where every input is compiled with the variables from the ones before")
+                .args(&[
+                    arg!(-i --"include-path" "Adds a search path to look for libraries").takes_value(true).multiple_occurrences(true).min_values(0),
+                    arg!(-a --allow "Allow the use of a builtin").takes_value(true).multiple_occurrences(true).min_values(0),
+                    arg!(-d --deny "Deny the use of a builtin").takes_value(true).multiple_occurrences(true).min_values(0),
+                    arg!(--"allow-read" <DIR> "Allows the builtins that read files and directories, but only inside DIR").required(false).multiple_occurrences(true),
+                    arg!(--"allow-write" <DIR> "Allows the builtins that write and delete files and directories, but only inside DIR").required(false).multiple_occurrences(true),
+                    arg!(--"no-cache" "Compiles every library again instead of using the ones saved by earlier builds"),
+                    arg!(--"context-warning" [N] "Warns when code is compiled in more than N contexts at once, and shows where they were split (0 turns the warning off)"),
+                    arg!(--"max-contexts" [N] "Fails the build when code is compiled in more than N contexts at once, instead of getting slower and slower"),
+                    arg!(--seed <N> "Seeds $.random so it gives the same values every build, instead of the #[seed] of the script").required(false),
+                    arg!(--"fixed-time" <SECONDS> "Makes $.time return this time instead of the system time").required(false),
+                    arg!(--"input-file" <FILE> "Answers $.get_input with the lines of this file instead of asking").required(false),
                 ]),
 
             Command::new("doc")
@@ -168,6 +328,39 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     arg!(-l --"lib" "Creates a PCKP-compatible SPWN library"),
                     arg!(<PATH> "Path to create project in").value_hint(ValueHint::AnyPath),
                 ]),
+
+            Command::new("lsp")
+                .about("Starts a language server for editors, which talks over stdin and stdout"),
+
+            Command::new("dap")
+                .about("Starts a debug adapter for editors, which talks over stdin and stdout and can stop the build at breakpoints")
+                .args(&[
+                    arg!(-i --"include-path" "Adds a search path to look for libraries").takes_value(true).multiple_occurrences(true).min_values(0),
+                    arg!(-a --allow "Allow the use of a builtin").takes_value(true).multiple_occurrences(true).min_values(0),
+                    arg!(-d --deny "Deny the use of a builtin").takes_value(true).multiple_occurrences(true).min_values(0),
+                    arg!(--"allow-read" <DIR> "Allows the builtins that read files and directories, but only inside DIR").required(false).multiple_occurrences(true),
+                    arg!(--"allow-write" <DIR> "Allows the builtins that write and delete files and directories, but only inside DIR").required(false).multiple_occurrences(true),
+                    arg!(--"no-cache" "Compiles every library again instead of using the ones saved by earlier builds, so breakpoints in them are hit"),
+                    arg!(--"context-warning" [N] "Warns when code is compiled in more than N contexts at once, and shows where they were split (0 turns the warning off)"),
+                    arg!(--"max-contexts" [N] "Fails the build when code is compiled in more than N contexts at once, instead of getting slower and slower"),
+                    arg!(--seed <N> "Seeds $.random so it gives the same values every build, instead of the #[seed] of the script").required(false),
+                    arg!(--"fixed-time" <SECONDS> "Makes $.time return this time instead of the system time").required(false),
+                    arg!(--"input-file" <FILE> "Answers $.get_input with the lines of this file instead of asking").required(false),
+                ]),
+
+            Command::new("fmt")
+                .about("Formats SPWN files, keeping their comments")
+                .args(&[
+                    arg!(--check "Only checks if the files are formatted, and fails if any of them would change"),
+                    arg!(<FILES> "Files to format").multiple_values(true).value_hint(ValueHint::FilePath),
+                ]),
+
+            Command::new("parse")
+                .about("Parses a file and prints its syntax tree")
+                .args(&[
+                    arg!(--emit [FORMAT] "Output format").possible_values(["ast-json"]).default_value("ast-json"),
+                    arg!(<SCRIPT> "Path to spwn source file").value_hint(ValueHint::FilePath),
+                ]),
         ]
     )
     .arg_required_else_help(true)
@@ -229,6 +422,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let source = SpwnSource::String(internment::LocalIntern::from(unparsed));
 
         build_spwn_source(source, unparsed.to_string(), options)
+    } else if let Some(repl_cmd) = matches.subcommand_matches("repl") {
+        run_repl(CompileSettings::from(repl_cmd)?)
     } else if let Some(doc_cmd) = matches.subcommand_matches("doc") {
         let lib_path = doc_cmd.value_of("LIBRARY").unwrap();
         if "$" == lib_path {
@@ -310,12 +505,187 @@ folders:
             .unwrap();
         }
 
+        Ok(())
+    } else if matches.subcommand_matches("lsp").is_some() {
+        #[cfg(not(target_arch = "wasm32"))]
+        lsp::run().map_err(|e| e as Box<dyn std::error::Error>)?;
+        Ok(())
+    } else if let Some(dap_cmd) = matches.subcommand_matches("dap") {
+        let settings = CompileSettings::from(dap_cmd)?;
+        spwn::dap::run(
+            settings.include_paths,
+            settings.compile_options,
+            settings.permissions,
+        )?;
+        Ok(())
+    } else if let Some(fmt_cmd) = matches.subcommand_matches("fmt") {
+        let check = fmt_cmd.is_present("check");
+        let mut failed = false;
+        for file in fmt_cmd.values_of("FILES").unwrap_or_default() {
+            let code = fs::read_to_string(file)?;
+            let formatted = match spwn::format_spwn(code.clone(), SpwnSource::File(file.into())) {
+                Ok(f) => f,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    eprint_with_color(&format!("Could not format {}", file), Color::Red);
+                    failed = true;
+                    continue;
+                }
+            };
+            if formatted == code.replace("\r\n", "\n") {
+                continue;
+            }
+            if check {
+                eprint_with_color(&format!("{} is not formatted", file), Color::Red);
+                failed = true;
+            } else {
+                fs::write(file, formatted)?;
+                print_with_color(&format!("Formatted {}", file), Color::Green);
+            }
+        }
+        if failed {
+            std::process::exit(ERROR_EXIT_CODE);
+        }
+        Ok(())
+    } else if let Some(parse_cmd) = matches.subcommand_matches("parse") {
+        let script_path = PathBuf::from(parse_cmd.value_of("SCRIPT").unwrap());
+        let code = fs::read_to_string(&script_path)?;
+        // ast-json is the only format for now
+        match spwn::ast_json(code, SpwnSource::File(script_path)) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(ERROR_EXIT_CODE);
+            }
+        }
         Ok(())
     } else {
         unreachable!()
     }
 }
 
+const REPL_HELP: &str =
+    "Type SPWN code to compile it, and the value of the last expression is shown.
+Code with unclosed brackets continues on the next line, and an empty line ends it.
+  :type <expression>  shows the type of an expression
+  :objects            shows the triggers and objects added so far
+  :reset              forgets all the variables and objects
+  :help               shows this message
+  :quit               exits";
+
+fn new_repl<'a>(
+    settings: &CompileSettings,
+    std_out: &'a mut std::io::Stdout,
+) -> Result<spwn::repl::Repl<'a>, Box<dyn std::error::Error>> {
+    // imports with relative paths are found from the current directory
+    let path = std::env::current_dir()?.join("<repl>");
+    match spwn::repl::Repl::new(
+        path,
+        settings.include_paths.clone(),
+        settings.compile_options.clone(),
+        settings.permissions.clone(),
+        std_out,
+    ) {
+        Ok(repl) => Ok(repl),
+        Err(err) => {
+            create_report(ErrorReport::from(err))
+                .eprint(SpwnCache::default())
+                .unwrap();
+            std::process::exit(ERROR_EXIT_CODE);
+        }
+    }
+}
+
+fn run_repl(settings: CompileSettings) -> Result<(), Box<dyn std::error::Error>> {
+    use spwn::repl::is_incomplete;
+
+    let mut std_out = std::io::stdout();
+    let mut cache = SpwnCache::default();
+    let mut repl = new_repl(&settings, &mut std_out)?;
+    // the warnings from earlier inputs that were already shown
+    let mut shown_warnings = 0;
+
+    print_with_color("Type :help to see the commands", Color::Cyan);
+    let mut input = String::new();
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { ". " });
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            // end of the input
+            println!();
+            break;
+        }
+
+        if input.is_empty() {
+            let command = line.trim();
+            let result = match command.split_once(' ').unwrap_or((command, "")) {
+                ("", _) => continue,
+                (":help", _) => {
+                    println!("{}", REPL_HELP);
+                    continue;
+                }
+                (":quit", _) | (":q", _) => break,
+                (":reset", _) => {
+                    drop(repl);
+                    repl = new_repl(&settings, &mut std_out)?;
+                    shown_warnings = 0;
+                    print_with_color("Started a new session", Color::Cyan);
+                    continue;
+                }
+                (":objects", _) => {
+                    let objects = repl.objects();
+                    if objects.is_empty() {
+                        println!("No objects yet");
+                    }
+                    for object in objects {
+                        println!("{}", object);
+                    }
+                    continue;
+                }
+                (":type", code) => repl.type_of(code),
+                (other, _) if other.starts_with(':') => {
+                    eprint_with_color(
+                        &format!("Unknown command {}, type :help to see the commands", other),
+                        Color::Red,
+                    );
+                    continue;
+                }
+                _ => {
+                    input = line;
+                    if is_incomplete(&input) {
+                        continue;
+                    }
+                    repl.eval(std::mem::take(&mut input).trim_end())
+                }
+            };
+            match result {
+                Ok(values) => values.iter().for_each(|v| println!("{}", v)),
+                Err(err) => create_report(err).eprint(&mut cache).unwrap(),
+            }
+        } else {
+            // an empty line ends the input even if it isn't finished, to show the error
+            let ended = line.trim().is_empty();
+            input += &line;
+            if !ended && is_incomplete(&input) {
+                continue;
+            }
+            match repl.eval(std::mem::take(&mut input).trim_end()) {
+                Ok(values) => values.iter().for_each(|v| println!("{}", v)),
+                Err(err) => create_report(err).eprint(&mut cache).unwrap(),
+            }
+        }
+
+        for warning in &repl.globals().warnings[shown_warnings..] {
+            create_warning_report(warning.clone())
+                .eprint(&mut cache)
+                .unwrap();
+        }
+        shown_warnings = repl.globals().warnings.len();
+    }
+    Ok(())
+}
+
 fn build_spwn_source(
     source: SpwnSource,
     unparsed: String,
@@ -329,17 +699,23 @@ fn build_spwn_source(
         }
     }
     print_with_color("Parsing ...", Color::Green);
-    let (statements, notes) = match parse_spwn(
+    let (statements, notes, errors) = parse_spwn_partial(
         unparsed,
         source.clone(),
         ::compiler::builtins::BUILTIN_NAMES,
-    ) {
-        Err(err) => {
-            create_report(ErrorReport::from(err)).eprint(cache).unwrap();
-            std::process::exit(ERROR_EXIT_CODE);
+    );
+    if !errors.is_empty() {
+        let count = errors.len();
+        for err in errors {
+            create_report(ErrorReport::from(err))
+                .eprint(&mut cache)
+                .unwrap();
         }
-        Ok(p) => p,
-    };
+        if count > 1 {
+            eprint_with_color(&format!("Found {} syntax errors", count), Color::Red);
+        }
+        std::process::exit(ERROR_EXIT_CODE);
+    }
     let tags = notes.tag.tags.iter();
     for tag in tags {
         match tag.0.as_str() {
@@ -395,11 +771,22 @@ fn build_spwn_source(
     } else {
         String::new()
     };
+    let source_map_path = options.source_map.take().map(|path| {
+        path.unwrap_or_else(|| match &source {
+            SpwnSource::File(script) => {
+                let mut path = script.clone().into_os_string();
+                path.push(".map");
+                PathBuf::from(path)
+            }
+            _ => PathBuf::from("eval.spwn.map"),
+        })
+    });
     let mut std_out = std::io::stdout();
     let mut compiled = match compiler::compile_spwn(
         statements,
         source,
         options.include_paths,
+        options.compile_options,
         notes,
         options.permissions,
         level_string.clone(),
@@ -411,22 +798,96 @@ fn build_spwn_source(
         }
         Ok(p) => p,
     };
+    if let (Some(path), Some(profile)) = (&options.profile_file, profile(&compiled)) {
+        fs::write(path, &profile.folded_time)?;
+        // the same stacks, weighed by how many values were stored
+        let values_path = path.with_extension("values.folded");
+        fs::write(&values_path, &profile.folded_values)?;
+
+        print_with_color("\nProfile:", Color::Magenta);
+        println!("{}", profile);
+        print_with_color(
+            &format!(
+                "Wrote folded stacks to {} and {}",
+                path.display(),
+                values_path.display()
+            ),
+            Color::White,
+        );
+    }
+    let warning_count = compiled.warnings.len();
+    for warning in std::mem::take(&mut compiled.warnings) {
+        // this one was already shown by `show_warning_now`
+        if warning.lint != Lint::ContextExplosion {
+            create_warning_report(warning).eprint(&mut cache).unwrap();
+        }
+    }
+    if options.deny_warnings && warning_count > 0 {
+        eprint_with_color(
+            &format!(
+                "Build failed: found {} warning{} with --deny-warnings",
+                warning_count,
+                if warning_count == 1 { "" } else { "s" }
+            ),
+            Color::Red,
+        );
+        std::process::exit(ERROR_EXIT_CODE);
+    }
     if options.gd_enabled {
-        let reserved = optimizer::ReservedIds::from_objects(&compiled.objects, &compiled.func_ids);
+        let reserved = reserved_ids(&compiled);
+
+        let unoptimized_triggers = if options.trigger_report {
+            Some(compiled.func_ids.clone())
+        } else {
+            None
+        };
 
         let has_stuff = compiled.func_ids.iter().any(|x| !x.obj_list.is_empty());
         if options.opti_enabled && has_stuff {
             print_with_color("Optimizing triggers...", Color::Cyan);
-            compiled.func_ids = optimize(compiled.func_ids, compiled.closed_groups, reserved);
+            if options.verify_opt {
+                let (optimized, report) =
+                    optimize_and_verify(compiled.func_ids, compiled.closed_groups, reserved);
+                compiled.func_ids = optimized;
+                print_with_color("Verifying optimization...", Color::Cyan);
+                if report.is_equivalent() {
+                    print_with_color(&report.to_string(), Color::Green);
+                } else {
+                    eprint_with_color(&report.to_string(), Color::Red);
+
+                    std::process::exit(ERROR_EXIT_CODE);
+                }
+            } else {
+                compiled.func_ids = optimize(compiled.func_ids, compiled.closed_groups, reserved);
+            }
+        }
+
+        if let Some(func_ids) = unoptimized_triggers {
+            print_with_color("\nTrigger report:", Color::Magenta);
+            println!("{}", trigger_report(&compiled, &func_ids));
         }
 
+        sync_groups::share_groups(
+            &mut compiled.func_ids,
+            &compiled.objects,
+            &compiled.sync_groups,
+        );
+
         let mut objects = leveldata::apply_fn_ids(&compiled.func_ids);
 
-        objects.extend(compiled.objects);
+        objects.extend(std::mem::take(&mut compiled.objects));
 
         print_with_color(&format!("{} objects added", objects.len()), Color::White);
 
-        let (new_ls, used_ids) = leveldata::append_objects(objects, &level_string)?;
+        let used_ids = leveldata::assign_ids(&mut objects, &level_string)?;
+        if let Some(path) = source_map_path {
+            fs::write(&path, source_map(&compiled, &objects).to_json())?;
+            print_with_color(
+                &format!("Wrote source map to {}", path.display()),
+                Color::White,
+            );
+        }
+        let new_ls = leveldata::serialize_objects(objects);
 
         print_with_color("\nLevel:", Color::Magenta);
         for (i, len) in used_ids.iter().enumerate() {
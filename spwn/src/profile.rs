@@ -0,0 +1,141 @@
+// turns the samples from `compiler::profiler` into folded stacks for flamegraph
+// tools (like inferno or flamegraph.pl), and a table of the slowest frames
+//
+// a folded stack is one line per stack of frames, with the frames separated
+// by `;` and the weight of the stack at the end
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::globals::Globals;
+use crate::profiler::{Frame, Sample};
+use crate::trigger_report::Locator;
+use shared::ImportType;
+
+// how many rows are shown in the table
+const MAX_ROWS: usize = 15;
+
+#[derive(Clone, Copy)]
+enum Measure {
+    // microseconds
+    Time,
+    // how many values were stored
+    Values,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileRow {
+    pub name: String,
+    pub sample: Sample,
+}
+
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub folded_time: String,
+    pub folded_values: String,
+    // every frame, with the most time spent in it (not counting the frames it called) first
+    pub frames: Vec<ProfileRow>,
+}
+
+fn frame_name(frame: &Frame, locator: &mut Locator) -> String {
+    let name = match frame {
+        Frame::Import(ImportType::Lib(name)) => format!("import {}", name),
+        Frame::Import(ImportType::Script(path)) => format!("import {}", path.display()),
+        Frame::Macro(area) => {
+            let location = locator.locate(*area);
+            if location.snippet.is_empty() {
+                location.line
+            } else {
+                format!("{} {}", location.line, location.snippet)
+            }
+        }
+    };
+    // `;` separates the frames
+    name.replace(';', ",")
+}
+
+fn folded(stacks: &BTreeMap<String, Sample>, measure: Measure) -> String {
+    let mut out = String::new();
+    for (stack, sample) in stacks {
+        let weight = match measure {
+            Measure::Time => sample.time.as_micros() as u64,
+            Measure::Values => sample.values,
+        };
+        if weight > 0 {
+            out += &format!("{} {}\n", stack, weight);
+        }
+    }
+    out
+}
+
+/// Returns `None` if the script wasn't compiled with `CompileOptions::profile`
+pub fn profile(globals: &Globals) -> Option<Profile> {
+    let profiler = globals.profiler.as_ref()?;
+    let mut locator = Locator::new();
+    let root = Locator::file_name(globals.path.as_ref()).replace(';', ",");
+
+    let mut stacks: BTreeMap<String, Sample> = BTreeMap::new();
+    let mut frames: BTreeMap<String, Sample> = BTreeMap::new();
+    for (stack, sample) in profiler.samples() {
+        let names: Vec<String> = stack.iter().map(|f| frame_name(f, &mut locator)).collect();
+
+        *frames
+            .entry(names.last().cloned().unwrap_or_else(|| root.clone()))
+            .or_default() += sample;
+
+        let mut folded_stack = root.clone();
+        for name in names {
+            folded_stack += ";";
+            folded_stack += &name;
+        }
+        // different frames can have the same name, like two calls on one line
+        *stacks.entry(folded_stack).or_default() += sample;
+    }
+
+    let mut frames: Vec<ProfileRow> = frames
+        .into_iter()
+        .map(|(name, sample)| ProfileRow { name, sample })
+        .collect();
+    frames.sort_by_key(|row| std::cmp::Reverse(row.sample.time));
+
+    Some(Profile {
+        folded_time: folded(&stacks, Measure::Time),
+        folded_values: folded(&stacks, Measure::Values),
+        frames,
+    })
+}
+
+fn format_time(time: Duration) -> String {
+    format!("{:.1}ms", time.as_secs_f64() * 1000.0)
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Slowest macro calls and imports:")?;
+        writeln!(
+            f,
+            "{:>10} {:>10} {:>8}  source",
+            "self time", "values", "calls"
+        )?;
+        for row in self.frames.iter().take(MAX_ROWS) {
+            writeln!(
+                f,
+                "{:>10} {:>10} {:>8}  {}",
+                format_time(row.sample.time),
+                row.sample.values,
+                row.sample.calls,
+                row.name
+            )?;
+        }
+        if self.frames.len() > MAX_ROWS {
+            writeln!(
+                f,
+                "{:>10} ... and {} more",
+                "",
+                self.frames.len() - MAX_ROWS
+            )?;
+        }
+        Ok(())
+    }
+}
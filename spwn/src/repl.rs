@@ -0,0 +1,276 @@
+// an interactive session for `spwn repl`
+//
+// the session keeps one `Globals` and one context for everything that is
+// typed into it, so the variables, triggers and ids from earlier inputs are
+// still there for the next one
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use ::compiler::builtins::{BuiltinPermissions, Id, BUILTIN_NAMES};
+use ::compiler::compiler::{compile_statements, import_std, CompileOptions};
+use ::compiler::compiler_types::{handle_unary_operator, EvalExpression};
+use ::compiler::context::{Context, FullContext, VariableData};
+use ::compiler::context_limits::ContextLimits;
+use ::compiler::globals::Globals;
+use ::compiler::leveldata::{GdObj, ObjParam};
+use ::compiler::reproducible::Reproducible;
+use ::compiler::value::Value;
+use errors::compiler_info::{CodeArea, CompilerInfo};
+use errors::{ErrorReport, RuntimeError};
+use internment::LocalIntern;
+use shared::{SpwnSource, StoredValue};
+
+use crate::ast;
+use crate::builtins::Builtin;
+use crate::parse_spwn;
+use crate::parser::open_brackets;
+use crate::trigger_report::Locator;
+
+pub struct Repl<'a> {
+    globals: Globals<'a>,
+    // boxed so the pointer every context has to the root context stays valid
+    context: Box<FullContext>,
+}
+
+/// Returns `true` if the code has brackets that aren't closed yet, so the REPL
+/// should keep reading lines
+pub fn is_incomplete(code: &str) -> bool {
+    open_brackets(code) > 0
+}
+
+fn id_str(id: Id, class: &str) -> String {
+    match id {
+        Id::Specific(n) => format!("{}{}", n, class),
+        // numbered so the ids of different objects can be told apart
+        Id::Arbitrary(n) => format!("?{}{}", n, class),
+    }
+}
+
+fn param_str(param: &ObjParam) -> String {
+    match param {
+        ObjParam::Group(g) => id_str(g.id, "g"),
+        ObjParam::Color(c) => id_str(c.id, "c"),
+        ObjParam::Block(b) => id_str(b.id, "b"),
+        ObjParam::Item(i) => id_str(i.id, "i"),
+        ObjParam::GroupList(list) => {
+            let groups: Vec<_> = list.iter().map(|g| id_str(g.id, "g")).collect();
+            format!("[{}]", groups.join(", "))
+        }
+        ObjParam::Text(t) => format!("{:?}", t),
+        other => other.to_string(),
+    }
+}
+
+#[allow(clippy::result_large_err)]
+impl<'a> Repl<'a> {
+    /// Imports the standard library and starts an empty session. `path` is used to
+    /// find the scripts that are imported with a relative path.
+    pub fn new(
+        path: PathBuf,
+        included_paths: Vec<PathBuf>,
+        options: CompileOptions,
+        permissions: BuiltinPermissions,
+        std_out: &'a mut impl Write,
+    ) -> Result<Self, RuntimeError> {
+        let source = SpwnSource::File(path);
+        let mut globals = Globals::new(source.clone(), permissions, String::new(), std_out);
+        globals.includes = included_paths;
+        globals.module_cache = options.module_cache;
+        globals.context_limits = ContextLimits::new(options.context_warning, options.max_contexts);
+        globals.reproducible = Reproducible::new(options.seed, options.fixed_time, options.inputs);
+
+        let mut context = Box::new(FullContext::new(&globals));
+        context.set_root();
+        import_std(
+            &mut context,
+            &mut globals,
+            CompilerInfo::from_area(CodeArea {
+                file: LocalIntern::new(source),
+                pos: (0, 0),
+            }),
+        )?;
+        Ok(Repl { globals, context })
+    }
+
+    pub fn globals(&self) -> &Globals<'a> {
+        &self.globals
+    }
+
+    /// Compiles the code, and returns how the value of the last statement is displayed
+    /// in each context, if it's an expression that isn't `null`. The variables are
+    /// kept as they were if the code fails.
+    pub fn eval(&mut self, code: &str) -> Result<Vec<String>, ErrorReport> {
+        let values = self.run(code)?;
+        // displaying a value can run a macro, which could garbage collect the others
+        self.globals.push_new_preserved();
+        for (val, _) in &values {
+            self.globals.push_preserved_val(*val);
+        }
+        let shown = self.display(values);
+        self.globals.pop_preserved();
+        shown.map_err(ErrorReport::from)
+    }
+
+    fn display(
+        &mut self,
+        values: Vec<(StoredValue, Context)>,
+    ) -> Result<Vec<String>, RuntimeError> {
+        let mut out = Vec::new();
+        for (val, context) in values {
+            if self.globals.stored_values[val] == Value::Null {
+                continue;
+            }
+            let mut context = FullContext::Single(context);
+            let info = CompilerInfo::from_area(self.globals.get_area(val));
+            handle_unary_operator(
+                val,
+                Builtin::DisplayOp,
+                &mut context,
+                &mut self.globals,
+                &info,
+            )?;
+            for c in context.iter() {
+                let shown = c.inner().return_value;
+                out.push(match &self.globals.stored_values[shown] {
+                    Value::Str(s) => s.clone(),
+                    val => val.clone().to_str(&mut self.globals),
+                });
+            }
+        }
+        Ok(out)
+    }
+
+    /// The type of the value of the code in each context, like `@number`
+    pub fn type_of(&mut self, code: &str) -> Result<Vec<String>, ErrorReport> {
+        let values = self.run(code)?;
+        let mut types: Vec<String> = values
+            .into_iter()
+            .map(|(val, _)| {
+                format!(
+                    "@{}",
+                    self.globals.stored_values[val].get_type_str(&self.globals)
+                )
+            })
+            .collect();
+        types.dedup();
+        Ok(types)
+    }
+
+    /// The triggers and other objects that have been added so far, one per line,
+    /// with the input that added them
+    pub fn objects(&self) -> Vec<String> {
+        let mut locator = Locator::new();
+        let triggers = self
+            .globals
+            .func_ids
+            .iter()
+            .flat_map(|f| f.obj_list.iter().map(|(obj, _)| obj));
+        triggers
+            .chain(self.globals.objects.iter())
+            .map(|obj| {
+                let origin = match self.globals.object_origins.get(&obj.unique_id) {
+                    Some(origin) => {
+                        // the call in the input, instead of somewhere in a library
+                        let area = origin.call_stack.first().unwrap_or(&origin.area);
+                        let location = locator.locate(*area);
+                        format!("{} {}", location.line, location.snippet)
+                    }
+                    None => "<unknown>".to_string(),
+                };
+                format!("{}: {}", origin, object_str(obj))
+            })
+            .collect()
+    }
+
+    // compiles the code, and returns the value of the last statement in each context
+    // if it's an expression (and the context it's in)
+    fn run(&mut self, code: &str) -> Result<Vec<(StoredValue, Context)>, ErrorReport> {
+        let source = SpwnSource::String(LocalIntern::new(code.to_string()));
+        let (mut statements, _) = parse_spwn(code.to_string(), source.clone(), BUILTIN_NAMES)?;
+        let last = match statements.last() {
+            Some(ast::Statement {
+                body: ast::StatementBody::Expr(_),
+                arrow: false,
+                ..
+            }) => match statements.pop().map(|s| s.body) {
+                Some(ast::StatementBody::Expr(expr)) => Some(expr),
+                _ => unreachable!(),
+            },
+            _ => None,
+        };
+
+        // the values of the variables from before can't be garbage collected,
+        // since they're put back if the code fails
+        let before = (*self.context).clone();
+        self.globals.push_new_preserved();
+        for c in (*self.context).with_breaks() {
+            for stack in c.inner().get_variables().values() {
+                for VariableData { val, .. } in stack {
+                    self.globals.push_preserved_val(*val);
+                }
+            }
+        }
+        let info = CompilerInfo::from_area(CodeArea {
+            file: LocalIntern::new(source),
+            pos: (0, 0),
+        });
+        let result = self.run_statements(&statements, last, info);
+        self.globals.pop_preserved();
+        if result.is_err() {
+            *self.context = before;
+        }
+        result.map_err(ErrorReport::from)
+    }
+
+    fn run_statements(
+        &mut self,
+        statements: &[ast::Statement],
+        last: Option<ast::Expression>,
+        mut info: CompilerInfo,
+    ) -> Result<Vec<(StoredValue, Context)>, RuntimeError> {
+        compile_statements(
+            statements,
+            &mut self.context,
+            &mut self.globals,
+            info.clone(),
+        )?;
+        for c in self.context.with_breaks() {
+            if let Some((breaktype, area)) = c.inner().broken {
+                return Err(RuntimeError::BreakNeverUsedError {
+                    breaktype,
+                    info: CompilerInfo::from_area(area),
+                    broke: area,
+                    dropped: area,
+                    reason: "it's not inside a macro or loop".to_string(),
+                });
+            }
+        }
+
+        let expr = match last {
+            Some(expr) => expr,
+            None => return Ok(Vec::new()),
+        };
+        if let Some(first) = expr.values.first() {
+            info.position.pos = (first.pos.0, expr.values.last().unwrap().pos.1);
+        }
+        expr.eval(&mut self.context, &mut self.globals, info, true)?;
+        let values = self
+            .context
+            .iter()
+            .map(|c| (c.inner().return_value, c.inner().clone()))
+            .collect();
+        self.context.reset_return_vals(&self.globals);
+        Ok(values)
+    }
+}
+
+fn object_str(obj: &GdObj) -> String {
+    let mut params: Vec<_> = obj.params.iter().collect();
+    params.sort_by_key(|(key, _)| **key);
+    let params: Vec<_> = params
+        .into_iter()
+        .map(|(key, val)| format!("{}: {}", key, param_str(val)))
+        .collect();
+    format!("obj {{ {} }}", params.join(", "))
+}
@@ -0,0 +1,129 @@
+// the source map that `--source-map` writes next to the level
+//
+// it has every object that is added to the level, in the order they're added,
+// with the object id, position and groups they have in the editor and the
+// lines of code that made them. triggers that the optimizer merged into one
+// have the origins of all of them
+
+use serde::Serialize;
+
+use crate::ast::ObjectMode;
+use crate::builtins::Id;
+use crate::compiler_info::CodeArea;
+use crate::globals::Globals;
+use crate::leveldata::{GdObj, ObjParam};
+use crate::trigger_report::Locator;
+
+const VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct SourceMap {
+    pub version: u32,
+    pub objects: Vec<MappedObject>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MappedObject {
+    pub object_id: Option<u16>,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub groups: Vec<u16>,
+    pub trigger: bool,
+    pub origins: Vec<Origin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceLocation {
+    pub file: String,
+    // starting at 1, `None` if the file couldn't be read
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub code: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Origin {
+    #[serde(flatten)]
+    pub location: SourceLocation,
+    // the macro calls that led to the object, outermost first
+    pub calls: Vec<SourceLocation>,
+}
+
+impl SourceMap {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+fn source_location(area: CodeArea, locator: &mut Locator) -> SourceLocation {
+    let location = locator.locate(area);
+    SourceLocation {
+        file: location.file,
+        line: location.position.map(|(line, _)| line),
+        column: location.position.map(|(_, col)| col),
+        code: location.snippet,
+    }
+}
+
+fn number(obj: &GdObj, key: u16) -> Option<f64> {
+    match obj.params.get(&key) {
+        Some(ObjParam::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn groups(obj: &GdObj) -> Vec<u16> {
+    let ids = match obj.params.get(&57) {
+        Some(ObjParam::Group(g)) => vec![g.id],
+        Some(ObjParam::GroupList(list)) => list.iter().map(|g| g.id).collect(),
+        _ => Vec::new(),
+    };
+    ids.into_iter()
+        .filter_map(|id| match id {
+            Id::Specific(n) => Some(n),
+            Id::Arbitrary(_) => None,
+        })
+        .collect()
+}
+
+/// The objects should be the ones that are added to the level, after
+/// `leveldata::assign_ids` so their groups are the ones in the editor
+pub fn source_map(globals: &Globals, objects: &[GdObj]) -> SourceMap {
+    let mut locator = Locator::new();
+    let objects = objects
+        .iter()
+        .map(|obj| {
+            let mut origins: Vec<Origin> = Vec::new();
+            for id in obj.origin_ids() {
+                let origin = match globals.object_origins.get(&id) {
+                    Some(origin) => origin,
+                    None => continue,
+                };
+                let origin = Origin {
+                    location: source_location(origin.area, &mut locator),
+                    calls: origin
+                        .call_stack
+                        .iter()
+                        .map(|area| source_location(*area, &mut locator))
+                        .collect(),
+                };
+                // like the triggers a loop made, which were merged
+                if !origins.contains(&origin) {
+                    origins.push(origin);
+                }
+            }
+            MappedObject {
+                object_id: number(obj, 1).map(|n| n as u16),
+                x: number(obj, 2),
+                y: number(obj, 3),
+                groups: groups(obj),
+                trigger: obj.mode == ObjectMode::Trigger,
+                origins,
+            }
+        })
+        .collect();
+    SourceMap {
+        version: VERSION,
+        objects,
+    }
+}
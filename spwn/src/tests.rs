@@ -2,7 +2,15 @@
 
 use std::path::PathBuf;
 
+use crate::compile_source;
+use crate::leveldata;
+use crate::reserved_ids;
 use crate::run_spwn;
+use crate::source_map::source_map;
+use crate::sync_groups;
+use crate::trigger_report::trigger_report;
+use crate::verify_optimization;
+use shared::SpwnSource;
 
 macro_rules! run_test {
     {$([$attr:ident])? NAME: $name:ident CODE: $code:literal $(OUTPUT: $output:literal)?} => {
@@ -21,6 +29,26 @@ macro_rules! run_test {
     };
 }
 
+// checks that optimizing the triggers of a script in the test folder
+// doesn't change what they do
+macro_rules! verify_opt_test {
+    {NAME: $name:ident FILE: $file:literal} => {
+        #[test]
+        fn $name() {
+            let path = PathBuf::from("../test").join($file);
+            let code = std::fs::read_to_string(&path).unwrap();
+            let report = match verify_optimization(code, SpwnSource::File(path), vec![PathBuf::from("./")]) {
+                Ok(a) => a,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    panic!("test {} failed, see message above", stringify!($name));
+                }
+            };
+            assert!(report.is_equivalent(), "{}", report);
+        }
+    };
+}
+
 // just basic parsing
 run_test! {
     NAME: basic_parsing
@@ -86,6 +114,199 @@ run_test! {
     OUTPUT: r"38"
 }
 
+run_test! {
+    NAME: bitwise
+    CODE: r"
+#[no_std]
+$.print(0b1100 .& 0b1010, ' ', 0b1100 .| 0b1010, ' ', 0b1100 .^ 0b1010)
+$.print(1 << 4, ' ', 256 >> 2, ' ', ~5)
+$.print(1 << 2 + 1, ' ', 6 .& 3 == 2, ' ', 1 .| 2 .^ 3 .& 1)
+type @flags
+impl @flags {
+    _bit_and_: (self, other: @number) => 'overridden',
+}
+$.print(@flags::{} .& 1)
+    "
+    OUTPUT: r"
+8 14 6
+16 64 -6
+8 true 3
+overridden
+    "
+}
+
+run_test! {
+    [should_panic]
+    NAME: bitwise_float
+    CODE: r"#[no_std] $.print(1.5 .| 1)"
+}
+
+run_test! {
+    NAME: exact_ints
+    CODE: r"
+#[no_std]
+big = 0x7FFFFFFFFFFFFFFF
+$.print(big, ' ', big - 0x1, ' ', 9007199254740993 + 0x2, ' ', big - 1)
+$.print(0x10 is @int, ' ', 16 is @int, ' ', 0x10 is @number, ' ', 0x10 == 16)
+$.print(0x10 + 0x1, ' ', 0x10 + 0.5, ' ', 0x7 / 0x2, ' ', -0x7 /% 0x2, ' ', 0x2 ^ 0x3e)
+$.print(16 as @int is @int, ' ', 0x10 as @number is @int, ' ', '0xff' as @int)
+let n = 0x1
+n += 0x1
+n++
+$.print(n, ' ', n is @int, ' ', [1, 2, 3][0x1], ' ', 0xff .& 0x0f)
+    "
+    OUTPUT: r"
+9223372036854775807 9223372036854775806 9007199254740995 9223372036854776000
+true false true true
+17 16.5 3.5 -4 4611686018427387904
+true false 255
+3 true 2 15
+    "
+}
+
+// @ints have the methods of @number
+run_test! {
+    NAME: int_number_methods
+    CODE: r"
+x = 0xFF
+$.print(x.clamp(0, 5), ' ', (-0x3).abs(), ' ', 0x2.ordinal(), ' ', x.lerp(0, 2))
+    "
+    OUTPUT: r"
+5 3 2nd 510
+    "
+}
+
+// counters take @ints wherever they take @numbers
+run_test! {
+    NAME: counter_int_operand
+    CODE: r"
+c = counter(3)
+c += 0x1
+$.print((c + 0x2).type, ' ', (c - 9007199254740993).type, ' ', (c * 0x2).type)
+    "
+    OUTPUT: r"
+@counter @counter @counter
+    "
+}
+
+run_test! {
+    [should_panic]
+    NAME: int_overflow
+    CODE: r"#[no_std] $.print(0x7FFFFFFFFFFFFFFF + 0x1)"
+}
+
+run_test! {
+    [should_panic]
+    NAME: float_as_int
+    CODE: r"#[no_std] $.print(1.5 as @int)"
+}
+
+run_test! {
+    NAME: int_hash
+    CODE: r"#[no_std] $.print($.hash('hello') is @int, ' ', $.hash('hello') == $.hash('hel' + 'lo'))"
+    OUTPUT: "true true"
+}
+
+run_test! {
+    [should_panic]
+    NAME: range_out_of_bounds
+    CODE: r"#[no_std] $.print(0..5000000000)"
+}
+
+run_test! {
+    NAME: try_catch
+    CODE: r"
+#[no_std]
+try {
+    throw 'oops'
+} catch e {
+    $.print(e.kind, ': ', e.message, ' at ', e.location.start)
+}
+try {
+    $.print(undefined_thing)
+} catch e {
+    $.print(e.kind, ': ', e.message)
+}
+try {
+    $.print(0x7FFFFFFFFFFFFFFF * 0x2)
+} catch e {
+    $.print(e.kind, ': ', e.message)
+}
+let x = 1
+try {
+    let y = 5
+    x = 2
+    throw 'fail'
+} catch {
+    $.print(x)
+}
+try {
+    $.print('fine')
+} catch e {
+    $.print('unreachable')
+}
+    "
+    OUTPUT: r"
+throw: oops at 21
+custom: `undefined_thing` is not defined in this scope
+builtin: Integer overflow: the result doesn't fit in an @int
+2
+fine
+    "
+}
+
+// the triggers and objects from a try block that failed aren't kept
+#[test]
+fn try_removes_output() {
+    use crate::leveldata::ObjParam;
+
+    let code = r"
+extract obj_props
+$.add(obj { OBJ_ID: 1, X: 0, Y: 0 })
+try {
+    $.add(obj { OBJ_ID: 1, X: 30, Y: 0 })
+    $.add(trigger { OBJ_ID: 1007, TARGET: 10g })
+    f = !{ $.add(trigger { OBJ_ID: 1007, TARGET: 11g }) }
+    f!
+    throw 'fail'
+} catch {
+    $.add(trigger { OBJ_ID: 1007, TARGET: 12g })
+}
+try {
+    $.add(obj { OBJ_ID: 1, X: 60, Y: 0 })
+} catch {}
+"
+    .to_string();
+    let path = PathBuf::from("try_removes_output.spwn");
+    let mut std_out = Vec::<u8>::new();
+    let compiled = compile_source(
+        code,
+        SpwnSource::File(path),
+        vec![PathBuf::from("./")],
+        &mut std_out,
+    )
+    .unwrap();
+    let targets: Vec<&ObjParam> = compiled
+        .func_ids
+        .iter()
+        .flat_map(|f| f.obj_list.iter())
+        .filter_map(|(obj, _)| obj.params.get(&51))
+        .collect();
+    assert_eq!(targets, [&ObjParam::Group(crate::builtins::Group::new(12))]);
+    let xs: Vec<&ObjParam> = compiled
+        .objects
+        .iter()
+        .filter_map(|obj| obj.params.get(&2))
+        .collect();
+    assert_eq!(xs, [&ObjParam::Number(0.0), &ObjParam::Number(60.0)]);
+}
+
+run_test! {
+    [should_panic]
+    NAME: throw_in_catch
+    CODE: r"#[no_std] try { throw 'a' } catch e { throw e.message }"
+}
+
 // std things
 
 // strings
@@ -99,6 +320,21 @@ Hello world!
     "
 }
 
+run_test! {
+    NAME: format_strings
+    CODE: r"
+#[no_std]
+x = 3
+d = {a: [1, 2], b: 'text'}
+$.print(f'x = {x}, a = {d.a}, b = {d.b}')
+$.print(f'{{x}} = {x + 1 if x > 2 else 0}\t{5g}')
+    "
+    OUTPUT: r"
+x = 3, a = [1, 2], b = text
+{x} = 4	5g
+    "
+}
+
 // arrays
 run_test! {
     NAME: arr
@@ -118,3 +354,1339 @@ true
 true
     "
 }
+
+// optimizer
+verify_opt_test! {
+    NAME: verify_opt_physics
+    FILE: "physics.spwn"
+}
+
+verify_opt_test! {
+    NAME: verify_opt_bugtest
+    FILE: "bugtest.spwn"
+}
+
+verify_opt_test! {
+    NAME: verify_opt_counter_compare
+    FILE: "counter_compare.spwn"
+}
+
+verify_opt_test! {
+    NAME: verify_opt_instant_count_fold
+    FILE: "instant_count_fold.spwn"
+}
+
+// changing a condition has to show up in the report
+#[test]
+fn verify_opt_conditions() {
+    use crate::leveldata::ObjParam;
+
+    let path = PathBuf::from("../test/counter_compare.spwn");
+    let code = std::fs::read_to_string(&path).unwrap();
+    let mut std_out = Vec::<u8>::new();
+    let compiled = compile_source(
+        code,
+        SpwnSource::File(path),
+        vec![PathBuf::from("./")],
+        &mut std_out,
+    )
+    .unwrap();
+    let reserved = reserved_ids(&compiled);
+    let (mut optimized, _) =
+        optimizer::optimize::optimize_triggers(compiled.func_ids.clone(), reserved.clone());
+
+    let verify = |after: &[crate::compiler_types::FunctionId]| {
+        optimizer::verify::verify_optimization(&compiled.func_ids, after, &reserved, true)
+    };
+    assert!(verify(&optimized).is_equivalent());
+
+    // `c == 3` checks for 4 instead
+    let (obj, _) = optimized
+        .iter_mut()
+        .flat_map(|f| f.obj_list.iter_mut())
+        .find(|(obj, _)| {
+            obj.params.get(&1) == Some(&ObjParam::Number(1811.0))
+                && obj.params.get(&88) == Some(&ObjParam::Number(0.0))
+        })
+        .unwrap();
+    obj.params.insert(77, ObjParam::Number(4.0));
+    let report = verify(&optimized);
+    let changed: Vec<String> = report
+        .missing
+        .iter()
+        .chain(&report.added)
+        .map(|effect| effect.conditions.join(" and "))
+        .collect();
+    assert_eq!(changed, ["item ?2 == 3", "item ?2 == 4"]);
+}
+
+// a group that is reached twice is compared with both of its delays,
+// not just the shortest one
+#[test]
+fn verify_opt_delays() {
+    use crate::leveldata::ObjParam;
+
+    let code = r"
+f = !{ 10g.move(10, 0) }
+f!
+wait(0.5)
+f!
+"
+    .to_string();
+    let mut std_out = Vec::<u8>::new();
+    let compiled = compile_source(
+        code.clone(),
+        SpwnSource::String(internment::LocalIntern::new(code)),
+        vec![PathBuf::from("./")],
+        &mut std_out,
+    )
+    .unwrap();
+    let reserved = reserved_ids(&compiled);
+    let (mut optimized, _) =
+        optimizer::optimize::optimize_triggers(compiled.func_ids.clone(), reserved.clone());
+
+    let verify = |after: &[crate::compiler_types::FunctionId]| {
+        optimizer::verify::verify_optimization(&compiled.func_ids, after, &reserved, true)
+    };
+    assert!(verify(&optimized).is_equivalent());
+
+    // the second call happens later
+    let (obj, _) = optimized
+        .iter_mut()
+        .flat_map(|f| f.obj_list.iter_mut())
+        .find(|(obj, _)| obj.params.get(&63) == Some(&ObjParam::Number(0.5)))
+        .unwrap();
+    obj.params.insert(63, ObjParam::Number(0.7));
+    let report = verify(&optimized);
+    let missing: Vec<i64> = report.missing.iter().map(|effect| effect.delay).collect();
+    let added: Vec<i64> = report.added.iter().map(|effect| effect.delay).collect();
+    assert_eq!((missing, added), (vec![500], vec![700]));
+}
+
+// (all triggers, instant count triggers) before and after optimizing
+fn instant_count_totals(file: &str) -> ((usize, usize), (usize, usize)) {
+    use crate::leveldata::ObjParam;
+
+    let path = PathBuf::from(format!("../test/{}", file));
+    let code = std::fs::read_to_string(&path).unwrap();
+    let mut std_out = Vec::<u8>::new();
+    let compiled = compile_source(
+        code,
+        SpwnSource::File(path),
+        vec![PathBuf::from("./")],
+        &mut std_out,
+    )
+    .unwrap();
+    let reserved = reserved_ids(&compiled);
+    let (optimized, _) =
+        optimizer::optimize::optimize_triggers(compiled.func_ids.clone(), reserved.clone());
+    let report =
+        optimizer::verify::verify_optimization(&compiled.func_ids, &optimized, &reserved, true);
+    assert!(report.is_equivalent(), "{}", report);
+
+    let count = |func_ids: &[crate::compiler_types::FunctionId]| {
+        let objects = func_ids.iter().flat_map(|f| f.obj_list.iter());
+        let instant_counts = objects
+            .clone()
+            .filter(|(obj, _)| obj.params.get(&1) == Some(&ObjParam::Number(1811.0)))
+            .count();
+        (objects.count(), instant_counts)
+    };
+    (count(&compiled.func_ids), count(&optimized))
+}
+
+#[test]
+fn instant_count_merge() {
+    assert_eq!(
+        instant_count_totals("counter_compare.spwn"),
+        ((78, 26), (40, 17))
+    );
+}
+
+#[test]
+fn instant_count_fold() {
+    assert_eq!(
+        instant_count_totals("instant_count_fold.spwn"),
+        ((8, 5), (6, 3))
+    );
+}
+
+// the optimized triggers and the groups they use are the same every time
+#[test]
+fn optimize_deterministic() {
+    let path = PathBuf::from("../test/physics.spwn");
+    let code = std::fs::read_to_string(&path).unwrap();
+    let mut std_out = Vec::<u8>::new();
+    let compiled = compile_source(
+        code,
+        SpwnSource::File(path),
+        vec![PathBuf::from("./")],
+        &mut std_out,
+    )
+    .unwrap();
+    let reserved = reserved_ids(&compiled);
+
+    let optimize = || {
+        let optimized = optimizer::optimize::optimize(
+            compiled.func_ids.clone(),
+            compiled.closed_groups,
+            reserved.clone(),
+        );
+        optimized
+            .iter()
+            .flat_map(|f| f.obj_list.iter())
+            .map(|(obj, _)| {
+                let mut params: Vec<String> = obj
+                    .params
+                    .iter()
+                    .map(|(k, v)| format!("{}:{:?}", k, v))
+                    .collect();
+                params.sort();
+                params.join(",")
+            })
+            .collect::<Vec<String>>()
+    };
+    let first = optimize();
+    for _ in 0..4 {
+        assert_eq!(optimize(), first);
+    }
+}
+
+// every trigger should be traced back to where it was made
+#[test]
+fn trigger_report_counter_compare() {
+    let path = PathBuf::from("../test/counter_compare.spwn");
+    let code = std::fs::read_to_string(&path).unwrap();
+    let mut std_out = Vec::<u8>::new();
+    let mut compiled = compile_source(
+        code,
+        SpwnSource::File(path),
+        vec![PathBuf::from("./")],
+        &mut std_out,
+    )
+    .unwrap();
+
+    let before = compiled.func_ids.clone();
+    let reserved = reserved_ids(&compiled);
+    compiled.func_ids =
+        optimizer::optimize::optimize(before.clone(), compiled.closed_groups, reserved);
+
+    let report = trigger_report(&compiled, &before);
+
+    let count = |func_ids: &[crate::compiler_types::FunctionId]| {
+        func_ids.iter().map(|f| f.obj_list.len()).sum::<usize>()
+    };
+    assert!(report.files.iter().all(|r| r.name != "<unknown>"));
+    assert_eq!(
+        report
+            .files
+            .iter()
+            .map(|r| r.before.triggers)
+            .sum::<usize>(),
+        count(&before)
+    );
+    assert_eq!(
+        report.lines.iter().map(|r| r.after.triggers).sum::<usize>(),
+        count(&compiled.func_ids)
+    );
+    assert!(report
+        .macros
+        .iter()
+        .any(|r| r.name.starts_with("../test/counter_compare.spwn:7 ") && r.before.triggers > 0));
+}
+
+#[test]
+fn path_scoped_permissions() {
+    use crate::builtins::{Builtin, BuiltinPermissions};
+    use compiler::path_scopes::FsAccess;
+
+    let dir = std::env::temp_dir().join("spwn-path-scopes-test");
+    let assets = dir.join("assets");
+    std::fs::create_dir_all(&assets).unwrap();
+
+    let mut permissions = BuiltinPermissions::new();
+    assert!(!permissions.is_allowed(Builtin::ReadFile));
+    permissions.allow_path(FsAccess::Read, &assets);
+    assert!(permissions.is_allowed(Builtin::ReadFile));
+    assert!(permissions.is_allowed(Builtin::FileExists));
+    assert!(!permissions.is_allowed(Builtin::WriteFile));
+
+    assert!(permissions
+        .check_path(Builtin::ReadFile, &assets.join("data.txt"))
+        .is_ok());
+    assert!(permissions
+        .check_path(Builtin::ReadDir, &assets.join("new/../levels"))
+        .is_ok());
+    let denied = permissions
+        .check_path(Builtin::ReadFile, &assets.join("../secret.txt"))
+        .unwrap_err();
+    assert!(denied.contains("secret.txt") && denied.contains("--allow-read"));
+    assert!(permissions
+        .check_path(Builtin::FileKind, &dir.join("secret.txt"))
+        .is_err());
+    // only the builtins that read are limited
+    assert!(permissions
+        .check_path(Builtin::WriteFile, &dir.join("out.txt"))
+        .is_ok());
+
+    #[cfg(unix)]
+    {
+        let link = assets.join("up");
+        if std::fs::symlink_metadata(&link).is_err() {
+            std::os::unix::fs::symlink(&dir, &link).unwrap();
+        }
+        assert!(permissions
+            .check_path(Builtin::ReadFile, &link.join("secret.txt"))
+            .is_err());
+
+        // a link to a file that doesn't exist yet, which writing would create
+        permissions.allow_path(FsAccess::Write, &assets);
+        let dangling = assets.join("pwned.txt");
+        if std::fs::symlink_metadata(&dangling).is_err() {
+            std::os::unix::fs::symlink("../outside/pwned.txt", &dangling).unwrap();
+        }
+        assert!(permissions
+            .check_path(Builtin::WriteFile, &dangling)
+            .is_err());
+        // links that point to each other
+        let looped = assets.join("loop");
+        if std::fs::symlink_metadata(&looped).is_err() {
+            std::os::unix::fs::symlink("loop", &looped).unwrap();
+        }
+        assert!(permissions.check_path(Builtin::WriteFile, &looped).is_err());
+    }
+}
+
+#[test]
+fn package_permissions() {
+    use crate::builtins::{Builtin, BuiltinPermissions};
+
+    let path = PathBuf::from("../test/package_permissions/main.spwn");
+    let code = std::fs::read_to_string(&path).unwrap();
+    let source = SpwnSource::File(path);
+    let (statements, notes) =
+        parser::parser::parse_spwn(code, source.clone(), crate::builtins::BUILTIN_NAMES).unwrap();
+    let mut permissions = BuiltinPermissions::new();
+    permissions.set(Builtin::ReadFile, true);
+    permissions.set(Builtin::ReadDir, true);
+
+    let mut std_out = Vec::<u8>::new();
+    compiler::compiler::compile_script(
+        statements,
+        source,
+        vec![PathBuf::from("./")],
+        Default::default(),
+        notes,
+        permissions,
+        String::new(),
+        &mut std_out,
+    )
+    .unwrap();
+    let out = String::from_utf8(std_out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "hello from a data file");
+    assert!(lines[1].contains("`reader@1.0.0`") && lines[1].contains("add `readdir`"));
+    // the script itself can still use everything the build allows
+    assert_eq!(lines[2], "['reader@1.0.0']");
+}
+
+#[test]
+fn source_map_counter_compare() {
+    let path = PathBuf::from("../test/counter_compare.spwn");
+    let code = std::fs::read_to_string(&path).unwrap();
+    let mut std_out = Vec::<u8>::new();
+    let mut compiled = compile_source(
+        code,
+        SpwnSource::File(path),
+        vec![PathBuf::from("./")],
+        &mut std_out,
+    )
+    .unwrap();
+
+    let reserved = reserved_ids(&compiled);
+    compiled.func_ids =
+        optimizer::optimize::optimize(compiled.func_ids, compiled.closed_groups, reserved);
+    let mut objects = leveldata::apply_fn_ids(&compiled.func_ids);
+    objects.extend(compiled.objects.clone());
+    leveldata::assign_ids(&mut objects, "").unwrap();
+
+    let map = source_map(&compiled, &objects);
+    assert_eq!(map.objects.len(), objects.len());
+    assert!(map.objects.iter().all(|o| !o.origins.is_empty()));
+    // the instant count triggers the optimizer merged keep where all of them came from
+    assert!(map
+        .objects
+        .iter()
+        .any(|o| o.object_id == Some(1811) && o.origins.len() > 1));
+    // the comparison on line 17 can be found through the macro calls
+    assert!(map.objects.iter().any(|o| o.origins.iter().any(|origin| {
+        origin.calls.first().map(|c| (c.file.as_str(), c.line))
+            == Some(("../test/counter_compare.spwn", Some(17)))
+    })));
+}
+
+run_test! {
+    NAME: sync_group_syntax
+    CODE: r"
+#[no_std]
+sync a
+sync a[1] {
+    $.print('part 1')
+}
+sync a['two'] {
+    $.print('part 2')
+}
+    "
+    OUTPUT: r"
+part 1
+part 2
+    "
+}
+
+run_test! {
+    [should_panic]
+    NAME: sync_part_of_non_sync_group
+    CODE: r"
+#[no_std]
+a = 1
+sync a[1] {}
+    "
+}
+
+#[test]
+fn sync_groups_share_groups() {
+    let path = PathBuf::from("../test/sync_groups.spwn");
+    let code = std::fs::read_to_string(&path).unwrap();
+    let mut std_out = Vec::<u8>::new();
+    let mut compiled = compile_source(
+        code,
+        SpwnSource::File(path),
+        vec![PathBuf::from("./")],
+        &mut std_out,
+    )
+    .unwrap();
+
+    let reserved = reserved_ids(&compiled);
+    compiled.func_ids =
+        optimizer::optimize::optimize(compiled.func_ids, compiled.closed_groups, reserved);
+
+    let used_groups = |compiled: &crate::globals::Globals| {
+        let mut objects = leveldata::apply_fn_ids(&compiled.func_ids);
+        objects.extend(compiled.objects.clone());
+        leveldata::append_objects(objects, "").unwrap().1[0]
+    };
+
+    let before = used_groups(&compiled);
+    sync_groups::share_groups(
+        &mut compiled.func_ids,
+        &compiled.objects,
+        &compiled.sync_groups,
+    );
+    let after = used_groups(&compiled);
+    assert!(after < before, "{} groups -> {} groups", before, after);
+
+    // every shared trigger can be toggled off by the sync group
+    let sync_toggle = compiled.sync_groups[0].toggle_group;
+    let shared = compiled
+        .func_ids
+        .iter()
+        .flat_map(|f| &f.obj_list)
+        .filter(|(obj, _)| {
+            matches!(
+                obj.params.get(&57),
+                Some(leveldata::ObjParam::GroupList(list)) if list.contains(&sync_toggle)
+            )
+        })
+        .count();
+    assert!(shared > 0);
+}
+
+#[test]
+fn lsp_definitions() {
+    use lsp::document::Document;
+    use lsp::symbols::{DefinitionKind, Symbols};
+
+    let code = "a = 1
+f = #[desc(\"adds a\")] (a: @number) {
+    return a + 1
+}
+b = f(a)
+";
+    let document = Document::new(code);
+    let (statements, _) = crate::parse_spwn(
+        code.to_string(),
+        SpwnSource::String(internment::LocalIntern::new(code.to_string())),
+        &[],
+    )
+    .unwrap();
+    let symbols = Symbols::from_statements(&statements);
+
+    let definition_at = |offset| {
+        let (_, word) = document.word_at(offset).unwrap();
+        let def = symbols
+            .definition(word, DefinitionKind::Variable, offset)
+            .unwrap();
+        (
+            document.position(def.pos.0).line,
+            def.kind,
+            def.desc.clone(),
+        )
+    };
+
+    // the argument shadows the variable inside the macro
+    assert_eq!(
+        definition_at(code.find("a + 1").unwrap()),
+        (1, DefinitionKind::Argument, None)
+    );
+    assert_eq!(
+        definition_at(code.find("f(a)").unwrap() + 2),
+        (0, DefinitionKind::Variable, None)
+    );
+    assert_eq!(
+        definition_at(code.find("f(a)").unwrap()),
+        (1, DefinitionKind::Variable, Some("adds a".to_string()))
+    );
+}
+
+// a compile that was cancelled doesn't report anything
+#[test]
+fn lsp_cancel_compile() {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    let code = "#[no_std]\na = 1\nb = a + c\n".to_string();
+    let path = std::env::current_dir().unwrap().join("lsp_cancel.spwn");
+    let compile = |cancelled| {
+        lsp::diagnostics::compile(
+            code.clone(),
+            path.clone(),
+            vec![PathBuf::from("./")],
+            Arc::new(AtomicBool::new(cancelled)),
+        )
+    };
+
+    assert!(compile(true).is_none());
+    let result = compile(false).unwrap();
+    assert_eq!(result.diagnostics.len(), 1);
+    assert!(result.diagnostics[0]
+        .message
+        .starts_with("`c` is not defined in this scope"));
+}
+
+#[test]
+fn parser_error_recovery() {
+    use parser::ast::StatementBody;
+
+    let code = "a = 1
+b = 2 + ]
+f = () {
+    d = )
+    e = 4
+}
+g = 5 6
+h = 7
+";
+    let (statements, _, errors) = parser::parser::parse_spwn_partial(
+        code.to_string(),
+        SpwnSource::String(internment::LocalIntern::new(code.to_string())),
+        &[],
+    );
+    assert_eq!(errors.len(), 3, "{:?}", errors);
+    let lines: Vec<&str> = errors
+        .iter()
+        .map(|e| code[..e.pos().0].lines().last().unwrap().trim())
+        .collect();
+    assert_eq!(lines, ["b = 2 +", "d =", "g = 5"]);
+
+    // the statements around the errors are still there
+    assert_eq!(statements.len(), 5);
+    assert_eq!(
+        statements[1].body,
+        StatementBody::Invalid("b = 2 + ]".to_string())
+    );
+    assert_eq!(&code[statements[1].pos.0..statements[1].pos.1], "b = 2 + ]");
+    assert!(matches!(statements[4].body, StatementBody::Definition(_)));
+}
+
+// the errors from parsing ahead aren't kept when the code is parsed again
+#[test]
+fn parser_error_recovery_speculative() {
+    for (code, count) in [
+        ("d = (cb = !{ z = 1 + }) { return 1 }\n", 1),
+        ("d = (a, b = !{ z = 1 + }) { return 1 }\n", 1),
+        ("d = (a) -> @number { z = 1 + }\n", 1),
+        ("d = [[a]] (x) { z = 1 + }\n", 1),
+        ("d = [a[!{ z = 1 + }], 1]\n", 1),
+        ("d = (!{ z = 1 + })\ne = (!{ y = 2 + })\n", 2),
+    ] {
+        let (_, _, errors) = parser::parser::parse_spwn_partial(
+            code.to_string(),
+            SpwnSource::String(internment::LocalIntern::new(code.to_string())),
+            &[],
+        );
+        assert_eq!(errors.len(), count, "{}: {:?}", code, errors);
+    }
+}
+
+#[test]
+fn format_string_spans() {
+    use parser::ast::{FormatStrPart, StatementBody, ValueBody};
+
+    let code = "a = f'x = {b}!'";
+    let (statements, _) = parser::parser::parse_spwn(
+        code.to_string(),
+        SpwnSource::String(internment::LocalIntern::new(code.to_string())),
+        &[],
+    )
+    .unwrap();
+    let parts = match &statements[0].body {
+        StatementBody::Definition(d) => match &d.value.as_ref().unwrap().values[0].value.body {
+            ValueBody::FormatStr(parts) => parts.clone(),
+            a => panic!("expected a format string, found {:?}", a),
+        },
+        a => panic!("expected a definition, found {:?}", a),
+    };
+    assert_eq!(parts.len(), 3);
+    assert_eq!(parts[0], FormatStrPart::Str("x = ".to_string()));
+    match &parts[1] {
+        FormatStrPart::Expr(e) => assert_eq!(&code[e.values[0].pos.0..e.values[0].pos.1], "b"),
+        a => panic!("expected an expression, found {:?}", a),
+    }
+
+    // errors in the braces point into the string
+    for (code, at) in [
+        ("a = f'x = {1 + }'", "}'"),
+        ("a = f'x = {1 2}'", "2}"),
+        ("a = f'}'", "}"),
+    ] {
+        let (_, _, errors) = parser::parser::parse_spwn_partial(
+            code.to_string(),
+            SpwnSource::String(internment::LocalIntern::new(code.to_string())),
+            &[],
+        );
+        assert_eq!(errors[0].pos().0, code.find(at).unwrap(), "{}", code);
+    }
+}
+
+fn spwn_files(dir: &std::path::Path, files: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            spwn_files(&path, files);
+        } else if path.extension().is_some_and(|e| e == "spwn") {
+            files.push(path);
+        }
+    }
+}
+
+fn comment_count(code: &str) -> usize {
+    code.matches("//").count() + code.matches("/*").count()
+}
+
+#[test]
+fn fmt_round_trip() {
+    let mut files = Vec::new();
+    spwn_files(std::path::Path::new("../test"), &mut files);
+    spwn_files(std::path::Path::new("../libraries"), &mut files);
+
+    let mut formatted_files = 0;
+    for path in files {
+        let code = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("\r\n", "\n");
+        let source = SpwnSource::File(path.clone());
+        // some of the older test files use syntax that is no longer supported
+        if parser::parser::parse_spwn(code.clone(), source.clone(), &[]).is_err() {
+            continue;
+        }
+        // format_spwn checks that the syntax tree is unchanged
+        let formatted = crate::format_spwn(code.clone(), source.clone())
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert_eq!(
+            comment_count(&code),
+            comment_count(&formatted),
+            "{} lost comments",
+            path.display()
+        );
+        let again = crate::format_spwn(formatted.clone(), source).unwrap();
+        assert_eq!(formatted, again, "{} is not idempotent", path.display());
+        formatted_files += 1;
+    }
+    assert!(formatted_files > 0);
+}
+
+#[test]
+fn fmt_keeps_comments() {
+    let code = "// header
+a=1   // one
+
+
+b = {x:  1, /* inline */ y: 2}
+f = (a,b){
+  // inside
+  return a+b
+}
+";
+    let formatted = crate::format_spwn(
+        code.to_string(),
+        SpwnSource::String(internment::LocalIntern::new(code.to_string())),
+    )
+    .unwrap();
+    assert_eq!(
+        formatted,
+        "// header
+a = 1 // one
+
+b = {
+    x: 1, /* inline */
+    y: 2,
+}
+f = (a, b) {
+    // inside
+    return a + b
+}
+"
+    );
+}
+
+#[test]
+fn selective_imports() {
+    let path = PathBuf::from("../test/modules/main.spwn");
+    let code = std::fs::read_to_string(&path).unwrap();
+    let mut std_out = Vec::<u8>::new();
+    let source = SpwnSource::File(path);
+    if let Err(e) = compile_source(code, source, vec![PathBuf::from("./")], &mut std_out) {
+        panic!("{}", e);
+    }
+    assert_eq!(
+        String::from_utf8(std_out).unwrap().trim(),
+        "4 10 hidden value
+Use of private member
+Use of private member
+undefined
+Use of private member
+Imported name not found
+Use of private member
+false"
+    );
+}
+
+#[test]
+fn fmt_try_catch() {
+    let code = "try{ a=1 // one
+}catch  e {$.print(e.message)}
+try {} catch { b=2 }
+";
+    let formatted = crate::format_spwn(
+        code.to_string(),
+        SpwnSource::String(internment::LocalIntern::new(code.to_string())),
+    )
+    .unwrap();
+    assert_eq!(
+        formatted,
+        "try {
+    a = 1 // one
+} catch e {
+    $.print(e.message)
+}
+try {} catch {
+    b = 2
+}
+"
+    );
+}
+
+#[test]
+fn ast_json() {
+    let code = "#[no_std]\nlet a = 1 + 2\n";
+    let json = crate::ast_json(
+        code.to_string(),
+        SpwnSource::String(internment::LocalIntern::new(code.to_string())),
+    )
+    .unwrap();
+    let ast: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(ast["file_tag"]["tags"][0][0], "no_std");
+    let statement = &ast["statements"][0];
+    assert_eq!(statement["pos"], serde_json::json!([10, 23]));
+    let def = &statement["body"]["Definition"];
+    assert_eq!(def["mutable"], true);
+    assert_eq!(def["symbol"]["value"]["body"]["Symbol"], "a");
+    assert_eq!(def["value"]["operators"], serde_json::json!(["Plus"]));
+    assert_eq!(def["value"]["values"][1]["value"]["body"]["Number"], 2.0);
+}
+
+#[test]
+fn incremental_reparse() {
+    use parser::incremental::{ParsedFile, TextEdit};
+
+    let path = PathBuf::from("../test/bf/bf_interpreter.spwn");
+    let code = std::fs::read_to_string(&path).unwrap();
+    let source = SpwnSource::File(path);
+    let mut file = ParsedFile::parse(code, source.clone(), &[]);
+    assert!(file.errors.is_empty(), "{:?}", file.errors);
+
+    // replaces the first occurrence of `find`, and checks that the statements
+    // are the same as parsing the new text from scratch
+    let mut edit = |find: &str, replace: &str| {
+        let start = file.text.find(find).unwrap();
+        let reparsed = file.apply_edit(&TextEdit::new((start, start + find.len()), replace));
+        let (statements, _, errors) =
+            parser::parser::parse_spwn_partial(file.text.clone(), source.clone(), &[]);
+        assert_eq!(
+            file.statements, statements,
+            "edit `{}` -> `{}`",
+            find, replace
+        );
+        assert_eq!(file.errors.len(), errors.len());
+        reparsed.is_some()
+    };
+
+    assert!(edit("spacing = 10", "spacing = 12345"));
+    assert!(edit("bg_options = 2\n", "bg_options = 2\nnew_thing = 5\n"));
+    assert!(edit("return -num", "return 0 - num"));
+    assert!(edit("ended = counter(0)\n", ""));
+    // joins two statements together
+    edit("random_max = 4\n", "random_max = 4 + ");
+    // syntax errors reparse everything until they're fixed
+    assert!(!edit("counter(555i)", "counter(555i"));
+    assert!(!edit("counter(555i", "counter(555i)"));
+    assert!(edit("counter(555i)", "counter(556i)"));
+    // the top of the file
+    assert!(!edit("RIGHT = ?b", "RIGHT = 5g"));
+}
+
+#[test]
+fn lints() {
+    let lint_names = |code: &str| -> Result<Vec<&'static str>, String> {
+        let mut std_out = Vec::<u8>::new();
+        let source = SpwnSource::String(internment::LocalIntern::new(code.to_string()));
+        let globals = compile_source(
+            code.to_string(),
+            source,
+            vec![PathBuf::from("./")],
+            &mut std_out,
+        )?;
+        Ok(globals.warnings.iter().map(|w| w.lint.name()).collect())
+    };
+
+    let code = r#"
+unused = 5
+used = 3
+$.print(used)
+t = !{
+    $.print("hi")
+}
+let a = 1
+let a = 2
+$.print(a)
+f = () {
+    return a
+    $.print("never")
+}
+$.print(f())
+#[allow(unused_variables)]
+quiet = 1
+#[allow(unused_variables)]
+let quiet2 = 1
+_ignored = 1
+"#;
+    assert_eq!(
+        lint_names(code).unwrap(),
+        [
+            "unused_variables",
+            "unused_triggers",
+            "unused_variables",
+            "shadowed_let",
+            "unreachable_code",
+        ]
+    );
+
+    assert_eq!(
+        lint_names("#[allow(warnings)]\nunused = 5").unwrap(),
+        Vec::<&str>::new()
+    );
+    let err = lint_names("#[deny(unused_variables)]\nunused = 5").unwrap_err();
+    assert!(err.contains("Unused variable"), "{}", err);
+}
+
+#[test]
+fn module_cache() {
+    use compiler::builtins::BUILTIN_NAMES;
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("spwn-module-cache-{}", std::process::id()));
+    let cache = dir.join("cache");
+    let module = dir.join("module.spwn");
+    let main = dir.join("main.spwn");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        &main,
+        "m = import \"module.spwn\"\n$.print(m.value)\n$.print(@thing::describe())",
+    )
+    .unwrap();
+
+    // the output, and every trigger and object with where it is in the level
+    let build_level = || -> (String, Vec<String>) {
+        let code = fs::read_to_string(&main).unwrap();
+        let source = SpwnSource::File(main.clone());
+        let (statements, notes) =
+            parser::parser::parse_spwn(code, source.clone(), BUILTIN_NAMES).unwrap();
+        let mut std_out = Vec::<u8>::new();
+        let globals = compiler::compiler::compile_spwn(
+            statements,
+            source,
+            vec![PathBuf::from("./")],
+            compiler::compiler::CompileOptions {
+                module_cache: Some(cache.clone()),
+                ..Default::default()
+            },
+            notes,
+            Default::default(),
+            String::new(),
+            &mut std_out,
+        );
+        let globals = match globals {
+            Ok(g) => g,
+            Err(_) => panic!("build failed"),
+        };
+        let describe = |func: usize, obj: &compiler::leveldata::GdObj| {
+            let mut params: Vec<_> = obj
+                .params
+                .iter()
+                .map(|(k, v)| format!("{}:{}", k, v))
+                .collect();
+            params.sort();
+            let origin = globals.object_origins.contains_key(&obj.unique_id);
+            format!("{} {} {} {}", func, obj.unique_id, origin, params.join(","))
+        };
+        let mut level = Vec::new();
+        for (i, f) in globals.func_ids.iter().enumerate() {
+            level.push(format!("function {} {:?}", i, f.parent));
+            for (obj, order) in &f.obj_list {
+                level.push(format!("{} {}", describe(i, obj), order.0));
+            }
+        }
+        for obj in &globals.objects {
+            level.push(describe(obj.func_id, obj));
+        }
+        (String::from_utf8(std_out).unwrap(), level)
+    };
+    let build = || build_level().0;
+    let modified = || -> Vec<std::time::SystemTime> {
+        let mut times: Vec<_> = fs::read_dir(&cache)
+            .unwrap()
+            .map(|f| f.unwrap().metadata().unwrap().modified().unwrap())
+            .collect();
+        times.sort();
+        times
+    };
+
+    let module_code = r#"
+#[cache_output]
+type @thing
+impl @thing {
+    describe: () => "a thing",
+}
+return { value: VALUE, id: ?g }
+"#;
+    fs::write(&module, module_code.replace("VALUE", "1")).unwrap();
+    let first = build();
+    assert_eq!(first.trim(), "1\na thing");
+    // the std library and the module are saved
+    let saved = modified();
+    assert!(saved.len() >= 2);
+    assert!(fs::read_dir(&cache)
+        .unwrap()
+        .any(|f| fs::read_to_string(f.unwrap().path())
+            .unwrap()
+            .contains("a thing")));
+
+    // nothing changed, so everything is loaded instead of saved again
+    assert_eq!(build(), first);
+    assert_eq!(modified(), saved);
+
+    // changing the module compiles it again
+    fs::write(&module, module_code.replace("VALUE", "2")).unwrap();
+    assert_eq!(build().trim(), "2\na thing");
+
+    // modules that depend on more than their source are compiled every time
+    fs::write(
+        dir.join("impure.spwn"),
+        "#[cache_output]\nreturn { time: $.time() }",
+    )
+    .unwrap();
+    fs::write(&main, "m = import \"impure.spwn\"\n$.print(m.time > 0)").unwrap();
+    let count = modified().len();
+    assert_eq!(build().trim(), "true");
+    assert_eq!(modified().len(), count);
+
+    // the triggers and objects a module adds are added again when it's loaded
+    fs::write(
+        dir.join("triggers.spwn"),
+        r#"
+#[cache_output]
+extract obj_props
+g = ?g
+g.move(10, 0)
+$.add(obj { OBJ_ID: 1, X: 15, Y: 15, GROUPS: g })
+return { f: !{ g.toggle_off() }, g: g }
+"#,
+    )
+    .unwrap();
+    fs::write(&main, "m = import \"triggers.spwn\"\nm.f!\n$.print(m.g)").unwrap();
+    let compiled = build_level();
+    let saved = modified();
+    assert_eq!(saved.len(), count + 1);
+    let loaded = build_level();
+    assert_eq!(modified(), saved);
+    assert_eq!(loaded, compiled);
+    assert!(compiled.1.iter().any(|l| l.contains("1:1,")));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn profiler() {
+    let code = "g = () { return [1, 2, 3] }\nf = () { return g() }\nf()\nf()";
+    let source = SpwnSource::String(internment::LocalIntern::new(code.to_string()));
+    let (statements, notes) =
+        parser::parser::parse_spwn(code.to_string(), source.clone(), &[]).unwrap();
+    let mut std_out = Vec::<u8>::new();
+    let globals = compiler::compiler::compile_spwn(
+        statements,
+        source,
+        vec![PathBuf::from("./")],
+        compiler::compiler::CompileOptions {
+            profile: true,
+            ..Default::default()
+        },
+        notes,
+        Default::default(),
+        String::new(),
+        &mut std_out,
+    );
+    let globals = match globals {
+        Ok(g) => g,
+        Err(_) => panic!("build failed"),
+    };
+    let profile = crate::profile::profile(&globals).unwrap();
+
+    let stacks: Vec<&str> = profile
+        .folded_values
+        .lines()
+        .map(|l| l.rsplit_once(' ').unwrap().0)
+        .collect();
+    assert!(stacks.contains(&"<source>;import std"));
+    assert!(stacks.contains(&"<source>;<source>:3 f();<source>:2 g()"));
+    assert!(stacks.contains(&"<source>;<source>:4 f();<source>:2 g()"));
+
+    let g = profile.frames.iter().find(|r| r.name == "<source>:2 g()");
+    assert_eq!(g.map(|r| r.sample.calls), Some(2));
+}
+
+#[test]
+fn context_limits() {
+    use compiler::compiler::CompileOptions;
+    use errors::RuntimeError;
+
+    // every `bit()` returns in two contexts, so `chain(n)` ends in 2^n contexts
+    let code = |tag: &str| {
+        format!(
+            "#[no_std{}]
+bit = () {{
+    -> return 1
+    return 0
+}}
+chain = (depth) {{
+    if depth == 0 {{ return 0 }}
+    b = bit()
+    return chain(depth - 1) * 2 + b
+}}
+$.print(chain(4))
+",
+            tag
+        )
+    };
+    #[allow(clippy::result_large_err)]
+    let build = |code: String,
+                 options: CompileOptions|
+     -> Result<Vec<errors::Warning>, RuntimeError> {
+        let source = SpwnSource::String(internment::LocalIntern::new(code.clone()));
+        let (statements, notes) = parser::parser::parse_spwn(code, source.clone(), &[]).unwrap();
+        let mut std_out = Vec::<u8>::new();
+        let globals = compiler::compiler::compile_script(
+            statements,
+            source,
+            vec![PathBuf::from("./")],
+            options,
+            notes,
+            Default::default(),
+            String::new(),
+            &mut std_out,
+        )?;
+        assert_eq!(globals.context_limits.peak, 16);
+        Ok(globals.warnings)
+    };
+    let limits = |warn, max| CompileOptions {
+        context_warning: warn,
+        max_contexts: max,
+        ..Default::default()
+    };
+
+    assert!(build(code(""), Default::default()).unwrap().is_empty());
+
+    let warnings = build(code(""), limits(Some(8), None)).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].lint.name(), "context_explosion");
+    // the `-> return 1` in `bit` is where the contexts come from
+    let split = code("").find("-> return 1").unwrap();
+    assert!(warnings[0]
+        .labels
+        .iter()
+        .any(|(area, _)| area.pos.0 == split));
+
+    assert!(
+        build(code(", allow(context_explosion)"), limits(Some(8), None))
+            .unwrap()
+            .is_empty()
+    );
+
+    match build(code(""), limits(None, Some(8))) {
+        Err(RuntimeError::TooManyContexts { limit, origins, .. }) => {
+            assert_eq!(limit, 8);
+            assert!(origins.iter().any(|(area, _)| area.pos.0 == split));
+        }
+        _ => panic!("expected too many contexts"),
+    }
+    let denied = build(code(", deny(context_explosion)"), limits(Some(8), None));
+    assert!(matches!(
+        denied,
+        Err(RuntimeError::TooManyContexts { limit: 8, .. })
+    ));
+
+    // the warning is shown while building, before the script prints anything
+    thread_local! {
+        static EVENTS: std::cell::RefCell<Vec<&'static str>> = Default::default();
+    }
+    struct Output;
+    impl std::io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            EVENTS.with(|e| e.borrow_mut().push("print"));
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let code = code("");
+    let source = SpwnSource::String(internment::LocalIntern::new(code.clone()));
+    let (statements, notes) = parser::parser::parse_spwn(code, source.clone(), &[]).unwrap();
+    let mut output = Output;
+    let globals = compiler::compiler::compile_script(
+        statements,
+        source,
+        vec![PathBuf::from("./")],
+        CompileOptions {
+            show_warning: Some(|_| EVENTS.with(|e| e.borrow_mut().push("warning"))),
+            ..limits(Some(8), None)
+        },
+        notes,
+        Default::default(),
+        String::new(),
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(globals.warnings.len(), 1);
+    EVENTS.with(|e| {
+        let events = e.borrow();
+        assert_eq!(events.first(), Some(&"warning"));
+        assert!(events.contains(&"print"));
+    });
+}
+
+#[test]
+fn repl() {
+    use crate::repl::{is_incomplete, Repl};
+
+    let mut std_out = Vec::<u8>::new();
+    let mut repl = Repl::new(
+        PathBuf::from("./<repl>"),
+        vec![PathBuf::from("./")],
+        Default::default(),
+        Default::default(),
+        &mut std_out,
+    )
+    .unwrap();
+
+    assert_eq!(repl.eval("x = 5").unwrap(), Vec::<String>::new());
+    assert_eq!(repl.eval("x + 1").unwrap(), ["6"]);
+    assert_eq!(
+        repl.eval("f = (a) {\n    return a * x\n}\nf(2)").unwrap(),
+        ["10"]
+    );
+    assert_eq!(repl.type_of("f").unwrap(), ["@macro"]);
+    assert_eq!(repl.eval("$.print(\"hi\")").unwrap(), Vec::<String>::new());
+
+    // a failed input doesn't change the variables
+    assert!(repl.eval("x = 6\ny").is_err());
+    assert_eq!(repl.eval("x").unwrap(), ["5"]);
+
+    assert!(repl.objects().is_empty());
+    repl.eval("10g.toggle_on()").unwrap();
+    let objects = repl.objects();
+    assert_eq!(objects.len(), 1);
+    assert!(objects[0].contains("51: 10g"), "{}", objects[0]);
+
+    drop(repl);
+    assert_eq!(String::from_utf8(std_out).unwrap(), "hi\n");
+
+    assert!(is_incomplete("f = () {\n"));
+    assert!(is_incomplete("a = [1,\n2,\n"));
+    assert!(!is_incomplete("f = () {}"));
+    assert!(!is_incomplete("s = \"{\""));
+}
+
+#[test]
+fn debugger() {
+    use compiler::compiler::CompileOptions;
+    use compiler::debugger::{DebugClient, Debugger, Paused, Step, StopReason};
+    use compiler::value::Value;
+    use errors::compiler_info::CodeArea;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    let code = "#[no_std]
+let a = 1
+f = () {
+    $.breakpoint()
+    return a + 1
+}
+b = f()
+a = b
+";
+    struct Recorder {
+        code: &'static str,
+        breakpoint: usize,
+        steps: VecDeque<Step>,
+        // the reason, the first line of the statement and how many calls deep it is
+        stops: Vec<(StopReason, &'static str, usize)>,
+        b: Option<Value>,
+    }
+    impl DebugClient for Recorder {
+        fn has_breakpoint(&mut self, area: CodeArea) -> bool {
+            area.pos.0 == self.breakpoint
+        }
+        fn paused(&mut self, paused: Paused) -> Step {
+            let line = self.code[paused.area.pos.0..].lines().next().unwrap();
+            self.stops
+                .push((paused.reason, line, paused.call_stack.len()));
+            self.b = paused
+                .contexts
+                .iter()
+                .next()
+                .unwrap()
+                .inner()
+                .get_variable(internment::LocalIntern::new("b".to_string()))
+                .map(|val| paused.globals.stored_values[val].clone());
+            self.steps.pop_front().unwrap_or(Step::Continue)
+        }
+    }
+    let recorder = Rc::new(RefCell::new(Recorder {
+        code,
+        breakpoint: code.find("return a + 1").unwrap(),
+        steps: VecDeque::from([Step::Over, Step::Over, Step::Over, Step::Out, Step::Out]),
+        stops: Vec::new(),
+        b: None,
+    }));
+    let source = SpwnSource::String(internment::LocalIntern::new(code.to_string()));
+    let (statements, notes) = parser::parser::parse_spwn(
+        code.to_string(),
+        source.clone(),
+        compiler::builtins::BUILTIN_NAMES,
+    )
+    .unwrap();
+    let mut std_out = Vec::<u8>::new();
+    compiler::compiler::compile_script(
+        statements,
+        source,
+        vec![PathBuf::from("./")],
+        CompileOptions {
+            debugger: Some(Debugger::new(recorder.clone(), true)),
+            ..Default::default()
+        },
+        notes,
+        Default::default(),
+        String::new(),
+        &mut std_out,
+    )
+    .unwrap();
+
+    let recorder = recorder.borrow();
+    assert_eq!(
+        recorder.stops,
+        vec![
+            (StopReason::Entry, "let a = 1", 0),
+            (StopReason::Step, "f = () {", 0),
+            // stepping over the call still stops at `$.breakpoint()` inside it
+            (StopReason::Step, "b = f()", 0),
+            (StopReason::BreakpointCall, "$.breakpoint()", 1),
+            (StopReason::Breakpoint, "return a + 1", 1),
+            (StopReason::Step, "a = b", 0),
+        ]
+    );
+    assert_eq!(recorder.b, Some(Value::Number(2.0)));
+}
+
+#[test]
+fn reproducible_builtins() {
+    use compiler::compiler::CompileOptions;
+
+    let build = |code: &str, options: CompileOptions| {
+        let source = SpwnSource::String(internment::LocalIntern::new(code.to_string()));
+        let (statements, notes) = parser::parser::parse_spwn(
+            code.to_string(),
+            source.clone(),
+            compiler::builtins::BUILTIN_NAMES,
+        )
+        .unwrap();
+        let mut std_out = Vec::<u8>::new();
+        let built = compiler::compiler::compile_script(
+            statements,
+            source,
+            vec![PathBuf::from("./")],
+            options,
+            notes,
+            Default::default(),
+            String::new(),
+            &mut std_out,
+        )
+        .is_ok();
+        built.then(|| String::from_utf8(std_out).unwrap())
+    };
+    let options = || CompileOptions {
+        fixed_time: Some(1234.5),
+        inputs: Some(vec!["first".to_string(), "second".to_string()]),
+        ..Default::default()
+    };
+    let code = "#[seed(42)]\n$.print($.random())\n$.print($.random(1..1000, 5))\n$.print($.time())\n$.print($.get_input('a'))\n$.print($.get_input('b'))";
+
+    let output = build(code, options()).unwrap();
+    assert_eq!(build(code, options()).unwrap(), output);
+    assert!(output.ends_with("1234.5\nfirst\nsecond\n"));
+
+    // `--seed` is used instead of the attribute
+    let seeded = CompileOptions {
+        seed: Some(42),
+        ..options()
+    };
+    assert_eq!(
+        build(&code.replace("#[seed(42)]", "#[seed(7)]"), seeded).unwrap(),
+        output
+    );
+
+    // no answers left, and a seed that isn't a positive integer
+    assert!(build(&format!("{}\n$.get_input('c')", code), options()).is_none());
+    assert!(build(&code.replace("42", "-1"), options()).is_none());
+}
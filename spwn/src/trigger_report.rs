@@ -0,0 +1,296 @@
+// shows which files, lines and macro calls the objects of a level come from
+//
+// every object remembers where it was created (see `ObjectOrigin`), and the
+// optimizer keeps that for the triggers it makes, so the same origins can be
+// counted before and after optimization
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use ariadne::Cache;
+
+use crate::builtins::Id;
+use crate::compiler_info::CodeArea;
+use crate::compiler_types::FunctionId;
+use crate::globals::Globals;
+use crate::leveldata::{GdObj, ObjParam};
+use crate::SpwnCache;
+use shared::SpwnSource;
+
+// how many rows are shown in each table
+const MAX_ROWS: usize = 15;
+const MAX_SNIPPET_LEN: usize = 40;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub objects: usize,
+    pub triggers: usize,
+    pub groups: usize,
+    pub items: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportRow {
+    pub name: String,
+    pub before: Usage,
+    pub after: Usage,
+}
+
+#[derive(Debug, Default)]
+pub struct TriggerReport {
+    // where the objects were created
+    pub files: Vec<ReportRow>,
+    pub lines: Vec<ReportRow>,
+    // every macro call counts all the objects created inside of it
+    pub macros: Vec<ReportRow>,
+}
+
+#[derive(Default)]
+struct UsageSet {
+    objects: usize,
+    triggers: usize,
+    groups: BTreeSet<Id>,
+    items: BTreeSet<Id>,
+}
+
+impl UsageSet {
+    fn add(&mut self, obj: &GdObj, is_trigger: bool) {
+        if is_trigger {
+            self.triggers += 1;
+        } else {
+            self.objects += 1;
+        }
+        for param in obj.params.values() {
+            match param {
+                ObjParam::Group(g) => {
+                    self.groups.insert(g.id);
+                }
+                ObjParam::GroupList(list) => {
+                    self.groups.extend(list.iter().map(|g| g.id));
+                }
+                ObjParam::Item(i) => {
+                    self.items.insert(i.id);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn usage(&self) -> Usage {
+        Usage {
+            objects: self.objects,
+            triggers: self.triggers,
+            groups: self.groups.len(),
+            items: self.items.len(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tables {
+    files: BTreeMap<String, UsageSet>,
+    lines: BTreeMap<String, UsageSet>,
+    macros: BTreeMap<String, UsageSet>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Location {
+    pub(crate) file: String,
+    // "file:line"
+    pub(crate) line: String,
+    // the source code at the area
+    pub(crate) snippet: String,
+    // the line and column, starting at 1
+    pub(crate) position: Option<(usize, usize)>,
+}
+
+// finds the lines of code areas, also used by the profiler
+pub(crate) struct Locator {
+    cache: SpwnCache,
+    found: HashMap<CodeArea, Location>,
+}
+
+impl Locator {
+    pub(crate) fn new() -> Self {
+        Locator {
+            cache: SpwnCache::default(),
+            found: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn file_name(file: &SpwnSource) -> String {
+        match file {
+            SpwnSource::File(path) | SpwnSource::BuiltIn(path) => path.display().to_string(),
+            SpwnSource::String(_) => "<source>".to_string(),
+        }
+    }
+
+    pub(crate) fn locate(&mut self, area: CodeArea) -> Location {
+        if let Some(found) = self.found.get(&area) {
+            return found.clone();
+        }
+        let file = Locator::file_name(area.file.as_ref());
+        let (line, snippet, position) = match self.cache.fetch(area.file.as_ref()) {
+            Ok(source) => match source.get_offset_line(area.pos.0) {
+                Some((line, line_num, col)) => {
+                    let snippet: String = line
+                        .chars()
+                        .skip(col)
+                        .take(area.pos.1.saturating_sub(area.pos.0).min(MAX_SNIPPET_LEN))
+                        .take_while(|c| *c != '\n')
+                        .collect();
+                    (
+                        format!("{}:{}", file, line_num + 1),
+                        snippet.trim().to_string(),
+                        Some((line_num + 1, col + 1)),
+                    )
+                }
+                None => (file.clone(), String::new(), None),
+            },
+            Err(_) => (file.clone(), String::new(), None),
+        };
+        let found = Location {
+            file,
+            line,
+            snippet,
+            position,
+        };
+        self.found.insert(area, found.clone());
+        found
+    }
+}
+
+fn collect_usage<'a>(
+    objects: impl Iterator<Item = (&'a GdObj, bool)>,
+    globals: &Globals,
+    locator: &mut Locator,
+) -> Tables {
+    let mut tables = Tables::default();
+    for (obj, is_trigger) in objects {
+        let origin = match globals.object_origins.get(&obj.unique_id) {
+            Some(o) => o,
+            None => {
+                for table in [&mut tables.files, &mut tables.lines] {
+                    table
+                        .entry("<unknown>".to_string())
+                        .or_default()
+                        .add(obj, is_trigger);
+                }
+                continue;
+            }
+        };
+
+        let location = locator.locate(origin.area);
+        tables
+            .files
+            .entry(location.file)
+            .or_default()
+            .add(obj, is_trigger);
+        tables
+            .lines
+            .entry(location.line)
+            .or_default()
+            .add(obj, is_trigger);
+
+        // a recursive macro should only count its objects once
+        let mut calls = BTreeSet::new();
+        for call in &origin.call_stack {
+            let location = locator.locate(*call);
+            calls.insert(format!("{} {}", location.line, location.snippet));
+        }
+        for call in calls {
+            tables.macros.entry(call).or_default().add(obj, is_trigger);
+        }
+    }
+    tables
+}
+
+fn to_rows(
+    before: BTreeMap<String, UsageSet>,
+    mut after: BTreeMap<String, UsageSet>,
+) -> Vec<ReportRow> {
+    let mut rows: Vec<ReportRow> = before
+        .into_iter()
+        .map(|(name, usage)| ReportRow {
+            after: after.remove(&name).map(|u| u.usage()).unwrap_or_default(),
+            before: usage.usage(),
+            name,
+        })
+        .collect();
+    // the optimizer can also add triggers somewhere else
+    rows.extend(after.into_iter().map(|(name, usage)| ReportRow {
+        name,
+        before: Usage::default(),
+        after: usage.usage(),
+    }));
+
+    rows.sort_by(|a, b| {
+        (b.before.triggers + b.before.objects)
+            .cmp(&(a.before.triggers + a.before.objects))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    rows
+}
+
+fn all_objects<'a>(
+    func_ids: &'a [FunctionId],
+    globals: &'a Globals,
+) -> impl Iterator<Item = (&'a GdObj, bool)> {
+    func_ids
+        .iter()
+        .flat_map(|f| f.obj_list.iter().map(|(obj, _)| (obj, true)))
+        .chain(globals.objects.iter().map(|obj| (obj, false)))
+}
+
+// `before` are the triggers before optimizing, the ones in `globals` are
+// used as the optimized triggers
+pub fn trigger_report(globals: &Globals, before: &[FunctionId]) -> TriggerReport {
+    let mut locator = Locator::new();
+
+    let before = collect_usage(all_objects(before, globals), globals, &mut locator);
+    let after = collect_usage(
+        all_objects(&globals.func_ids, globals),
+        globals,
+        &mut locator,
+    );
+
+    TriggerReport {
+        files: to_rows(before.files, after.files),
+        lines: to_rows(before.lines, after.lines),
+        macros: to_rows(before.macros, after.macros),
+    }
+}
+
+fn write_table(f: &mut fmt::Formatter<'_>, title: &str, rows: &[ReportRow]) -> fmt::Result {
+    writeln!(f, "{}:", title)?;
+    writeln!(
+        f,
+        "{:>8} {:>17} {:>15} {:>13}  source",
+        "objects", "triggers", "groups", "items"
+    )?;
+    for row in rows.iter().take(MAX_ROWS) {
+        writeln!(
+            f,
+            "{:>8} {:>17} {:>15} {:>13}  {}",
+            row.before.objects,
+            format!("{} -> {}", row.before.triggers, row.after.triggers),
+            format!("{} -> {}", row.before.groups, row.after.groups),
+            format!("{} -> {}", row.before.items, row.after.items),
+            row.name
+        )?;
+    }
+    if rows.len() > MAX_ROWS {
+        writeln!(f, "{:>8} ... and {} more", "", rows.len() - MAX_ROWS)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for TriggerReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_table(f, "Objects per file", &self.files)?;
+        writeln!(f)?;
+        write_table(f, "Objects per line", &self.lines)?;
+        writeln!(f)?;
+        write_table(f, "Objects per macro call", &self.macros)
+    }
+}